@@ -0,0 +1,11 @@
+CREATE TABLE treatment (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    default_duration_minutes INTEGER NOT NULL,
+    default_price INTEGER NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+ALTER TABLE appointment ADD COLUMN treatment_id INTEGER
+    CONSTRAINT fk_treatment REFERENCES treatment (id) ON DELETE SET NULL;
@@ -0,0 +1,43 @@
+CREATE TABLE appointment_new (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    start_date DATETIME NOT NULL,
+    duration_minutes INTEGER NOT NULL,
+    employee_id INTEGER,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE,
+    CONSTRAINT fk_employee FOREIGN KEY (employee_id) REFERENCES employee (id) ON DELETE SET NULL
+);
+
+INSERT INTO appointment_new (id, customer_id, number, start_date, duration_minutes, employee_id)
+SELECT id, customer_id, number, start_date, duration_minutes, employee_id
+FROM appointment;
+
+CREATE TABLE appointment_item (
+    id INTEGER PRIMARY KEY,
+    appointment_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    treatment_id INTEGER,
+    description TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    unit_price INTEGER NOT NULL,
+    discount INTEGER NOT NULL,
+    CONSTRAINT fk_appointment FOREIGN KEY (appointment_id) REFERENCES appointment_new (id) ON DELETE CASCADE,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE SET NULL
+);
+
+INSERT INTO appointment_item (
+    appointment_id,
+    position,
+    treatment_id,
+    description,
+    quantity,
+    unit_price,
+    discount
+)
+SELECT id, 0, treatment_id, treatment, 1, price, 0
+FROM appointment;
+
+DROP TABLE appointment;
+
+ALTER TABLE appointment_new RENAME TO appointment;
@@ -0,0 +1,27 @@
+CREATE TABLE product (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    sku TEXT NOT NULL,
+    ean TEXT NOT NULL,
+    purchase_price INTEGER NOT NULL,
+    sale_price INTEGER NOT NULL,
+    reorder_threshold INTEGER NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE stock_movement (
+    id INTEGER PRIMARY KEY,
+    product_id INTEGER NOT NULL,
+    date DATETIME NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('sale', 'receipt', 'correction')),
+    quantity INTEGER NOT NULL,
+    appointment_item_id INTEGER,
+    note TEXT NOT NULL,
+    CONSTRAINT fk_product FOREIGN KEY (product_id) REFERENCES product (id) ON DELETE CASCADE,
+    CONSTRAINT fk_appointment_item FOREIGN KEY (appointment_item_id) REFERENCES appointment_item (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_stock_movement_product ON stock_movement (product_id);
+
+ALTER TABLE appointment_item ADD COLUMN product_id INTEGER
+    CONSTRAINT fk_product REFERENCES product (id) ON DELETE SET NULL;
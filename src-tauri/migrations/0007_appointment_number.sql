@@ -0,0 +1,17 @@
+UPDATE appointment
+SET number = r.number
+FROM (
+    SELECT
+        id,
+        ROW_NUMBER() OVER (PARTITION BY customer_id ORDER BY start_date ASC, id ASC) AS number
+    FROM appointment
+) r
+WHERE appointment.id = r.id
+AND appointment.customer_id IN (
+    SELECT customer_id
+    FROM appointment
+    GROUP BY customer_id, number
+    HAVING COUNT(*) > 1
+);
+
+CREATE UNIQUE INDEX idx_appointment_customer_number ON appointment (customer_id, number);
@@ -0,0 +1,3 @@
+ALTER TABLE appointment ADD COLUMN ical_uid TEXT;
+
+CREATE UNIQUE INDEX idx_appointment_ical_uid ON appointment (ical_uid) WHERE ical_uid IS NOT NULL;
@@ -0,0 +1,19 @@
+ALTER TABLE customer ADD COLUMN email TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE reminder (
+    id INTEGER PRIMARY KEY,
+    appointment_id INTEGER NOT NULL,
+    channel TEXT NOT NULL CHECK (channel IN ('email', 'sms')),
+    recipient TEXT NOT NULL,
+    appointment_start_date DATETIME NOT NULL,
+    due_date DATETIME NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'sent', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_date DATETIME NOT NULL,
+    last_error TEXT,
+    sent_date DATETIME,
+    CONSTRAINT fk_appointment FOREIGN KEY (appointment_id) REFERENCES appointment (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_reminder_appointment ON reminder (appointment_id);
+CREATE INDEX idx_reminder_status ON reminder (status, next_attempt_date);
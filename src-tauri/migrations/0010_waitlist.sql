@@ -0,0 +1,23 @@
+CREATE TABLE waitlist_entry (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    treatment_id INTEGER,
+    treatment TEXT NOT NULL,
+    duration_minutes INTEGER NOT NULL,
+    employee_id INTEGER,
+    note TEXT NOT NULL,
+    created_date DATETIME NOT NULL,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE SET NULL,
+    CONSTRAINT fk_employee FOREIGN KEY (employee_id) REFERENCES employee (id) ON DELETE SET NULL
+);
+
+CREATE TABLE waitlist_window (
+    id INTEGER PRIMARY KEY,
+    waitlist_entry_id INTEGER NOT NULL,
+    start_date DATETIME NOT NULL,
+    end_date DATETIME NOT NULL,
+    CONSTRAINT fk_waitlist_entry FOREIGN KEY (waitlist_entry_id) REFERENCES waitlist_entry (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_waitlist_window_entry ON waitlist_window (waitlist_entry_id);
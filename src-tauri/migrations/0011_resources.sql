@@ -0,0 +1,24 @@
+CREATE TABLE resource (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('room', 'device')),
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE treatment_resource (
+    treatment_id INTEGER NOT NULL,
+    resource_id INTEGER NOT NULL,
+    PRIMARY KEY (treatment_id, resource_id),
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE CASCADE,
+    CONSTRAINT fk_resource FOREIGN KEY (resource_id) REFERENCES resource (id) ON DELETE CASCADE
+);
+
+CREATE TABLE appointment_resource (
+    appointment_id INTEGER NOT NULL,
+    resource_id INTEGER NOT NULL,
+    PRIMARY KEY (appointment_id, resource_id),
+    CONSTRAINT fk_appointment FOREIGN KEY (appointment_id) REFERENCES appointment (id) ON DELETE CASCADE,
+    CONSTRAINT fk_resource FOREIGN KEY (resource_id) REFERENCES resource (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_appointment_resource_resource ON appointment_resource (resource_id);
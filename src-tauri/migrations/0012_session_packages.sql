@@ -0,0 +1,31 @@
+CREATE TABLE session_package (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    treatment_id INTEGER,
+    session_count INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    validity_days INTEGER,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE SET NULL
+);
+
+CREATE TABLE customer_package (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    session_package_id INTEGER,
+    name TEXT NOT NULL,
+    treatment_id INTEGER,
+    session_count INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    purchase_date DATE NOT NULL,
+    expiry_date DATE,
+    note TEXT NOT NULL,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE,
+    CONSTRAINT fk_session_package FOREIGN KEY (session_package_id) REFERENCES session_package (id) ON DELETE SET NULL,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_customer_package_customer ON customer_package (customer_id);
+
+ALTER TABLE appointment_item ADD COLUMN customer_package_id INTEGER
+    CONSTRAINT fk_customer_package REFERENCES customer_package (id) ON DELETE SET NULL;
@@ -0,0 +1,7 @@
+-- Existing appointments are assigned the practice time zone on startup, which also fills the
+-- UTC instants.
+ALTER TABLE appointment ADD COLUMN time_zone TEXT;
+ALTER TABLE appointment ADD COLUMN start_utc DATETIME;
+ALTER TABLE appointment ADD COLUMN end_utc DATETIME;
+
+CREATE INDEX idx_appointment_utc ON appointment (start_utc, end_utc);
@@ -0,0 +1,5 @@
+-- VAT rates are stored in basis points, 1900 is 19 %. All prices are gross prices, existing
+-- ones are taken to include the standard rate.
+ALTER TABLE treatment ADD COLUMN vat_rate INTEGER NOT NULL DEFAULT 1900;
+ALTER TABLE product ADD COLUMN vat_rate INTEGER NOT NULL DEFAULT 1900;
+ALTER TABLE appointment_item ADD COLUMN vat_rate INTEGER NOT NULL DEFAULT 1900;
@@ -0,0 +1,85 @@
+-- Invoices are drafts until they are issued. Issuing assigns the number, afterwards the
+-- invoice can only be cancelled by a credit note.
+CREATE TABLE invoice (
+    id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL DEFAULT 'invoice',
+    status TEXT NOT NULL DEFAULT 'draft',
+    number TEXT UNIQUE,
+    sequence_year INTEGER,
+    sequence INTEGER,
+    customer_id INTEGER,
+    customer_name TEXT NOT NULL,
+    address_street TEXT NOT NULL,
+    address_city TEXT NOT NULL,
+    issue_date DATE,
+    due_date DATE,
+    currency TEXT NOT NULL,
+    cancelled_invoice_id INTEGER,
+    note TEXT NOT NULL,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE SET NULL,
+    CONSTRAINT fk_cancelled_invoice FOREIGN KEY (cancelled_invoice_id) REFERENCES invoice (id)
+);
+
+CREATE INDEX idx_invoice_customer ON invoice (customer_id);
+CREATE UNIQUE INDEX idx_invoice_sequence ON invoice (kind, sequence_year, sequence);
+
+CREATE TABLE invoice_item (
+    id INTEGER PRIMARY KEY,
+    invoice_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    appointment_id INTEGER,
+    date DATE,
+    description TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    unit_price INTEGER NOT NULL,
+    discount INTEGER NOT NULL,
+    vat_rate INTEGER NOT NULL,
+    CONSTRAINT fk_invoice FOREIGN KEY (invoice_id) REFERENCES invoice (id) ON DELETE CASCADE,
+    CONSTRAINT fk_appointment FOREIGN KEY (appointment_id) REFERENCES appointment (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_invoice_item_invoice ON invoice_item (invoice_id);
+CREATE INDEX idx_invoice_item_appointment ON invoice_item (appointment_id);
+
+-- Issued invoices are immutable. Only the cancellation is recorded on them, the references to
+-- deleted customers and appointments are still cleared.
+CREATE TRIGGER invoice_issued_update BEFORE UPDATE OF
+    kind, number, sequence_year, sequence, customer_name, address_street, address_city,
+    issue_date, due_date, currency, cancelled_invoice_id, note
+ON invoice
+WHEN OLD.status <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
+
+CREATE TRIGGER invoice_issued_status BEFORE UPDATE OF status ON invoice
+WHEN OLD.status <> 'draft' AND NOT (OLD.status = 'issued' AND NEW.status = 'cancelled')
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
+
+CREATE TRIGGER invoice_issued_delete BEFORE DELETE ON invoice
+WHEN OLD.status <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be deleted');
+END;
+
+CREATE TRIGGER invoice_item_issued_insert BEFORE INSERT ON invoice_item
+WHEN (SELECT status FROM invoice WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
+
+CREATE TRIGGER invoice_item_issued_update BEFORE UPDATE OF
+    invoice_id, position, date, description, quantity, unit_price, discount, vat_rate
+ON invoice_item
+WHEN (SELECT status FROM invoice WHERE id = OLD.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
+
+CREATE TRIGGER invoice_item_issued_delete BEFORE DELETE ON invoice_item
+WHEN (SELECT status FROM invoice WHERE id = OLD.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
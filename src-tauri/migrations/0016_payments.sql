@@ -0,0 +1,30 @@
+CREATE TABLE payment (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    date DATE NOT NULL,
+    amount INTEGER NOT NULL,
+    method TEXT NOT NULL,
+    reference TEXT NOT NULL,
+    note TEXT NOT NULL,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_payment_customer ON payment (customer_id);
+
+-- Part of a payment that settles an appointment or an invoice. The rest of the payment is a
+-- credit of the customer.
+CREATE TABLE payment_allocation (
+    id INTEGER PRIMARY KEY,
+    payment_id INTEGER NOT NULL,
+    appointment_id INTEGER,
+    invoice_id INTEGER,
+    amount INTEGER NOT NULL,
+    CONSTRAINT fk_payment FOREIGN KEY (payment_id) REFERENCES payment (id) ON DELETE CASCADE,
+    CONSTRAINT fk_appointment FOREIGN KEY (appointment_id) REFERENCES appointment (id) ON DELETE CASCADE,
+    CONSTRAINT fk_invoice FOREIGN KEY (invoice_id) REFERENCES invoice (id),
+    CONSTRAINT target CHECK ((appointment_id IS NULL) <> (invoice_id IS NULL))
+);
+
+CREATE INDEX idx_payment_allocation_payment ON payment_allocation (payment_id);
+CREATE INDEX idx_payment_allocation_appointment ON payment_allocation (appointment_id);
+CREATE INDEX idx_payment_allocation_invoice ON payment_allocation (invoice_id);
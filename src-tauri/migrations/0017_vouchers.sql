@@ -0,0 +1,19 @@
+CREATE TABLE voucher (
+    id INTEGER PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    initial_value INTEGER NOT NULL,
+    issue_date DATE NOT NULL,
+    expiry_date DATE,
+    purchaser_customer_id INTEGER,
+    recipient_customer_id INTEGER,
+    note TEXT NOT NULL,
+    CONSTRAINT fk_purchaser FOREIGN KEY (purchaser_customer_id) REFERENCES customer (id) ON DELETE SET NULL,
+    CONSTRAINT fk_recipient FOREIGN KEY (recipient_customer_id) REFERENCES customer (id) ON DELETE SET NULL
+);
+
+-- Payments with the method 'voucher' redeem the voucher, the remaining value is the initial value
+-- minus these payments.
+ALTER TABLE payment ADD COLUMN voucher_id INTEGER
+    CONSTRAINT fk_voucher REFERENCES voucher (id);
+
+CREATE INDEX idx_payment_voucher ON payment (voucher_id);
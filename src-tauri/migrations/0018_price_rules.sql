@@ -0,0 +1,27 @@
+CREATE TABLE customer_tag (
+    customer_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (customer_id, tag),
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE
+);
+
+-- A rule applies if all of its conditions hold, conditions that are NULL or empty always hold.
+-- `weekdays` is a JSON list of ISO weekday numbers.
+CREATE TABLE price_rule (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    customer_tag TEXT,
+    treatment_id INTEGER,
+    min_visit_count INTEGER,
+    weekdays TEXT NOT NULL,
+    start_time TIME,
+    end_time TIME,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE CASCADE
+);
+
+-- Applied rules can't be deleted, so the discount of an item can always be traced back.
+ALTER TABLE appointment_item ADD COLUMN price_rule_id INTEGER
+    CONSTRAINT fk_price_rule REFERENCES price_rule (id);
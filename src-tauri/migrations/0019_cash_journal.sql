@@ -0,0 +1,51 @@
+-- Append-only journal of the cash register. Ids are assigned without gaps and every entry
+-- contains the SHA-256 hash of its content and the hash of the previous entry, so changed or
+-- removed rows break the chain.
+CREATE TABLE cash_journal_entry (
+    id INTEGER PRIMARY KEY,
+    created_at TEXT NOT NULL,
+    date DATE NOT NULL,
+    kind TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    -- No foreign key, the entry has to outlive the payment.
+    payment_id INTEGER,
+    description TEXT NOT NULL,
+    previous_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX idx_cash_journal_entry_payment ON cash_journal_entry (payment_id);
+
+-- Daily closing, the counted cash becomes the opening balance of the next day. `last_entry_id`
+-- and `last_entry_hash` pin the state of the journal at the time of the closing.
+CREATE TABLE cash_closing (
+    id INTEGER PRIMARY KEY,
+    date DATE NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    expected INTEGER NOT NULL,
+    counted INTEGER NOT NULL,
+    note TEXT NOT NULL,
+    last_entry_id INTEGER NOT NULL,
+    last_entry_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE TRIGGER cash_journal_entry_update BEFORE UPDATE ON cash_journal_entry
+BEGIN
+    SELECT RAISE(ABORT, 'cash journal entries cannot be changed');
+END;
+
+CREATE TRIGGER cash_journal_entry_delete BEFORE DELETE ON cash_journal_entry
+BEGIN
+    SELECT RAISE(ABORT, 'cash journal entries cannot be deleted');
+END;
+
+CREATE TRIGGER cash_closing_update BEFORE UPDATE ON cash_closing
+BEGIN
+    SELECT RAISE(ABORT, 'cash closings cannot be changed');
+END;
+
+CREATE TRIGGER cash_closing_delete BEFORE DELETE ON cash_closing
+BEGIN
+    SELECT RAISE(ABORT, 'cash closings cannot be deleted');
+END;
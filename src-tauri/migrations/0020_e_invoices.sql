@@ -0,0 +1,14 @@
+-- Reference of the buyer for electronic invoices, e.g. an order number or the Leitweg-ID of a
+-- public authority.
+ALTER TABLE invoice ADD COLUMN buyer_reference TEXT NOT NULL DEFAULT '';
+
+DROP TRIGGER invoice_issued_update;
+
+CREATE TRIGGER invoice_issued_update BEFORE UPDATE OF
+    kind, number, sequence_year, sequence, customer_name, address_street, address_city,
+    issue_date, due_date, currency, cancelled_invoice_id, note, buyer_reference
+ON invoice
+WHEN OLD.status <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
@@ -0,0 +1,8 @@
+-- Employees with appointments are archived instead of deleted, so that the history keeps
+-- showing who performed an appointment.
+ALTER TABLE employee ADD COLUMN phone TEXT NOT NULL DEFAULT '';
+ALTER TABLE employee ADD COLUMN email TEXT NOT NULL DEFAULT '';
+ALTER TABLE employee ADD COLUMN color TEXT NOT NULL DEFAULT '';
+ALTER TABLE employee ADD COLUMN employed_from DATE;
+ALTER TABLE employee ADD COLUMN employed_until DATE;
+ALTER TABLE employee ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
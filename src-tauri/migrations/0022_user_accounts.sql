@@ -0,0 +1,10 @@
+CREATE TABLE user_account (
+    id INTEGER PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    -- Argon2id hash in the PHC string format.
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('admin', 'reception', 'therapist')),
+    employee_id INTEGER UNIQUE,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    CONSTRAINT fk_employee FOREIGN KEY (employee_id) REFERENCES employee (id) ON DELETE SET NULL
+);
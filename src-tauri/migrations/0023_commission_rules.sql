@@ -0,0 +1,15 @@
+-- Commission of an employee on the items of their appointments. A rule with a treatment applies
+-- before a rule with a category, which applies before a general rule. Only one rule applies to
+-- an item, so commissions never stack.
+CREATE TABLE commission_rule (
+    id INTEGER PRIMARY KEY,
+    employee_id INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('percentage', 'fixed')),
+    value INTEGER NOT NULL,
+    treatment_category TEXT,
+    treatment_id INTEGER,
+    CONSTRAINT fk_employee FOREIGN KEY (employee_id) REFERENCES employee (id) ON DELETE CASCADE,
+    CONSTRAINT fk_treatment FOREIGN KEY (treatment_id) REFERENCES treatment (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_commission_rule_employee ON commission_rule (employee_id);
@@ -0,0 +1,29 @@
+-- Electronic invoices need the postcode of the buyer as a separate field. It was entered as part
+-- of the city so far, e.g. "10115 Berlin", and is split off where the first word is a number.
+ALTER TABLE customer ADD COLUMN address_postcode TEXT NOT NULL DEFAULT '';
+
+UPDATE customer
+SET address_postcode = substr(trim(address_city), 1, instr(trim(address_city), ' ') - 1),
+    address_city = trim(substr(trim(address_city), instr(trim(address_city), ' ') + 1))
+WHERE instr(trim(address_city), ' ') > 1
+    AND substr(trim(address_city), 1, instr(trim(address_city), ' ') - 1) NOT GLOB '*[^0-9]*';
+
+ALTER TABLE invoice ADD COLUMN address_postcode TEXT NOT NULL DEFAULT '';
+
+-- The printed address stays the same, so issued invoices are split as well.
+DROP TRIGGER invoice_issued_update;
+
+UPDATE invoice
+SET address_postcode = substr(trim(address_city), 1, instr(trim(address_city), ' ') - 1),
+    address_city = trim(substr(trim(address_city), instr(trim(address_city), ' ') + 1))
+WHERE instr(trim(address_city), ' ') > 1
+    AND substr(trim(address_city), 1, instr(trim(address_city), ' ') - 1) NOT GLOB '*[^0-9]*';
+
+CREATE TRIGGER invoice_issued_update BEFORE UPDATE OF
+    kind, number, sequence_year, sequence, customer_name, address_street, address_postcode,
+    address_city, issue_date, due_date, currency, cancelled_invoice_id, note, buyer_reference
+ON invoice
+WHEN OLD.status <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'issued invoices cannot be changed');
+END;
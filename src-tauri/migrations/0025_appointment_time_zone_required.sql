@@ -0,0 +1,33 @@
+-- The time zone and the UTC instants of older appointments are assigned before this migration
+-- runs, as they depend on the time zone preference. SQLite cannot add NOT NULL to a column, so the
+-- table is rebuilt. This runs with foreign keys off, dropping the old table would otherwise delete
+-- the items, reminders and payment allocations of all appointments.
+CREATE TABLE appointment_new (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    start_date DATETIME NOT NULL,
+    duration_minutes INTEGER NOT NULL,
+    employee_id INTEGER,
+    ical_uid TEXT,
+    time_zone TEXT NOT NULL,
+    start_utc DATETIME NOT NULL,
+    end_utc DATETIME NOT NULL,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE CASCADE,
+    CONSTRAINT fk_employee FOREIGN KEY (employee_id) REFERENCES employee (id) ON DELETE SET NULL
+);
+
+INSERT INTO appointment_new (
+    id, customer_id, number, start_date, duration_minutes, employee_id, ical_uid, time_zone,
+    start_utc, end_utc
+)
+SELECT id, customer_id, number, start_date, duration_minutes, employee_id, ical_uid, time_zone,
+    start_utc, end_utc
+FROM appointment;
+
+DROP TABLE appointment;
+ALTER TABLE appointment_new RENAME TO appointment;
+
+CREATE UNIQUE INDEX idx_appointment_customer_number ON appointment (customer_id, number);
+CREATE UNIQUE INDEX idx_appointment_ical_uid ON appointment (ical_uid) WHERE ical_uid IS NOT NULL;
+CREATE INDEX idx_appointment_utc ON appointment (start_utc, end_utc);
@@ -0,0 +1,4 @@
+-- Commission rules apply to the appointments within their validity, open ends are unbounded. A
+-- changed commission is a new rule, so the reports of past periods stay the same.
+ALTER TABLE commission_rule ADD COLUMN valid_from DATE;
+ALTER TABLE commission_rule ADD COLUMN valid_until DATE;
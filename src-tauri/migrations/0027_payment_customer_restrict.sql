@@ -0,0 +1,25 @@
+-- Payments are kept for the accounting and the vouchers they redeem, so customers with payments
+-- can't be deleted anymore. Like 0025 this runs with foreign keys off, dropping the old table
+-- would otherwise delete the allocations of all payments.
+CREATE TABLE payment_new (
+    id INTEGER PRIMARY KEY,
+    customer_id INTEGER NOT NULL,
+    date DATE NOT NULL,
+    amount INTEGER NOT NULL,
+    method TEXT NOT NULL,
+    reference TEXT NOT NULL,
+    note TEXT NOT NULL,
+    voucher_id INTEGER,
+    CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customer (id) ON DELETE RESTRICT,
+    CONSTRAINT fk_voucher FOREIGN KEY (voucher_id) REFERENCES voucher (id)
+);
+
+INSERT INTO payment_new (id, customer_id, date, amount, method, reference, note, voucher_id)
+SELECT id, customer_id, date, amount, method, reference, note, voucher_id
+FROM payment;
+
+DROP TABLE payment;
+ALTER TABLE payment_new RENAME TO payment;
+
+CREATE INDEX idx_payment_customer ON payment (customer_id);
+CREATE INDEX idx_payment_voucher ON payment (voucher_id);
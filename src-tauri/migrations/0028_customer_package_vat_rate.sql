@@ -0,0 +1,11 @@
+-- Sold packages are revenue at the VAT rate of their treatment. Packages sold before take the
+-- rate of their treatment, or the standard rate like prices before VAT rates were tracked.
+ALTER TABLE customer_package ADD COLUMN vat_rate INTEGER NOT NULL DEFAULT 1900;
+
+UPDATE customer_package
+SET vat_rate = (
+    SELECT t.vat_rate
+    FROM treatment t
+    WHERE t.id = customer_package.treatment_id
+)
+WHERE treatment_id IN (SELECT id FROM treatment);
@@ -0,0 +1,29 @@
+-- The dates of the reminder queue are UTC instants like the ones of appointments, so changing the
+-- time zone preference doesn't move reminders that are already scheduled. The local dates are
+-- converted by their distance to the appointment, whose UTC start is known. Pending reminders
+-- are scheduled again on the next run anyway.
+UPDATE reminder
+SET due_date = (
+        SELECT strftime(
+            '%Y-%m-%dT%H:%M:%S+00:00',
+            a.start_utc,
+            printf(
+                '%+d seconds',
+                round((julianday(reminder.due_date) - julianday(reminder.appointment_start_date)) * 86400)
+            )
+        )
+        FROM appointment a
+        WHERE a.id = reminder.appointment_id
+    ),
+    next_attempt_date = (
+        SELECT strftime(
+            '%Y-%m-%dT%H:%M:%S+00:00',
+            a.start_utc,
+            printf(
+                '%+d seconds',
+                round((julianday(reminder.next_attempt_date) - julianday(reminder.appointment_start_date)) * 86400)
+            )
+        )
+        FROM appointment a
+        WHERE a.id = reminder.appointment_id
+    );
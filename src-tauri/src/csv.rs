@@ -0,0 +1,58 @@
+//! CSV output for reports.
+//!
+//! Files follow the conventions of spreadsheet programs in the language of the practice: German
+//! files are separated by semicolons and use a decimal comma. A byte order mark makes Excel
+//! read them as UTF-8.
+
+pub struct CsvWriter {
+    separator: char,
+    decimal: char,
+    content: String,
+}
+
+impl CsvWriter {
+    pub fn new(german: bool) -> Self {
+        let (separator, decimal) = if german { (';', ',') } else { (',', '.') };
+        Self {
+            separator,
+            decimal,
+            content: String::from("\u{feff}"),
+        }
+    }
+
+    pub fn row<S: AsRef<str>>(&mut self, fields: &[S]) {
+        for (index, field) in fields.iter().enumerate() {
+            if index > 0 {
+                self.content.push(self.separator);
+            }
+            let field = field.as_ref();
+            if field.contains([self.separator, '"', '\n', '\r']) {
+                self.content.push('"');
+                self.content.push_str(&field.replace('"', "\"\""));
+                self.content.push('"');
+            } else {
+                self.content.push_str(field);
+            }
+        }
+        self.content.push_str("\r\n");
+    }
+
+    /// Amount in minor units as a plain decimal number without currency.
+    pub fn amount(&self, amount: i64) -> String {
+        format!(
+            "{}{}{}{:02}",
+            if amount < 0 { "-" } else { "" },
+            amount.unsigned_abs() / 100,
+            self.decimal,
+            amount.unsigned_abs() % 100
+        )
+    }
+
+    pub fn decimal(&self, value: f64, precision: usize) -> String {
+        format!("{:.*}", precision, value).replace('.', &self.decimal.to_string())
+    }
+
+    pub fn finish(self) -> String {
+        self.content
+    }
+}
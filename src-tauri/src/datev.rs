@@ -0,0 +1,219 @@
+//! DATEV booking batches in the ASCII format (EXTF, format category 21).
+//!
+//! Files start with the batch header and the column names, followed by one line per booking.
+//! Fields are separated by semicolons, text fields are quoted and the file is encoded in
+//! Windows-1252 as the DATEV import expects.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// Columns of a booking line, the import maps them by name.
+const COLUMNS: [&str; 14] = [
+    "Umsatz (ohne Soll/Haben-Kz)",
+    "Soll/Haben-Kennzeichen",
+    "WKZ Umsatz",
+    "Kurs",
+    "Basis-Umsatz",
+    "WKZ Basis-Umsatz",
+    "Konto",
+    "Gegenkonto (ohne BU-Schlüssel)",
+    "BU-Schlüssel",
+    "Belegdatum",
+    "Belegfeld 1",
+    "Belegfeld 2",
+    "Skonto",
+    "Buchungstext",
+];
+
+pub struct DatevHeader<'a> {
+    pub created_at: NaiveDateTime,
+    pub consultant_number: i64,
+    pub client_number: i64,
+    pub fiscal_year_start: NaiveDate,
+    pub account_length: i64,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub label: &'a str,
+    pub currency: &'a str,
+}
+
+/// Booking of `amount` on `account` against `contra_account`. Positive amounts are debited to
+/// the account, negative ones credited.
+pub struct DatevBooking<'a> {
+    pub amount: i64,
+    pub account: i64,
+    pub contra_account: i64,
+    pub date: NaiveDate,
+    pub document: &'a str,
+    pub text: &'a str,
+}
+
+pub struct DatevWriter {
+    currency: String,
+    content: String,
+}
+
+impl DatevWriter {
+    pub fn new(header: &DatevHeader) -> Self {
+        let mut writer = Self {
+            currency: header.currency.to_string(),
+            content: String::new(),
+        };
+        writer.line(&[
+            text("EXTF"),
+            "700".to_string(),
+            "21".to_string(),
+            text("Buchungsstapel"),
+            "13".to_string(),
+            header.created_at.format("%Y%m%d%H%M%S%3f").to_string(),
+            String::new(),
+            text("RE"),
+            text(""),
+            text(""),
+            header.consultant_number.to_string(),
+            header.client_number.to_string(),
+            header.fiscal_year_start.format("%Y%m%d").to_string(),
+            header.account_length.to_string(),
+            header.from.format("%Y%m%d").to_string(),
+            header.to.format("%Y%m%d").to_string(),
+            text(&truncate(header.label, 30)),
+            text(""),
+            // Financial accounting, bookings are not locked after the import.
+            "1".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            text(header.currency),
+            String::new(),
+            text(""),
+            String::new(),
+            String::new(),
+            text(""),
+            String::new(),
+            String::new(),
+            text(""),
+            text(""),
+        ]);
+        writer.line(&COLUMNS.map(str::to_string));
+        writer
+    }
+
+    pub fn booking(&mut self, booking: &DatevBooking) {
+        let currency = text(&self.currency);
+        self.line(&[
+            amount(booking.amount.abs()),
+            text(if booking.amount < 0 { "H" } else { "S" }),
+            currency,
+            String::new(),
+            String::new(),
+            String::new(),
+            booking.account.to_string(),
+            booking.contra_account.to_string(),
+            text(""),
+            booking.date.format("%d%m").to_string(),
+            text(&document(booking.document)),
+            text(""),
+            String::new(),
+            text(&truncate(booking.text, 60)),
+        ]);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.content.chars().map(windows_1252).collect()
+    }
+
+    fn line(&mut self, fields: &[String]) {
+        self.content.push_str(&fields.join(";"));
+        self.content.push_str("\r\n");
+    }
+}
+
+fn text(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value.replace(['\r', '\n'], " ").replace('"', "\"\"")
+    )
+}
+
+/// Amount in minor units with a decimal comma.
+fn amount(amount: i64) -> String {
+    format!("{},{:02}", amount / 100, amount % 100)
+}
+
+fn truncate(value: &str, length: usize) -> String {
+    value.chars().take(length).collect()
+}
+
+/// Document numbers may only contain letters, digits and `$%&*+-/`.
+fn document(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "$%&*+-/".contains(*c))
+        .take(36)
+        .collect()
+}
+
+/// Latin-1 characters keep their code, the euro sign is the only other character of
+/// Windows-1252 that is likely to appear in names and texts.
+fn windows_1252(c: char) -> u8 {
+    match c as u32 {
+        code @ (0x00..=0x7f | 0xa0..=0xff) => code as u8,
+        0x20ac => 0x80,
+        _ => b'?',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn booking_batch() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        let mut writer = DatevWriter::new(&DatevHeader {
+            created_at: date(4, 2).and_hms_milli_opt(8, 30, 5, 120).unwrap(),
+            consultant_number: 1001,
+            client_number: 42,
+            fiscal_year_start: date(1, 1),
+            account_length: 4,
+            from: date(3, 1),
+            to: date(3, 31),
+            label: "Erlöse und Zahlungen",
+            currency: "EUR",
+        });
+        writer.booking(&DatevBooking {
+            amount: 5950,
+            account: 10000,
+            contra_account: 8400,
+            date: date(3, 4),
+            document: "2024-0001",
+            text: "Jörg \"Jo\" Weiß",
+        });
+        writer.booking(&DatevBooking {
+            amount: -1205,
+            account: 10000,
+            contra_account: 8300,
+            date: date(3, 15),
+            document: "R 2024/7 (€)",
+            text: "Öl\r\nCreme",
+        });
+
+        let content = writer.finish();
+        let lines: Vec<&[u8]> = content.split_inclusive(|&b| b == b'\n').collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            b"\"EXTF\";700;21;\"Buchungsstapel\";13;20240402083005120;;\"RE\";\"\";\"\";1001;42;\
+              20240101;4;20240301;20240331;\"Erl\xf6se und Zahlungen\";\"\";1;0;0;\"EUR\";;\"\";;;\
+              \"\";;;\"\";\"\"\r\n"
+        );
+        assert!(lines[1].starts_with(b"Umsatz (ohne Soll/Haben-Kz);Soll/Haben-Kennzeichen;"));
+        assert_eq!(
+            lines[2],
+            b"59,50;\"S\";\"EUR\";;;;10000;8400;\"\";0403;\"2024-0001\";\"\";;\
+              \"J\xf6rg \"\"Jo\"\" Wei\xdf\"\r\n"
+        );
+        assert_eq!(
+            lines[3],
+            b"12,05;\"H\";\"EUR\";;;;10000;8300;\"\";1503;\"R2024/7\";\"\";;\"\xd6l  Creme\"\r\n"
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! Electronic invoices following XRechnung 3.0, the German CIUS of EN 16931.
+//!
+//! Invoices are written in the OASIS UBL or the UN/CEFACT CII syntax. Prices are gross prices
+//! in this application, so the net amounts of the lines are derived from the VAT breakdown of
+//! the invoice and always add up to it. Credit notes store negated items and are written with
+//! positive amounts as the standard requires.
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+use crate::{
+    model::{InvoiceDto, InvoiceKind},
+    money,
+};
+
+const CUSTOMIZATION_ID: &str =
+    "urn:cen.eu:en16931:2017#compliant#urn:xeinkauf.de:kosit:xrechnung_3.0";
+const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+/// File name of the invoice embedded into a ZUGFeRD PDF of the XRECHNUNG profile.
+pub const ZUGFERD_FILE_NAME: &str = "xrechnung.xml";
+
+/// Items without VAT are treated as exempt, the reason is required for this category.
+const EXEMPTION_REASON: &str = "Umsatzsteuerbefreit";
+
+pub struct Party {
+    pub name: String,
+    pub street: String,
+    pub postcode: String,
+    pub city: String,
+    /// ISO 3166-1 alpha-2 code.
+    pub country: String,
+    /// Electronic address, invoices are delivered by email.
+    pub email: String,
+    pub vat_id: String,
+    pub tax_number: String,
+}
+
+pub struct Contact {
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+}
+
+pub struct EInvoice<'a> {
+    pub invoice: &'a InvoiceDto,
+    pub number: &'a str,
+    pub issue_date: NaiveDate,
+    pub due_date: Option<NaiveDate>,
+    /// Number of the invoice a credit note cancels.
+    pub preceding_number: Option<&'a str>,
+    pub buyer_reference: &'a str,
+    pub seller: Party,
+    pub seller_contact: Contact,
+    pub buyer: Party,
+    pub iban: String,
+    pub bic: String,
+    pub payment_terms: String,
+}
+
+struct Line<'a> {
+    description: &'a str,
+    date: Option<NaiveDate>,
+    quantity: i64,
+    net: i64,
+    vat_rate: i64,
+}
+
+struct Totals {
+    net: i64,
+    tax: i64,
+    gross: i64,
+    /// Net amount and VAT per rate.
+    taxes: Vec<(i64, i64, i64)>,
+}
+
+impl EInvoice<'_> {
+    fn is_credit_note(&self) -> bool {
+        self.invoice.kind == InvoiceKind::CreditNote
+    }
+
+    fn sign(&self) -> i64 {
+        if self.is_credit_note() { -1 } else { 1 }
+    }
+
+    fn currency(&self) -> &'static str {
+        money::currency_code(self.invoice.currency)
+    }
+
+    fn type_code(&self) -> &'static str {
+        if self.is_credit_note() { "381" } else { "380" }
+    }
+
+    /// Credit transfer with the account of the practice, otherwise an undefined payment means.
+    fn payment_means_code(&self) -> &'static str {
+        if self.iban.is_empty() { "1" } else { "58" }
+    }
+
+    fn totals(&self) -> Totals {
+        let sign = self.sign();
+        Totals {
+            net: sign * self.invoice.price.net.amount,
+            tax: sign * self.invoice.price.tax.amount,
+            gross: sign * self.invoice.price.gross.amount,
+            taxes: self
+                .invoice
+                .taxes
+                .iter()
+                .map(|tax| (tax.vat_rate, sign * tax.net.amount, sign * tax.tax.amount))
+                .collect(),
+        }
+    }
+
+    /// The net amounts of the lines are split from their gross amounts, a rounding difference
+    /// to the breakdown is added to the largest line of the rate.
+    fn lines(&self) -> Vec<Line<'_>> {
+        let sign = self.sign();
+        let mut lines: Vec<Line> = self
+            .invoice
+            .items
+            .iter()
+            .map(|item| Line {
+                description: &item.description,
+                date: item.date,
+                quantity: sign * item.quantity,
+                net: sign * item.total.net.amount,
+                vat_rate: item.vat_rate,
+            })
+            .collect();
+
+        let mut sums: BTreeMap<i64, i64> = BTreeMap::new();
+        for line in &lines {
+            *sums.entry(line.vat_rate).or_default() += line.net;
+        }
+        for tax in &self.invoice.taxes {
+            let difference = sign * tax.net.amount - sums.get(&tax.vat_rate).unwrap_or(&0);
+            if let Some(line) = lines
+                .iter_mut()
+                .filter(|line| line.vat_rate == tax.vat_rate)
+                .max_by_key(|line| line.net.abs())
+            {
+                line.net += difference;
+            }
+        }
+        lines
+    }
+}
+
+pub fn ubl(e_invoice: &EInvoice) -> String {
+    let credit_note = e_invoice.is_credit_note();
+    let currency = e_invoice.currency();
+    let totals = e_invoice.totals();
+    let (root, namespace, line_tag, quantity_tag) = if credit_note {
+        (
+            "ubl:CreditNote",
+            "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2",
+            "cac:CreditNoteLine",
+            "cbc:CreditedQuantity",
+        )
+    } else {
+        (
+            "ubl:Invoice",
+            "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2",
+            "cac:InvoiceLine",
+            "cbc:InvoicedQuantity",
+        )
+    };
+
+    let mut xml = Xml::new();
+    xml.start(
+        root,
+        &[
+            ("xmlns:ubl", namespace),
+            (
+                "xmlns:cac",
+                "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2",
+            ),
+            (
+                "xmlns:cbc",
+                "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2",
+            ),
+        ],
+    );
+    xml.element("cbc:CustomizationID", &[], CUSTOMIZATION_ID);
+    xml.element("cbc:ProfileID", &[], PROFILE_ID);
+    xml.element("cbc:ID", &[], e_invoice.number);
+    xml.element("cbc:IssueDate", &[], &ubl_date(e_invoice.issue_date));
+    if credit_note {
+        xml.element("cbc:CreditNoteTypeCode", &[], e_invoice.type_code());
+    } else {
+        if let Some(due_date) = e_invoice.due_date {
+            xml.element("cbc:DueDate", &[], &ubl_date(due_date));
+        }
+        xml.element("cbc:InvoiceTypeCode", &[], e_invoice.type_code());
+    }
+    if !e_invoice.invoice.note.trim().is_empty() {
+        xml.element("cbc:Note", &[], e_invoice.invoice.note.trim());
+    }
+    xml.element("cbc:DocumentCurrencyCode", &[], currency);
+    xml.element("cbc:BuyerReference", &[], e_invoice.buyer_reference);
+    if let Some(preceding_number) = e_invoice.preceding_number {
+        xml.start("cac:BillingReference", &[]);
+        xml.start("cac:InvoiceDocumentReference", &[]);
+        xml.element("cbc:ID", &[], preceding_number);
+        xml.end();
+        xml.end();
+    }
+
+    xml.start("cac:AccountingSupplierParty", &[]);
+    ubl_party(&mut xml, &e_invoice.seller, Some(&e_invoice.seller_contact));
+    xml.end();
+    xml.start("cac:AccountingCustomerParty", &[]);
+    ubl_party(&mut xml, &e_invoice.buyer, None);
+    xml.end();
+
+    xml.start("cac:PaymentMeans", &[]);
+    xml.element("cbc:PaymentMeansCode", &[], e_invoice.payment_means_code());
+    if !e_invoice.iban.is_empty() {
+        xml.start("cac:PayeeFinancialAccount", &[]);
+        xml.element("cbc:ID", &[], &e_invoice.iban);
+        if !e_invoice.bic.is_empty() {
+            xml.start("cac:FinancialInstitutionBranch", &[]);
+            xml.element("cbc:ID", &[], &e_invoice.bic);
+            xml.end();
+        }
+        xml.end();
+    }
+    xml.end();
+    xml.start("cac:PaymentTerms", &[]);
+    xml.element("cbc:Note", &[], &e_invoice.payment_terms);
+    xml.end();
+
+    xml.start("cac:TaxTotal", &[]);
+    xml.element(
+        "cbc:TaxAmount",
+        &[("currencyID", currency)],
+        &amount(totals.tax),
+    );
+    for (vat_rate, net, tax) in &totals.taxes {
+        xml.start("cac:TaxSubtotal", &[]);
+        xml.element(
+            "cbc:TaxableAmount",
+            &[("currencyID", currency)],
+            &amount(*net),
+        );
+        xml.element("cbc:TaxAmount", &[("currencyID", currency)], &amount(*tax));
+        ubl_tax_category(&mut xml, "cac:TaxCategory", *vat_rate, true);
+        xml.end();
+    }
+    xml.end();
+
+    xml.start("cac:LegalMonetaryTotal", &[]);
+    for (tag, value) in [
+        ("cbc:LineExtensionAmount", totals.net),
+        ("cbc:TaxExclusiveAmount", totals.net),
+        ("cbc:TaxInclusiveAmount", totals.gross),
+        ("cbc:PayableAmount", totals.gross),
+    ] {
+        xml.element(tag, &[("currencyID", currency)], &amount(value));
+    }
+    xml.end();
+
+    for (index, line) in e_invoice.lines().iter().enumerate() {
+        xml.start(line_tag, &[]);
+        xml.element("cbc:ID", &[], &(index + 1).to_string());
+        xml.element(
+            quantity_tag,
+            &[("unitCode", "C62")],
+            &line.quantity.to_string(),
+        );
+        xml.element(
+            "cbc:LineExtensionAmount",
+            &[("currencyID", currency)],
+            &amount(line.net),
+        );
+        if let Some(date) = line.date {
+            xml.start("cac:InvoicePeriod", &[]);
+            xml.element("cbc:StartDate", &[], &ubl_date(date));
+            xml.element("cbc:EndDate", &[], &ubl_date(date));
+            xml.end();
+        }
+        xml.start("cac:Item", &[]);
+        xml.element("cbc:Name", &[], line.description);
+        ubl_tax_category(&mut xml, "cac:ClassifiedTaxCategory", line.vat_rate, false);
+        xml.end();
+        xml.start("cac:Price", &[]);
+        xml.element(
+            "cbc:PriceAmount",
+            &[("currencyID", currency)],
+            &unit_price(line.net, line.quantity),
+        );
+        xml.end();
+        xml.end();
+    }
+
+    xml.end();
+    xml.finish()
+}
+
+fn ubl_party(xml: &mut Xml, party: &Party, contact: Option<&Contact>) {
+    xml.start("cac:Party", &[]);
+    xml.element("cbc:EndpointID", &[("schemeID", "EM")], &party.email);
+    xml.start("cac:PostalAddress", &[]);
+    xml.element("cbc:StreetName", &[], &party.street);
+    xml.element("cbc:CityName", &[], &party.city);
+    xml.element("cbc:PostalZone", &[], &party.postcode);
+    xml.start("cac:Country", &[]);
+    xml.element("cbc:IdentificationCode", &[], &party.country);
+    xml.end();
+    xml.end();
+    for (id, scheme) in [(&party.vat_id, "VAT"), (&party.tax_number, "FC")] {
+        if !id.is_empty() {
+            xml.start("cac:PartyTaxScheme", &[]);
+            xml.element("cbc:CompanyID", &[], id);
+            xml.start("cac:TaxScheme", &[]);
+            xml.element("cbc:ID", &[], scheme);
+            xml.end();
+            xml.end();
+        }
+    }
+    xml.start("cac:PartyLegalEntity", &[]);
+    xml.element("cbc:RegistrationName", &[], &party.name);
+    xml.end();
+    if let Some(contact) = contact {
+        xml.start("cac:Contact", &[]);
+        xml.element("cbc:Name", &[], &contact.name);
+        xml.element("cbc:Telephone", &[], &contact.phone);
+        xml.element("cbc:ElectronicMail", &[], &contact.email);
+        xml.end();
+    }
+    xml.end();
+}
+
+fn ubl_tax_category(xml: &mut Xml, tag: &'static str, vat_rate: i64, with_reason: bool) {
+    xml.start(tag, &[]);
+    xml.element("cbc:ID", &[], category_code(vat_rate));
+    xml.element("cbc:Percent", &[], &percent(vat_rate));
+    if with_reason && vat_rate == 0 {
+        xml.element("cbc:TaxExemptionReason", &[], EXEMPTION_REASON);
+    }
+    xml.start("cac:TaxScheme", &[]);
+    xml.element("cbc:ID", &[], "VAT");
+    xml.end();
+    xml.end();
+}
+
+pub fn cii(e_invoice: &EInvoice) -> String {
+    let currency = e_invoice.currency();
+    let totals = e_invoice.totals();
+
+    let mut xml = Xml::new();
+    xml.start(
+        "rsm:CrossIndustryInvoice",
+        &[
+            (
+                "xmlns:rsm",
+                "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100",
+            ),
+            (
+                "xmlns:ram",
+                "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100",
+            ),
+            (
+                "xmlns:udt",
+                "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100",
+            ),
+        ],
+    );
+
+    xml.start("rsm:ExchangedDocumentContext", &[]);
+    xml.start("ram:BusinessProcessSpecifiedDocumentContextParameter", &[]);
+    xml.element("ram:ID", &[], PROFILE_ID);
+    xml.end();
+    xml.start("ram:GuidelineSpecifiedDocumentContextParameter", &[]);
+    xml.element("ram:ID", &[], CUSTOMIZATION_ID);
+    xml.end();
+    xml.end();
+
+    xml.start("rsm:ExchangedDocument", &[]);
+    xml.element("ram:ID", &[], e_invoice.number);
+    xml.element("ram:TypeCode", &[], e_invoice.type_code());
+    cii_date(&mut xml, "ram:IssueDateTime", e_invoice.issue_date);
+    if !e_invoice.invoice.note.trim().is_empty() {
+        xml.start("ram:IncludedNote", &[]);
+        xml.element("ram:Content", &[], e_invoice.invoice.note.trim());
+        xml.end();
+    }
+    xml.end();
+
+    xml.start("rsm:SupplyChainTradeTransaction", &[]);
+    for (index, line) in e_invoice.lines().iter().enumerate() {
+        xml.start("ram:IncludedSupplyChainTradeLineItem", &[]);
+        xml.start("ram:AssociatedDocumentLineDocument", &[]);
+        xml.element("ram:LineID", &[], &(index + 1).to_string());
+        xml.end();
+        xml.start("ram:SpecifiedTradeProduct", &[]);
+        xml.element("ram:Name", &[], line.description);
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeAgreement", &[]);
+        xml.start("ram:NetPriceProductTradePrice", &[]);
+        xml.element(
+            "ram:ChargeAmount",
+            &[],
+            &unit_price(line.net, line.quantity),
+        );
+        xml.end();
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeDelivery", &[]);
+        xml.element(
+            "ram:BilledQuantity",
+            &[("unitCode", "C62")],
+            &line.quantity.to_string(),
+        );
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeSettlement", &[]);
+        xml.start("ram:ApplicableTradeTax", &[]);
+        xml.element("ram:TypeCode", &[], "VAT");
+        xml.element("ram:CategoryCode", &[], category_code(line.vat_rate));
+        xml.element("ram:RateApplicablePercent", &[], &percent(line.vat_rate));
+        xml.end();
+        if let Some(date) = line.date {
+            xml.start("ram:BillingSpecifiedPeriod", &[]);
+            cii_date(&mut xml, "ram:StartDateTime", date);
+            cii_date(&mut xml, "ram:EndDateTime", date);
+            xml.end();
+        }
+        xml.start("ram:SpecifiedTradeSettlementLineMonetarySummation", &[]);
+        xml.element("ram:LineTotalAmount", &[], &amount(line.net));
+        xml.end();
+        xml.end();
+        xml.end();
+    }
+
+    xml.start("ram:ApplicableHeaderTradeAgreement", &[]);
+    xml.element("ram:BuyerReference", &[], e_invoice.buyer_reference);
+    cii_party(
+        &mut xml,
+        "ram:SellerTradeParty",
+        &e_invoice.seller,
+        Some(&e_invoice.seller_contact),
+    );
+    cii_party(&mut xml, "ram:BuyerTradeParty", &e_invoice.buyer, None);
+    xml.end();
+
+    xml.start("ram:ApplicableHeaderTradeDelivery", &[]);
+    xml.end();
+
+    xml.start("ram:ApplicableHeaderTradeSettlement", &[]);
+    xml.element("ram:InvoiceCurrencyCode", &[], currency);
+    xml.start("ram:SpecifiedTradeSettlementPaymentMeans", &[]);
+    xml.element("ram:TypeCode", &[], e_invoice.payment_means_code());
+    if !e_invoice.iban.is_empty() {
+        xml.start("ram:PayeePartyCreditorFinancialAccount", &[]);
+        xml.element("ram:IBANID", &[], &e_invoice.iban);
+        xml.end();
+        if !e_invoice.bic.is_empty() {
+            xml.start("ram:PayeeSpecifiedCreditorFinancialInstitution", &[]);
+            xml.element("ram:BICID", &[], &e_invoice.bic);
+            xml.end();
+        }
+    }
+    xml.end();
+    for (vat_rate, net, tax) in &totals.taxes {
+        xml.start("ram:ApplicableTradeTax", &[]);
+        xml.element("ram:CalculatedAmount", &[], &amount(*tax));
+        xml.element("ram:TypeCode", &[], "VAT");
+        if *vat_rate == 0 {
+            xml.element("ram:ExemptionReason", &[], EXEMPTION_REASON);
+        }
+        xml.element("ram:BasisAmount", &[], &amount(*net));
+        xml.element("ram:CategoryCode", &[], category_code(*vat_rate));
+        xml.element("ram:RateApplicablePercent", &[], &percent(*vat_rate));
+        xml.end();
+    }
+    xml.start("ram:SpecifiedTradePaymentTerms", &[]);
+    xml.element("ram:Description", &[], &e_invoice.payment_terms);
+    if let Some(due_date) = e_invoice.due_date
+        && !e_invoice.is_credit_note()
+    {
+        cii_date(&mut xml, "ram:DueDateDateTime", due_date);
+    }
+    xml.end();
+    xml.start("ram:SpecifiedTradeSettlementHeaderMonetarySummation", &[]);
+    xml.element("ram:LineTotalAmount", &[], &amount(totals.net));
+    xml.element("ram:TaxBasisTotalAmount", &[], &amount(totals.net));
+    xml.element(
+        "ram:TaxTotalAmount",
+        &[("currencyID", currency)],
+        &amount(totals.tax),
+    );
+    xml.element("ram:GrandTotalAmount", &[], &amount(totals.gross));
+    xml.element("ram:DuePayableAmount", &[], &amount(totals.gross));
+    xml.end();
+    if let Some(preceding_number) = e_invoice.preceding_number {
+        xml.start("ram:InvoiceReferencedDocument", &[]);
+        xml.element("ram:IssuerAssignedID", &[], preceding_number);
+        xml.end();
+    }
+    xml.end();
+
+    xml.end();
+    xml.end();
+    xml.finish()
+}
+
+fn cii_party(xml: &mut Xml, tag: &'static str, party: &Party, contact: Option<&Contact>) {
+    xml.start(tag, &[]);
+    xml.element("ram:Name", &[], &party.name);
+    if let Some(contact) = contact {
+        xml.start("ram:DefinedTradeContact", &[]);
+        xml.element("ram:PersonName", &[], &contact.name);
+        xml.start("ram:TelephoneUniversalCommunication", &[]);
+        xml.element("ram:CompleteNumber", &[], &contact.phone);
+        xml.end();
+        xml.start("ram:EmailURIUniversalCommunication", &[]);
+        xml.element("ram:URIID", &[], &contact.email);
+        xml.end();
+        xml.end();
+    }
+    xml.start("ram:PostalTradeAddress", &[]);
+    xml.element("ram:PostcodeCode", &[], &party.postcode);
+    xml.element("ram:LineOne", &[], &party.street);
+    xml.element("ram:CityName", &[], &party.city);
+    xml.element("ram:CountryID", &[], &party.country);
+    xml.end();
+    xml.start("ram:URIUniversalCommunication", &[]);
+    xml.element("ram:URIID", &[("schemeID", "EM")], &party.email);
+    xml.end();
+    for (id, scheme) in [(&party.vat_id, "VA"), (&party.tax_number, "FC")] {
+        if !id.is_empty() {
+            xml.start("ram:SpecifiedTaxRegistration", &[]);
+            xml.element("ram:ID", &[("schemeID", scheme)], id);
+            xml.end();
+        }
+    }
+    xml.end();
+}
+
+fn cii_date(xml: &mut Xml, tag: &'static str, date: NaiveDate) {
+    xml.start(tag, &[]);
+    xml.element(
+        "udt:DateTimeString",
+        &[("format", "102")],
+        &date.format("%Y%m%d").to_string(),
+    );
+    xml.end();
+}
+
+/// XMP descriptions that mark a PDF/A-3 as ZUGFeRD invoice with the embedded CII document. The
+/// Factur-X namespace is no predefined XMP schema, so PDF/A requires its extension schema.
+pub fn zugferd_metadata() -> String {
+    let property = |name: &str, description: &str| {
+        format!(
+            r#"                <rdf:li rdf:parseType="Resource">
+                  <pdfaProperty:name>{}</pdfaProperty:name>
+                  <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+                  <pdfaProperty:category>external</pdfaProperty:category>
+                  <pdfaProperty:description>{}</pdfaProperty:description>
+                </rdf:li>
+"#,
+            name, description
+        )
+    };
+    format!(
+        r#"    <rdf:Description rdf:about=""
+        xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/"
+        xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#"
+        xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+      <pdfaExtension:schemas>
+        <rdf:Bag>
+          <rdf:li rdf:parseType="Resource">
+            <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>
+            <pdfaSchema:namespaceURI>{namespace}</pdfaSchema:namespaceURI>
+            <pdfaSchema:prefix>fx</pdfaSchema:prefix>
+            <pdfaSchema:property>
+              <rdf:Seq>
+{properties}              </rdf:Seq>
+            </pdfaSchema:property>
+          </rdf:li>
+        </rdf:Bag>
+      </pdfaExtension:schemas>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:fx="{namespace}">
+      <fx:DocumentType>INVOICE</fx:DocumentType>
+      <fx:DocumentFileName>{file_name}</fx:DocumentFileName>
+      <fx:Version>1.0</fx:Version>
+      <fx:ConformanceLevel>XRECHNUNG</fx:ConformanceLevel>
+    </rdf:Description>
+"#,
+        namespace = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#",
+        properties = [
+            property("DocumentFileName", "Name of the embedded XML invoice file"),
+            property("DocumentType", "INVOICE"),
+            property("Version", "Version of the XML schema"),
+            property("ConformanceLevel", "Conformance level of the invoice"),
+        ]
+        .concat(),
+        file_name = ZUGFERD_FILE_NAME,
+    )
+}
+
+fn category_code(vat_rate: i64) -> &'static str {
+    if vat_rate == 0 { "E" } else { "S" }
+}
+
+fn ubl_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Amount in minor units with a decimal point.
+fn amount(amount: i64) -> String {
+    format!(
+        "{}{}.{:02}",
+        if amount < 0 { "-" } else { "" },
+        amount.unsigned_abs() / 100,
+        amount.unsigned_abs() % 100
+    )
+}
+
+/// Net price of a single unit with four decimal places, so the price times the quantity
+/// matches the line amount as closely as possible.
+fn unit_price(net: i64, quantity: i64) -> String {
+    let price = if quantity == 0 {
+        0
+    } else {
+        money::round_div(net as i128 * 100, quantity as i128)
+    };
+    format!(
+        "{}{}.{:04}",
+        if price < 0 { "-" } else { "" },
+        price.unsigned_abs() / 10_000,
+        price.unsigned_abs() % 10_000
+    )
+}
+
+/// VAT rate in basis points as percentage without trailing zeros.
+fn percent(vat_rate: i64) -> String {
+    let text = format!("{}.{:02}", vat_rate / 100, vat_rate % 100);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Indented XML without mixed content.
+struct Xml {
+    content: String,
+    open: Vec<&'static str>,
+}
+
+impl Xml {
+    fn new() -> Self {
+        Self {
+            content: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            open: Vec::new(),
+        }
+    }
+
+    fn tag(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+        self.content.push_str(&"  ".repeat(self.open.len()));
+        self.content.push('<');
+        self.content.push_str(tag);
+        for (name, value) in attributes {
+            self.content
+                .push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        self.content.push('>');
+    }
+
+    fn start(&mut self, tag: &'static str, attributes: &[(&str, &str)]) {
+        self.tag(tag, attributes);
+        self.content.push('\n');
+        self.open.push(tag);
+    }
+
+    fn end(&mut self) {
+        let tag = self.open.pop().expect("no open element");
+        self.content.push_str(&"  ".repeat(self.open.len()));
+        self.content.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn element(&mut self, tag: &str, attributes: &[(&str, &str)], text: &str) {
+        self.tag(tag, attributes);
+        self.content.push_str(&escape(text));
+        self.content.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn finish(self) -> String {
+        debug_assert!(self.open.is_empty());
+        self.content
+    }
+}
@@ -3,6 +3,18 @@ use derive_more::Display;
 /// Represent errors in the application
 #[derive(Debug, Display, Clone)]
 pub enum ServiceError {
+    #[display("Bad Request: {}", _0)]
+    BadRequest(String),
+
+    #[display("Conflict: {}", _0)]
+    Conflict(String),
+
+    #[display("Unauthorized: {}", _0)]
+    Unauthorized(String),
+
+    #[display("Forbidden: {}", _0)]
+    Forbidden(String),
+
     #[display("Internal Server Error: '{}'\n{}", _0, _1)]
     InternalServerError(&'static str, String),
 }
@@ -40,12 +52,54 @@ impl From<std::str::Utf8Error> for ServiceError {
     }
 }
 
+impl From<chrono::ParseError> for ServiceError {
+    fn from(error: chrono::ParseError) -> Self {
+        ServiceError::InternalServerError("Date parse error", format!("{}", error))
+    }
+}
+
+impl From<serde_json::Error> for ServiceError {
+    fn from(error: serde_json::Error) -> Self {
+        ServiceError::InternalServerError("Json error", format!("{}", error))
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for ServiceError {
+    fn from(error: lettre::transport::smtp::Error) -> Self {
+        ServiceError::InternalServerError("Smtp error", format!("{}", error))
+    }
+}
+
+impl From<lettre::address::AddressError> for ServiceError {
+    fn from(error: lettre::address::AddressError) -> Self {
+        ServiceError::BadRequest(format!("Invalid e-mail address: {}", error))
+    }
+}
+
+impl From<lettre::error::Error> for ServiceError {
+    fn from(error: lettre::error::Error) -> Self {
+        ServiceError::InternalServerError("E-mail error", format!("{}", error))
+    }
+}
+
 impl From<sqlx::Error> for ServiceError {
     fn from(error: sqlx::Error) -> Self {
         ServiceError::InternalServerError("Sqlx error", format!("{}", error))
     }
 }
 
+impl From<sqlx::migrate::MigrateError> for ServiceError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        ServiceError::InternalServerError("Migration error", format!("{}", error))
+    }
+}
+
+impl From<argon2::password_hash::Error> for ServiceError {
+    fn from(error: argon2::password_hash::Error) -> Self {
+        ServiceError::InternalServerError("Password hash error", format!("{}", error))
+    }
+}
+
 impl<T> From<std::sync::PoisonError<std::sync::RwLockWriteGuard<'_, T>>> for ServiceError {
     fn from(error: std::sync::PoisonError<std::sync::RwLockWriteGuard<'_, T>>) -> Self {
         ServiceError::InternalServerError("Lock poison error", format!("{:?}", error))
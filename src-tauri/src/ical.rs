@@ -0,0 +1,530 @@
+//! Minimal RFC 5545 (iCalendar) support for exchanging appointments with calendar apps.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    time_zone,
+};
+
+const PRODUCT_ID: &str = "-//customer-manager//customer-manager//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+pub struct IcalEvent {
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: Option<String>,
+}
+
+/// An event read from a calendar file, times are resolved to absolute instants.
+pub struct IcalCalendarEvent {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub description: String,
+    pub start: Option<IcalTime>,
+    pub end: Option<IcalTime>,
+    pub duration_minutes: Option<i64>,
+    pub attendees: Vec<IcalAttendee>,
+    pub recurring: bool,
+    pub cancelled: bool,
+}
+
+pub enum IcalTime {
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+pub struct IcalAttendee {
+    pub name: String,
+    pub email: String,
+}
+
+/// Uid of an exported appointment. It only depends on the appointment id and the host of the
+/// installation, so importing the same calendar again updates the events instead of duplicating
+/// them, while calendars of other installations are never mistaken for local appointments.
+pub fn appointment_uid(appointment_id: i64, host: &str) -> String {
+    format!("appointment-{}@{}", appointment_id, host)
+}
+
+/// Reverse of [`appointment_uid`], uids of other hosts are foreign.
+pub fn parse_appointment_uid(uid: &str, host: &str) -> Option<i64> {
+    let (local, uid_host) = uid.rsplit_once('@')?;
+    if uid_host != host {
+        return None;
+    }
+    local.strip_prefix("appointment-")?.parse().ok()
+}
+
+pub fn write_calendar(events: &[IcalEvent]) -> String {
+    let timestamp = format_date_time(Utc::now());
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape_text(&event.uid)));
+        lines.push(format!("DTSTAMP:{}", timestamp));
+        lines.push(format!("DTSTART:{}", format_date_time(event.start)));
+        lines.push(format!("DTEND:{}", format_date_time(event.end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = String::new();
+    for line in lines {
+        fold_line(&mut calendar, &line);
+    }
+    calendar
+}
+
+/// Times are written in UTC, so calendar apps show them correctly in any time zone.
+fn format_date_time(date_time: DateTime<Utc>) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits content lines longer than 75 octets without breaking multi-byte characters.
+fn fold_line(output: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            output.push_str("\r\n ");
+            octets = 1;
+        }
+        output.push(c);
+        octets += c.len_utf8();
+    }
+    output.push_str("\r\n");
+}
+
+/// Reads all `VEVENT`s of a calendar file. Unknown properties and components are ignored.
+/// Floating times without a zone are taken to be in `zone`.
+pub fn parse_calendar(content: &str, zone: Tz) -> ServiceResult<Vec<IcalCalendarEvent>> {
+    let lines = unfold_lines(content);
+
+    if !lines
+        .iter()
+        .any(|line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err(ServiceError::BadRequest(
+            "File is not an iCalendar file".to_string(),
+        ));
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<IcalCalendarEvent> = None;
+    // Nested components like VALARM must not overwrite the properties of the event.
+    let mut nested_depth = 0;
+
+    for line in &lines {
+        let Some(property) = ContentLine::parse(line) else {
+            continue;
+        };
+
+        match (property.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if property.value.eq_ignore_ascii_case("VEVENT") => {
+                current = Some(IcalCalendarEvent {
+                    uid: None,
+                    summary: String::new(),
+                    description: String::new(),
+                    start: None,
+                    end: None,
+                    duration_minutes: None,
+                    attendees: Vec::new(),
+                    recurring: false,
+                    cancelled: false,
+                });
+            }
+            ("BEGIN", Some(_)) => nested_depth += 1,
+            ("END", Some(_)) if nested_depth > 0 => nested_depth -= 1,
+            ("END", Some(_)) if property.value.eq_ignore_ascii_case("VEVENT") => {
+                events.extend(current.take());
+            }
+            (_, Some(_)) if nested_depth > 0 => {}
+            ("UID", Some(event)) => event.uid = Some(property.value.trim().to_string()),
+            ("SUMMARY", Some(event)) => event.summary = unescape_text(&property.value),
+            ("DESCRIPTION", Some(event)) => event.description = unescape_text(&property.value),
+            ("DTSTART", Some(event)) => event.start = parse_time(&property, zone),
+            ("DTEND", Some(event)) => event.end = parse_time(&property, zone),
+            ("DURATION", Some(event)) => event.duration_minutes = parse_duration(&property.value),
+            ("RRULE", Some(event)) | ("RDATE", Some(event)) => event.recurring = true,
+            ("STATUS", Some(event)) => {
+                event.cancelled = property.value.eq_ignore_ascii_case("CANCELLED")
+            }
+            ("ATTENDEE", Some(event)) => {
+                let email = property.value.trim();
+                let email = email
+                    .strip_prefix("mailto:")
+                    .or_else(|| email.strip_prefix("MAILTO:"))
+                    .unwrap_or(email);
+                event.attendees.push(IcalAttendee {
+                    name: property.parameter("CN").unwrap_or_default(),
+                    email: email.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+struct ContentLine {
+    name: String,
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn parse(line: &str) -> Option<Self> {
+        let mut in_quotes = false;
+        let mut separators = Vec::new();
+        let mut value_start = None;
+
+        for (index, c) in line.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => separators.push(index),
+                ':' if !in_quotes => {
+                    value_start = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let value_start = value_start?;
+        let name_end = separators.first().copied().unwrap_or(value_start);
+
+        let mut parameters = Vec::new();
+        let mut bounds = separators;
+        bounds.push(value_start);
+        for pair in bounds.windows(2) {
+            let parameter = &line[pair[0] + 1..pair[1]];
+            if let Some((key, value)) = parameter.split_once('=') {
+                parameters.push((
+                    key.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                ));
+            }
+        }
+
+        Some(Self {
+            name: line[..name_end].to_ascii_uppercase(),
+            parameters,
+            value: line[value_start + 1..].to_string(),
+        })
+    }
+
+    fn parameter(&self, key: &str) -> Option<String> {
+        self.parameters
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    }
+}
+
+fn unfold_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Converts date or date time values. Times with a known `TZID` are resolved in that zone, other
+/// local times in `zone`. Times skipped by a daylight saving change are moved forward.
+fn parse_time(property: &ContentLine, zone: Tz) -> Option<IcalTime> {
+    let value = property.value.trim();
+
+    if property.parameter("VALUE").as_deref() == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(IcalTime::Date);
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let date_time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(IcalTime::DateTime(
+            DateTime::<Utc>::from_naive_utc_and_offset(date_time, Utc),
+        ));
+    }
+
+    let zone = property
+        .parameter("TZID")
+        .and_then(|name| time_zone::parse_time_zone(&name).ok())
+        .unwrap_or(zone);
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|local| IcalTime::DateTime(time_zone::resolve_local_time_lenient(zone, local)))
+}
+
+/// Parses durations like `PT1H30M` or `P1DT2H`, seconds are truncated.
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            _ => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match (c, in_time) {
+                    ('W', false) => 7 * 24 * 3600,
+                    ('D', false) => 24 * 3600,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    // A number without unit is malformed.
+    if !number.is_empty() {
+        return None;
+    }
+
+    Some(sign * seconds / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().to_utc()
+    }
+
+    #[test]
+    fn appointment_uids_belong_to_their_host() {
+        let uid = appointment_uid(42, "3f2a.customer-manager");
+        assert_eq!(uid, "appointment-42@3f2a.customer-manager");
+        assert_eq!(
+            parse_appointment_uid(&uid, "3f2a.customer-manager"),
+            Some(42)
+        );
+        assert_eq!(parse_appointment_uid(&uid, "9c1b.customer-manager"), None);
+        assert_eq!(
+            parse_appointment_uid("appointment-42@customer-manager", "3f2a.customer-manager"),
+            None
+        );
+        assert_eq!(
+            parse_appointment_uid("event-42@3f2a.customer-manager", "3f2a.customer-manager"),
+            None
+        );
+    }
+
+    #[test]
+    fn events_are_written_in_utc_and_escaped() {
+        let calendar = write_calendar(&[IcalEvent {
+            uid: "appointment-1@host".to_string(),
+            start: utc("2024-03-31T00:30:00Z"),
+            end: utc("2024-03-31T01:30:00Z"),
+            summary: "Massage, Öl; Fango\\Wärme".to_string(),
+            description: Some("Anna\r\nBerg".to_string()),
+        }]);
+
+        let lines: Vec<&str> = calendar.split("\r\n").collect();
+        assert_eq!(lines[0], "BEGIN:VCALENDAR");
+        assert!(lines.contains(&"UID:appointment-1@host"));
+        assert!(lines.contains(&"DTSTART:20240331T003000Z"));
+        assert!(lines.contains(&"DTEND:20240331T013000Z"));
+        assert!(lines.contains(&r"SUMMARY:Massage\, Öl\; Fango\\Wärme"));
+        assert!(lines.contains(&r"DESCRIPTION:Anna\nBerg"));
+        assert_eq!(lines[lines.len() - 2], "END:VCALENDAR");
+        assert_eq!(lines[lines.len() - 1], "");
+    }
+
+    #[test]
+    fn long_lines_are_folded_between_characters() {
+        let summary = "ä".repeat(60);
+        let calendar = write_calendar(&[IcalEvent {
+            uid: "appointment-1@host".to_string(),
+            start: utc("2024-03-01T09:00:00Z"),
+            end: utc("2024-03-01T10:00:00Z"),
+            summary: summary.clone(),
+            description: None,
+        }]);
+
+        let lines: Vec<&str> = calendar.split("\r\n").collect();
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("SUMMARY:"))
+            .unwrap();
+        // "SUMMARY:" and 33 two-octet characters fill 74 octets, the next one would exceed 75.
+        assert_eq!(lines[start].len(), 74);
+        assert_eq!(lines[start + 1].len(), 1 + 27 * 2);
+        assert!(lines[start + 1].starts_with(' '));
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+
+        let events = parse_calendar(&calendar, chrono_tz::UTC).unwrap();
+        assert_eq!(events[0].summary, summary);
+    }
+
+    #[test]
+    fn written_events_are_read_back() {
+        let calendar = write_calendar(&[IcalEvent {
+            uid: "appointment-7@host".to_string(),
+            start: utc("2024-10-27T00:30:00Z"),
+            end: utc("2024-10-27T01:30:00Z"),
+            summary: "Massage, Öl; Fango".to_string(),
+            description: Some("Anna\nBerg".to_string()),
+        }]);
+
+        let events = parse_calendar(&calendar, chrono_tz::Europe::Berlin).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.uid.as_deref(), Some("appointment-7@host"));
+        assert_eq!(event.summary, "Massage, Öl; Fango");
+        assert_eq!(event.description, "Anna\nBerg");
+        assert!(matches!(event.start, Some(IcalTime::DateTime(start))
+            if start == utc("2024-10-27T00:30:00Z")));
+        assert!(matches!(event.end, Some(IcalTime::DateTime(end))
+            if end == utc("2024-10-27T01:30:00Z")));
+    }
+
+    #[test]
+    fn times_are_resolved_in_their_zone() {
+        let calendar = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+UID:utc\r
+DTSTART:20240301T090000Z\r
+DURATION:PT1H30M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:zoned\r
+DTSTART;TZID=America/New_York:20240301T090000\r
+DTEND;TZID=America/New_York:20240301T100000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:floating\r
+DTSTART:20240701T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:unknown-zone\r
+DTSTART;TZID=Mitteleuropäische Zeit:20240301T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:all-day\r
+DTSTART;VALUE=DATE:20240301\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_calendar(calendar, chrono_tz::Europe::Berlin).unwrap();
+        let start = |uid: &str| match &events
+            .iter()
+            .find(|event| event.uid.as_deref() == Some(uid))
+            .unwrap()
+            .start
+        {
+            Some(IcalTime::DateTime(start)) => start.to_rfc3339(),
+            Some(IcalTime::Date(date)) => date.to_string(),
+            None => String::new(),
+        };
+
+        assert_eq!(start("utc"), "2024-03-01T09:00:00+00:00");
+        assert_eq!(events[0].duration_minutes, Some(90));
+        assert_eq!(start("zoned"), "2024-03-01T14:00:00+00:00");
+        assert!(matches!(events[1].end, Some(IcalTime::DateTime(end))
+            if end == utc("2024-03-01T15:00:00Z")));
+        // Floating times and unknown zones are taken to be in the practice zone.
+        assert_eq!(start("floating"), "2024-07-01T07:00:00+00:00");
+        assert_eq!(start("unknown-zone"), "2024-03-01T08:00:00+00:00");
+        assert_eq!(start("all-day"), "2024-03-01");
+    }
+
+    #[test]
+    fn nested_components_and_flags_are_handled() {
+        let calendar = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+UID:weekly\r
+SUMMARY:Massage\r
+DTSTART:20240301T090000Z\r
+RRULE:FREQ=WEEKLY\r
+BEGIN:VALARM\r
+DESCRIPTION:Erinnerung\r
+END:VALARM\r
+STATUS:CANCELLED\r
+ATTENDEE;CN=\"Berg; Anna\";ROLE=REQ-PARTICIPANT:MAILTO:anna@example.org\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_calendar(calendar, chrono_tz::Europe::Berlin).unwrap();
+        let event = &events[0];
+        assert!(event.recurring);
+        assert!(event.cancelled);
+        assert_eq!(event.description, "");
+        assert_eq!(event.attendees[0].name, "Berg; Anna");
+        assert_eq!(event.attendees[0].email, "anna@example.org");
+
+        assert!(parse_calendar("BEGIN:VCARD\r\nEND:VCARD\r\n", chrono_tz::UTC).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("PT45M"), Some(45));
+        assert_eq!(parse_duration("P1DT2H"), Some(26 * 60));
+        assert_eq!(parse_duration("P1W"), Some(7 * 24 * 60));
+        assert_eq!(parse_duration("-PT15M"), Some(-15));
+        assert_eq!(parse_duration("PT1H30"), None);
+        assert_eq!(parse_duration("1H"), None);
+    }
+}
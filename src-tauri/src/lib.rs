@@ -1,16 +1,43 @@
+use std::path::PathBuf;
+
+use chrono::{NaiveDate, NaiveDateTime};
 use tauri::{Manager, path::BaseDirectory};
 use tauri_plugin_opener::OpenerExt;
 
+use crate::model::UserRole;
 use crate::state::State;
 
+mod csv;
+mod datev;
+mod e_invoice;
 mod error;
+mod ical;
 mod model;
+mod money;
+mod pdf;
 mod state;
+mod time_zone;
+mod true_type;
+
+/// Roles allowed to call a command.
+const EVERYONE: &[UserRole] = &[UserRole::Admin, UserRole::Reception, UserRole::Therapist];
+const FRONT_DESK: &[UserRole] = &[UserRole::Admin, UserRole::Reception];
+const ADMIN: &[UserRole] = &[UserRole::Admin];
+
+/// Fails unless a user with one of the roles is logged in.
+fn authorize(state: &State, roles: &[UserRole]) -> Result<(), String> {
+    state
+        .user
+        .authorize(roles)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
 
 #[tauri::command]
 async fn get_employee_list(
     state: tauri::State<'_, State>,
 ) -> Result<Vec<model::EmployeeDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .employee
@@ -24,6 +51,7 @@ async fn get_employee_by_id(
     state: tauri::State<'_, State>,
     id: i64,
 ) -> Result<Option<model::EmployeeDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .employee
@@ -37,6 +65,7 @@ async fn store_employee(
     state: tauri::State<'_, State>,
     employee: model::EditEmployeeDto,
 ) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
     state
         .inner()
         .employee
@@ -47,6 +76,7 @@ async fn store_employee(
 
 #[tauri::command]
 async fn delete_employee(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
     state
         .inner()
         .employee
@@ -59,6 +89,7 @@ async fn delete_employee(state: tauri::State<'_, State>, id: i64) -> Result<(),
 async fn get_customer_list(
     state: tauri::State<'_, State>,
 ) -> Result<Vec<model::CustomerDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .customer
@@ -72,6 +103,7 @@ async fn get_customer_by_id(
     state: tauri::State<'_, State>,
     id: i64,
 ) -> Result<Option<model::CustomerDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .customer
@@ -85,6 +117,7 @@ async fn store_customer(
     state: tauri::State<'_, State>,
     customer: model::EditCustomerDto,
 ) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
     state
         .inner()
         .customer
@@ -95,6 +128,7 @@ async fn store_customer(
 
 #[tauri::command]
 async fn delete_customer(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
     state
         .inner()
         .customer
@@ -108,6 +142,7 @@ async fn get_customer_appointment_list(
     state: tauri::State<'_, State>,
     customer_id: i64,
 ) -> Result<Vec<model::CustomerAppointmentDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .appointment
@@ -121,6 +156,7 @@ async fn get_customer_appointment_by_id(
     state: tauri::State<'_, State>,
     id: i64,
 ) -> Result<Option<model::CustomerAppointmentDto>, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .appointment
@@ -134,6 +170,7 @@ async fn store_customer_appointment(
     state: tauri::State<'_, State>,
     appointment: model::EditCustomerAppointmentDto,
 ) -> Result<i64, String> {
+    authorize(&state, EVERYONE)?;
     state
         .inner()
         .appointment
@@ -142,11 +179,56 @@ async fn store_customer_appointment(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn check_appointment_conflicts(
+    state: tauri::State<'_, State>,
+    appointment: model::EditCustomerAppointmentDto,
+) -> Result<Vec<model::AppointmentConflictDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .appointment
+        .check_appointment_conflicts(&appointment)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn plan_reschedule(
+    state: tauri::State<'_, State>,
+    employee_id: i64,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<model::RescheduleProposalDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .reschedule
+        .plan_reschedule(employee_id, from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn apply_reschedule(
+    state: tauri::State<'_, State>,
+    changes: Vec<model::RescheduleChangeDto>,
+) -> Result<model::RescheduleSummaryDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .reschedule
+        .apply_reschedule(changes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn delete_customer_appointment(
     state: tauri::State<'_, State>,
     id: i64,
-) -> Result<(), String> {
+) -> Result<Vec<model::WaitlistEntryDto>, String> {
+    authorize(&state, FRONT_DESK)?;
     state
         .inner()
         .appointment
@@ -155,14 +237,205 @@ async fn delete_customer_appointment(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn renumber_customer_appointments(
+    state: tauri::State<'_, State>,
+    customer_id: i64,
+) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .appointment
+        .renumber_appointments(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_customer_visit_prediction(
+    state: tauri::State<'_, State>,
+    customer_id: i64,
+) -> Result<Option<model::VisitPredictionDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .appointment
+        .get_visit_prediction(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_overdue_customers(
+    state: tauri::State<'_, State>,
+    tolerance_days: i64,
+) -> Result<Vec<model::OverdueCustomerDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .appointment
+        .get_overdue_customers(tolerance_days)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_appointments_ical(
+    state: tauri::State<'_, State>,
+    filter: model::AppointmentFilterDto,
+    path: PathBuf,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .ical
+        .export_appointments(filter, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn preview_ical_import(
+    state: tauri::State<'_, State>,
+    path: PathBuf,
+) -> Result<model::IcalImportPreviewDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .ical
+        .preview_import(&path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_appointments(
+    state: tauri::State<'_, State>,
+    appointments: Vec<model::IcalImportAppointmentDto>,
+) -> Result<Vec<i64>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .appointment
+        .import_appointments(appointments)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_reminder_list(
+    state: tauri::State<'_, State>,
+    appointment_id: Option<i64>,
+    status: Option<model::ReminderStatus>,
+) -> Result<Vec<model::ReminderDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .reminder
+        .get_reminder_list(appointment_id, status)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn send_due_reminders(state: tauri::State<'_, State>) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .reminder
+        .send_due_reminders()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn retry_reminder(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .reminder
+        .retry_reminder(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_waitlist(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::WaitlistEntryDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .waitlist
+        .get_waitlist()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_waitlist_entry_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::WaitlistEntryDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .waitlist
+        .get_waitlist_entry_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_waitlist_for_slot(
+    state: tauri::State<'_, State>,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    employee_id: Option<i64>,
+) -> Result<Vec<model::WaitlistEntryDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .waitlist
+        .get_waitlist_for_slot(start_date, end_date, employee_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_waitlist_entry(
+    state: tauri::State<'_, State>,
+    entry: model::EditWaitlistEntryDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .waitlist
+        .store_waitlist_entry(entry)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_waitlist_entry(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .waitlist
+        .delete_waitlist_entry(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn get_preference_list(
     state: tauri::State<'_, State>,
 ) -> Result<Vec<model::PreferenceDto>, String> {
+    let session = state.user.authorize(EVERYONE).map_err(|e| e.to_string())?;
     state
         .inner()
         .preference
-        .get_preference_list()
+        .get_preference_list(session.role == UserRole::Admin)
         .await
         .map_err(|e| e.to_string())
 }
@@ -172,6 +445,7 @@ async fn store_preference(
     state: tauri::State<'_, State>,
     preference: model::EditPreferenceDto,
 ) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
     state
         .inner()
         .preference
@@ -180,11 +454,965 @@ async fn store_preference(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn get_treatment_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::TreatmentDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .treatment
+        .get_treatment_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_treatment_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::TreatmentDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .treatment
+        .get_treatment_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_treatment(
+    state: tauri::State<'_, State>,
+    treatment: model::EditTreatmentDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .treatment
+        .store_treatment(treatment)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_treatment(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .treatment
+        .delete_treatment(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_product_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::ProductDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .product
+        .get_product_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_product_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::ProductDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .product
+        .get_product_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_product(
+    state: tauri::State<'_, State>,
+    product: model::EditProductDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .product
+        .store_product(product)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_product(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .product
+        .delete_product(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_low_stock_product_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::ProductDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .product
+        .get_low_stock_product_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_stock_movement_list(
+    state: tauri::State<'_, State>,
+    product_id: i64,
+) -> Result<Vec<model::StockMovementDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .product
+        .get_stock_movement_list(product_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_stock_movement(
+    state: tauri::State<'_, State>,
+    movement: model::EditStockMovementDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .product
+        .store_stock_movement(movement)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_resource_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::ResourceDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .resource
+        .get_resource_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_resource_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::ResourceDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .resource
+        .get_resource_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_resource(
+    state: tauri::State<'_, State>,
+    resource: model::EditResourceDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .resource
+        .store_resource(resource)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_resource(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .resource
+        .delete_resource(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_resource_calendar(
+    state: tauri::State<'_, State>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<model::ResourceCalendarDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .resource
+        .get_resource_calendar(from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_session_package_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::SessionPackageDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .package
+        .get_session_package_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_session_package_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::SessionPackageDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .package
+        .get_session_package_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_session_package(
+    state: tauri::State<'_, State>,
+    package: model::EditSessionPackageDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .package
+        .store_session_package(package)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_session_package(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .package
+        .delete_session_package(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_customer_package_list(
+    state: tauri::State<'_, State>,
+    customer_id: i64,
+) -> Result<Vec<model::CustomerPackageDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .package
+        .get_customer_package_list(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_customer_package_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::CustomerPackageDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .package
+        .get_customer_package_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_customer_package(
+    state: tauri::State<'_, State>,
+    package: model::EditCustomerPackageDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .package
+        .store_customer_package(package)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_customer_package(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .package
+        .delete_customer_package(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_invoice_list(
+    state: tauri::State<'_, State>,
+    customer_id: Option<i64>,
+) -> Result<Vec<model::InvoiceDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .get_invoice_list(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_invoice_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::InvoiceDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .get_invoice_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn create_invoice(
+    state: tauri::State<'_, State>,
+    appointment_ids: Vec<i64>,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .create_invoice(appointment_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_invoice(
+    state: tauri::State<'_, State>,
+    invoice: model::EditInvoiceDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .store_invoice(invoice)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_invoice(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .delete_invoice(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn issue_invoice(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<model::InvoiceDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .issue_invoice(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cancel_invoice(state: tauri::State<'_, State>, id: i64) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .cancel_invoice(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_invoice_pdf(
+    state: tauri::State<'_, State>,
+    id: i64,
+    zugferd: bool,
+    path: PathBuf,
+) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .export_invoice_pdf(id, zugferd, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_invoice_xml(
+    state: tauri::State<'_, State>,
+    id: i64,
+    syntax: model::EInvoiceSyntax,
+    path: PathBuf,
+) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .invoice
+        .export_invoice_xml(id, syntax, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_payment_list(
+    state: tauri::State<'_, State>,
+    customer_id: Option<i64>,
+) -> Result<Vec<model::PaymentDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .get_payment_list(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_payment_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::PaymentDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .get_payment_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_payment(
+    state: tauri::State<'_, State>,
+    payment: model::EditPaymentDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .store_payment(payment)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_payment(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .delete_payment(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_open_item_list(
+    state: tauri::State<'_, State>,
+    customer_id: Option<i64>,
+) -> Result<Vec<model::OpenItemDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .get_open_item_list(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_customer_balance(
+    state: tauri::State<'_, State>,
+    customer_id: i64,
+) -> Result<model::CustomerBalanceDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .payment
+        .get_customer_balance(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_voucher_list(
+    state: tauri::State<'_, State>,
+    customer_id: Option<i64>,
+) -> Result<Vec<model::VoucherDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .voucher
+        .get_voucher_list(customer_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_voucher_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::VoucherDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .voucher
+        .get_voucher_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_voucher_by_code(
+    state: tauri::State<'_, State>,
+    code: String,
+) -> Result<Option<model::VoucherDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .voucher
+        .get_voucher_by_code(code)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_voucher(
+    state: tauri::State<'_, State>,
+    voucher: model::EditVoucherDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .voucher
+        .store_voucher(voucher)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_voucher(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .voucher
+        .delete_voucher(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_voucher_liability_report(
+    state: tauri::State<'_, State>,
+    date: NaiveDate,
+) -> Result<model::VoucherLiabilityReportDto, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .voucher
+        .get_voucher_liability_report(date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_revenue_report(
+    state: tauri::State<'_, State>,
+    filter: model::RevenueReportFilterDto,
+) -> Result<model::RevenueReportDto, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .report
+        .get_revenue_report(filter)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_revenue_report_csv(
+    state: tauri::State<'_, State>,
+    filter: model::RevenueReportFilterDto,
+    path: PathBuf,
+) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .report
+        .export_revenue_report_csv(filter, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_price_rule_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::PriceRuleDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .price_rule
+        .get_price_rule_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_price_rule_by_id(
+    state: tauri::State<'_, State>,
+    id: i64,
+) -> Result<Option<model::PriceRuleDto>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .price_rule
+        .get_price_rule_by_id(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_price_rule(
+    state: tauri::State<'_, State>,
+    rule: model::EditPriceRuleDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .price_rule
+        .store_price_rule(rule)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_price_rule(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .price_rule
+        .delete_price_rule(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn price_appointment(
+    state: tauri::State<'_, State>,
+    appointment: model::EditCustomerAppointmentDto,
+) -> Result<Vec<Option<model::AppliedPriceRuleDto>>, String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .price_rule
+        .price_appointment(appointment)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_cash_entry_list(
+    state: tauri::State<'_, State>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<model::CashEntryDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .cash_journal
+        .get_cash_entry_list(from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_cash_entry(
+    state: tauri::State<'_, State>,
+    entry: model::EditCashEntryDto,
+) -> Result<i64, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .cash_journal
+        .store_cash_entry(entry)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_cash_closing_list(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<model::CashClosingDto>, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .cash_journal
+        .get_cash_closing_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn close_cash_day(
+    state: tauri::State<'_, State>,
+    date: NaiveDate,
+    counted: i64,
+    note: String,
+) -> Result<model::CashClosingDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .cash_journal
+        .close_cash_day(date, counted, note)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn verify_cash_journal(
+    state: tauri::State<'_, State>,
+) -> Result<model::CashJournalVerificationDto, String> {
+    authorize(&state, FRONT_DESK)?;
+    state
+        .inner()
+        .cash_journal
+        .verify_cash_journal()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_datev_bookings(
+    state: tauri::State<'_, State>,
+    from: NaiveDate,
+    to: NaiveDate,
+    path: PathBuf,
+) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .accounting
+        .export_datev_bookings(from, to, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Session commands are the only ones that work without login.
+#[tauri::command]
+async fn is_setup_required(state: tauri::State<'_, State>) -> Result<bool, String> {
+    state
+        .inner()
+        .user
+        .is_setup_required()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn setup_admin(
+    state: tauri::State<'_, State>,
+    username: String,
+    password: String,
+) -> Result<model::SessionDto, String> {
+    state
+        .inner()
+        .user
+        .setup_admin(&username, &password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn login(
+    state: tauri::State<'_, State>,
+    username: String,
+    password: String,
+) -> Result<model::SessionDto, String> {
+    state
+        .inner()
+        .user
+        .login(&username, &password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn logout(state: tauri::State<'_, State>) -> Result<(), String> {
+    state.inner().user.logout().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_session(state: tauri::State<'_, State>) -> Result<Option<model::SessionDto>, String> {
+    state.inner().user.get_session().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn change_password(
+    state: tauri::State<'_, State>,
+    current_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    authorize(&state, EVERYONE)?;
+    state
+        .inner()
+        .user
+        .change_password(&current_password, &new_password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_user_list(state: tauri::State<'_, State>) -> Result<Vec<model::UserDto>, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .user
+        .get_user_list()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_user(
+    state: tauri::State<'_, State>,
+    user: model::EditUserDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .user
+        .store_user(user)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_user(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .user
+        .delete_user(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_commission_rule_list(
+    state: tauri::State<'_, State>,
+    employee_id: Option<i64>,
+) -> Result<Vec<model::CommissionRuleDto>, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .commission
+        .get_commission_rule_list(employee_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn store_commission_rule(
+    state: tauri::State<'_, State>,
+    rule: model::EditCommissionRuleDto,
+) -> Result<i64, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .commission
+        .store_commission_rule(rule)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_commission_rule(state: tauri::State<'_, State>, id: i64) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .commission
+        .delete_commission_rule(id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_commission_report(
+    state: tauri::State<'_, State>,
+    filter: model::CommissionReportFilterDto,
+) -> Result<model::CommissionReportDto, String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .commission
+        .get_commission_report(filter)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_commission_report_csv(
+    state: tauri::State<'_, State>,
+    filter: model::CommissionReportFilterDto,
+    path: PathBuf,
+) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
+    state
+        .inner()
+        .commission
+        .export_commission_report_csv(filter, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn open_app_data_directory(
     app: tauri::AppHandle,
     state: tauri::State<'_, State>,
 ) -> Result<(), String> {
+    authorize(&state, ADMIN)?;
     app.opener()
         .reveal_item_in_dir(&state.inner().db_path)
         .map_err(|e| e.to_string())
@@ -202,7 +1430,9 @@ pub fn run() {
                 .resolve("", BaseDirectory::AppData)
                 .expect("failed to resolve app data dir");
 
-            let state = tauri::async_runtime::block_on(async { State::new(&app_data_dir).await });
+            let state = tauri::async_runtime::block_on(async { State::new(&app_data_dir).await })
+                .map_err(|e| e.to_string())?;
+            tauri::async_runtime::spawn(state.reminder.clone().run());
             app.manage(state);
 
             Ok(())
@@ -220,9 +1450,98 @@ pub fn run() {
             get_customer_appointment_list,
             get_customer_appointment_by_id,
             store_customer_appointment,
+            check_appointment_conflicts,
+            plan_reschedule,
+            apply_reschedule,
             delete_customer_appointment,
+            renumber_customer_appointments,
+            get_customer_visit_prediction,
+            get_overdue_customers,
+            export_appointments_ical,
+            preview_ical_import,
+            import_appointments,
+            get_reminder_list,
+            send_due_reminders,
+            retry_reminder,
+            get_waitlist,
+            get_waitlist_entry_by_id,
+            get_waitlist_for_slot,
+            store_waitlist_entry,
+            delete_waitlist_entry,
             get_preference_list,
             store_preference,
+            get_treatment_list,
+            get_treatment_by_id,
+            store_treatment,
+            delete_treatment,
+            get_product_list,
+            get_product_by_id,
+            store_product,
+            delete_product,
+            get_low_stock_product_list,
+            get_stock_movement_list,
+            store_stock_movement,
+            get_resource_list,
+            get_resource_by_id,
+            store_resource,
+            delete_resource,
+            get_resource_calendar,
+            get_session_package_list,
+            get_session_package_by_id,
+            store_session_package,
+            delete_session_package,
+            get_customer_package_list,
+            get_customer_package_by_id,
+            store_customer_package,
+            delete_customer_package,
+            get_invoice_list,
+            get_invoice_by_id,
+            create_invoice,
+            store_invoice,
+            delete_invoice,
+            issue_invoice,
+            cancel_invoice,
+            export_invoice_pdf,
+            export_invoice_xml,
+            get_payment_list,
+            get_payment_by_id,
+            store_payment,
+            delete_payment,
+            get_open_item_list,
+            get_customer_balance,
+            get_voucher_list,
+            get_voucher_by_id,
+            get_voucher_by_code,
+            store_voucher,
+            delete_voucher,
+            get_voucher_liability_report,
+            get_revenue_report,
+            export_revenue_report_csv,
+            get_price_rule_list,
+            get_price_rule_by_id,
+            store_price_rule,
+            delete_price_rule,
+            price_appointment,
+            get_cash_entry_list,
+            store_cash_entry,
+            get_cash_closing_list,
+            close_cash_day,
+            verify_cash_journal,
+            export_datev_bookings,
+            is_setup_required,
+            setup_admin,
+            login,
+            logout,
+            get_session,
+            change_password,
+            get_user_list,
+            store_user,
+            delete_user,
+            get_commission_rule_list,
+            store_commission_rule,
+            delete_commission_rule,
+            get_commission_report,
+            export_commission_report_csv,
             open_app_data_directory,
         ])
         .run(tauri::generate_context!())
@@ -1,16 +1,73 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Eur,
+    Chf,
+    Usd,
+    Gbp,
+}
+
+/// Amount in the minor unit of the currency, e.g. cents.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: Currency,
+}
+
+/// Gross price split into net amount and contained VAT.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PriceDto {
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+}
+
+/// Part of a sum taxed with the same VAT rate, the rate is given in basis points.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TaxBreakdownDto {
+    pub vat_rate: i64,
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+}
+
+/// Employee with contact data. `color` is the calendar colour as `#rrggbb` or empty. Archived
+/// employees are not `active` but still referenced by their appointments.
+#[derive(Serialize, Clone)]
 pub struct EmployeeDto {
     pub id: i64,
     pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub color: String,
+    pub employed_from: Option<NaiveDate>,
+    pub employed_until: Option<NaiveDate>,
+    pub active: bool,
 }
 
 #[derive(Deserialize)]
 pub struct EditEmployeeDto {
     pub id: Option<i64>,
     pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub color: String,
+    pub employed_from: Option<NaiveDate>,
+    pub employed_until: Option<NaiveDate>,
+    pub active: bool,
+}
+
+/// Employee referenced by customers, appointments and waitlist entries.
+#[derive(Serialize, Clone)]
+pub struct EmployeeRefDto {
+    pub id: i64,
+    pub name: String,
+    pub active: bool,
 }
 
 #[derive(Serialize)]
@@ -20,13 +77,18 @@ pub struct CustomerDto {
     pub first_name: String,
     pub last_name: String,
     pub address_street: String,
+    pub address_postcode: String,
     pub address_city: String,
     pub phone: String,
     pub mobile_phone: String,
+    pub email: String,
     pub birthdate: Option<NaiveDate>,
     pub customer_since: Option<NaiveDate>,
     pub note: String,
-    pub responsible_employee: Option<EmployeeDto>,
+    pub responsible_employee: Option<EmployeeRefDto>,
+    pub tags: Vec<String>,
+    /// Packages that are neither used up nor expired.
+    pub packages: Vec<CustomerPackageDto>,
 }
 
 #[derive(Deserialize)]
@@ -36,16 +98,20 @@ pub struct EditCustomerDto {
     pub first_name: String,
     pub last_name: String,
     pub address_street: String,
+    pub address_postcode: String,
     pub address_city: String,
     pub phone: String,
     pub mobile_phone: String,
+    pub email: String,
     pub birthdate: Option<NaiveDate>,
     pub customer_since: Option<NaiveDate>,
     pub note: String,
     pub responsible_employee_id: Option<i64>,
+    /// Free labels like "student" that price rules can refer to.
+    pub tags: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct CustomerAppointmentDto {
     pub id: i64,
     pub customer_id: i64,
@@ -53,21 +119,1037 @@ pub struct CustomerAppointmentDto {
     pub start_date: NaiveDateTime,
     pub duration_minutes: i64,
     pub end_date: NaiveDateTime,
+    /// IANA zone of the local start and end date.
+    pub time_zone: String,
+    pub start_utc: DateTime<Utc>,
+    pub end_utc: DateTime<Utc>,
     pub period_days: Option<i64>,
     pub treatment: String,
-    pub price: i64,
-    pub employee: Option<EmployeeDto>,
+    pub price: PriceDto,
+    pub taxes: Vec<TaxBreakdownDto>,
+    pub employee: Option<EmployeeRefDto>,
+    pub items: Vec<AppointmentItemDto>,
+    pub resources: Vec<ResourceDto>,
 }
 
 #[derive(Deserialize)]
 pub struct EditCustomerAppointmentDto {
     pub id: Option<i64>,
     pub customer_id: i64,
+    /// Local time in the zone of the stored appointment, new appointments use the practice
+    /// time zone.
     pub start_date: NaiveDateTime,
+    /// Which occurrence is meant if the start date occurs twice because the clocks are set back.
+    pub ambiguous_time: Option<AmbiguousTime>,
+    pub duration_minutes: i64,
+    pub employee_id: Option<i64>,
+    pub items: Vec<EditAppointmentItemDto>,
+    /// Resources booked in addition to the ones required by the catalog treatments.
+    pub resource_ids: Vec<i64>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AmbiguousTime {
+    Earlier,
+    Later,
+}
+
+#[derive(Deserialize)]
+pub struct AppointmentFilterDto {
+    pub customer_id: Option<i64>,
+    pub employee_id: Option<i64>,
+    pub resource_id: Option<i64>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+pub struct IcalImportPreviewDto {
+    pub events: Vec<IcalImportEventDto>,
+    pub skipped: Vec<IcalSkippedEventDto>,
+}
+
+/// Proposal for a single event. `customer_id` is only set for unambiguous matches, otherwise
+/// the candidates have to be reviewed.
+#[derive(Serialize)]
+pub struct IcalImportEventDto {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub description: String,
+    pub start_date: NaiveDateTime,
+    pub duration_minutes: i64,
+    pub appointment_id: Option<i64>,
+    pub customer_id: Option<i64>,
+    pub candidates: Vec<IcalCustomerCandidateDto>,
+}
+
+#[derive(Serialize)]
+pub struct IcalCustomerCandidateDto {
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub mobile_phone: String,
+    pub match_kind: CustomerMatchKind,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomerMatchKind {
+    Email,
+    Phone,
+    Name,
+}
+
+#[derive(Serialize)]
+pub struct IcalSkippedEventDto {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub reason: IcalSkipReason,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IcalSkipReason {
+    MissingStart,
+    AllDay,
+    Recurring,
+    Cancelled,
+}
+
+#[derive(Deserialize)]
+pub struct IcalImportAppointmentDto {
+    pub ical_uid: Option<String>,
+    pub appointment: EditCustomerAppointmentDto,
+}
+
+#[derive(Serialize)]
+pub struct VisitPredictionDto {
+    pub customer_id: i64,
+    pub last_visit: NaiveDate,
+    pub typical_interval_days: i64,
+    pub predicted_next_visit: NaiveDate,
+}
+
+#[derive(Serialize)]
+pub struct OverdueCustomerDto {
+    pub customer_id: i64,
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub mobile_phone: String,
+    pub last_visit: NaiveDate,
+    pub typical_interval_days: i64,
+    pub predicted_next_visit: NaiveDate,
+    pub overdue_days: i64,
+}
+
+/// Customer waiting for a free slot. `treatment` is the desired treatment as free text, the
+/// catalog reference is optional.
+#[derive(Serialize)]
+pub struct WaitlistEntryDto {
+    pub id: i64,
+    pub customer_id: i64,
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub mobile_phone: String,
+    pub treatment_id: Option<i64>,
+    pub treatment: String,
     pub duration_minutes: i64,
+    pub employee: Option<EmployeeRefDto>,
+    pub note: String,
+    pub created_date: NaiveDateTime,
+    pub windows: Vec<WaitlistWindowDto>,
+}
+
+#[derive(Deserialize)]
+pub struct EditWaitlistEntryDto {
+    pub id: Option<i64>,
+    pub customer_id: i64,
+    pub treatment_id: Option<i64>,
     pub treatment: String,
+    pub duration_minutes: i64,
+    pub employee_id: Option<i64>,
+    pub note: String,
+    pub windows: Vec<WaitlistWindowDto>,
+}
+
+/// Time range in which the customer could come in.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WaitlistWindowDto {
+    pub start_date: NaiveDateTime,
+    pub end_date: NaiveDateTime,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AppointmentItemDto {
+    pub id: i64,
+    pub treatment_id: Option<i64>,
+    pub product_id: Option<i64>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    /// VAT rate in basis points, 1900 is 19 %.
+    pub vat_rate: i64,
+    pub total: PriceDto,
+    pub customer_package_id: Option<i64>,
+    /// Price rule that granted the discount.
+    pub price_rule_id: Option<i64>,
+    pub price_rule_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EditAppointmentItemDto {
+    pub treatment_id: Option<i64>,
+    pub product_id: Option<i64>,
+    pub description: String,
+    pub quantity: i64,
+    /// Gross price in minor units, like all prices that are entered.
+    pub unit_price: i64,
+    pub discount: i64,
+    pub vat_rate: i64,
+    /// Package that pays for this item. New appointments use an open package of the customer
+    /// for the treatment automatically.
+    pub customer_package_id: Option<i64>,
+    /// Price rule that granted the discount. The best matching rule is applied to treatment
+    /// items without a discount automatically, the discount of a rule is recomputed unless the
+    /// item is stored unchanged.
+    pub price_rule_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct TreatmentDto {
+    pub id: i64,
+    pub name: String,
+    pub category: String,
+    pub default_duration_minutes: i64,
+    pub default_price: i64,
+    pub vat_rate: i64,
+    pub active: bool,
+    pub resource_ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct EditTreatmentDto {
+    pub id: Option<i64>,
+    pub name: String,
+    pub category: String,
+    pub default_duration_minutes: i64,
+    pub default_price: i64,
+    pub vat_rate: i64,
+    pub active: bool,
+    pub resource_ids: Vec<i64>,
+}
+
+#[derive(Serialize)]
+pub struct SessionPackageDto {
+    pub id: i64,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
+    pub price: i64,
+    pub validity_days: Option<i64>,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EditSessionPackageDto {
+    pub id: Option<i64>,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
     pub price: i64,
+    pub validity_days: Option<i64>,
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum PriceRuleKind {
+    /// Value in basis points of the item price, 1000 is 10 %.
+    Percentage,
+    /// Value in minor units per unit of the item.
+    Fixed,
+}
+
+/// Discount on treatment items. Conditions that are not set always hold, the rule applies if all
+/// of them hold for the appointment.
+#[derive(Serialize)]
+pub struct PriceRuleDto {
+    pub id: i64,
+    pub name: String,
+    pub kind: PriceRuleKind,
+    pub value: i64,
+    pub customer_tag: Option<String>,
+    pub treatment_id: Option<i64>,
+    /// Number of earlier visits the customer needs.
+    pub min_visit_count: Option<i64>,
+    /// ISO weekday numbers of the appointment start, 1 is Monday.
+    pub weekdays: Vec<u32>,
+    /// Local start time of the appointment, the end is exclusive.
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EditPriceRuleDto {
+    pub id: Option<i64>,
+    pub name: String,
+    pub kind: PriceRuleKind,
+    pub value: i64,
+    pub customer_tag: Option<String>,
+    pub treatment_id: Option<i64>,
+    pub min_visit_count: Option<i64>,
+    pub weekdays: Vec<u32>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub active: bool,
+}
+
+/// Rule that applies to an item, with the discount it grants.
+#[derive(Serialize, Clone)]
+pub struct AppliedPriceRuleDto {
+    pub price_rule_id: i64,
+    pub name: String,
+    pub discount: i64,
+}
+
+/// Package bought by a customer. Sessions of completed appointments are `used`, sessions of
+/// upcoming appointments are `booked`.
+#[derive(Serialize)]
+pub struct CustomerPackageDto {
+    pub id: i64,
+    pub customer_id: i64,
+    pub session_package_id: Option<i64>,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
+    pub used_sessions: i64,
+    pub booked_sessions: i64,
+    pub remaining_sessions: i64,
+    pub price: i64,
+    /// VAT rate in basis points, the price is revenue at this rate when the package is sold.
+    pub vat_rate: i64,
+    pub purchase_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub expired: bool,
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+pub struct EditCustomerPackageDto {
+    pub id: Option<i64>,
+    pub customer_id: i64,
+    pub session_package_id: Option<i64>,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
+    pub price: i64,
+    pub vat_rate: i64,
+    pub purchase_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub note: String,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Room,
+    Device,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ResourceDto {
+    pub id: i64,
+    pub name: String,
+    pub kind: ResourceKind,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EditResourceDto {
+    pub id: Option<i64>,
+    pub name: String,
+    pub kind: ResourceKind,
+    pub active: bool,
+}
+
+#[derive(Serialize)]
+pub struct ResourceCalendarDto {
+    pub resource: ResourceDto,
+    pub appointments: Vec<CustomerAppointmentDto>,
+}
+
+#[derive(Serialize, sqlx::Type, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AppointmentConflictKind {
+    Employee,
+    Resource,
+}
+
+/// Another appointment that overlaps and needs the same employee or resource. `name` is the
+/// name of that employee or resource.
+#[derive(Serialize)]
+pub struct AppointmentConflictDto {
+    pub kind: AppointmentConflictKind,
+    pub appointment_id: i64,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub start_date: NaiveDateTime,
+    pub end_date: NaiveDateTime,
     pub employee_id: Option<i64>,
+    pub resource_id: Option<i64>,
+    pub name: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RescheduleAction {
+    /// Keep the time, another employee takes over.
+    Reassign,
+    /// Keep the employee, move to a free slot after the absence.
+    Shift,
+    /// Neither a free employee nor a free slot was found.
+    Unresolved,
+}
+
+/// Proposed change for an appointment of an absent employee. `employee` and `start_date` are
+/// the proposed values, unresolved proposals keep the current ones.
+#[derive(Serialize)]
+pub struct RescheduleProposalDto {
+    pub appointment: CustomerAppointmentDto,
+    pub first_name: String,
+    pub last_name: String,
+    pub action: RescheduleAction,
+    pub employee: Option<EmployeeRefDto>,
+    pub start_date: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct RescheduleChangeDto {
+    pub appointment_id: i64,
+    pub employee_id: Option<i64>,
+    pub start_date: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct RescheduledAppointmentDto {
+    pub appointment_id: i64,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub old_start_date: NaiveDateTime,
+    pub new_start_date: NaiveDateTime,
+    pub old_employee: Option<EmployeeRefDto>,
+    pub new_employee: Option<EmployeeRefDto>,
+}
+
+#[derive(Serialize)]
+pub struct RescheduleSummaryDto {
+    pub reassigned: i64,
+    pub shifted: i64,
+    pub changes: Vec<RescheduledAppointmentDto>,
+}
+
+#[derive(Serialize)]
+pub struct ProductDto {
+    pub id: i64,
+    pub name: String,
+    pub sku: String,
+    pub ean: String,
+    pub purchase_price: i64,
+    pub sale_price: i64,
+    pub vat_rate: i64,
+    pub stock: i64,
+    pub reorder_threshold: i64,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EditProductDto {
+    pub id: Option<i64>,
+    pub name: String,
+    pub sku: String,
+    pub ean: String,
+    pub purchase_price: i64,
+    pub sale_price: i64,
+    pub vat_rate: i64,
+    pub reorder_threshold: i64,
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum StockMovementKind {
+    Sale,
+    Receipt,
+    Correction,
+}
+
+#[derive(Serialize)]
+pub struct StockMovementDto {
+    pub id: i64,
+    pub product_id: i64,
+    pub date: NaiveDateTime,
+    pub kind: StockMovementKind,
+    pub quantity: i64,
+    pub appointment_id: Option<i64>,
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+pub struct EditStockMovementDto {
+    pub product_id: i64,
+    pub date: NaiveDateTime,
+    pub kind: StockMovementKind,
+    pub quantity: i64,
+    pub note: String,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ReminderChannel {
+    Email,
+    Sms,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ReminderStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+/// Queued reminder for an appointment. Failed deliveries stay `pending` while they are retried,
+/// `failed` is only set once all attempts are used up.
+#[derive(Serialize)]
+pub struct ReminderDto {
+    pub id: i64,
+    pub appointment_id: i64,
+    pub customer_id: i64,
+    pub channel: ReminderChannel,
+    pub recipient: String,
+    pub appointment_start_date: NaiveDateTime,
+    pub due_date: DateTime<Utc>,
+    pub status: ReminderStatus,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub sent_date: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum InvoiceKind {
+    Invoice,
+    CreditNote,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Draft,
+    Issued,
+    /// Issued and cancelled by a credit note.
+    Cancelled,
+}
+
+/// Syntax of electronic invoices, both follow the XRechnung specification.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EInvoiceSyntax {
+    Ubl,
+    Cii,
+}
+
+/// Invoice or credit note. Recipient and items are copies, so later changes of the customer or
+/// the appointments do not alter it. The number is assigned when the invoice is issued.
+#[derive(Serialize, Clone)]
+pub struct InvoiceDto {
+    pub id: i64,
+    pub kind: InvoiceKind,
+    pub status: InvoiceStatus,
+    pub number: Option<String>,
+    pub customer_id: Option<i64>,
+    pub customer_name: String,
+    pub address_street: String,
+    pub address_postcode: String,
+    pub address_city: String,
+    /// Order number or Leitweg-ID of the buyer for electronic invoices.
+    pub buyer_reference: String,
+    pub issue_date: Option<NaiveDate>,
+    pub due_date: Option<NaiveDate>,
+    pub currency: Currency,
+    /// For credit notes the invoice they cancel.
+    pub cancelled_invoice_id: Option<i64>,
+    /// For cancelled invoices the credit note that cancels them.
+    pub credit_note_id: Option<i64>,
+    pub note: String,
+    pub items: Vec<InvoiceItemDto>,
+    pub price: PriceDto,
+    pub taxes: Vec<TaxBreakdownDto>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct InvoiceItemDto {
+    pub id: i64,
+    pub appointment_id: Option<i64>,
+    pub date: Option<NaiveDate>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    pub vat_rate: i64,
+    pub total: PriceDto,
+}
+
+/// Only drafts can be stored. Without dates they are set when the invoice is issued.
+#[derive(Deserialize)]
+pub struct EditInvoiceDto {
+    pub id: Option<i64>,
+    pub customer_id: Option<i64>,
+    pub customer_name: String,
+    pub address_street: String,
+    pub address_postcode: String,
+    pub address_city: String,
+    pub buyer_reference: String,
+    pub issue_date: Option<NaiveDate>,
+    pub due_date: Option<NaiveDate>,
+    pub note: String,
+    pub items: Vec<EditInvoiceItemDto>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EditInvoiceItemDto {
+    pub appointment_id: Option<i64>,
+    pub date: Option<NaiveDate>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    pub vat_rate: i64,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    Transfer,
+    Voucher,
+}
+
+/// Amounts are gross amounts in minor units of the practice currency.
+#[derive(Serialize)]
+pub struct PaymentDto {
+    pub id: i64,
+    pub customer_id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+    pub method: PaymentMethod,
+    /// Redeemed voucher of a voucher payment.
+    pub voucher_id: Option<i64>,
+    pub reference: String,
+    pub note: String,
+    pub allocations: Vec<PaymentAllocationDto>,
+    /// Part of the amount that is not allocated and remains a credit of the customer.
+    pub unallocated: i64,
+}
+
+#[derive(Deserialize)]
+pub struct EditPaymentDto {
+    pub id: Option<i64>,
+    pub customer_id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+    pub method: PaymentMethod,
+    /// Required for voucher payments, the voucher must cover the amount.
+    pub voucher_id: Option<i64>,
+    pub reference: String,
+    pub note: String,
+    pub allocations: Vec<PaymentAllocationDto>,
+}
+
+/// Either an appointment or an issued invoice is settled by the amount.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentAllocationDto {
+    pub appointment_id: Option<i64>,
+    pub invoice_id: Option<i64>,
+    pub amount: i64,
+}
+
+#[derive(Serialize, sqlx::Type, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum OpenItemKind {
+    Appointment,
+    Invoice,
+}
+
+/// Past appointment or issued invoice that is not paid in full. Appointments on an issued
+/// invoice are only listed as part of the invoice.
+#[derive(Serialize)]
+pub struct OpenItemDto {
+    pub kind: OpenItemKind,
+    pub appointment_id: Option<i64>,
+    pub invoice_id: Option<i64>,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    /// Appointment number or invoice number.
+    pub reference: String,
+    pub date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub amount: Money,
+    pub paid: Money,
+    pub outstanding: Money,
+}
+
+/// A positive balance is owed by the customer, a negative one is a credit.
+#[derive(Serialize)]
+pub struct CustomerBalanceDto {
+    pub customer_id: i64,
+    pub due: Money,
+    pub paid: Money,
+    pub balance: Money,
+}
+
+/// Incoming amounts are positive, outgoing ones negative.
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum CashEntryKind {
+    /// Cash payment of a customer.
+    Payment,
+    /// Change of an already booked cash payment, e.g. after it was edited or deleted.
+    Correction,
+    Refund,
+    Withdrawal,
+    Deposit,
+    /// Difference between counted and expected cash at the daily closing.
+    Difference,
+}
+
+#[derive(Serialize)]
+pub struct CashEntryDto {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub date: NaiveDate,
+    pub kind: CashEntryKind,
+    pub amount: i64,
+    pub payment_id: Option<i64>,
+    pub description: String,
+    pub hash: String,
+}
+
+/// Manual booking of a refund, withdrawal or deposit. The amount is positive, the direction
+/// follows from the kind.
+#[derive(Deserialize)]
+pub struct EditCashEntryDto {
+    pub date: NaiveDate,
+    pub kind: CashEntryKind,
+    pub amount: i64,
+    pub description: String,
+}
+
+/// `expected` is the opening balance plus the entries of the closed days, a difference to the
+/// counted cash is booked as an entry of the closing date.
+#[derive(Serialize)]
+pub struct CashClosingDto {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+    pub opening_balance: i64,
+    pub receipts: i64,
+    pub payouts: i64,
+    pub expected: i64,
+    pub counted: i64,
+    pub difference: i64,
+    pub note: String,
+    pub last_entry_id: i64,
+    pub hash: String,
+}
+
+#[derive(Serialize)]
+pub struct CashJournalVerificationDto {
+    pub valid: bool,
+    pub entry_count: i64,
+    pub closing_count: i64,
+    pub issues: Vec<CashJournalIssueDto>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CashJournalIssueKind {
+    /// Entries are missing between two entries or after a closing.
+    Missing,
+    /// The content of the entry does not match its hash.
+    Modified,
+    /// The entry does not refer to the hash of its predecessor.
+    BrokenChain,
+    /// The closing does not match its content, its predecessor or the journal.
+    ClosingMismatch,
+}
+
+#[derive(Serialize)]
+pub struct CashJournalIssueDto {
+    pub kind: CashJournalIssueKind,
+    pub entry_id: Option<i64>,
+    pub closing_id: Option<i64>,
+    pub message: String,
+}
+
+/// Values are gross amounts in minor units of the practice currency.
+#[derive(Serialize)]
+pub struct VoucherDto {
+    pub id: i64,
+    pub code: String,
+    pub initial_value: i64,
+    pub remaining_value: i64,
+    pub issue_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub purchaser_customer_id: Option<i64>,
+    pub recipient_customer_id: Option<i64>,
+    pub note: String,
+    pub redemptions: Vec<VoucherRedemptionDto>,
+}
+
+/// The code is generated when the voucher is created and can't be changed.
+#[derive(Deserialize)]
+pub struct EditVoucherDto {
+    pub id: Option<i64>,
+    pub initial_value: i64,
+    pub issue_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub purchaser_customer_id: Option<i64>,
+    pub recipient_customer_id: Option<i64>,
+    pub note: String,
+}
+
+#[derive(Serialize)]
+pub struct VoucherRedemptionDto {
+    pub payment_id: i64,
+    pub customer_id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+}
+
+/// Remaining values of the vouchers issued up to the given date, counting redemptions up to that
+/// date. Vouchers that expired before are no liability anymore and only summed up.
+#[derive(Serialize)]
+pub struct VoucherLiabilityReportDto {
+    pub date: NaiveDate,
+    pub vouchers: Vec<VoucherDto>,
+    pub outstanding: Money,
+    pub expired: Money,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RevenueGrouping {
+    Day,
+    Week,
+    Month,
+    Year,
+    Employee,
+    Treatment,
+    Customer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RevenueBasis {
+    /// Prices of the appointment items by appointment date.
+    Appointments,
+    /// Received payments by payment date.
+    Payments,
+}
+
+/// Both dates are inclusive.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RevenueReportFilterDto {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: RevenueGrouping,
+    pub basis: RevenueBasis,
+}
+
+/// Revenue of the requested period, compared with the period of the same length right before it.
+#[derive(Serialize)]
+pub struct RevenueReportDto {
+    pub filter: RevenueReportFilterDto,
+    pub previous_from: NaiveDate,
+    pub previous_to: NaiveDate,
+    pub rows: Vec<RevenueRowDto>,
+    pub total: RevenueRowDto,
+}
+
+/// Rows of a period grouping are compared with the preceding day, week, month or year, other
+/// rows with the same employee, treatment or customer in the previous period. Payments carry no
+/// VAT, so their net amount is the gross amount.
+#[derive(Serialize)]
+pub struct RevenueRowDto {
+    pub key: String,
+    pub label: String,
+    /// Number of appointments or payments.
+    pub count: i64,
+    pub price: PriceDto,
+    pub previous_count: i64,
+    pub previous_gross: Money,
+    /// Change of the gross amount, `None` if there was no revenue before.
+    pub change_percent: Option<f64>,
+}
+
+/// Accounts of the DATEV export, stored in the preferences. Revenue accounts are expected to be
+/// automatic accounts that derive the VAT from the account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DatevAccountsDto {
+    /// Debtor account all customers are booked on.
+    pub receivable_account: i64,
+    pub revenue_accounts: Vec<DatevRevenueAccountDto>,
+    pub payment_accounts: Vec<DatevPaymentAccountDto>,
+}
+
+/// VAT rate in basis points.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DatevRevenueAccountDto {
+    pub vat_rate: i64,
+    pub account: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DatevPaymentAccountDto {
+    pub method: PaymentMethod,
+    pub account: i64,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum UserRole {
+    Admin,
+    Reception,
+    Therapist,
+}
+
+/// Account to log in with, therapists are usually linked to their employee.
+#[derive(Serialize)]
+pub struct UserDto {
+    pub id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub employee: Option<EmployeeRefDto>,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EditUserDto {
+    pub id: Option<i64>,
+    pub username: String,
+    /// New password, empty keeps the current password of existing accounts.
+    pub password: String,
+    pub role: UserRole,
+    pub employee_id: Option<i64>,
+    pub active: bool,
+}
+
+/// User that is logged in.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionDto {
+    pub user_id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub employee_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum CommissionKind {
+    /// Value in basis points of the net revenue of the item, 1000 is 10 %.
+    Percentage,
+    /// Value in minor units per unit of a treatment item.
+    Fixed,
+}
+
+/// Commission of an employee. A rule applies to the items of one treatment, of one treatment
+/// category or, if neither is set, to all items. Fixed rules only apply to treatment items.
+/// Both validity dates are inclusive, open ends are unbounded.
+#[derive(Serialize)]
+pub struct CommissionRuleDto {
+    pub id: i64,
+    pub employee_id: i64,
+    pub kind: CommissionKind,
+    pub value: i64,
+    pub treatment_category: Option<String>,
+    pub treatment_id: Option<i64>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+}
+
+/// Once a rule applies to past appointments, only the end of its validity can be changed.
+#[derive(Deserialize)]
+pub struct EditCommissionRuleDto {
+    pub id: Option<i64>,
+    pub employee_id: i64,
+    pub kind: CommissionKind,
+    pub value: i64,
+    pub treatment_category: Option<String>,
+    pub treatment_id: Option<i64>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+}
+
+/// Both dates are inclusive, without an employee the report covers all employees.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct CommissionReportFilterDto {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub employee_id: Option<i64>,
+}
+
+/// Revenue amounts are net amounts, the basis of the commission.
+#[derive(Serialize)]
+pub struct CommissionReportDto {
+    pub filter: CommissionReportFilterDto,
+    pub employees: Vec<EmployeeCommissionDto>,
+    pub revenue: Money,
+    pub commission: Money,
+}
+
+#[derive(Serialize)]
+pub struct EmployeeCommissionDto {
+    pub employee: EmployeeRefDto,
+    pub appointments: Vec<CommissionAppointmentDto>,
+    pub revenue: Money,
+    pub commission: Money,
+}
+
+#[derive(Serialize)]
+pub struct CommissionAppointmentDto {
+    pub appointment_id: i64,
+    pub start_date: NaiveDateTime,
+    pub customer_id: i64,
+    pub customer_name: String,
+    pub treatment: String,
+    pub revenue: Money,
+    pub commission: Money,
 }
 
 #[derive(Serialize)]
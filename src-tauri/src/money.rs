@@ -0,0 +1,182 @@
+//! Rounding rules for prices.
+//!
+//! All prices are gross prices in minor units. The VAT contained in a sum is computed once per
+//! rate on the summed gross amounts and rounded half away from zero, so the parts of a
+//! breakdown always add up to the gross total.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{Currency, Money, PriceDto, TaxBreakdownDto},
+};
+
+/// VAT rates are given in basis points, so this is a rate of 100 %.
+pub const VAT_RATE_BASIS: i64 = 10_000;
+
+pub fn money(amount: i64, currency: Currency) -> Money {
+    Money { amount, currency }
+}
+
+/// ISO 4217 code of the currency.
+pub fn currency_code(currency: Currency) -> &'static str {
+    match currency {
+        Currency::Eur => "EUR",
+        Currency::Chf => "CHF",
+        Currency::Usd => "USD",
+        Currency::Gbp => "GBP",
+    }
+}
+
+pub fn validate_vat_rate(vat_rate: i64) -> ServiceResult<()> {
+    if !(0..=VAT_RATE_BASIS).contains(&vat_rate) {
+        return Err(ServiceError::BadRequest(format!(
+            "VAT rate must be between 0 and {}",
+            VAT_RATE_BASIS
+        )));
+    }
+    Ok(())
+}
+
+/// Integer division rounding half away from zero.
+pub fn round_div(numerator: i128, denominator: i128) -> i64 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    let rounded = if 2 * remainder.abs() >= denominator.abs() {
+        quotient + numerator.signum() * denominator.signum()
+    } else {
+        quotient
+    };
+    rounded as i64
+}
+
+/// Splits a gross amount into net amount and VAT.
+pub fn split_gross(gross: i64, vat_rate: i64, currency: Currency) -> PriceDto {
+    let net = round_div(
+        gross as i128 * VAT_RATE_BASIS as i128,
+        (VAT_RATE_BASIS + vat_rate) as i128,
+    );
+    PriceDto {
+        net: money(net, currency),
+        tax: money(gross - net, currency),
+        gross: money(gross, currency),
+    }
+}
+
+/// Total of gross amounts with their VAT rates, together with the breakdown per rate ordered by
+/// rate. The total is the sum of the rounded parts.
+pub fn sum_prices(
+    lines: impl IntoIterator<Item = (i64, i64)>,
+    currency: Currency,
+) -> (PriceDto, Vec<TaxBreakdownDto>) {
+    let mut gross_per_rate: BTreeMap<i64, i64> = BTreeMap::new();
+    for (gross, vat_rate) in lines {
+        *gross_per_rate.entry(vat_rate).or_default() += gross;
+    }
+
+    let taxes: Vec<TaxBreakdownDto> = gross_per_rate
+        .into_iter()
+        .map(|(vat_rate, gross)| {
+            let price = split_gross(gross, vat_rate, currency);
+            TaxBreakdownDto {
+                vat_rate,
+                net: price.net,
+                tax: price.tax,
+                gross: price.gross,
+            }
+        })
+        .collect();
+
+    let net = taxes.iter().map(|tax| tax.net.amount).sum();
+    let tax = taxes.iter().map(|tax| tax.tax.amount).sum();
+    let gross = taxes.iter().map(|tax| tax.gross.amount).sum();
+    (
+        PriceDto {
+            net: money(net, currency),
+            tax: money(tax, currency),
+            gross: money(gross, currency),
+        },
+        taxes,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakdown(taxes: &[TaxBreakdownDto]) -> Vec<(i64, i64, i64, i64)> {
+        taxes
+            .iter()
+            .map(|tax| {
+                (
+                    tax.vat_rate,
+                    tax.net.amount,
+                    tax.tax.amount,
+                    tax.gross.amount,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(round_div(5, 2), 3);
+        assert_eq!(round_div(-5, 2), -3);
+        assert_eq!(round_div(7, -2), -4);
+        assert_eq!(round_div(4, 3), 1);
+        assert_eq!(round_div(-4, 3), -1);
+        assert_eq!(round_div(0, 3), 0);
+    }
+
+    #[test]
+    fn taxes_are_rounded_once_per_rate() {
+        // Rounded per line, each 0.10 would contain 0.02 VAT, 0.06 in total.
+        let (price, taxes) = sum_prices([(10, 1900), (10, 1900), (10, 1900)], Currency::Eur);
+        assert_eq!(
+            (price.net.amount, price.tax.amount, price.gross.amount),
+            (25, 5, 30)
+        );
+        assert_eq!(breakdown(&taxes), vec![(1900, 25, 5, 30)]);
+
+        let (price, taxes) = sum_prices(
+            [(1190, 1900), (1070, 700), (500, 0), (1195, 1900)],
+            Currency::Chf,
+        );
+        assert_eq!(
+            breakdown(&taxes),
+            vec![
+                (0, 500, 0, 500),
+                (700, 1000, 70, 1070),
+                (1900, 2004, 381, 2385)
+            ]
+        );
+        assert_eq!(price.net, money(3504, Currency::Chf));
+        assert_eq!(price.tax, money(451, Currency::Chf));
+        assert_eq!(price.gross, money(3955, Currency::Chf));
+    }
+
+    #[test]
+    fn credits_are_rounded_like_charges() {
+        let (price, taxes) = sum_prices([(-10, 1900), (-10, 1900), (-10, 1900)], Currency::Eur);
+        assert_eq!(breakdown(&taxes), vec![(1900, -25, -5, -30)]);
+        assert_eq!(price.gross, money(-30, Currency::Eur));
+
+        let (price, taxes) = sum_prices([(1190, 1900), (-1190, 1900)], Currency::Eur);
+        assert_eq!(breakdown(&taxes), vec![(1900, 0, 0, 0)]);
+        assert_eq!(
+            price,
+            PriceDto {
+                net: money(0, Currency::Eur),
+                tax: money(0, Currency::Eur),
+                gross: money(0, Currency::Eur),
+            }
+        );
+    }
+
+    #[test]
+    fn empty_sums_are_zero() {
+        let (price, taxes) = sum_prices([], Currency::Usd);
+        assert!(taxes.is_empty());
+        assert_eq!(price.gross, money(0, Currency::Usd));
+    }
+}
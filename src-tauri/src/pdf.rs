@@ -0,0 +1,539 @@
+//! Minimal PDF/A-3b writer for simple text documents like invoices.
+//!
+//! Text is set in the embedded DejaVu Sans fonts and encoded as WinAnsi, characters outside of it
+//! are replaced by a question mark. The fonts are subset to the WinAnsi characters. An sRGB output
+//! intent and the identification in the XMP metadata make the file PDF/A-3b, the archival format
+//! that permits embedding the XML of an e-invoice.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use crate::true_type::TrueTypeFont;
+
+/// A4 in points.
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+const FIRST_CHAR: u8 = 0x20;
+
+/// Characters of the WinAnsi codes 0x80 to 0x9f, the other printable codes match Latin-1.
+const WIN_ANSI_HIGH: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+
+/// Font program and metrics as written into the document.
+struct EmbeddedFont {
+    /// Subset tag and PostScript name.
+    name: &'static str,
+    /// Widths of the codes from [`FIRST_CHAR`] to 255 in thousandths of the font size.
+    widths: Vec<u16>,
+    bbox: [i32; 4],
+    ascent: i32,
+    descent: i32,
+    cap_height: i32,
+    stem_v: i32,
+    program: Vec<u8>,
+}
+
+impl EmbeddedFont {
+    fn load(name: &'static str, data: &'static [u8], stem_v: i32) -> Self {
+        let font = TrueTypeFont::parse(data);
+        let scale = |value: i32| (value as f32 * 1000.0 / font.units_per_em as f32).round() as i32;
+        let glyph_of = |code: u8| win_ansi_char(code).map_or(0, |c| font.glyph_id(c));
+
+        let glyphs: BTreeSet<u16> = (FIRST_CHAR..=u8::MAX).map(glyph_of).collect();
+        Self {
+            name,
+            widths: (FIRST_CHAR..=u8::MAX)
+                .map(|code| scale(font.advance(glyph_of(code)) as i32) as u16)
+                .collect(),
+            bbox: font.bbox.map(|value| scale(value as i32)),
+            ascent: scale(font.ascent as i32),
+            descent: scale(font.descent as i32),
+            cap_height: scale(font.y_max(font.glyph_id('H')) as i32),
+            stem_v,
+            program: font.subset(&glyphs),
+        }
+    }
+}
+
+fn embedded_font(font: Font) -> &'static EmbeddedFont {
+    static FONTS: OnceLock<[EmbeddedFont; 2]> = OnceLock::new();
+    let fonts = FONTS.get_or_init(|| {
+        [
+            EmbeddedFont::load(
+                "WINANA+DejaVuSans",
+                include_bytes!("../fonts/DejaVuSans.ttf"),
+                80,
+            ),
+            EmbeddedFont::load(
+                "WINANB+DejaVuSans-Bold",
+                include_bytes!("../fonts/DejaVuSans-Bold.ttf"),
+                140,
+            ),
+        ]
+    });
+    match font {
+        Font::Regular => &fonts[0],
+        Font::Bold => &fonts[1],
+    }
+}
+
+/// Content of a single page, coordinates start at the bottom left corner.
+#[derive(Default)]
+pub struct PdfPage {
+    content: Vec<u8>,
+}
+
+impl PdfPage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws a single line of text, `x` is the left or right edge depending on the alignment.
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, align: Align, text: &str) {
+        let x = match align {
+            Align::Left => x,
+            Align::Right => x - text_width(text, font, size),
+        };
+        let font_name = match font {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        };
+
+        self.content.extend_from_slice(
+            format!(
+                "BT /{} {} Tf {} {} Td (",
+                font_name,
+                number(size),
+                number(x),
+                number(y)
+            )
+            .as_bytes(),
+        );
+        for byte in encode_win_ansi(text) {
+            match byte {
+                b'(' | b')' | b'\\' => self.content.extend_from_slice(&[b'\\', byte]),
+                0x20..=0x7e => self.content.push(byte),
+                _ => self
+                    .content
+                    .extend_from_slice(format!("\\{:03o}", byte).as_bytes()),
+            }
+        }
+        self.content.extend_from_slice(b") Tj ET\n");
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.content.extend_from_slice(
+            format!(
+                "0.5 w {} {} m {} {} l S\n",
+                number(x1),
+                number(y1),
+                number(x2),
+                number(y2)
+            )
+            .as_bytes(),
+        );
+    }
+}
+
+pub fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    let widths = &embedded_font(font).widths;
+    let units: u32 = encode_win_ansi(text)
+        .into_iter()
+        .map(|code| widths[(code - FIRST_CHAR) as usize] as u32)
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// Splits the text into lines that fit into the given width, breaking at spaces.
+pub fn wrap_text(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if !line.is_empty() && text_width(&candidate, font, size) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// File embedded into the document as an associated file, e.g. the XML of an e-invoice.
+pub struct PdfAttachment<'a> {
+    pub file_name: &'a str,
+    pub description: &'a str,
+    pub mime_type: &'a str,
+    pub data: &'a [u8],
+    /// Additional `rdf:Description` elements of the XMP metadata that describe the attachment.
+    pub metadata: &'a str,
+}
+
+pub fn write_document(
+    title: &str,
+    pages: &[PdfPage],
+    attachment: Option<&PdfAttachment>,
+) -> Vec<u8> {
+    // The catalog and the page tree come first, they are filled in at the end.
+    let mut objects: Vec<Vec<u8>> = vec![Vec::new(), Vec::new()];
+    let mut add = |object: Vec<u8>| {
+        objects.push(object);
+        objects.len()
+    };
+
+    let font_ids = [Font::Regular, Font::Bold].map(|font| {
+        let font = embedded_font(font);
+        let file_id = add(stream(
+            &format!("/Length1 {}", font.program.len()),
+            &font.program,
+        ));
+        let descriptor_id = add(
+            format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags 32 /FontBBox [{} {} {} {}] \
+                 /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV {} /FontFile2 {} 0 R >>",
+                font.name,
+                font.bbox[0],
+                font.bbox[1],
+                font.bbox[2],
+                font.bbox[3],
+                font.ascent,
+                font.descent,
+                font.cap_height,
+                font.stem_v,
+                file_id
+            )
+            .into_bytes(),
+        );
+        add(format!(
+            "<< /Type /Font /Subtype /TrueType /BaseFont /{} /FirstChar {} /LastChar 255 \
+             /Widths [{}] /Encoding /WinAnsiEncoding /FontDescriptor {} 0 R >>",
+            font.name,
+            FIRST_CHAR,
+            font.widths
+                .iter()
+                .map(|width| width.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            descriptor_id
+        )
+        .into_bytes())
+    });
+    let profile_id = add(stream("/N 3", &srgb_profile()));
+
+    let mut kids = Vec::with_capacity(pages.len());
+    for page in pages {
+        let content_id = add(stream("", &page.content));
+        let page_id = add(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 {} 0 R /F2 {} 0 R >> >> /Contents {} 0 R >>",
+            number(PAGE_WIDTH),
+            number(PAGE_HEIGHT),
+            font_ids[0],
+            font_ids[1],
+            content_id
+        )
+        .into_bytes());
+        kids.push(format!("{} 0 R", page_id));
+    }
+
+    let attachment_entries = match attachment {
+        Some(attachment) => {
+            let file_id = add(stream(
+                &format!(
+                    "/Type /EmbeddedFile /Subtype /{} /Params << /Size {} >>",
+                    attachment.mime_type.replace('/', "#2F"),
+                    attachment.data.len()
+                ),
+                attachment.data,
+            ));
+            let name = literal(attachment.file_name);
+            let filespec_id = add(format!(
+                "<< /Type /Filespec /F {} /UF {} /Desc {} /AFRelationship /Alternative \
+                 /EF << /F {} 0 R /UF {} 0 R >> >>",
+                name,
+                name,
+                literal(attachment.description),
+                file_id,
+                file_id
+            )
+            .into_bytes());
+            format!(
+                " /Names << /EmbeddedFiles << /Names [{} {} 0 R] >> >> /AF [{} 0 R]",
+                name, filespec_id, filespec_id
+            )
+        }
+        None => String::new(),
+    };
+    let metadata = xmp_metadata(
+        title,
+        attachment.map_or("", |attachment| attachment.metadata),
+    );
+    let metadata_id = add(stream("/Type /Metadata /Subtype /XML", metadata.as_bytes()));
+
+    objects[0] = format!(
+        "<< /Type /Catalog /Pages 2 0 R /Metadata {} 0 R \
+         /OutputIntents [<< /Type /OutputIntent /S /GTS_PDFA1 \
+         /OutputConditionIdentifier (sRGB IEC61966-2.1) /Info (sRGB IEC61966-2.1) \
+         /DestOutputProfile {} 0 R >>]{} >>",
+        metadata_id, profile_id, attachment_entries
+    )
+    .into_bytes();
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    )
+    .into_bytes();
+
+    let mut output = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(output.len());
+        output.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        output.extend_from_slice(object);
+        output.extend_from_slice(b"\nendobj\n");
+    }
+
+    // PDF/A requires a file identifier, the hash of the content keeps it reproducible.
+    let id: String = Sha256::digest(&output)[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let xref_offset = output.len();
+    output.extend_from_slice(format!("xref\n0 {}\n", objects.len() + 1).as_bytes());
+    output.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        output.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    output.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /ID [<{}> <{}>] >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            id,
+            id,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+    output
+}
+
+/// Stream object, the line break before `endstream` is not part of the data.
+fn stream(entries: &str, data: &[u8]) -> Vec<u8> {
+    let mut object = if entries.is_empty() {
+        format!("<< /Length {} >>\nstream\n", data.len())
+    } else {
+        format!("<< {} /Length {} >>\nstream\n", entries, data.len())
+    }
+    .into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// XMP packet that identifies the document as PDF/A-3b.
+fn xmp_metadata(title: &str, descriptions: &str) -> String {
+    format!(
+        r#"<?xpacket begin="\u{{feff}}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+      <pdfaid:part>3</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">{}</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+    </rdf:Description>
+{}  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        escape_xml(title),
+        descriptions
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// ICC profile of sRGB for the output intent, version 2 with the primaries adapted to D50.
+fn srgb_profile() -> Vec<u8> {
+    fn s15_fixed16(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(values: [f64; 3]) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for value in values {
+            tag.extend_from_slice(&s15_fixed16(value));
+        }
+        tag
+    }
+    const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+    let description = b"sRGB IEC61966-2.1\0";
+    let mut desc = b"desc\0\0\0\0".to_vec();
+    desc.extend_from_slice(&(description.len() as u32).to_be_bytes());
+    desc.extend_from_slice(description);
+    // Empty Unicode and ScriptCode descriptions.
+    desc.extend_from_slice(&[0; 4 + 4 + 2 + 1 + 67]);
+
+    let mut copyright = b"text\0\0\0\0".to_vec();
+    copyright.extend_from_slice(b"No copyright, use freely\0");
+
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend_from_slice(&1024u32.to_be_bytes());
+    for index in 0..1024 {
+        let x = index as f64 / 1023.0;
+        let y = if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        };
+        curve.extend_from_slice(&((y * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", desc),
+        (b"cprt", copyright),
+        (b"wtpt", xyz(D50)),
+        (b"rXYZ", xyz([0.4361, 0.2225, 0.0139])),
+        (b"gXYZ", xyz([0.3851, 0.7169, 0.0971])),
+        (b"bXYZ", xyz([0.1431, 0.0606, 0.7141])),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_offset = 128 + 4 + 12 * tags.len();
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_offset + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let mut profile = vec![0u8; 128];
+    let size = (128 + table.len() + data.len()) as u32;
+    profile[0..4].copy_from_slice(&size.to_be_bytes());
+    profile[8..12].copy_from_slice(&0x0210_0000u32.to_be_bytes());
+    profile[12..16].copy_from_slice(b"mntr");
+    profile[16..20].copy_from_slice(b"RGB ");
+    profile[20..24].copy_from_slice(b"XYZ ");
+    for (index, value) in [2024u16, 1, 1, 0, 0, 0].into_iter().enumerate() {
+        profile[24 + 2 * index..26 + 2 * index].copy_from_slice(&value.to_be_bytes());
+    }
+    profile[36..40].copy_from_slice(b"acsp");
+    for (index, value) in D50.into_iter().enumerate() {
+        profile[68 + 4 * index..72 + 4 * index].copy_from_slice(&s15_fixed16(value));
+    }
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+/// Numbers with at most two decimal places, as short as possible.
+fn number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Literal string in PDFDocEncoding, which matches WinAnsi for the common characters.
+fn literal(text: &str) -> String {
+    let mut output = String::from("(");
+    for byte in encode_win_ansi(text) {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                output.push('\\');
+                output.push(byte as char);
+            }
+            0x20..=0x7e => output.push(byte as char),
+            _ => output.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    output.push(')');
+    output
+}
+
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => c as u8,
+            _ => WIN_ANSI_HIGH
+                .iter()
+                .position(|&high| high == Some(c))
+                .map_or(b'?', |index| 0x80 + index as u8),
+        })
+        .collect()
+}
+
+fn win_ansi_char(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7e | 0xa0..=0xff => Some(code as char),
+        0x80..=0x9f => WIN_ANSI_HIGH[(code - 0x80) as usize],
+        _ => None,
+    }
+}
@@ -0,0 +1,497 @@
+use chrono::{Datelike, NaiveDate};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    datev::{DatevBooking, DatevHeader, DatevWriter},
+    error::{ServiceError, ServiceResult},
+    model::{DatevAccountsDto, DatevPaymentAccountDto, DatevRevenueAccountDto, PaymentMethod},
+    money,
+    state::preference,
+    time_zone,
+};
+
+const CONSULTANT_NUMBER_KEY: &str = "datev.consultant-number";
+const CLIENT_NUMBER_KEY: &str = "datev.client-number";
+const ACCOUNT_LENGTH_KEY: &str = "datev.account-length";
+const ACCOUNTS_KEY: &str = "datev.accounts";
+
+const DEFAULT_ACCOUNT_LENGTH: i64 = 4;
+
+#[derive(Clone)]
+pub struct AccountingState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl AccountingState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    /// Exports the revenue and the payments between both dates as a DATEV booking batch.
+    /// Revenue is booked from the debtor account to the revenue account of the VAT rate,
+    /// payments from the account of the payment method to the debtor account. Sold vouchers
+    /// are booked from the debtor account to the voucher account, which their redemptions are
+    /// paid from.
+    pub async fn export_datev_bookings(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        path: &Path,
+    ) -> ServiceResult<()> {
+        if to < from {
+            return Err(ServiceError::BadRequest(
+                "export must not end before it starts".to_string(),
+            ));
+        }
+        // A batch belongs to a single fiscal year, which is the calendar year.
+        if from.year() != to.year() {
+            return Err(ServiceError::BadRequest(
+                "export must not span several fiscal years".to_string(),
+            ));
+        }
+
+        let mut connection = self.pool.acquire().await.unwrap();
+        let consultant_number =
+            preference::get_preference::<i64>(connection.as_mut(), CONSULTANT_NUMBER_KEY).await?;
+        let client_number =
+            preference::get_preference::<i64>(connection.as_mut(), CLIENT_NUMBER_KEY).await?;
+        let (Some(consultant_number), Some(client_number)) = (consultant_number, client_number)
+        else {
+            return Err(ServiceError::BadRequest(
+                "DATEV consultant and client number are not set".to_string(),
+            ));
+        };
+        let account_length =
+            preference::get_preference::<i64>(connection.as_mut(), ACCOUNT_LENGTH_KEY)
+                .await?
+                .unwrap_or(DEFAULT_ACCOUNT_LENGTH);
+        let accounts =
+            preference::get_preference::<DatevAccountsDto>(connection.as_mut(), ACCOUNTS_KEY)
+                .await?
+                .unwrap_or_else(default_accounts);
+        let zone = preference::get_time_zone(connection.as_mut()).await?;
+        let currency = preference::get_currency(connection.as_mut()).await?;
+
+        let mut writer = DatevWriter::new(&DatevHeader {
+            created_at: time_zone::now_local(zone),
+            consultant_number,
+            client_number,
+            fiscal_year_start: NaiveDate::from_ymd_opt(from.year(), 1, 1).unwrap(),
+            account_length,
+            from,
+            to,
+            label: "Erlöse und Zahlungen",
+            currency: money::currency_code(currency),
+        });
+
+        for row in load_revenue(connection.as_mut(), from, to).await? {
+            let account = accounts
+                .revenue_accounts
+                .iter()
+                .find(|account| account.vat_rate == row.vat_rate)
+                .ok_or_else(|| {
+                    ServiceError::BadRequest(format!(
+                        "no DATEV revenue account for the VAT rate of {} %",
+                        row.vat_rate as f64 / 100.0
+                    ))
+                })?;
+            writer.booking(&DatevBooking {
+                amount: row.gross,
+                account: accounts.receivable_account,
+                contra_account: account.account,
+                date: row.date,
+                document: &row.document,
+                text: &row.name,
+            });
+        }
+
+        let voucher_sales = load_voucher_sales(connection.as_mut(), from, to).await?;
+        if !voucher_sales.is_empty() {
+            let account = payment_account(&accounts, PaymentMethod::Voucher)?;
+            for row in voucher_sales {
+                writer.booking(&DatevBooking {
+                    amount: row.initial_value,
+                    account: accounts.receivable_account,
+                    contra_account: account,
+                    date: row.issue_date,
+                    document: &row.code,
+                    text: &row.name,
+                });
+            }
+        }
+
+        for row in load_payments(connection.as_mut(), from, to).await? {
+            let account = payment_account(&accounts, row.method)?;
+            let document = if row.reference.trim().is_empty() {
+                row.id.to_string()
+            } else {
+                row.reference
+            };
+            writer.booking(&DatevBooking {
+                amount: row.amount,
+                account,
+                contra_account: accounts.receivable_account,
+                date: row.date,
+                document: &document,
+                text: &format!("{} {}", row.first_name, row.last_name),
+            });
+        }
+
+        tokio::fs::write(path, writer.finish()).await?;
+        Ok(())
+    }
+}
+
+/// Accounts of the SKR03 chart of accounts, used until the practice configures its own.
+fn default_accounts() -> DatevAccountsDto {
+    DatevAccountsDto {
+        receivable_account: 10000,
+        revenue_accounts: vec![
+            DatevRevenueAccountDto {
+                vat_rate: 1900,
+                account: 8400,
+            },
+            DatevRevenueAccountDto {
+                vat_rate: 700,
+                account: 8300,
+            },
+            DatevRevenueAccountDto {
+                vat_rate: 0,
+                account: 8200,
+            },
+        ],
+        payment_accounts: vec![
+            DatevPaymentAccountDto {
+                method: PaymentMethod::Cash,
+                account: 1000,
+            },
+            DatevPaymentAccountDto {
+                method: PaymentMethod::Card,
+                account: 1360,
+            },
+            DatevPaymentAccountDto {
+                method: PaymentMethod::Transfer,
+                account: 1200,
+            },
+            DatevPaymentAccountDto {
+                method: PaymentMethod::Voucher,
+                account: 1700,
+            },
+        ],
+    }
+}
+
+fn payment_account(accounts: &DatevAccountsDto, method: PaymentMethod) -> ServiceResult<i64> {
+    accounts
+        .payment_accounts
+        .iter()
+        .find(|account| account.method == method)
+        .map(|account| account.account)
+        .ok_or_else(|| {
+            ServiceError::BadRequest(format!(
+                "no DATEV account for {} payments",
+                method_name(method)
+            ))
+        })
+}
+
+fn method_name(method: PaymentMethod) -> &'static str {
+    match method {
+        PaymentMethod::Cash => "cash",
+        PaymentMethod::Card => "card",
+        PaymentMethod::Transfer => "transfer",
+        PaymentMethod::Voucher => "voucher",
+    }
+}
+
+/// Gross revenue per document and VAT rate:
+///
+/// - Issued invoices and credit notes on their issue date, from their stored items. Credit
+///   notes have negative quantities, so they reverse the cancelled invoice.
+/// - Appointments that are not part of an issued invoice on their date, from their items.
+///   Appointments of a cancelled invoice are booked again, as the credit note reversed them.
+/// - Sold packages on their purchase date, the sessions used later are free.
+async fn load_revenue(
+    connection: &mut SqliteConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ServiceResult<Vec<RevenueRow>> {
+    Ok(sqlx::query_as::<_, RevenueRow>(
+        r#"
+        SELECT
+            inv.issue_date AS date,
+            i.vat_rate,
+            SUM(i.quantity * i.unit_price - i.discount) AS gross,
+            inv.number AS document,
+            inv.customer_name AS name,
+            1 AS source,
+            inv.id AS source_id
+        FROM invoice_item i
+        JOIN invoice inv ON inv.id = i.invoice_id
+        WHERE inv.status <> 'draft' AND inv.issue_date BETWEEN $1 AND $2
+        GROUP BY inv.id, i.vat_rate
+
+        UNION ALL
+
+        SELECT
+            date(a.start_date) AS date,
+            i.vat_rate,
+            SUM(i.quantity * i.unit_price - i.discount) AS gross,
+            CAST(a.id AS TEXT) AS document,
+            c.first_name || ' ' || c.last_name AS name,
+            0 AS source,
+            a.id AS source_id
+        FROM appointment_item i
+        JOIN appointment a ON a.id = i.appointment_id
+        JOIN customer c ON c.id = a.customer_id
+        WHERE date(a.start_date) BETWEEN $1 AND $2
+            AND NOT EXISTS (
+                SELECT 1
+                FROM invoice_item ii
+                JOIN invoice n ON n.id = ii.invoice_id
+                WHERE ii.appointment_id = a.id
+                    AND n.kind = 'invoice'
+                    AND n.status = 'issued'
+            )
+        GROUP BY a.id, i.vat_rate
+
+        UNION ALL
+
+        SELECT
+            cp.purchase_date AS date,
+            cp.vat_rate,
+            cp.price AS gross,
+            'P' || cp.id AS document,
+            c.first_name || ' ' || c.last_name AS name,
+            2 AS source,
+            cp.id AS source_id
+        FROM customer_package cp
+        JOIN customer c ON c.id = cp.customer_id
+        WHERE cp.purchase_date BETWEEN $1 AND $2
+
+        ORDER BY date ASC, source ASC, source_id ASC, vat_rate DESC;
+    "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .filter(|row| row.gross != 0)
+    .collect())
+}
+
+/// Vouchers issued between both dates. They are a liability until they are redeemed, not
+/// revenue.
+async fn load_voucher_sales(
+    connection: &mut SqliteConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ServiceResult<Vec<VoucherSaleRow>> {
+    Ok(sqlx::query_as::<_, VoucherSaleRow>(
+        r#"
+        SELECT
+            v.code,
+            v.issue_date,
+            v.initial_value,
+            COALESCE(c.first_name || ' ' || c.last_name, '') AS name
+        FROM voucher v
+        LEFT JOIN customer c ON c.id = v.purchaser_customer_id
+        WHERE v.issue_date BETWEEN $1 AND $2
+        ORDER BY v.issue_date ASC, v.id ASC;
+    "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(connection)
+    .await?)
+}
+
+async fn load_payments(
+    connection: &mut SqliteConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ServiceResult<Vec<PaymentRow>> {
+    Ok(sqlx::query_as::<_, PaymentRow>(
+        r#"
+        SELECT p.id, p.date, p.amount, p.method, p.reference, c.first_name, c.last_name
+        FROM payment p
+        JOIN customer c ON c.id = p.customer_id
+        WHERE p.date BETWEEN $1 AND $2 AND p.amount <> 0
+        ORDER BY p.date ASC, p.id ASC;
+    "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(connection)
+    .await?)
+}
+
+#[derive(sqlx::FromRow)]
+struct RevenueRow {
+    pub date: NaiveDate,
+    pub vat_rate: i64,
+    pub gross: i64,
+    pub document: String,
+    pub name: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct VoucherSaleRow {
+    pub code: String,
+    pub issue_date: NaiveDate,
+    pub initial_value: i64,
+    pub name: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct PaymentRow {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+    pub method: PaymentMethod,
+    pub reference: String,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{EditCustomerPackageDto, EditVoucherDto};
+    use crate::state::testing::{appointment, customer, item, set_preference, state, temp_dir};
+
+    #[tokio::test]
+    async fn revenue_follows_invoices() {
+        let state = state().await;
+        set_preference(&state, super::CONSULTANT_NUMBER_KEY, 1001).await;
+        set_preference(&state, super::CLIENT_NUMBER_KEY, 42).await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+
+        // Invoices are issued today, so all documents are dated today.
+        let first = state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                "2024-03-04T09:00:00".parse().unwrap(),
+                vec![item("Massage", 1, 5950)],
+            ))
+            .await
+            .unwrap();
+        let cancelled_id = state.invoice.create_invoice(vec![first]).await.unwrap();
+        let cancelled = state.invoice.issue_invoice(cancelled_id).await.unwrap();
+        let today = cancelled.issue_date.unwrap();
+        let credit_note_id = state.invoice.cancel_invoice(cancelled_id).await.unwrap();
+        let credit_note = state
+            .invoice
+            .get_invoice_by_id(credit_note_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let at = |hour| today.and_hms_opt(hour, 0, 0).unwrap();
+        let uninvoiced = state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                at(9),
+                vec![item("Massage", 1, 5950), {
+                    let mut oil = item("Öl", 1, 1070);
+                    oil.vat_rate = 700;
+                    oil
+                }],
+            ))
+            .await
+            .unwrap();
+        let invoiced = state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                at(11),
+                vec![item("Fango", 2, 1190)],
+            ))
+            .await
+            .unwrap();
+        let invoice_id = state.invoice.create_invoice(vec![invoiced]).await.unwrap();
+        let invoice = state.invoice.issue_invoice(invoice_id).await.unwrap();
+
+        let package_id = state
+            .package
+            .store_customer_package(EditCustomerPackageDto {
+                id: None,
+                customer_id,
+                session_package_id: None,
+                name: "10 × Massage".to_string(),
+                treatment_id: None,
+                session_count: 10,
+                price: 50000,
+                vat_rate: 700,
+                purchase_date: today,
+                expiry_date: None,
+                note: String::new(),
+            })
+            .await
+            .unwrap();
+        let voucher_id = state
+            .voucher
+            .store_voucher(EditVoucherDto {
+                id: None,
+                initial_value: 3000,
+                issue_date: today,
+                expiry_date: None,
+                purchaser_customer_id: Some(customer_id),
+                recipient_customer_id: None,
+                note: String::new(),
+            })
+            .await
+            .unwrap();
+        let voucher = state
+            .voucher
+            .get_voucher_by_id(voucher_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let path = temp_dir().join("EXTF_Buchungsstapel.csv");
+        state
+            .accounting
+            .export_datev_bookings(today, today, &path)
+            .await
+            .unwrap();
+        // Latin-1 is enough for the texts of the test.
+        let content: String = std::fs::read(&path)
+            .unwrap()
+            .into_iter()
+            .map(char::from)
+            .collect();
+
+        let day = today.format("%d%m");
+        let line = |amount: &str, side: &str, account: i64, document: &str| {
+            format!(
+                "{};\"{}\";\"EUR\";;;;10000;{};\"\";{};\"{}\";\"\";;\"Anna Berg\"",
+                amount, side, account, day, document
+            )
+        };
+        let number = |invoice: &crate::model::InvoiceDto| invoice.number.clone().unwrap();
+        assert_eq!(
+            content.split("\r\n").skip(2).collect::<Vec<_>>(),
+            vec![
+                // Only the appointments that are not on an issued invoice.
+                line("59,50", "S", 8400, &uninvoiced.to_string()),
+                line("10,70", "S", 8300, &uninvoiced.to_string()),
+                // The cancelled invoice and its credit note cancel each other out.
+                line("59,50", "S", 8400, &number(&cancelled)),
+                line("59,50", "H", 8400, &number(&credit_note)),
+                line("23,80", "S", 8400, &number(&invoice)),
+                line("500,00", "S", 8300, &format!("P{}", package_id)),
+                line("30,00", "S", 1700, &voucher.code),
+                String::new(),
+            ]
+        );
+    }
+}
@@ -1,12 +1,26 @@
-use chrono::{Duration, NaiveDate, NaiveDateTime};
-use sqlx::{Pool, Sqlite};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::{
-    error::ServiceResult,
-    model::{CustomerAppointmentDto, EditCustomerAppointmentDto, EmployeeDto},
+    error::{ServiceError, ServiceResult},
+    ical,
+    model::{
+        AppointmentConflictDto, AppointmentConflictKind, AppointmentFilterDto, AppointmentItemDto,
+        Currency, CustomerAppointmentDto, EditAppointmentItemDto, EditCustomerAppointmentDto,
+        EmployeeRefDto, IcalImportAppointmentDto, OverdueCustomerDto, PriceDto, ResourceDto,
+        ResourceKind, StockMovementKind, VisitPredictionDto, WaitlistEntryDto,
+    },
+    money,
+    state::{preference, price_rule, waitlist},
+    time_zone,
 };
 
+/// Number of most recent visit periods that are considered for the typical visit interval.
+const RECENT_PERIOD_COUNT: usize = 5;
+
 #[derive(Clone)]
 pub struct AppointmentState {
     pool: Arc<Pool<Sqlite>>,
@@ -30,10 +44,12 @@ impl AppointmentState {
                 a.number,
                 a.start_date,
                 a.duration_minutes,
-                a.treatment,
-                a.price,
+                a.time_zone,
+                a.start_utc,
+                a.end_utc,
                 e.id AS employee_id,
-                e.name AS employee_name
+                e.name AS employee_name,
+                e.active AS employee_active
             FROM appointment a
             LEFT JOIN employee e ON a.employee_id = e.id
             WHERE a.customer_id = $1
@@ -47,10 +63,52 @@ impl AppointmentState {
         .map(|row| row.into())
         .collect();
 
+        let currency = preference::get_currency(connection.as_mut()).await?;
+        let mut items = sqlx::query_as::<_, AppointmentItemRow>(
+            r#"
+            SELECT
+                i.id,
+                i.appointment_id,
+                i.treatment_id,
+                i.product_id,
+                i.description,
+                i.quantity,
+                i.unit_price,
+                i.discount,
+                i.vat_rate,
+                i.customer_package_id,
+                i.price_rule_id,
+                r.name AS price_rule_name
+            FROM appointment_item i
+            JOIN appointment a ON i.appointment_id = a.id
+            LEFT JOIN price_rule r ON r.id = i.price_rule_id
+            WHERE a.customer_id = $1
+            ORDER BY i.appointment_id ASC, i.position ASC;
+        "#,
+        )
+        .bind(customer_id)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .fold(
+            HashMap::<i64, Vec<AppointmentItemDto>>::new(),
+            |mut map, row| {
+                map.entry(row.appointment_id)
+                    .or_default()
+                    .push(row.into_item(currency));
+                map
+            },
+        );
+
+        attach_resources(connection.as_mut(), &mut appointments).await?;
+
         let mut last_start_date: Option<NaiveDate> = None;
         for appointment in &mut appointments {
-            let duration = Duration::minutes(appointment.duration_minutes);
-            appointment.end_date = appointment.start_date + duration;
+            attach_items(
+                appointment,
+                items.remove(&appointment.id).unwrap_or_default(),
+                currency,
+            );
 
             if let Some(last_start_date) = last_start_date {
                 let delta = appointment.start_date.date() - last_start_date;
@@ -64,134 +122,1550 @@ impl AppointmentState {
         Ok(appointments)
     }
 
+    /// Appointments matching all given filters, ordered by start date. The date range is
+    /// inclusive on both ends.
+    pub async fn get_appointment_list_by_filter(
+        &self,
+        filter: &AppointmentFilterDto,
+    ) -> ServiceResult<Vec<CustomerAppointmentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let from = filter.from.map(|date| date.and_time(NaiveTime::MIN));
+        let to = filter
+            .to
+            .map(|date| (date + Duration::days(1)).and_time(NaiveTime::MIN));
+
+        let mut appointments: Vec<CustomerAppointmentDto> = sqlx::query_as::<_, AppointmentRow>(
+            r#"
+            SELECT 
+                a.id,
+                a.customer_id,
+                a.number,
+                a.start_date,
+                a.duration_minutes,
+                a.time_zone,
+                a.start_utc,
+                a.end_utc,
+                e.id AS employee_id,
+                e.name AS employee_name,
+                e.active AS employee_active
+            FROM appointment a
+            LEFT JOIN employee e ON a.employee_id = e.id
+            WHERE ($1 IS NULL OR a.customer_id = $1)
+            AND ($2 IS NULL OR a.employee_id = $2)
+            AND ($3 IS NULL OR a.start_date >= $3)
+            AND ($4 IS NULL OR a.start_date < $4)
+            AND ($5 IS NULL OR EXISTS (
+                SELECT 1
+                FROM appointment_resource ar
+                WHERE ar.appointment_id = a.id AND ar.resource_id = $5
+            ))
+            ORDER BY a.start_date ASC;
+        "#,
+        )
+        .bind(filter.customer_id)
+        .bind(filter.employee_id)
+        .bind(from)
+        .bind(to)
+        .bind(filter.resource_id)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+        let currency = preference::get_currency(connection.as_mut()).await?;
+        let mut items = sqlx::query_as::<_, AppointmentItemRow>(
+            r#"
+            SELECT
+                i.id,
+                i.appointment_id,
+                i.treatment_id,
+                i.product_id,
+                i.description,
+                i.quantity,
+                i.unit_price,
+                i.discount,
+                i.vat_rate,
+                i.customer_package_id,
+                i.price_rule_id,
+                r.name AS price_rule_name
+            FROM appointment_item i
+            JOIN appointment a ON i.appointment_id = a.id
+            LEFT JOIN price_rule r ON r.id = i.price_rule_id
+            WHERE ($1 IS NULL OR a.customer_id = $1)
+            AND ($2 IS NULL OR a.employee_id = $2)
+            AND ($3 IS NULL OR a.start_date >= $3)
+            AND ($4 IS NULL OR a.start_date < $4)
+            AND ($5 IS NULL OR EXISTS (
+                SELECT 1
+                FROM appointment_resource ar
+                WHERE ar.appointment_id = a.id AND ar.resource_id = $5
+            ))
+            ORDER BY i.appointment_id ASC, i.position ASC;
+        "#,
+        )
+        .bind(filter.customer_id)
+        .bind(filter.employee_id)
+        .bind(from)
+        .bind(to)
+        .bind(filter.resource_id)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .fold(
+            HashMap::<i64, Vec<AppointmentItemDto>>::new(),
+            |mut map, row| {
+                map.entry(row.appointment_id)
+                    .or_default()
+                    .push(row.into_item(currency));
+                map
+            },
+        );
+
+        attach_resources(connection.as_mut(), &mut appointments).await?;
+
+        for appointment in &mut appointments {
+            attach_items(
+                appointment,
+                items.remove(&appointment.id).unwrap_or_default(),
+                currency,
+            );
+        }
+
+        Ok(appointments)
+    }
+
     pub async fn get_appointment_by_id(
         &self,
         id: i64,
     ) -> ServiceResult<Option<CustomerAppointmentDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
-        Ok(sqlx::query_as::<_, AppointmentRow>(
-            r#"
+        let Some(mut appointment): Option<CustomerAppointmentDto> =
+            sqlx::query_as::<_, AppointmentRow>(
+                r#"
             SELECT 
                 a.id,
                 a.customer_id,
                 a.number,
                 a.start_date,
                 a.duration_minutes,
-                a.treatment,
-                a.price,
+                a.time_zone,
+                a.start_utc,
+                a.end_utc,
                 e.id AS employee_id,
-                e.name AS employee_name
+                e.name AS employee_name,
+                e.active AS employee_active
             FROM appointment a
             LEFT JOIN employee e ON a.employee_id = e.id
             WHERE a.id = $1;
+        "#,
+            )
+            .bind(id)
+            .fetch_optional(connection.as_mut())
+            .await?
+            .map(|row| row.into())
+        else {
+            return Ok(None);
+        };
+
+        let currency = preference::get_currency(connection.as_mut()).await?;
+        let items = get_item_list(connection.as_mut(), id, currency).await?;
+        attach_items(&mut appointment, items, currency);
+        attach_resources(connection.as_mut(), std::slice::from_mut(&mut appointment)).await?;
+
+        Ok(Some(appointment))
+    }
+
+    /// Appointment an imported event refers to. Uids of this installation name the appointment
+    /// directly, all other uids are looked up among the remembered ones. These include local
+    /// uids of appointments that were deleted and imported again.
+    pub async fn get_appointment_by_ical_uid(
+        &self,
+        uid: &str,
+    ) -> ServiceResult<Option<CustomerAppointmentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let host = preference::get_ical_host(connection.as_mut()).await?;
+        if let Some(id) = ical::parse_appointment_uid(uid, &host)
+            && let Some(appointment) = self.get_appointment_by_id(id).await?
+        {
+            return Ok(Some(appointment));
+        }
+
+        let id = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT a.id
+            FROM appointment a
+            WHERE a.ical_uid = $1;
         "#,
         )
-        .bind(id)
+        .bind(uid)
         .fetch_optional(connection.as_mut())
-        .await?
-        .map(|row| row.into()))
+        .await?;
+
+        match id {
+            Some(row) => self.get_appointment_by_id(row.id).await,
+            None => Ok(None),
+        }
     }
 
+    /// Stores the appointment in the zone it was entered in, new appointments in the practice
+    /// time zone. Start dates skipped by a daylight saving change are rejected, ones that occur
+    /// twice need the choice of the occurrence unless they are kept unchanged.
     pub async fn store_appointment(
         &self,
         appointment: EditCustomerAppointmentDto,
     ) -> ServiceResult<i64> {
+        // The conflict checks read before writing, an immediate transaction takes the write
+        // lock up front instead of failing on the upgrade when saves run concurrently.
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let appointment_id = store_appointment(transaction.as_mut(), &appointment).await?;
+        transaction.commit().await?;
+        Ok(appointment_id)
+    }
+
+    /// Conflicts the appointment would cause if it was stored, including the resources required
+    /// by its catalog treatments.
+    pub async fn check_appointment_conflicts(
+        &self,
+        appointment: &EditCustomerAppointmentDto,
+    ) -> ServiceResult<Vec<AppointmentConflictDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
+        let resource_ids = get_required_resource_ids(connection.as_mut(), appointment).await?;
+        let zone = get_appointment_time_zone(connection.as_mut(), appointment.id).await?;
+        let start_utc = resolve_start(connection.as_mut(), appointment, zone).await?;
+        find_conflicts(
+            connection.as_mut(),
+            appointment.id,
+            start_utc,
+            start_utc + Duration::minutes(appointment.duration_minutes),
+            appointment.employee_id,
+            &resource_ids,
+        )
+        .await
+    }
 
-        let q = if let Some(appointment_id) = appointment.id {
-            sqlx::query(
-                r#"
-                UPDATE appointment
-                SET customer_id = $2,
-                    start_date = $3,
-                    duration_minutes = $4,
-                    treatment = $5,
-                    price = $6,
-                    employee_id = $7
-                WHERE id = $1;
-            "#,
+    /// Stores all imported appointments in a single transaction. Uids other than the one of the
+    /// stored appointment itself are remembered, so importing the same calendar again updates
+    /// these appointments. This includes uids of local appointments that have been deleted.
+    pub async fn import_appointments(
+        &self,
+        appointments: Vec<IcalImportAppointmentDto>,
+    ) -> ServiceResult<Vec<i64>> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let host = preference::get_ical_host(transaction.as_mut()).await?;
+
+        let mut appointment_ids = Vec::with_capacity(appointments.len());
+        for import in appointments {
+            let appointment_id =
+                store_appointment(transaction.as_mut(), &import.appointment).await?;
+
+            let foreign_uid = import
+                .ical_uid
+                .filter(|uid| ical::parse_appointment_uid(uid, &host) != Some(appointment_id));
+            if let Some(uid) = foreign_uid {
+                sqlx::query(
+                    r#"
+                    UPDATE appointment
+                    SET ical_uid = $2
+                    WHERE id = $1;
+                "#,
+                )
+                .bind(appointment_id)
+                .bind(uid)
+                .execute(transaction.as_mut())
+                .await?;
+            }
+
+            appointment_ids.push(appointment_id);
+        }
+
+        transaction.commit().await?;
+        Ok(appointment_ids)
+    }
+
+    pub async fn get_visit_prediction(
+        &self,
+        customer_id: i64,
+    ) -> ServiceResult<Option<VisitPredictionDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let now = time_zone::now_local(preference::get_time_zone(connection.as_mut()).await?);
+        let start_dates: Vec<NaiveDate> = sqlx::query_as::<_, VisitRow>(
+            r#"
+            SELECT a.customer_id, a.start_date
+            FROM appointment a
+            WHERE a.customer_id = $1 AND a.start_date <= $2
+            ORDER BY a.start_date ASC;
+        "#,
+        )
+        .bind(customer_id)
+        .bind(now)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.start_date.date())
+        .collect();
+
+        Ok(predict_next_visit(customer_id, &start_dates))
+    }
+
+    /// Customers whose predicted next visit lies more than `tolerance_days` in the past and who
+    /// have no upcoming appointment, the longest overdue first.
+    pub async fn get_overdue_customers(
+        &self,
+        tolerance_days: i64,
+    ) -> ServiceResult<Vec<OverdueCustomerDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let now = time_zone::now_local(preference::get_time_zone(connection.as_mut()).await?);
+
+        let visits = sqlx::query_as::<_, VisitRow>(
+            r#"
+            SELECT a.customer_id, a.start_date
+            FROM appointment a
+            WHERE a.start_date <= $1
+            AND a.customer_id NOT IN (
+                SELECT b.customer_id
+                FROM appointment b
+                WHERE b.start_date > $1
             )
-            .bind(appointment_id)
-        } else {
-            let number = sqlx::query_as::<_, NumberRow>(
+            ORDER BY a.customer_id ASC, a.start_date ASC;
+        "#,
+        )
+        .bind(now)
+        .fetch_all(connection.as_mut())
+        .await?;
+
+        let mut predictions: HashMap<i64, VisitPredictionDto> = HashMap::new();
+        for chunk in visits.chunk_by(|a, b| a.customer_id == b.customer_id) {
+            let customer_id = chunk[0].customer_id;
+            let start_dates: Vec<NaiveDate> =
+                chunk.iter().map(|row| row.start_date.date()).collect();
+
+            if let Some(prediction) = predict_next_visit(customer_id, &start_dates) {
+                let overdue_days = (now.date() - prediction.predicted_next_visit).num_days();
+                if overdue_days > tolerance_days {
+                    predictions.insert(customer_id, prediction);
+                }
+            }
+        }
+
+        let mut overdue_customers: Vec<OverdueCustomerDto> =
+            sqlx::query_as::<_, CustomerContactRow>(
                 r#"
-            SELECT a.number
-            FROM appointment a
-            WHERE a.customer_id = $1
-            ORDER BY number DESC
-            LIMIT 1;
-            "#,
+            SELECT
+                c.id,
+                c.title,
+                c.first_name,
+                c.last_name,
+                c.phone,
+                c.mobile_phone
+            FROM customer c;
+        "#,
             )
-            .bind(appointment.customer_id)
-            .fetch_optional(connection.as_mut())
+            .fetch_all(connection.as_mut())
             .await?
-            .unwrap_or_default();
+            .into_iter()
+            .filter_map(|row| {
+                let prediction = predictions.remove(&row.id)?;
+                Some(OverdueCustomerDto {
+                    customer_id: row.id,
+                    title: row.title,
+                    first_name: row.first_name,
+                    last_name: row.last_name,
+                    phone: row.phone,
+                    mobile_phone: row.mobile_phone,
+                    last_visit: prediction.last_visit,
+                    typical_interval_days: prediction.typical_interval_days,
+                    predicted_next_visit: prediction.predicted_next_visit,
+                    overdue_days: (now.date() - prediction.predicted_next_visit).num_days(),
+                })
+            })
+            .collect();
 
-            sqlx::query(
-                r#"
-                INSERT INTO appointment (
-                    number,
-                    customer_id,
-                    start_date,
-                    duration_minutes,
-                    treatment,
-                    price,
-                    employee_id
-                )
-                VALUES ($1, $2, $3, $4, $5, $6, $7);
+        overdue_customers.sort_by_key(|customer| -customer.overdue_days);
+        Ok(overdue_customers)
+    }
+
+    /// Renumbers all appointments of a customer in chronological order, closing gaps left by
+    /// deleted or back-dated appointments. The order follows the instants, appointments entered
+    /// in different time zones can't be compared by their local times.
+    pub async fn renumber_appointments(&self, customer_id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        // Move the numbers out of the way first, the unique index is checked for every row.
+        sqlx::query(
+            r#"
+                UPDATE appointment
+                SET number = -number
+                WHERE customer_id = $1;
             "#,
-            )
-            .bind(number.number + 1)
+        )
+        .bind(customer_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+                UPDATE appointment
+                SET number = r.number
+                FROM (
+                    SELECT
+                        id,
+                        ROW_NUMBER() OVER (ORDER BY start_utc ASC, id ASC) AS number
+                    FROM appointment
+                    WHERE customer_id = $1
+                ) r
+                WHERE appointment.id = r.id;
+            "#,
+        )
+        .bind(customer_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the appointment and returns the waitlist entries that fit into the freed slot.
+    /// Appointments of issued invoices are kept until the invoice is cancelled.
+    pub async fn delete_appointment(&self, id: i64) -> ServiceResult<Vec<WaitlistEntryDto>> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(number) = get_invoice_number(transaction.as_mut(), id).await? {
+            return Err(ServiceError::Conflict(format!(
+                "appointment is part of invoice {} and can only be deleted after cancelling it",
+                number
+            )));
+        }
+
+        let slot = sqlx::query_as::<_, SlotRow>(
+            r#"
+                SELECT a.start_date, a.duration_minutes, a.time_zone, a.end_utc, a.employee_id
+                FROM appointment a
+                WHERE a.id = $1;
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(transaction.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+                DELETE FROM appointment
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        let entries = match slot {
+            Some(slot) => {
+                waitlist::find_matching_entries(
+                    transaction.as_mut(),
+                    slot.start_date,
+                    local_end_date(
+                        &slot.time_zone,
+                        slot.start_date,
+                        slot.duration_minutes,
+                        slot.end_utc,
+                    ),
+                    slot.employee_id,
+                )
+                .await?
+            }
+            None => Vec::new(),
         };
 
-        q.bind(appointment.customer_id)
-            .bind(appointment.start_date)
-            .bind(appointment.duration_minutes)
-            .bind(&appointment.treatment)
-            .bind(appointment.price)
-            .bind(appointment.employee_id)
-            .execute(connection.as_mut())
-            .await?;
+        transaction.commit().await?;
+        Ok(entries)
+    }
+}
+
+/// Assigns the practice time zone to appointments stored before appointments had one, which
+/// also fills their UTC instants. Runs once before the migration that makes these required.
+pub async fn assign_time_zones(pool: &Pool<Sqlite>) -> ServiceResult<()> {
+    let mut transaction = pool.begin_with("BEGIN IMMEDIATE").await?;
 
-        if let Some(appointment_id) = appointment.id {
-            return Ok(appointment_id);
+    let rows = sqlx::query_as::<_, ZoneAssignmentRow>(
+        r#"
+        SELECT a.id, a.start_date, a.duration_minutes
+        FROM appointment a
+        WHERE a.time_zone IS NULL OR a.start_utc IS NULL OR a.end_utc IS NULL;
+    "#,
+    )
+    .fetch_all(transaction.as_mut())
+    .await?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let zone = preference::get_time_zone(transaction.as_mut()).await?;
+    for row in rows {
+        let start_utc = time_zone::resolve_local_time_lenient(zone, row.start_date);
+        sqlx::query(
+            r#"
+            UPDATE appointment
+            SET start_date = $2,
+                time_zone = $3,
+                start_utc = $4,
+                end_utc = $5
+            WHERE id = $1;
+        "#,
+        )
+        .bind(row.id)
+        .bind(time_zone::to_local_time(zone, start_utc))
+        .bind(zone.name())
+        .bind(start_utc)
+        .bind(start_utc + Duration::minutes(row.duration_minutes))
+        .execute(transaction.as_mut())
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Stores the appointment with its items and resources. Fails with a conflict if the changed
+/// booking overlaps with another appointment of the employee or one of the resources.
+pub async fn store_appointment(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<i64> {
+    let resource_ids = get_required_resource_ids(&mut *connection, appointment).await?;
+
+    let zone = get_appointment_time_zone(&mut *connection, appointment.id).await?;
+    let start_utc = resolve_start(&mut *connection, appointment, zone).await?;
+    let end_utc = start_utc + Duration::minutes(appointment.duration_minutes);
+
+    let invoiced = match appointment.id {
+        Some(appointment_id) => {
+            check_invoiced_appointment(&mut *connection, appointment_id, appointment).await?
+        }
+        None => false,
+    };
+
+    // Only check conflicts if the booking itself changed, otherwise old overlapping entries
+    // could not be edited anymore.
+    if is_booking_changed(&mut *connection, appointment, &resource_ids).await? {
+        let conflicts = find_conflicts(
+            &mut *connection,
+            appointment.id,
+            start_utc,
+            end_utc,
+            appointment.employee_id,
+            &resource_ids,
+        )
+        .await?;
+
+        if !conflicts.is_empty() {
+            return Err(ServiceError::Conflict(describe_conflicts(&conflicts)));
         }
+    }
 
-        Ok(sqlx::query_as::<_, IdRow>(
+    // The number is computed inside the statement itself, so concurrent saves cannot hand
+    // out the same number twice. Moving an appointment to another customer appends it to
+    // the numbering of the new customer.
+    let q = if let Some(appointment_id) = appointment.id {
+        sqlx::query(
+            r#"
+            UPDATE appointment
+            SET number = CASE
+                    WHEN customer_id = $2 THEN number
+                    ELSE (
+                        SELECT COALESCE(MAX(b.number), 0) + 1
+                        FROM appointment b
+                        WHERE b.customer_id = $2
+                    )
+                END,
+                customer_id = $2,
+                start_date = $3,
+                duration_minutes = $4,
+                employee_id = $5,
+                time_zone = $6,
+                start_utc = $7,
+                end_utc = $8
+            WHERE id = $1;
+        "#,
+        )
+        .bind(appointment_id)
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO appointment (
+                number,
+                customer_id,
+                start_date,
+                duration_minutes,
+                employee_id,
+                time_zone,
+                start_utc,
+                end_utc
+            )
+            VALUES (
+                (
+                    SELECT COALESCE(MAX(b.number), 0) + 1
+                    FROM appointment b
+                    WHERE b.customer_id = $1
+                ),
+                $1,
+                $2,
+                $3,
+                $4,
+                $5,
+                $6,
+                $7
+            );
+        "#,
+        )
+    };
+
+    q.bind(appointment.customer_id)
+        .bind(appointment.start_date)
+        .bind(appointment.duration_minutes)
+        .bind(appointment.employee_id)
+        .bind(zone.name())
+        .bind(start_utc)
+        .bind(end_utc)
+        .execute(&mut *connection)
+        .await?;
+
+    let appointment_id = if let Some(appointment_id) = appointment.id {
+        appointment_id
+    } else {
+        sqlx::query_as::<_, IdRow>(
             r#"
         SELECT last_insert_rowid() as id;
         "#,
         )
-        .fetch_one(connection.as_mut())
+        .fetch_one(&mut *connection)
         .await?
-        .id)
+        .id
+    };
+
+    // The items of invoiced appointments are unchanged, storing them again could apply a
+    // price rule the invoice doesn't know about.
+    if !invoiced {
+        store_item_list(&mut *connection, appointment_id, appointment).await?;
     }
 
-    pub async fn delete_appointment(&self, id: i64) -> ServiceResult<()> {
-        let mut connection = self.pool.acquire().await.unwrap();
+    sqlx::query(
+        r#"
+            DELETE FROM appointment_resource
+            WHERE appointment_id = $1;
+        "#,
+    )
+    .bind(appointment_id)
+    .execute(&mut *connection)
+    .await?;
 
+    for resource_id in &resource_ids {
         sqlx::query(
             r#"
-                DELETE FROM appointment
-                WHERE id = $1;
-            "#,
+            INSERT INTO appointment_resource (appointment_id, resource_id)
+            VALUES ($1, $2);
+        "#,
         )
-        .bind(id)
-        .execute(connection.as_mut())
+        .bind(appointment_id)
+        .bind(resource_id)
+        .execute(&mut *connection)
         .await?;
+    }
 
-        Ok(())
+    Ok(appointment_id)
+}
+
+/// Number of the issued invoice the appointment is part of. Cancelled invoices and drafts don't
+/// count, as their appointments can be invoiced again.
+pub async fn get_invoice_number(
+    connection: &mut SqliteConnection,
+    appointment_id: i64,
+) -> ServiceResult<Option<String>> {
+    Ok(sqlx::query_as::<_, InvoiceNumberRow>(
+        r#"
+        SELECT inv.number
+        FROM invoice_item i
+        JOIN invoice inv ON inv.id = i.invoice_id
+        WHERE i.appointment_id = $1 AND inv.kind = 'invoice' AND inv.status = 'issued'
+        LIMIT 1;
+    "#,
+    )
+    .bind(appointment_id)
+    .fetch_optional(connection)
+    .await?
+    .map(|row| row.number))
+}
+
+/// Whether the appointment is part of an issued invoice. The customer, the day and the items
+/// of such appointments are on the invoice and can't be changed until it is cancelled, only
+/// the time, the duration, the employee and the resources can.
+async fn check_invoiced_appointment(
+    connection: &mut SqliteConnection,
+    appointment_id: i64,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<bool> {
+    let Some(number) = get_invoice_number(&mut *connection, appointment_id).await? else {
+        return Ok(false);
+    };
+    let Some(stored) = get_edit_appointment(&mut *connection, appointment_id).await? else {
+        return Ok(false);
+    };
+
+    let items_unchanged = stored.items.len() == appointment.items.len()
+        && stored
+            .items
+            .iter()
+            .zip(&appointment.items)
+            .all(|(stored, item)| {
+                stored.treatment_id == item.treatment_id
+                    && stored.product_id == item.product_id
+                    && stored.description == item.description
+                    && stored.quantity == item.quantity
+                    && stored.unit_price == item.unit_price
+                    && stored.discount == item.discount
+                    && stored.vat_rate == item.vat_rate
+                    && stored.customer_package_id == item.customer_package_id
+                    && stored.price_rule_id == item.price_rule_id
+            });
+    if stored.customer_id != appointment.customer_id
+        || stored.start_date.date() != appointment.start_date.date()
+        || !items_unchanged
+    {
+        return Err(ServiceError::Conflict(format!(
+            "appointment is part of invoice {}, its customer, day and items can only be changed \
+            after cancelling the invoice",
+            number
+        )));
+    }
+    Ok(true)
+}
+
+/// The stored appointment in the shape expected by [`store_appointment`]. All booked resources
+/// are returned as explicit resources.
+pub async fn get_edit_appointment(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> ServiceResult<Option<EditCustomerAppointmentDto>> {
+    let Some(row) = sqlx::query_as::<_, EditAppointmentRow>(
+        r#"
+        SELECT a.id, a.customer_id, a.start_date, a.duration_minutes, a.employee_id
+        FROM appointment a
+        WHERE a.id = $1;
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *connection)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let currency = preference::get_currency(&mut *connection).await?;
+    let items = get_item_list(&mut *connection, id, currency)
+        .await?
+        .into_iter()
+        .map(|item| EditAppointmentItemDto {
+            treatment_id: item.treatment_id,
+            product_id: item.product_id,
+            description: item.description,
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            discount: item.discount,
+            vat_rate: item.vat_rate,
+            customer_package_id: item.customer_package_id,
+            price_rule_id: item.price_rule_id,
+        })
+        .collect();
+
+    let resource_ids = sqlx::query_as::<_, ResourceIdRow>(
+        r#"
+        SELECT ar.resource_id
+        FROM appointment_resource ar
+        WHERE ar.appointment_id = $1
+        ORDER BY ar.resource_id ASC;
+    "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .map(|row| row.resource_id)
+    .collect();
+
+    Ok(Some(EditCustomerAppointmentDto {
+        id: Some(row.id),
+        customer_id: row.customer_id,
+        start_date: row.start_date,
+        ambiguous_time: None,
+        duration_minutes: row.duration_minutes,
+        employee_id: row.employee_id,
+        items,
+        resource_ids,
+    }))
+}
+
+/// Stored appointments keep the zone they were entered in, new ones use the practice time zone.
+pub async fn get_appointment_time_zone(
+    connection: &mut SqliteConnection,
+    appointment_id: Option<i64>,
+) -> ServiceResult<Tz> {
+    let stored = match appointment_id {
+        Some(appointment_id) => sqlx::query_as::<_, TimeZoneRow>(
+            r#"
+            SELECT a.time_zone
+            FROM appointment a
+            WHERE a.id = $1;
+        "#,
+        )
+        .bind(appointment_id)
+        .fetch_optional(&mut *connection)
+        .await?
+        .map(|row| row.time_zone),
+        None => None,
+    };
+
+    match stored {
+        Some(name) => time_zone::parse_time_zone(&name),
+        None => preference::get_time_zone(connection).await,
+    }
+}
+
+/// Instant of the local start date. A start date that occurs twice keeps the occurrence of the
+/// stored appointment if it is unchanged and no other one is chosen.
+async fn resolve_start(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+    zone: Tz,
+) -> ServiceResult<DateTime<Utc>> {
+    if let (Some(appointment_id), None) = (appointment.id, appointment.ambiguous_time) {
+        let stored = sqlx::query_as::<_, StoredStartRow>(
+            r#"
+            SELECT a.start_date, a.start_utc
+            FROM appointment a
+            WHERE a.id = $1;
+        "#,
+        )
+        .bind(appointment_id)
+        .fetch_optional(connection)
+        .await?;
+        if let Some(stored) = stored
+            && stored.start_date == appointment.start_date
+        {
+            return Ok(stored.start_utc);
+        }
+    }
+
+    time_zone::resolve_local_time(zone, appointment.start_date, appointment.ambiguous_time)
+}
+
+/// Local end of an appointment. Going through the UTC instant keeps the duration exact if a
+/// daylight saving change falls into the appointment.
+fn local_end_date(
+    zone: &str,
+    start_date: NaiveDateTime,
+    duration_minutes: i64,
+    end_utc: DateTime<Utc>,
+) -> NaiveDateTime {
+    match time_zone::parse_time_zone(zone) {
+        Ok(zone) => time_zone::to_local_time(zone, end_utc),
+        Err(_) => start_date + Duration::minutes(duration_minutes),
     }
 }
 
+/// The explicitly booked resources together with the ones required by the catalog treatments
+/// of the items, sorted and without duplicates.
+async fn get_required_resource_ids(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<Vec<i64>> {
+    let treatment_ids: Vec<i64> = appointment
+        .items
+        .iter()
+        .filter_map(|item| item.treatment_id)
+        .collect();
+
+    let mut resource_ids = sqlx::query_as::<_, ResourceIdRow>(
+        r#"
+        SELECT DISTINCT tr.resource_id
+        FROM treatment_resource tr
+        WHERE tr.treatment_id IN (SELECT value FROM json_each($1));
+    "#,
+    )
+    .bind(serde_json::to_string(&treatment_ids)?)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| row.resource_id)
+    .chain(appointment.resource_ids.iter().copied())
+    .collect::<Vec<_>>();
+
+    resource_ids.sort();
+    resource_ids.dedup();
+    Ok(resource_ids)
+}
+
+async fn is_booking_changed(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+    resource_ids: &[i64],
+) -> ServiceResult<bool> {
+    let Some(appointment_id) = appointment.id else {
+        return Ok(true);
+    };
+
+    let Some(slot) = sqlx::query_as::<_, SlotRow>(
+        r#"
+        SELECT a.start_date, a.duration_minutes, a.time_zone, a.end_utc, a.employee_id
+        FROM appointment a
+        WHERE a.id = $1;
+    "#,
+    )
+    .bind(appointment_id)
+    .fetch_optional(&mut *connection)
+    .await?
+    else {
+        return Ok(true);
+    };
+
+    let stored_resource_ids: Vec<i64> = sqlx::query_as::<_, ResourceIdRow>(
+        r#"
+        SELECT ar.resource_id
+        FROM appointment_resource ar
+        WHERE ar.appointment_id = $1
+        ORDER BY ar.resource_id ASC;
+    "#,
+    )
+    .bind(appointment_id)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .map(|row| row.resource_id)
+    .collect();
+
+    Ok(slot.start_date != appointment.start_date
+        || slot.duration_minutes != appointment.duration_minutes
+        || slot.employee_id != appointment.employee_id
+        || stored_resource_ids != resource_ids)
+}
+
+/// Appointments overlapping the given instants that need the same employee or one of the
+/// given resources. `exclude_id` is the appointment that is checked itself.
+pub async fn find_conflicts(
+    connection: &mut SqliteConnection,
+    exclude_id: Option<i64>,
+    start_utc: DateTime<Utc>,
+    end_utc: DateTime<Utc>,
+    employee_id: Option<i64>,
+    resource_ids: &[i64],
+) -> ServiceResult<Vec<AppointmentConflictDto>> {
+    Ok(sqlx::query_as::<_, ConflictRow>(
+        r#"
+        SELECT
+            'employee' AS kind,
+            a.id AS appointment_id,
+            a.customer_id,
+            c.first_name,
+            c.last_name,
+            a.start_date,
+            a.duration_minutes,
+            a.time_zone,
+            a.end_utc,
+            e.id AS employee_id,
+            NULL AS resource_id,
+            e.name
+        FROM appointment a
+        JOIN customer c ON c.id = a.customer_id
+        JOIN employee e ON e.id = a.employee_id
+        WHERE a.employee_id = $1
+            AND ($2 IS NULL OR a.id <> $2)
+            AND a.start_utc < $4
+            AND a.end_utc > $3
+        UNION ALL
+        SELECT
+            'resource' AS kind,
+            a.id AS appointment_id,
+            a.customer_id,
+            c.first_name,
+            c.last_name,
+            a.start_date,
+            a.duration_minutes,
+            a.time_zone,
+            a.end_utc,
+            NULL AS employee_id,
+            r.id AS resource_id,
+            r.name
+        FROM appointment a
+        JOIN customer c ON c.id = a.customer_id
+        JOIN appointment_resource ar ON ar.appointment_id = a.id
+        JOIN resource r ON r.id = ar.resource_id
+        WHERE ar.resource_id IN (SELECT value FROM json_each($5))
+            AND ($2 IS NULL OR a.id <> $2)
+            AND a.start_utc < $4
+            AND a.end_utc > $3
+        ORDER BY start_date ASC, appointment_id ASC;
+    "#,
+    )
+    .bind(employee_id)
+    .bind(exclude_id)
+    .bind(start_utc)
+    .bind(end_utc)
+    .bind(serde_json::to_string(resource_ids)?)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect())
+}
+
+fn describe_conflicts(conflicts: &[AppointmentConflictDto]) -> String {
+    conflicts
+        .iter()
+        .map(|conflict| {
+            format!(
+                "{} is already booked from {} to {} ({} {})",
+                conflict.name,
+                conflict.start_date.format("%Y-%m-%d %H:%M"),
+                conflict.end_date.format("%H:%M"),
+                conflict.first_name,
+                conflict.last_name,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Loads the booked resources of all given appointments with a single query.
+async fn attach_resources(
+    connection: &mut SqliteConnection,
+    appointments: &mut [CustomerAppointmentDto],
+) -> ServiceResult<()> {
+    let appointment_ids: Vec<i64> = appointments.iter().map(|a| a.id).collect();
+
+    let mut resources = sqlx::query_as::<_, AppointmentResourceRow>(
+        r#"
+        SELECT
+            ar.appointment_id,
+            r.id,
+            r.name,
+            r.kind,
+            r.active
+        FROM appointment_resource ar
+        JOIN resource r ON r.id = ar.resource_id
+        WHERE ar.appointment_id IN (SELECT value FROM json_each($1))
+        ORDER BY r.name ASC;
+    "#,
+    )
+    .bind(serde_json::to_string(&appointment_ids)?)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .fold(HashMap::<i64, Vec<ResourceDto>>::new(), |mut map, row| {
+        map.entry(row.appointment_id)
+            .or_default()
+            .push(ResourceDto {
+                id: row.id,
+                name: row.name,
+                kind: row.kind,
+                active: row.active,
+            });
+        map
+    });
+
+    for appointment in appointments {
+        appointment.resources = resources.remove(&appointment.id).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+async fn get_item_list(
+    connection: &mut SqliteConnection,
+    appointment_id: i64,
+    currency: Currency,
+) -> ServiceResult<Vec<AppointmentItemDto>> {
+    Ok(sqlx::query_as::<_, AppointmentItemRow>(
+        r#"
+        SELECT
+            i.id,
+            i.appointment_id,
+            i.treatment_id,
+            i.product_id,
+            i.description,
+            i.quantity,
+            i.unit_price,
+            i.discount,
+            i.vat_rate,
+            i.customer_package_id,
+            i.price_rule_id,
+            r.name AS price_rule_name
+        FROM appointment_item i
+        LEFT JOIN price_rule r ON r.id = i.price_rule_id
+        WHERE i.appointment_id = $1
+        ORDER BY i.position ASC;
+    "#,
+    )
+    .bind(appointment_id)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| row.into_item(currency))
+    .collect())
+}
+
+/// Replaces all items of an appointment, their order is taken from the given list.
+///
+/// Sold products are booked as stock movements that belong to their item, so the stock
+/// follows every change of the appointment. Items paid by a package of the customer are free,
+/// new appointments take a matching package on their own. The best applicable price rule
+/// grants the discount of new and changed items without a manual discount, unchanged items
+/// keep the discount of their rule, so later rule changes don't alter existing prices.
+async fn store_item_list(
+    connection: &mut SqliteConnection,
+    appointment_id: i64,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<()> {
+    let applicable_rules =
+        price_rule::get_applicable_price_rules(&mut *connection, appointment).await?;
+    let stored_rule_items = match appointment.id {
+        Some(appointment_id) => get_rule_item_list(&mut *connection, appointment_id).await?,
+        None => Vec::new(),
+    };
+
+    sqlx::query(
+        r#"
+            DELETE FROM appointment_item
+            WHERE appointment_id = $1;
+        "#,
+    )
+    .bind(appointment_id)
+    .execute(&mut *connection)
+    .await?;
+
+    for ((position, item), applicable_rules) in
+        appointment.items.iter().enumerate().zip(applicable_rules)
+    {
+        money::validate_vat_rate(item.vat_rate)?;
+
+        let customer_package_id = match (item.customer_package_id, item.treatment_id) {
+            (Some(customer_package_id), _) => {
+                check_customer_package(&mut *connection, customer_package_id, appointment, item)
+                    .await?;
+                Some(customer_package_id)
+            }
+            (None, Some(treatment_id)) if appointment.id.is_none() => {
+                find_customer_package(&mut *connection, appointment, treatment_id, item).await?
+            }
+            (None, _) => None,
+        };
+        // Discounts granted by a rule are kept while the item is unchanged, as the rule may have
+        // changed since. Otherwise they are recomputed, so they can't be set by the client, and
+        // a rule that no longer applies is replaced by the best one like for items without a
+        // discount. Manual discounts are kept as they are.
+        let kept = item.price_rule_id.is_some()
+            && stored_rule_items
+                .iter()
+                .any(|stored| stored.is_unchanged(item));
+        let (unit_price, discount, price_rule_id) = if customer_package_id.is_some() {
+            (0, 0, None)
+        } else if kept {
+            (item.unit_price, item.discount, item.price_rule_id)
+        } else if item.price_rule_id.is_some() || item.discount == 0 {
+            let applied_rule = item
+                .price_rule_id
+                .and_then(|price_rule_id| {
+                    applicable_rules
+                        .iter()
+                        .find(|applied| applied.price_rule_id == price_rule_id)
+                })
+                .or(applicable_rules.first());
+            match applied_rule {
+                Some(applied_rule) => (
+                    item.unit_price,
+                    applied_rule.discount,
+                    Some(applied_rule.price_rule_id),
+                ),
+                None => (item.unit_price, 0, None),
+            }
+        } else {
+            (item.unit_price, item.discount, None)
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO appointment_item (
+                appointment_id,
+                position,
+                treatment_id,
+                product_id,
+                description,
+                quantity,
+                unit_price,
+                discount,
+                vat_rate,
+                customer_package_id,
+                price_rule_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11);
+        "#,
+        )
+        .bind(appointment_id)
+        .bind(position as i64)
+        .bind(item.treatment_id)
+        .bind(item.product_id)
+        .bind(&item.description)
+        .bind(item.quantity)
+        .bind(unit_price)
+        .bind(discount)
+        .bind(item.vat_rate)
+        .bind(customer_package_id)
+        .bind(price_rule_id)
+        .execute(&mut *connection)
+        .await?;
+
+        if let Some(product_id) = item.product_id {
+            sqlx::query(
+                r#"
+                INSERT INTO stock_movement (
+                    product_id,
+                    date,
+                    kind,
+                    quantity,
+                    appointment_item_id,
+                    note
+                )
+                VALUES ($1, $2, $3, $4, last_insert_rowid(), '');
+            "#,
+            )
+            .bind(product_id)
+            .bind(appointment.start_date)
+            .bind(StockMovementKind::Sale)
+            .bind(-item.quantity)
+            .execute(&mut *connection)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stored items of the appointment with a discount granted by a price rule.
+async fn get_rule_item_list(
+    connection: &mut SqliteConnection,
+    appointment_id: i64,
+) -> ServiceResult<Vec<RuleItemRow>> {
+    Ok(sqlx::query_as::<_, RuleItemRow>(
+        r#"
+        SELECT i.treatment_id, i.quantity, i.unit_price, i.discount, i.price_rule_id
+        FROM appointment_item i
+        WHERE i.appointment_id = $1
+            AND i.price_rule_id IS NOT NULL;
+    "#,
+    )
+    .bind(appointment_id)
+    .fetch_all(connection)
+    .await?)
+}
+
+/// The oldest package of the customer for the treatment that is still valid on the appointment
+/// date and has enough sessions that are not booked yet.
+async fn find_customer_package(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+    treatment_id: i64,
+    item: &EditAppointmentItemDto,
+) -> ServiceResult<Option<i64>> {
+    Ok(sqlx::query_as::<_, IdRow>(
+        r#"
+        WITH package AS (
+            SELECT
+                cp.id,
+                cp.customer_id,
+                cp.treatment_id,
+                cp.purchase_date,
+                cp.expiry_date,
+                cp.session_count - COALESCE((
+                    SELECT SUM(i.quantity)
+                    FROM appointment_item i
+                    WHERE i.customer_package_id = cp.id
+                ), 0) AS available_sessions
+            FROM customer_package cp
+        )
+        SELECT p.id
+        FROM package p
+        WHERE p.customer_id = $1
+            AND p.treatment_id = $2
+            AND (p.expiry_date IS NULL OR p.expiry_date >= $3)
+            AND p.available_sessions >= $4
+        ORDER BY p.expiry_date IS NULL, p.expiry_date ASC, p.purchase_date ASC, p.id ASC
+        LIMIT 1;
+    "#,
+    )
+    .bind(appointment.customer_id)
+    .bind(treatment_id)
+    .bind(appointment.start_date.date())
+    .bind(item.quantity)
+    .fetch_optional(connection)
+    .await?
+    .map(|row| row.id))
+}
+
+async fn check_customer_package(
+    connection: &mut SqliteConnection,
+    customer_package_id: i64,
+    appointment: &EditCustomerAppointmentDto,
+    item: &EditAppointmentItemDto,
+) -> ServiceResult<()> {
+    let Some(package) = sqlx::query_as::<_, PackageBalanceRow>(
+        r#"
+        SELECT
+            cp.customer_id,
+            cp.expiry_date,
+            cp.session_count - COALESCE((
+                SELECT SUM(i.quantity)
+                FROM appointment_item i
+                WHERE i.customer_package_id = cp.id
+            ), 0) AS available_sessions
+        FROM customer_package cp
+        WHERE cp.id = $1;
+    "#,
+    )
+    .bind(customer_package_id)
+    .fetch_optional(connection)
+    .await?
+    else {
+        return Err(ServiceError::BadRequest(format!(
+            "package {} does not exist",
+            customer_package_id
+        )));
+    };
+
+    if package.customer_id != appointment.customer_id {
+        return Err(ServiceError::BadRequest(format!(
+            "package {} belongs to another customer",
+            customer_package_id
+        )));
+    }
+    if package
+        .expiry_date
+        .is_some_and(|expiry_date| expiry_date < appointment.start_date.date())
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "package {} is expired on the appointment date",
+            customer_package_id
+        )));
+    }
+    if package.available_sessions < item.quantity {
+        return Err(ServiceError::BadRequest(format!(
+            "package {} has no sessions left",
+            customer_package_id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Predicts the next visit from the median of the most recent periods between visits, like the
+/// `period_days` shown in the appointment list. Multiple appointments on the same day count as a
+/// single visit. At least two visits are required.
+fn predict_next_visit(customer_id: i64, start_dates: &[NaiveDate]) -> Option<VisitPredictionDto> {
+    let mut visits = start_dates.to_vec();
+    visits.sort();
+    visits.dedup();
+
+    let last_visit = *visits.last()?;
+    let mut periods: Vec<i64> = visits
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_days())
+        .rev()
+        .take(RECENT_PERIOD_COUNT)
+        .collect();
+
+    if periods.is_empty() {
+        return None;
+    }
+
+    periods.sort();
+    let middle = periods.len() / 2;
+    let typical_interval_days = if periods.len().is_multiple_of(2) {
+        (periods[middle - 1] + periods[middle] + 1) / 2
+    } else {
+        periods[middle]
+    };
+
+    Some(VisitPredictionDto {
+        customer_id,
+        last_visit,
+        typical_interval_days,
+        predicted_next_visit: last_visit + Duration::days(typical_interval_days),
+    })
+}
+
+/// The treatment summary and the price of an appointment are derived from its items.
+fn attach_items(
+    appointment: &mut CustomerAppointmentDto,
+    items: Vec<AppointmentItemDto>,
+    currency: Currency,
+) {
+    appointment.treatment = items
+        .iter()
+        .map(|item| item.description.trim())
+        .filter(|description| !description.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    (appointment.price, appointment.taxes) = money::sum_prices(
+        items
+            .iter()
+            .map(|item| (item.total.gross.amount, item.vat_rate)),
+        currency,
+    );
+    appointment.items = items;
+}
+
 #[derive(sqlx::FromRow)]
 struct IdRow {
     pub id: i64,
 }
 
-#[derive(sqlx::FromRow, Default)]
-struct NumberRow {
-    pub number: i64,
+#[derive(sqlx::FromRow)]
+struct PackageBalanceRow {
+    pub customer_id: i64,
+    pub expiry_date: Option<NaiveDate>,
+    pub available_sessions: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct ResourceIdRow {
+    pub resource_id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct AppointmentResourceRow {
+    pub appointment_id: i64,
+    pub id: i64,
+    pub name: String,
+    pub kind: ResourceKind,
+    pub active: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct ConflictRow {
+    pub kind: AppointmentConflictKind,
+    pub appointment_id: i64,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub start_date: NaiveDateTime,
+    pub duration_minutes: i64,
+    pub time_zone: String,
+    pub end_utc: DateTime<Utc>,
+    pub employee_id: Option<i64>,
+    pub resource_id: Option<i64>,
+    pub name: String,
+}
+
+impl From<ConflictRow> for AppointmentConflictDto {
+    fn from(row: ConflictRow) -> AppointmentConflictDto {
+        AppointmentConflictDto {
+            kind: row.kind,
+            appointment_id: row.appointment_id,
+            customer_id: row.customer_id,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            start_date: row.start_date,
+            end_date: local_end_date(
+                &row.time_zone,
+                row.start_date,
+                row.duration_minutes,
+                row.end_utc,
+            ),
+            employee_id: row.employee_id,
+            resource_id: row.resource_id,
+            name: row.name,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct EditAppointmentRow {
+    pub id: i64,
+    pub customer_id: i64,
+    pub start_date: NaiveDateTime,
+    pub duration_minutes: i64,
+    pub employee_id: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TimeZoneRow {
+    pub time_zone: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoiceNumberRow {
+    pub number: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct RuleItemRow {
+    pub treatment_id: Option<i64>,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    pub price_rule_id: Option<i64>,
+}
+
+impl RuleItemRow {
+    fn is_unchanged(&self, item: &EditAppointmentItemDto) -> bool {
+        (
+            self.treatment_id,
+            self.quantity,
+            self.unit_price,
+            self.discount,
+            self.price_rule_id,
+        ) == (
+            item.treatment_id,
+            item.quantity,
+            item.unit_price,
+            item.discount,
+            item.price_rule_id,
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ZoneAssignmentRow {
+    pub id: i64,
+    pub start_date: NaiveDateTime,
+    pub duration_minutes: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SlotRow {
+    pub start_date: NaiveDateTime,
+    pub duration_minutes: i64,
+    pub time_zone: String,
+    pub end_utc: DateTime<Utc>,
+    pub employee_id: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct VisitRow {
+    pub customer_id: i64,
+    pub start_date: NaiveDateTime,
+}
+
+#[derive(sqlx::FromRow)]
+struct CustomerContactRow {
+    pub id: i64,
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub mobile_phone: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredStartRow {
+    pub start_date: NaiveDateTime,
+    pub start_utc: DateTime<Utc>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -201,16 +1675,18 @@ struct AppointmentRow {
     pub number: i64,
     pub start_date: NaiveDateTime,
     pub duration_minutes: i64,
-    pub treatment: String,
-    pub price: i64,
+    pub time_zone: String,
+    pub start_utc: DateTime<Utc>,
+    pub end_utc: DateTime<Utc>,
     pub employee_id: Option<i64>,
     pub employee_name: Option<String>,
+    pub employee_active: Option<bool>,
 }
 
 impl From<AppointmentRow> for CustomerAppointmentDto {
     fn from(row: AppointmentRow) -> CustomerAppointmentDto {
-        let employee = match (row.employee_id, row.employee_name) {
-            (Some(id), Some(name)) => Some(EmployeeDto { id, name }),
+        let employee = match (row.employee_id, row.employee_name, row.employee_active) {
+            (Some(id), Some(name), Some(active)) => Some(EmployeeRefDto { id, name, active }),
             _ => None,
         };
 
@@ -220,11 +1696,398 @@ impl From<AppointmentRow> for CustomerAppointmentDto {
             number: row.number,
             start_date: row.start_date,
             duration_minutes: row.duration_minutes,
-            end_date: row.start_date,
+            end_date: local_end_date(
+                &row.time_zone,
+                row.start_date,
+                row.duration_minutes,
+                row.end_utc,
+            ),
+            time_zone: row.time_zone,
+            start_utc: row.start_utc,
+            end_utc: row.end_utc,
             period_days: None,
-            treatment: row.treatment,
-            price: row.price,
+            treatment: String::new(),
+            price: PriceDto::default(),
+            taxes: Vec::new(),
             employee,
+            items: Vec::new(),
+            resources: Vec::new(),
         }
     }
 }
+
+#[derive(sqlx::FromRow)]
+struct AppointmentItemRow {
+    pub id: i64,
+    pub appointment_id: i64,
+    pub treatment_id: Option<i64>,
+    pub product_id: Option<i64>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    pub vat_rate: i64,
+    pub customer_package_id: Option<i64>,
+    pub price_rule_id: Option<i64>,
+    pub price_rule_name: Option<String>,
+}
+
+impl AppointmentItemRow {
+    fn into_item(self, currency: Currency) -> AppointmentItemDto {
+        AppointmentItemDto {
+            id: self.id,
+            treatment_id: self.treatment_id,
+            product_id: self.product_id,
+            description: self.description,
+            quantity: self.quantity,
+            unit_price: self.unit_price,
+            discount: self.discount,
+            vat_rate: self.vat_rate,
+            total: money::split_gross(
+                self.quantity * self.unit_price - self.discount,
+                self.vat_rate,
+                currency,
+            ),
+            customer_package_id: self.customer_package_id,
+            price_rule_id: self.price_rule_id,
+            price_rule_name: self.price_rule_name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::AmbiguousTime;
+    use crate::state::testing::{appointment, customer, item, set_preference, state};
+
+    fn date(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn invoiced_appointments_are_locked() {
+        let state = state().await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let id = state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                date(4, 9),
+                vec![item("Massage", 1, 5000)],
+            ))
+            .await
+            .unwrap();
+        let edit = |start_date, items| EditCustomerAppointmentDto {
+            id: Some(id),
+            ..appointment(customer_id, start_date, items)
+        };
+
+        let invoice_id = state.invoice.create_invoice(vec![id]).await.unwrap();
+        // Drafts don't lock their appointments.
+        state
+            .appointment
+            .store_appointment(edit(date(4, 9), vec![item("Massage", 1, 5500)]))
+            .await
+            .unwrap();
+        state.invoice.delete_invoice(invoice_id).await.unwrap();
+        let invoice_id = state.invoice.create_invoice(vec![id]).await.unwrap();
+        state.invoice.issue_invoice(invoice_id).await.unwrap();
+
+        for changed in [
+            edit(date(4, 9), vec![item("Massage", 1, 6000)]),
+            edit(
+                date(4, 9),
+                vec![item("Massage", 1, 5500), item("Oil", 1, 900)],
+            ),
+            edit(date(5, 9), vec![item("Massage", 1, 5500)]),
+        ] {
+            assert!(matches!(
+                state.appointment.store_appointment(changed).await,
+                Err(ServiceError::Conflict(_))
+            ));
+        }
+        assert!(matches!(
+            state.appointment.delete_appointment(id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        assert!(matches!(
+            state.customer.delete_customer(customer_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        // The time on the same day can still be corrected.
+        state
+            .appointment
+            .store_appointment(edit(date(4, 11), vec![item("Massage", 1, 5500)]))
+            .await
+            .unwrap();
+        let stored = state
+            .appointment
+            .get_appointment_by_id(id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.start_date, date(4, 11));
+        assert_eq!(stored.items[0].unit_price, 5500);
+
+        // A credit note releases the appointment.
+        state.invoice.cancel_invoice(invoice_id).await.unwrap();
+        state
+            .appointment
+            .store_appointment(edit(date(4, 11), vec![item("Massage", 1, 6000)]))
+            .await
+            .unwrap();
+        state.appointment.delete_appointment(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ambiguous_start_dates_need_a_choice() {
+        let state = state().await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let at = |value: &str| value.parse::<NaiveDateTime>().unwrap();
+        let edit = |id, start_date, ambiguous_time| EditCustomerAppointmentDto {
+            id,
+            ambiguous_time,
+            ..appointment(customer_id, at(start_date), vec![item("Massage", 1, 5000)])
+        };
+        let start_utc = async |id| {
+            let appointment = state.appointment.get_appointment_by_id(id).await;
+            appointment.unwrap().unwrap().start_utc.to_rfc3339()
+        };
+
+        // The clocks in Berlin are set forward at 2:00 and back at 3:00.
+        assert!(matches!(
+            state
+                .appointment
+                .store_appointment(edit(None, "2024-03-31T02:30:00", None))
+                .await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(matches!(
+            state
+                .appointment
+                .store_appointment(edit(None, "2024-10-27T02:30:00", None))
+                .await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        let id = state
+            .appointment
+            .store_appointment(edit(
+                None,
+                "2024-10-27T02:30:00",
+                Some(AmbiguousTime::Later),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(start_utc(id).await, "2024-10-27T01:30:00+00:00");
+        let stored = state.appointment.get_appointment_by_id(id).await;
+        assert_eq!(stored.unwrap().unwrap().end_date, at("2024-10-27T03:30:00"));
+
+        // Unchanged start dates keep their occurrence.
+        state
+            .appointment
+            .store_appointment(edit(Some(id), "2024-10-27T02:30:00", None))
+            .await
+            .unwrap();
+        assert_eq!(start_utc(id).await, "2024-10-27T01:30:00+00:00");
+
+        state
+            .appointment
+            .store_appointment(edit(
+                Some(id),
+                "2024-10-27T02:30:00",
+                Some(AmbiguousTime::Earlier),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(start_utc(id).await, "2024-10-27T00:30:00+00:00");
+    }
+
+    /// Numbers of the appointments of the customer, ordered by their ids.
+    async fn numbers(state: &crate::state::State, customer_id: i64) -> Vec<i64> {
+        let mut appointments = state
+            .appointment
+            .get_appointment_list(customer_id)
+            .await
+            .unwrap();
+        appointments.sort_by_key(|appointment| appointment.id);
+        appointments
+            .into_iter()
+            .map(|appointment| appointment.number)
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_saves_get_distinct_numbers() {
+        let state = state().await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+
+        let saves = (0..8)
+            .map(|day| {
+                let appointments = state.appointment.clone();
+                tokio::spawn(async move {
+                    appointments
+                        .store_appointment(appointment(
+                            customer_id,
+                            date(4 + day, 9),
+                            vec![item("Massage", 1, 5000)],
+                        ))
+                        .await
+                        .unwrap();
+                    appointments
+                        .renumber_appointments(customer_id)
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for save in saves {
+            save.await.unwrap();
+        }
+
+        let mut numbers = numbers(&state, customer_id).await;
+        numbers.sort();
+        assert_eq!(numbers, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn renumbering_follows_back_dated_appointments() {
+        let state = state().await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let store = async |start_date| {
+            state
+                .appointment
+                .store_appointment(appointment(
+                    customer_id,
+                    start_date,
+                    vec![item("Massage", 1, 5000)],
+                ))
+                .await
+                .unwrap()
+        };
+
+        let march = store(date(11, 10)).await;
+        let april = store(date(31, 10) + Duration::days(1)).await;
+        // Back-dated appointments are numbered last until the customer is renumbered.
+        let earlier = store(date(4, 10)).await;
+        assert_eq!(numbers(&state, customer_id).await, vec![1, 2, 3]);
+
+        // Entered in London at 9:30, which is 10:30 in Berlin and after the appointment on
+        // the same day at 10:00.
+        let berlin = store(date(18, 10)).await;
+        set_preference(&state, time_zone::TIME_ZONE_KEY, "Europe/London").await;
+        let london = store(date(18, 9) + Duration::minutes(30)).await;
+        let deleted = store(date(25, 10)).await;
+        state.appointment.delete_appointment(deleted).await.unwrap();
+        assert_eq!(numbers(&state, customer_id).await, vec![1, 2, 3, 4, 5]);
+
+        state
+            .appointment
+            .renumber_appointments(customer_id)
+            .await
+            .unwrap();
+        assert!(march < april && april < earlier && earlier < berlin && berlin < london);
+        assert_eq!(numbers(&state, customer_id).await, vec![2, 5, 1, 3, 4]);
+    }
+
+    #[test]
+    fn visits_are_predicted_from_the_median_of_recent_periods() {
+        let day = |days: i64| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Duration::days(days);
+        let predict = |days: &[i64]| {
+            let start_dates: Vec<NaiveDate> = days.iter().map(|days| day(*days)).collect();
+            predict_next_visit(1, &start_dates).map(|prediction| {
+                (
+                    prediction.typical_interval_days,
+                    prediction.predicted_next_visit,
+                )
+            })
+        };
+
+        assert!(predict(&[]).is_none());
+        // Appointments on the same day are a single visit.
+        assert!(predict(&[10, 10]).is_none());
+        // Periods of 10, 30 and 14 days, unsorted input.
+        assert_eq!(predict(&[54, 0, 10, 40]), Some((14, day(68))));
+        // The median of 10 and 13 days is rounded up.
+        assert_eq!(predict(&[0, 10, 23]), Some((12, day(35))));
+        // Only the last five periods count, the first one of 100 days is ignored.
+        assert_eq!(
+            predict(&[0, 100, 107, 114, 121, 149, 156]),
+            Some((7, day(163)))
+        );
+    }
+
+    #[tokio::test]
+    async fn customers_are_overdue_beyond_the_tolerance() {
+        let state = state().await;
+        let today = time_zone::now_local(time_zone::DEFAULT_TIME_ZONE).date();
+        let customer_with_visits = async |last_name, days: &[i64]| {
+            let customer_id = state
+                .customer
+                .store_customer(customer("Anna", last_name))
+                .await
+                .unwrap();
+            for days in days {
+                let start_date = (today + Duration::days(*days)).and_hms_opt(10, 0, 0);
+                state
+                    .appointment
+                    .store_appointment(appointment(
+                        customer_id,
+                        start_date.unwrap(),
+                        vec![item("Massage", 1, 5000)],
+                    ))
+                    .await
+                    .unwrap();
+            }
+            customer_id
+        };
+
+        // Due today, which isn't overdue yet.
+        customer_with_visits("Berg", &[-60, -40, -20]).await;
+        let overdue = customer_with_visits("Klein", &[-100, -80, -60]).await;
+        let longer = customer_with_visits("Lang", &[-200, -150, -100]).await;
+        // Upcoming appointments keep customers off the list.
+        customer_with_visits("Roth", &[-100, -80, -60, 5]).await;
+        customer_with_visits("Single", &[-300]).await;
+
+        let overdue_customers = async |tolerance_days| {
+            state
+                .appointment
+                .get_overdue_customers(tolerance_days)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|customer| (customer.customer_id, customer.overdue_days))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            overdue_customers(0).await,
+            vec![(longer, 50), (overdue, 40)]
+        );
+        assert_eq!(overdue_customers(40).await, vec![(longer, 50)]);
+        assert_eq!(overdue_customers(50).await, Vec::new());
+    }
+}
@@ -0,0 +1,880 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{
+        CashClosingDto, CashEntryDto, CashEntryKind, CashJournalIssueDto, CashJournalIssueKind,
+        CashJournalVerificationDto, EditCashEntryDto,
+    },
+    state::preference,
+    time_zone,
+};
+
+/// Previous hash of the first entry and the first closing.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Clone)]
+pub struct CashJournalState {
+    pool: Arc<Pool<Sqlite>>,
+    head: CashJournalHead,
+}
+
+impl CashJournalState {
+    pub fn new(pool: Arc<Pool<Sqlite>>, head: CashJournalHead) -> Self {
+        Self { pool, head }
+    }
+
+    pub async fn get_cash_entry_list(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> ServiceResult<Vec<CashEntryDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_entry_rows(connection.as_mut())
+            .await?
+            .into_iter()
+            .filter(|row| {
+                from.is_none_or(|from| row.date >= from) && to.is_none_or(|to| row.date <= to)
+            })
+            .map(|row| {
+                Ok(CashEntryDto {
+                    id: row.id,
+                    created_at: parse_timestamp(&row.created_at)?,
+                    date: row.date,
+                    kind: row.kind,
+                    amount: row.amount,
+                    payment_id: row.payment_id,
+                    description: row.description,
+                    hash: row.hash,
+                })
+            })
+            .collect()
+    }
+
+    /// Books a refund, withdrawal or deposit. Payments, corrections and differences are only
+    /// booked by the application itself.
+    pub async fn store_cash_entry(&self, entry: EditCashEntryDto) -> ServiceResult<i64> {
+        if entry.amount <= 0 {
+            return Err(ServiceError::BadRequest(
+                "amount must be positive".to_string(),
+            ));
+        }
+        let amount = match entry.kind {
+            CashEntryKind::Deposit => entry.amount,
+            CashEntryKind::Refund | CashEntryKind::Withdrawal => -entry.amount,
+            _ => {
+                return Err(ServiceError::BadRequest(
+                    "only refunds, withdrawals and deposits can be booked manually".to_string(),
+                ));
+            }
+        };
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let id = append_entry(
+            transaction.as_mut(),
+            entry.date,
+            entry.kind,
+            amount,
+            None,
+            entry.description.trim(),
+        )
+        .await?;
+        transaction.commit().await?;
+        self.head.update_after_booking(&self.pool).await;
+        Ok(id)
+    }
+
+    pub async fn get_cash_closing_list(&self) -> ServiceResult<Vec<CashClosingDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_closing_list(connection.as_mut()).await
+    }
+
+    /// Closes all days up to the given date. The difference between the counted and the
+    /// expected cash is booked, so the counted cash is the opening balance of the next day.
+    pub async fn close_cash_day(
+        &self,
+        date: NaiveDate,
+        counted: i64,
+        note: String,
+    ) -> ServiceResult<CashClosingDto> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let last_closing = get_closing_rows(transaction.as_mut()).await?.pop();
+        if let Some(last_closing) = &last_closing
+            && last_closing.date >= date
+        {
+            return Err(ServiceError::Conflict(format!(
+                "cash is already closed up to {}",
+                last_closing.date
+            )));
+        }
+
+        let expected = last_closing
+            .as_ref()
+            .map(|closing| closing.counted)
+            .unwrap_or_default()
+            + get_entry_rows(transaction.as_mut())
+                .await?
+                .iter()
+                .filter(|row| {
+                    row.date <= date
+                        && last_closing
+                            .as_ref()
+                            .is_none_or(|closing| row.date > closing.date)
+                })
+                .map(|row| row.amount)
+                .sum::<i64>();
+
+        if counted != expected {
+            append_entry(
+                transaction.as_mut(),
+                date,
+                CashEntryKind::Difference,
+                counted - expected,
+                None,
+                note.trim(),
+            )
+            .await?;
+        }
+
+        let (last_entry_id, last_entry_hash) = get_last_entry(transaction.as_mut()).await?;
+        let closing = ClosingRow {
+            id: 0,
+            date,
+            created_at: timestamp(),
+            expected,
+            counted,
+            note: note.trim().to_string(),
+            last_entry_id,
+            last_entry_hash,
+            hash: String::new(),
+        };
+        let hash = closing_hash(
+            &closing,
+            last_closing
+                .as_ref()
+                .map_or(GENESIS_HASH, |closing| &closing.hash),
+        )?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO cash_closing (
+                date,
+                created_at,
+                expected,
+                counted,
+                note,
+                last_entry_id,
+                last_entry_hash,
+                hash
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+        "#,
+        )
+        .bind(closing.date)
+        .bind(&closing.created_at)
+        .bind(closing.expected)
+        .bind(closing.counted)
+        .bind(&closing.note)
+        .bind(closing.last_entry_id)
+        .bind(&closing.last_entry_hash)
+        .bind(&hash)
+        .execute(transaction.as_mut())
+        .await?;
+
+        let closing = get_closing_list(transaction.as_mut())
+            .await?
+            .pop()
+            .expect("closing was just stored");
+        transaction.commit().await?;
+        self.head.update_after_booking(&self.pool).await;
+        Ok(closing)
+    }
+
+    /// Recomputes the hash chain of entries and closings. Entries removed after the last
+    /// closing are detected against the recorded head of the journal.
+    pub async fn verify_cash_journal(&self) -> ServiceResult<CashJournalVerificationDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let entries = get_entry_rows(connection.as_mut()).await?;
+        let closings = get_closing_rows(connection.as_mut()).await?;
+
+        let mut issues = Vec::new();
+        let mut expected_id = 1;
+        let mut previous_hash = GENESIS_HASH.to_string();
+        for entry in &entries {
+            let gap = entry.id != expected_id;
+            if gap {
+                issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::Missing,
+                    entry_id: Some(entry.id),
+                    closing_id: None,
+                    message: format!("entries {} to {} are missing", expected_id, entry.id - 1),
+                });
+            }
+            if entry_hash(entry)? != entry.hash {
+                issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::Modified,
+                    entry_id: Some(entry.id),
+                    closing_id: None,
+                    message: format!("entry {} has been modified", entry.id),
+                });
+            } else if !gap && entry.previous_hash != previous_hash {
+                issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::BrokenChain,
+                    entry_id: Some(entry.id),
+                    closing_id: None,
+                    message: format!("entry {} does not follow the entry before it", entry.id),
+                });
+            }
+            expected_id = entry.id + 1;
+            previous_hash = entry.hash.clone();
+        }
+
+        if let Some(head) = self.head.read().await? {
+            match entries.iter().find(|entry| entry.id == head.id) {
+                Some(entry) if entry.hash == head.hash => {}
+                Some(_) => issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::Modified,
+                    entry_id: Some(head.id),
+                    closing_id: None,
+                    message: format!(
+                        "entry {} differs from the recorded head of the journal",
+                        head.id
+                    ),
+                }),
+                None if head.id >= expected_id => issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::Missing,
+                    entry_id: Some(head.id),
+                    closing_id: None,
+                    message: format!(
+                        "entries {} to {} at the end of the journal are missing",
+                        expected_id, head.id
+                    ),
+                }),
+                // Removed in the middle of the journal, already reported as a gap.
+                None => {}
+            }
+        }
+
+        let mut previous_hash = GENESIS_HASH.to_string();
+        for closing in &closings {
+            if closing_hash(closing, &previous_hash)? != closing.hash {
+                issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::ClosingMismatch,
+                    entry_id: None,
+                    closing_id: Some(closing.id),
+                    message: format!(
+                        "closing of {} has been modified or a closing before it is missing",
+                        closing.date
+                    ),
+                });
+            }
+            match entries
+                .iter()
+                .find(|entry| entry.id == closing.last_entry_id)
+            {
+                Some(entry) if entry.hash == closing.last_entry_hash => {}
+                Some(_) => issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::ClosingMismatch,
+                    entry_id: Some(closing.last_entry_id),
+                    closing_id: Some(closing.id),
+                    message: format!(
+                        "entry {} differs from the state at the closing of {}",
+                        closing.last_entry_id, closing.date
+                    ),
+                }),
+                None if closing.last_entry_id >= expected_id => issues.push(CashJournalIssueDto {
+                    kind: CashJournalIssueKind::Missing,
+                    entry_id: Some(closing.last_entry_id),
+                    closing_id: Some(closing.id),
+                    message: format!(
+                        "entries {} to {} of the closing of {} are missing",
+                        expected_id, closing.last_entry_id, closing.date
+                    ),
+                }),
+                // Removed in the middle of the journal, already reported as a gap.
+                None => {}
+            }
+            previous_hash = closing.hash.clone();
+        }
+
+        Ok(CashJournalVerificationDto {
+            valid: issues.is_empty(),
+            entry_count: entries.len() as i64,
+            closing_count: closings.len() as i64,
+            issues,
+        })
+    }
+}
+
+/// Id and hash of the last entry, kept in a file next to the database. Nothing in the database
+/// refers to the entries booked after the last closing, so their removal is detected against it.
+#[derive(Clone)]
+pub struct CashJournalHead {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl CashJournalHead {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Records the last entry of the journal. The head only moves forward, entries missing
+    /// below it are reported by the verification instead.
+    pub async fn update(&self, pool: &Pool<Sqlite>) -> ServiceResult<()> {
+        let _lock = self.lock.lock().await;
+        let mut connection = pool.acquire().await?;
+        let (id, hash) = get_last_entry(connection.as_mut()).await?;
+        if id == 0 || self.read().await?.is_some_and(|head| head.id >= id) {
+            return Ok(());
+        }
+
+        // Written to a temporary file first, so the head is never left half written.
+        let temp_path = self.path.with_extension("tmp");
+        tokio::fs::write(&temp_path, serde_json::to_vec(&JournalHead { id, hash })?).await?;
+        tokio::fs::rename(&temp_path, &self.path).await?;
+        Ok(())
+    }
+
+    /// The booking is already committed when the head is recorded, so a failure is only logged.
+    /// The head catches up with the next booking or the next start.
+    pub async fn update_after_booking(&self, pool: &Pool<Sqlite>) {
+        if let Err(e) = self.update(pool).await {
+            error!("Failed to record the head of the cash journal: {}", e);
+        }
+    }
+
+    async fn read(&self) -> ServiceResult<Option<JournalHead>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Books the change of the cash amount of a payment, which is zero for other payment methods
+/// and deleted payments. The first booking uses the payment date, later ones are corrections
+/// of the current day.
+pub async fn book_payment(
+    connection: &mut SqliteConnection,
+    payment_id: i64,
+    date: NaiveDate,
+    cash_amount: i64,
+    description: &str,
+) -> ServiceResult<()> {
+    let booked = sqlx::query_as::<_, BookedRow>(
+        r#"
+        SELECT COUNT(*) AS count, COALESCE(SUM(e.amount), 0) AS amount
+        FROM cash_journal_entry e
+        WHERE e.payment_id = $1;
+    "#,
+    )
+    .bind(payment_id)
+    .fetch_one(&mut *connection)
+    .await?;
+    if booked.amount == cash_amount {
+        return Ok(());
+    }
+
+    let (kind, date) = if booked.count == 0 {
+        (CashEntryKind::Payment, date)
+    } else {
+        let zone = preference::get_time_zone(&mut *connection).await?;
+        (CashEntryKind::Correction, time_zone::now_local(zone).date())
+    };
+    append_entry(
+        connection,
+        date,
+        kind,
+        cash_amount - booked.amount,
+        Some(payment_id),
+        description,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Appends an entry to the chain, the caller has to hold a write transaction.
+async fn append_entry(
+    connection: &mut SqliteConnection,
+    date: NaiveDate,
+    kind: CashEntryKind,
+    amount: i64,
+    payment_id: Option<i64>,
+    description: &str,
+) -> ServiceResult<i64> {
+    if let Some(closing) = get_closing_rows(&mut *connection).await?.pop()
+        && closing.date >= date
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "cash is already closed up to {}",
+            closing.date
+        )));
+    }
+
+    let (last_id, previous_hash) = get_last_entry(&mut *connection).await?;
+    let mut entry = EntryRow {
+        id: last_id + 1,
+        created_at: timestamp(),
+        date,
+        kind,
+        amount,
+        payment_id,
+        description: description.to_string(),
+        previous_hash,
+        hash: String::new(),
+    };
+    entry.hash = entry_hash(&entry)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO cash_journal_entry (
+            id,
+            created_at,
+            date,
+            kind,
+            amount,
+            payment_id,
+            description,
+            previous_hash,
+            hash
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);
+    "#,
+    )
+    .bind(entry.id)
+    .bind(&entry.created_at)
+    .bind(entry.date)
+    .bind(entry.kind)
+    .bind(entry.amount)
+    .bind(entry.payment_id)
+    .bind(&entry.description)
+    .bind(&entry.previous_hash)
+    .bind(&entry.hash)
+    .execute(connection)
+    .await?;
+
+    Ok(entry.id)
+}
+
+/// Id and hash of the last entry, zero and the genesis hash for an empty journal.
+async fn get_last_entry(connection: &mut SqliteConnection) -> ServiceResult<(i64, String)> {
+    Ok(sqlx::query_as::<_, LastEntryRow>(
+        r#"
+        SELECT e.id, e.hash
+        FROM cash_journal_entry e
+        ORDER BY e.id DESC
+        LIMIT 1;
+    "#,
+    )
+    .fetch_optional(connection)
+    .await?
+    .map_or((0, GENESIS_HASH.to_string()), |row| (row.id, row.hash)))
+}
+
+async fn get_closing_list(connection: &mut SqliteConnection) -> ServiceResult<Vec<CashClosingDto>> {
+    let entries = get_entry_rows(&mut *connection).await?;
+    let mut opening_balance = 0;
+    let mut previous_date: Option<NaiveDate> = None;
+
+    get_closing_rows(connection)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let closed_entries = entries.iter().filter(|entry| {
+                entry.date <= row.date
+                    && previous_date.is_none_or(|previous_date| entry.date > previous_date)
+                    && entry.kind != CashEntryKind::Difference
+            });
+            let (receipts, payouts) = closed_entries.fold((0, 0), |(receipts, payouts), entry| {
+                if entry.amount > 0 {
+                    (receipts + entry.amount, payouts)
+                } else {
+                    (receipts, payouts - entry.amount)
+                }
+            });
+
+            let closing = CashClosingDto {
+                id: row.id,
+                date: row.date,
+                created_at: parse_timestamp(&row.created_at)?,
+                opening_balance,
+                receipts,
+                payouts,
+                expected: row.expected,
+                counted: row.counted,
+                difference: row.counted - row.expected,
+                note: row.note,
+                last_entry_id: row.last_entry_id,
+                hash: row.hash,
+            };
+            opening_balance = row.counted;
+            previous_date = Some(row.date);
+            Ok(closing)
+        })
+        .collect()
+}
+
+async fn get_entry_rows(connection: &mut SqliteConnection) -> ServiceResult<Vec<EntryRow>> {
+    Ok(sqlx::query_as::<_, EntryRow>(
+        r#"
+        SELECT
+            e.id,
+            e.created_at,
+            e.date,
+            e.kind,
+            e.amount,
+            e.payment_id,
+            e.description,
+            e.previous_hash,
+            e.hash
+        FROM cash_journal_entry e
+        ORDER BY e.id ASC;
+    "#,
+    )
+    .fetch_all(connection)
+    .await?)
+}
+
+async fn get_closing_rows(connection: &mut SqliteConnection) -> ServiceResult<Vec<ClosingRow>> {
+    Ok(sqlx::query_as::<_, ClosingRow>(
+        r#"
+        SELECT
+            c.id,
+            c.date,
+            c.created_at,
+            c.expected,
+            c.counted,
+            c.note,
+            c.last_entry_id,
+            c.last_entry_hash,
+            c.hash
+        FROM cash_closing c
+        ORDER BY c.date ASC;
+    "#,
+    )
+    .fetch_all(connection)
+    .await?)
+}
+
+fn entry_hash(entry: &EntryRow) -> ServiceResult<String> {
+    let content = serde_json::to_string(&(
+        entry.id,
+        &entry.created_at,
+        entry.date,
+        entry.kind,
+        entry.amount,
+        entry.payment_id,
+        &entry.description,
+        &entry.previous_hash,
+    ))?;
+    Ok(sha256(&content))
+}
+
+fn closing_hash(closing: &ClosingRow, previous_hash: &str) -> ServiceResult<String> {
+    let content = serde_json::to_string(&(
+        closing.date,
+        &closing.created_at,
+        closing.expected,
+        closing.counted,
+        &closing.note,
+        closing.last_entry_id,
+        &closing.last_entry_hash,
+        previous_hash,
+    ))?;
+    Ok(sha256(&content))
+}
+
+fn sha256(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Timestamps are hashed as stored, so they are kept as text.
+fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(timestamp: &str) -> ServiceResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(timestamp)?.with_timezone(&Utc))
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalHead {
+    pub id: i64,
+    pub hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct BookedRow {
+    pub count: i64,
+    pub amount: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct LastEntryRow {
+    pub id: i64,
+    pub hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct EntryRow {
+    pub id: i64,
+    pub created_at: String,
+    pub date: NaiveDate,
+    pub kind: CashEntryKind,
+    pub amount: i64,
+    pub payment_id: Option<i64>,
+    pub description: String,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ClosingRow {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub created_at: String,
+    pub expected: i64,
+    pub counted: i64,
+    pub note: String,
+    pub last_entry_id: i64,
+    pub last_entry_hash: String,
+    pub hash: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{EditPaymentDto, PaymentMethod};
+    use crate::state::State;
+    use crate::state::testing::{customer, state};
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    async fn book(state: &State, day: &str, kind: CashEntryKind, amount: i64) -> i64 {
+        state
+            .cash_journal
+            .store_cash_entry(EditCashEntryDto {
+                date: date(day),
+                kind,
+                amount,
+                description: format!("{:?}", kind),
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Runs a statement the triggers of the journal would reject.
+    async fn tamper(state: &State, trigger: &str, sql: &str) {
+        let pool = &state.cash_journal.pool;
+        sqlx::raw_sql(&format!("DROP TRIGGER {};", trigger))
+            .execute(pool.as_ref())
+            .await
+            .unwrap();
+        sqlx::raw_sql(sql).execute(pool.as_ref()).await.unwrap();
+    }
+
+    async fn issues(state: &State) -> Vec<(CashJournalIssueKind, Option<i64>)> {
+        let verification = state.cash_journal.verify_cash_journal().await.unwrap();
+        assert_eq!(verification.valid, verification.issues.is_empty());
+        verification
+            .issues
+            .into_iter()
+            .map(|issue| (issue.kind, issue.entry_id))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn entries_are_chained() {
+        let state = state().await;
+        book(&state, "2025-03-03", CashEntryKind::Deposit, 20000).await;
+        book(&state, "2025-03-03", CashEntryKind::Withdrawal, 5000).await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        state
+            .payment
+            .store_payment(EditPaymentDto {
+                id: None,
+                customer_id,
+                date: date("2025-03-04"),
+                amount: 3500,
+                method: PaymentMethod::Cash,
+                voucher_id: None,
+                reference: "Haircut".to_string(),
+                note: String::new(),
+                allocations: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let entries = state
+            .cash_journal
+            .get_cash_entry_list(None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.id, entry.kind, entry.amount))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, CashEntryKind::Deposit, 20000),
+                (2, CashEntryKind::Withdrawal, -5000),
+                (3, CashEntryKind::Payment, 3500),
+            ]
+        );
+        assert!(
+            state
+                .cash_journal
+                .store_cash_entry(EditCashEntryDto {
+                    date: date("2025-03-04"),
+                    kind: CashEntryKind::Difference,
+                    amount: 100,
+                    description: String::new(),
+                })
+                .await
+                .is_err()
+        );
+        assert!(issues(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn closing_books_the_difference() {
+        let state = state().await;
+        book(&state, "2025-03-03", CashEntryKind::Deposit, 20000).await;
+        book(&state, "2025-03-04", CashEntryKind::Refund, 1500).await;
+
+        let closing = state
+            .cash_journal
+            .close_cash_day(date("2025-03-03"), 19500, "counted".to_string())
+            .await
+            .unwrap();
+        assert_eq!(closing.opening_balance, 0);
+        assert_eq!(closing.expected, 20000);
+        assert_eq!(closing.difference, -500);
+        assert_eq!(closing.last_entry_id, 3);
+
+        // Closed days take no more entries and are not closed twice.
+        assert!(
+            state
+                .cash_journal
+                .close_cash_day(date("2025-03-03"), 19500, String::new())
+                .await
+                .is_err()
+        );
+        let entry = EditCashEntryDto {
+            date: date("2025-03-03"),
+            kind: CashEntryKind::Deposit,
+            amount: 100,
+            description: String::new(),
+        };
+        assert!(state.cash_journal.store_cash_entry(entry).await.is_err());
+
+        let closing = state
+            .cash_journal
+            .close_cash_day(date("2025-03-04"), 18000, String::new())
+            .await
+            .unwrap();
+        assert_eq!(closing.opening_balance, 19500);
+        assert_eq!(closing.payouts, 1500);
+        assert_eq!(closing.expected, 18000);
+        assert_eq!(closing.difference, 0);
+        assert_eq!(
+            state
+                .cash_journal
+                .get_cash_closing_list()
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(issues(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn detects_modified_entries() {
+        let state = state().await;
+        book(&state, "2025-03-03", CashEntryKind::Deposit, 20000).await;
+        book(&state, "2025-03-03", CashEntryKind::Withdrawal, 5000).await;
+
+        tamper(
+            &state,
+            "cash_journal_entry_update",
+            "UPDATE cash_journal_entry SET amount = -500 WHERE id = 2;",
+        )
+        .await;
+        assert_eq!(
+            issues(&state).await,
+            vec![(CashJournalIssueKind::Modified, Some(2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_entries_removed_after_the_last_closing() {
+        let state = state().await;
+        book(&state, "2025-03-03", CashEntryKind::Deposit, 20000).await;
+        state
+            .cash_journal
+            .close_cash_day(date("2025-03-03"), 20000, String::new())
+            .await
+            .unwrap();
+        book(&state, "2025-03-04", CashEntryKind::Withdrawal, 5000).await;
+        book(&state, "2025-03-04", CashEntryKind::Withdrawal, 2000).await;
+
+        tamper(
+            &state,
+            "cash_journal_entry_delete",
+            "DELETE FROM cash_journal_entry WHERE id > 1;",
+        )
+        .await;
+        assert_eq!(
+            issues(&state).await,
+            vec![(CashJournalIssueKind::Missing, Some(3))]
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_removed_closings() {
+        let state = state().await;
+        book(&state, "2025-03-03", CashEntryKind::Deposit, 20000).await;
+        for day in ["2025-03-03", "2025-03-04"] {
+            state
+                .cash_journal
+                .close_cash_day(date(day), 20000, String::new())
+                .await
+                .unwrap();
+        }
+
+        tamper(
+            &state,
+            "cash_closing_delete",
+            "DELETE FROM cash_closing WHERE date = '2025-03-03';",
+        )
+        .await;
+        assert_eq!(
+            issues(&state).await,
+            vec![(CashJournalIssueKind::ClosingMismatch, None)]
+        );
+    }
+}
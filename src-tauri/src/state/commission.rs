@@ -0,0 +1,812 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    csv::CsvWriter,
+    error::{ServiceError, ServiceResult},
+    model::{
+        CommissionAppointmentDto, CommissionKind, CommissionReportDto, CommissionReportFilterDto,
+        CommissionRuleDto, EditCommissionRuleDto, EmployeeCommissionDto, EmployeeRefDto,
+    },
+    money,
+    state::preference,
+    time_zone,
+};
+
+/// Percentages are given in basis points like VAT rates.
+const PERCENTAGE_BASIS: i64 = 10_000;
+const LANGUAGE_KEY: &str = "general.language";
+
+#[derive(Clone)]
+pub struct CommissionState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl CommissionState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_commission_rule_list(
+        &self,
+        employee_id: Option<i64>,
+    ) -> ServiceResult<Vec<CommissionRuleDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_commission_rule_list(connection.as_mut(), employee_id).await
+    }
+
+    pub async fn store_commission_rule(&self, rule: EditCommissionRuleDto) -> ServiceResult<i64> {
+        match rule.kind {
+            CommissionKind::Percentage if !(1..=PERCENTAGE_BASIS).contains(&rule.value) => {
+                return Err(ServiceError::BadRequest(format!(
+                    "percentage must be between 1 and {}",
+                    PERCENTAGE_BASIS
+                )));
+            }
+            CommissionKind::Fixed if rule.value <= 0 => {
+                return Err(ServiceError::BadRequest(
+                    "commission must be positive".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        let treatment_category = rule
+            .treatment_category
+            .as_deref()
+            .map(str::trim)
+            .filter(|category| !category.is_empty());
+        if treatment_category.is_some() && rule.treatment_id.is_some() {
+            return Err(ServiceError::BadRequest(
+                "rule applies either to a treatment or to a category".to_string(),
+            ));
+        }
+        if let (Some(from), Some(until)) = (rule.valid_from, rule.valid_until)
+            && until < from
+        {
+            return Err(ServiceError::BadRequest(
+                "rule must not end before it starts".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(rule_id) = rule.id
+            && let Some(stored) = get_commission_rule(transaction.as_mut(), rule_id).await?
+            && let Some(last_use) = get_last_use(transaction.as_mut(), &stored).await?
+        {
+            let unchanged = (
+                stored.employee_id,
+                stored.kind,
+                stored.value,
+                stored.treatment_category.as_deref(),
+                stored.treatment_id,
+                stored.valid_from,
+            ) == (
+                rule.employee_id,
+                rule.kind,
+                rule.value,
+                treatment_category,
+                rule.treatment_id,
+                rule.valid_from,
+            );
+            if !unchanged {
+                return Err(ServiceError::Conflict(
+                    "commission rule applies to past appointments, please end it and add a new \
+                     rule instead"
+                        .to_string(),
+                ));
+            }
+            if rule.valid_until.is_some_and(|until| until < last_use) {
+                return Err(ServiceError::Conflict(format!(
+                    "commission rule applies to appointments up to {}",
+                    last_use
+                )));
+            }
+        }
+
+        let q = if let Some(rule_id) = rule.id {
+            sqlx::query(
+                r#"
+                UPDATE commission_rule
+                SET employee_id = $2,
+                    kind = $3,
+                    value = $4,
+                    treatment_category = $5,
+                    treatment_id = $6,
+                    valid_from = $7,
+                    valid_until = $8
+                WHERE id = $1;
+            "#,
+            )
+            .bind(rule_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO commission_rule (
+                    employee_id,
+                    kind,
+                    value,
+                    treatment_category,
+                    treatment_id,
+                    valid_from,
+                    valid_until
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7);
+            "#,
+            )
+        };
+
+        q.bind(rule.employee_id)
+            .bind(rule.kind)
+            .bind(rule.value)
+            .bind(treatment_category)
+            .bind(rule.treatment_id)
+            .bind(rule.valid_from)
+            .bind(rule.valid_until)
+            .execute(transaction.as_mut())
+            .await?;
+
+        if let Some(rule_id) = rule.id {
+            transaction.commit().await?;
+            return Ok(rule_id);
+        }
+
+        let rule_id = sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(transaction.as_mut())
+        .await?
+        .id;
+        transaction.commit().await?;
+        Ok(rule_id)
+    }
+
+    /// Rules that apply to past appointments are kept for the reports of these, they can only
+    /// be ended.
+    pub async fn delete_commission_rule(&self, id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(rule) = get_commission_rule(transaction.as_mut(), id).await?
+            && get_last_use(transaction.as_mut(), &rule).await?.is_some()
+        {
+            return Err(ServiceError::Conflict(
+                "commission rule applies to past appointments, please end it instead".to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM commission_rule
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_commission_report(
+        &self,
+        filter: CommissionReportFilterDto,
+    ) -> ServiceResult<CommissionReportDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_commission_report(connection.as_mut(), filter).await
+    }
+
+    /// One line per appointment and a total line per employee, for the payroll.
+    pub async fn export_commission_report_csv(
+        &self,
+        filter: CommissionReportFilterDto,
+        path: &Path,
+    ) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let report = get_commission_report(connection.as_mut(), filter).await?;
+        let german = preference::get_preference::<String>(connection.as_mut(), LANGUAGE_KEY)
+            .await?
+            .is_some_and(|language| language == "de");
+
+        let mut csv = CsvWriter::new(german);
+        csv.row(&if german {
+            [
+                "Mitarbeiter-ID",
+                "Mitarbeiter",
+                "Termin-ID",
+                "Datum",
+                "Kunde",
+                "Behandlung",
+                "Umsatz netto",
+                "Provision",
+            ]
+        } else {
+            [
+                "Employee ID",
+                "Employee",
+                "Appointment ID",
+                "Date",
+                "Customer",
+                "Treatment",
+                "Net revenue",
+                "Commission",
+            ]
+        });
+        for employee in &report.employees {
+            for appointment in &employee.appointments {
+                csv.row(&[
+                    employee.employee.id.to_string(),
+                    employee.employee.name.clone(),
+                    appointment.appointment_id.to_string(),
+                    appointment.start_date.format("%Y-%m-%d %H:%M").to_string(),
+                    appointment.customer_name.clone(),
+                    appointment.treatment.clone(),
+                    csv.amount(appointment.revenue.amount),
+                    csv.amount(appointment.commission.amount),
+                ]);
+            }
+            csv.row(&[
+                employee.employee.id.to_string(),
+                employee.employee.name.clone(),
+                String::new(),
+                String::new(),
+                String::new(),
+                if german { "Summe" } else { "Total" }.to_string(),
+                csv.amount(employee.revenue.amount),
+                csv.amount(employee.commission.amount),
+            ]);
+        }
+
+        tokio::fs::write(path, csv.finish()).await?;
+        Ok(())
+    }
+}
+
+pub async fn get_commission_rule_list(
+    connection: &mut SqliteConnection,
+    employee_id: Option<i64>,
+) -> ServiceResult<Vec<CommissionRuleDto>> {
+    Ok(sqlx::query_as::<_, CommissionRuleRow>(
+        r#"
+        SELECT
+            r.id,
+            r.employee_id,
+            r.kind,
+            r.value,
+            r.treatment_category,
+            r.treatment_id,
+            r.valid_from,
+            r.valid_until
+        FROM commission_rule r
+        WHERE ($1 IS NULL OR r.employee_id = $1)
+        ORDER BY r.employee_id ASC, r.valid_from ASC, r.id ASC;
+    "#,
+    )
+    .bind(employee_id)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect())
+}
+
+/// Whether a rule of the treatment applies to past appointments, the rule would be removed with
+/// the treatment.
+pub async fn has_used_treatment_rules(
+    connection: &mut SqliteConnection,
+    treatment_id: i64,
+) -> ServiceResult<bool> {
+    let rules = get_commission_rule_list(&mut *connection, None).await?;
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.treatment_id == Some(treatment_id))
+    {
+        if get_last_use(&mut *connection, rule).await?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn get_commission_rule(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> ServiceResult<Option<CommissionRuleDto>> {
+    Ok(sqlx::query_as::<_, CommissionRuleRow>(
+        r#"
+        SELECT
+            r.id,
+            r.employee_id,
+            r.kind,
+            r.value,
+            r.treatment_category,
+            r.treatment_id,
+            r.valid_from,
+            r.valid_until
+        FROM commission_rule r
+        WHERE r.id = $1;
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(connection)
+    .await?
+    .map(|row| row.into()))
+}
+
+/// Date of the last appointment up to today with an item the rule applies to. Whether a more
+/// specific rule took precedence is not checked, the rule counts as used either way.
+async fn get_last_use(
+    connection: &mut SqliteConnection,
+    rule: &CommissionRuleDto,
+) -> ServiceResult<Option<NaiveDate>> {
+    let zone = preference::get_time_zone(&mut *connection).await?;
+    let today = time_zone::now_local(zone).date();
+    let until = rule.valid_until.map_or(today, |until| until.min(today));
+
+    Ok(sqlx::query_as::<_, UsedItemRow>(
+        r#"
+        SELECT
+            date(a.start_date) AS date,
+            i.treatment_id,
+            t.category AS treatment_category
+        FROM appointment_item i
+        JOIN appointment a ON a.id = i.appointment_id
+        LEFT JOIN treatment t ON t.id = i.treatment_id
+        WHERE a.employee_id = $1
+            AND ($2 IS NULL OR date(a.start_date) >= $2)
+            AND date(a.start_date) <= $3
+        ORDER BY a.start_date DESC;
+    "#,
+    )
+    .bind(rule.employee_id)
+    .bind(rule.valid_from)
+    .bind(until)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .find(|item| specificity(rule, item.treatment_id, item.treatment_category.as_deref()).is_some())
+    .map(|item| item.date))
+}
+
+/// Commission on the appointments of the employees between the filter dates. Appointments
+/// without an employee earn no commission and are left out.
+pub async fn get_commission_report(
+    connection: &mut SqliteConnection,
+    filter: CommissionReportFilterDto,
+) -> ServiceResult<CommissionReportDto> {
+    if filter.to < filter.from {
+        return Err(ServiceError::BadRequest(
+            "report must not end before it starts".to_string(),
+        ));
+    }
+
+    let currency = preference::get_currency(&mut *connection).await?;
+    let rules = get_commission_rule_list(&mut *connection, filter.employee_id).await?;
+    let items = sqlx::query_as::<_, ItemRow>(
+        r#"
+        SELECT
+            a.id AS appointment_id,
+            a.start_date,
+            e.id AS employee_id,
+            e.name AS employee_name,
+            e.active AS employee_active,
+            a.customer_id,
+            c.first_name,
+            c.last_name,
+            i.description,
+            i.treatment_id,
+            t.category AS treatment_category,
+            i.quantity,
+            i.vat_rate,
+            i.quantity * i.unit_price - i.discount AS gross
+        FROM appointment_item i
+        JOIN appointment a ON a.id = i.appointment_id
+        JOIN employee e ON e.id = a.employee_id
+        JOIN customer c ON c.id = a.customer_id
+        LEFT JOIN treatment t ON t.id = i.treatment_id
+        WHERE date(a.start_date) BETWEEN $1 AND $2
+            AND ($3 IS NULL OR a.employee_id = $3)
+        ORDER BY e.name ASC, e.id ASC, a.start_date ASC, a.id ASC, i.position ASC;
+    "#,
+    )
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.employee_id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let mut employees: Vec<EmployeeCommissionDto> = Vec::new();
+    let mut treatments: Vec<String> = Vec::new();
+    for item in items {
+        let revenue = money::split_gross(item.gross, item.vat_rate, currency)
+            .net
+            .amount;
+        let commission = commission_of(&rules, &item, revenue);
+
+        if employees
+            .last()
+            .is_none_or(|employee| employee.employee.id != item.employee_id)
+        {
+            employees.push(EmployeeCommissionDto {
+                employee: EmployeeRefDto {
+                    id: item.employee_id,
+                    name: item.employee_name.clone(),
+                    active: item.employee_active,
+                },
+                appointments: Vec::new(),
+                revenue: money::money(0, currency),
+                commission: money::money(0, currency),
+            });
+        }
+        let employee = employees.last_mut().unwrap();
+        employee.revenue.amount += revenue;
+        employee.commission.amount += commission;
+
+        if employee
+            .appointments
+            .last()
+            .is_none_or(|appointment| appointment.appointment_id != item.appointment_id)
+        {
+            treatments.clear();
+            employee.appointments.push(CommissionAppointmentDto {
+                appointment_id: item.appointment_id,
+                start_date: item.start_date,
+                customer_id: item.customer_id,
+                customer_name: format!("{} {}", item.first_name, item.last_name)
+                    .trim()
+                    .to_string(),
+                treatment: String::new(),
+                revenue: money::money(0, currency),
+                commission: money::money(0, currency),
+            });
+        }
+        let appointment = employee.appointments.last_mut().unwrap();
+        appointment.revenue.amount += revenue;
+        appointment.commission.amount += commission;
+        treatments.push(item.description);
+        appointment.treatment = treatments.join(", ");
+    }
+
+    Ok(CommissionReportDto {
+        filter,
+        revenue: money::money(
+            employees
+                .iter()
+                .map(|employee| employee.revenue.amount)
+                .sum(),
+            currency,
+        ),
+        commission: money::money(
+            employees
+                .iter()
+                .map(|employee| employee.commission.amount)
+                .sum(),
+            currency,
+        ),
+        employees,
+    })
+}
+
+/// Commission of the most specific rule of the employee valid on the appointment date that
+/// applies to the item, the higher commission wins between rules that are equally specific.
+fn commission_of(rules: &[CommissionRuleDto], item: &ItemRow, revenue: i64) -> i64 {
+    let date = item.start_date.date();
+    rules
+        .iter()
+        .filter(|rule| rule.employee_id == item.employee_id)
+        .filter(|rule| {
+            rule.valid_from.is_none_or(|from| from <= date)
+                && rule.valid_until.is_none_or(|until| date <= until)
+        })
+        .filter_map(|rule| {
+            let specificity =
+                specificity(rule, item.treatment_id, item.treatment_category.as_deref())?;
+            let commission = match rule.kind {
+                CommissionKind::Percentage => money::round_div(
+                    revenue as i128 * rule.value as i128,
+                    PERCENTAGE_BASIS as i128,
+                ),
+                CommissionKind::Fixed => rule.value * item.quantity,
+            };
+            Some((specificity, commission))
+        })
+        .max()
+        .map(|(_, commission)| commission)
+        .unwrap_or(0)
+}
+
+/// How specific the rule is for an item, none if it does not apply to the item.
+fn specificity(
+    rule: &CommissionRuleDto,
+    treatment_id: Option<i64>,
+    treatment_category: Option<&str>,
+) -> Option<u8> {
+    match (&rule.treatment_category, rule.treatment_id) {
+        (_, Some(rule_treatment_id)) if treatment_id == Some(rule_treatment_id) => Some(2),
+        (Some(category), None)
+            if treatment_category.is_some_and(|item_category| {
+                item_category.to_lowercase() == category.to_lowercase()
+            }) =>
+        {
+            Some(1)
+        }
+        (None, None) if rule.kind == CommissionKind::Percentage => Some(0),
+        (None, None) if treatment_id.is_some() => Some(0),
+        _ => None,
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct UsedItemRow {
+    pub date: NaiveDate,
+    pub treatment_id: Option<i64>,
+    pub treatment_category: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct CommissionRuleRow {
+    pub id: i64,
+    pub employee_id: i64,
+    pub kind: CommissionKind,
+    pub value: i64,
+    pub treatment_category: Option<String>,
+    pub treatment_id: Option<i64>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+}
+
+impl From<CommissionRuleRow> for CommissionRuleDto {
+    fn from(row: CommissionRuleRow) -> CommissionRuleDto {
+        CommissionRuleDto {
+            id: row.id,
+            employee_id: row.employee_id,
+            kind: row.kind,
+            value: row.value,
+            treatment_category: row.treatment_category,
+            treatment_id: row.treatment_id,
+            valid_from: row.valid_from,
+            valid_until: row.valid_until,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ItemRow {
+    pub appointment_id: i64,
+    pub start_date: NaiveDateTime,
+    pub employee_id: i64,
+    pub employee_name: String,
+    pub employee_active: bool,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub description: String,
+    pub treatment_id: Option<i64>,
+    pub treatment_category: Option<String>,
+    pub quantity: i64,
+    pub vat_rate: i64,
+    pub gross: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{EditEmployeeDto, EditTreatmentDto};
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Employee with a massage of 119.00 gross on each of the dates.
+    async fn employee_with_appointments(state: &State, dates: &[&str]) -> i64 {
+        let employee_id = state
+            .employee
+            .store_employee(EditEmployeeDto {
+                id: None,
+                name: "Eva".to_string(),
+                phone: String::new(),
+                email: String::new(),
+                color: String::new(),
+                employed_from: None,
+                employed_until: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        for day in dates {
+            let mut appointment = appointment(
+                customer_id,
+                date(day).and_hms_opt(10, 0, 0).unwrap(),
+                vec![item("Massage", 1, 11900)],
+            );
+            appointment.employee_id = Some(employee_id);
+            state
+                .appointment
+                .store_appointment(appointment)
+                .await
+                .unwrap();
+        }
+        employee_id
+    }
+
+    fn rule(
+        employee_id: i64,
+        value: i64,
+        valid_from: Option<&str>,
+        valid_until: Option<&str>,
+    ) -> EditCommissionRuleDto {
+        EditCommissionRuleDto {
+            id: None,
+            employee_id,
+            kind: CommissionKind::Percentage,
+            value,
+            treatment_category: None,
+            treatment_id: None,
+            valid_from: valid_from.map(date),
+            valid_until: valid_until.map(date),
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_the_rule_valid_on_the_appointment_date() {
+        let state = state().await;
+        let employee_id = employee_with_appointments(&state, &["2024-01-31", "2024-02-01"]).await;
+        state
+            .commission
+            .store_commission_rule(rule(employee_id, 1000, None, Some("2024-01-31")))
+            .await
+            .unwrap();
+        state
+            .commission
+            .store_commission_rule(rule(employee_id, 2000, Some("2024-02-01"), None))
+            .await
+            .unwrap();
+        assert!(
+            state
+                .commission
+                .store_commission_rule(rule(
+                    employee_id,
+                    2000,
+                    Some("2024-02-01"),
+                    Some("2024-01-31")
+                ))
+                .await
+                .is_err()
+        );
+
+        let report = state
+            .commission
+            .get_commission_report(CommissionReportFilterDto {
+                from: date("2024-01-01"),
+                to: date("2024-02-29"),
+                employee_id: Some(employee_id),
+            })
+            .await
+            .unwrap();
+        let commissions: Vec<i64> = report.employees[0]
+            .appointments
+            .iter()
+            .map(|appointment| appointment.commission.amount)
+            .collect();
+        assert_eq!(commissions, vec![1000, 2000]);
+    }
+
+    #[tokio::test]
+    async fn used_rules_are_kept() {
+        let state = state().await;
+        let employee_id = employee_with_appointments(&state, &["2024-01-15"]).await;
+        let used = state
+            .commission
+            .store_commission_rule(rule(employee_id, 1000, None, None))
+            .await
+            .unwrap();
+        let unused = state
+            .commission
+            .store_commission_rule(rule(employee_id, 1000, Some("2024-02-01"), None))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            state.commission.delete_commission_rule(used).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        let changed = EditCommissionRuleDto {
+            id: Some(used),
+            ..rule(employee_id, 1500, None, None)
+        };
+        assert!(matches!(
+            state.commission.store_commission_rule(changed).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        let ended = |until| EditCommissionRuleDto {
+            id: Some(used),
+            ..rule(employee_id, 1000, None, Some(until))
+        };
+        assert!(matches!(
+            state
+                .commission
+                .store_commission_rule(ended("2024-01-14"))
+                .await,
+            Err(ServiceError::Conflict(_))
+        ));
+        state
+            .commission
+            .store_commission_rule(ended("2024-01-31"))
+            .await
+            .unwrap();
+
+        state
+            .commission
+            .delete_commission_rule(unused)
+            .await
+            .unwrap();
+        let rules = state
+            .commission
+            .get_commission_rule_list(Some(employee_id))
+            .await
+            .unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].valid_until, Some(date("2024-01-31")));
+    }
+
+    #[tokio::test]
+    async fn used_treatment_rules_keep_their_treatment() {
+        let state = state().await;
+        let treatment_id = state
+            .treatment
+            .store_treatment(EditTreatmentDto {
+                id: None,
+                name: "Massage".to_string(),
+                category: String::new(),
+                default_duration_minutes: 60,
+                default_price: 11900,
+                vat_rate: 1900,
+                active: true,
+                resource_ids: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let employee_id = employee_with_appointments(&state, &[]).await;
+        let mut massage = item("Massage", 1, 11900);
+        massage.treatment_id = Some(treatment_id);
+        let mut appointment = appointment(
+            1,
+            date("2024-01-15").and_hms_opt(10, 0, 0).unwrap(),
+            vec![massage],
+        );
+        appointment.employee_id = Some(employee_id);
+        state
+            .appointment
+            .store_appointment(appointment)
+            .await
+            .unwrap();
+
+        let rule = EditCommissionRuleDto {
+            kind: CommissionKind::Fixed,
+            treatment_id: Some(treatment_id),
+            ..rule(employee_id, 500, None, None)
+        };
+        state.commission.store_commission_rule(rule).await.unwrap();
+        assert!(matches!(
+            state.treatment.delete_treatment(treatment_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+}
@@ -1,10 +1,12 @@
 use chrono::NaiveDate;
-use sqlx::{Pool, Sqlite};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::{
-    error::ServiceResult,
-    model::{CustomerDto, EditCustomerDto, EmployeeDto},
+    error::{ServiceError, ServiceResult},
+    model::{CustomerDto, CustomerPackageDto, EditCustomerDto, EmployeeRefDto},
+    state::package,
 };
 
 #[derive(Clone)]
@@ -19,7 +21,7 @@ impl CustomerState {
 
     pub async fn get_customer_list(&self) -> ServiceResult<Vec<CustomerDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
-        Ok(sqlx::query_as::<_, CustomerRow>(
+        let rows = sqlx::query_as::<_, CustomerRow>(
             r#"
             SELECT 
                 c.id,
@@ -27,28 +29,49 @@ impl CustomerState {
                 c.first_name,
                 c.last_name,
                 c.address_street,
+                c.address_postcode,
                 c.address_city,
                 c.phone,
                 c.mobile_phone,
+                c.email,
                 c.birthdate,
                 c.customer_since,
                 c.note,
                 e.id AS responsible_employee_id,
-                e.name AS responsible_employee_name
+                e.name AS responsible_employee_name,
+                e.active AS responsible_employee_active
             FROM customer c
             LEFT JOIN employee e ON c.responsible_employee_id = e.id;
         "#,
         )
         .fetch_all(connection.as_mut())
-        .await?
-        .into_iter()
-        .map(|row| row.into())
-        .collect())
+        .await?;
+
+        let mut tags = get_tag_list(connection.as_mut(), None).await?;
+        let mut packages: HashMap<i64, Vec<CustomerPackageDto>> = HashMap::new();
+        for package in package::get_package_list(connection.as_mut(), None, None).await? {
+            if is_open(&package) {
+                packages
+                    .entry(package.customer_id)
+                    .or_default()
+                    .push(package);
+            }
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut customer: CustomerDto = row.into();
+                customer.tags = tags.remove(&customer.id).unwrap_or_default();
+                customer.packages = packages.remove(&customer.id).unwrap_or_default();
+                customer
+            })
+            .collect())
     }
 
     pub async fn get_customer_by_id(&self, id: i64) -> ServiceResult<Option<CustomerDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
-        Ok(sqlx::query_as::<_, CustomerRow>(
+        let row = sqlx::query_as::<_, CustomerRow>(
             r#"
             SELECT 
                 c.id,
@@ -56,14 +79,17 @@ impl CustomerState {
                 c.first_name,
                 c.last_name,
                 c.address_street,
+                c.address_postcode,
                 c.address_city,
                 c.phone,
                 c.mobile_phone,
+                c.email,
                 c.birthdate,
                 c.customer_since,
                 c.note,
                 e.id AS responsible_employee_id,
-                e.name AS responsible_employee_name
+                e.name AS responsible_employee_name,
+                e.active AS responsible_employee_active
             FROM customer c
             LEFT JOIN employee e ON c.responsible_employee_id = e.id
             WHERE c.id = $1;
@@ -71,12 +97,27 @@ impl CustomerState {
         )
         .bind(id)
         .fetch_optional(connection.as_mut())
-        .await?
-        .map(|row| row.into()))
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let mut customer: CustomerDto = row.into();
+        customer.tags = get_tag_list(connection.as_mut(), Some(id))
+            .await?
+            .remove(&id)
+            .unwrap_or_default();
+        customer.packages = package::get_package_list(connection.as_mut(), Some(id), None)
+            .await?
+            .into_iter()
+            .filter(is_open)
+            .collect();
+        Ok(Some(customer))
     }
 
     pub async fn store_customer(&self, customer: EditCustomerDto) -> ServiceResult<i64> {
-        let mut connection = self.pool.acquire().await.unwrap();
+        let mut transaction = self.pool.begin().await?;
 
         let q = if let Some(customer_id) = customer.id {
             sqlx::query(
@@ -86,13 +127,15 @@ impl CustomerState {
                     first_name = $3,
                     last_name = $4,
                     address_street = $5,
-                    address_city = $6,
-                    phone = $7,
-                    mobile_phone = $8,
-                    birthdate = $9,
-                    customer_since = $10,
-                    note = $11,
-                    responsible_employee_id = $12
+                    address_postcode = $6,
+                    address_city = $7,
+                    phone = $8,
+                    mobile_phone = $9,
+                    email = $10,
+                    birthdate = $11,
+                    customer_since = $12,
+                    note = $13,
+                    responsible_employee_id = $14
                 WHERE id = $1;
             "#,
             )
@@ -105,15 +148,17 @@ impl CustomerState {
                     first_name,
                     last_name,
                     address_street,
+                    address_postcode,
                     address_city,
                     phone,
                     mobile_phone,
+                    email,
                     birthdate,
                     customer_since,
                     note,
                     responsible_employee_id
                 )
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11);
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13);
             "#,
             )
         };
@@ -122,32 +167,100 @@ impl CustomerState {
             .bind(&customer.first_name)
             .bind(&customer.last_name)
             .bind(&customer.address_street)
+            .bind(&customer.address_postcode)
             .bind(&customer.address_city)
             .bind(&customer.phone)
             .bind(&customer.mobile_phone)
+            .bind(&customer.email)
             .bind(customer.birthdate)
             .bind(customer.customer_since)
             .bind(&customer.note)
             .bind(customer.responsible_employee_id)
-            .execute(connection.as_mut())
+            .execute(transaction.as_mut())
+            .await?;
+
+        let customer_id = if let Some(customer_id) = customer.id {
+            sqlx::query(
+                r#"
+                DELETE FROM customer_tag
+                WHERE customer_id = $1;
+            "#,
+            )
+            .bind(customer_id)
+            .execute(transaction.as_mut())
             .await?;
 
-        if let Some(customer_id) = customer.id {
-            return Ok(customer_id);
+            customer_id
+        } else {
+            sqlx::query_as::<_, IdRow>(
+                r#"
+            SELECT last_insert_rowid() as id;
+            "#,
+            )
+            .fetch_one(transaction.as_mut())
+            .await?
+            .id
+        };
+
+        for tag in normalize_tags(&customer.tags) {
+            sqlx::query(
+                r#"
+                INSERT INTO customer_tag (customer_id, tag)
+                VALUES ($1, $2);
+            "#,
+            )
+            .bind(customer_id)
+            .bind(tag)
+            .execute(transaction.as_mut())
+            .await?;
         }
 
-        Ok(sqlx::query_as::<_, IdRow>(
+        transaction.commit().await?;
+        Ok(customer_id)
+    }
+
+    /// Customers with payments are kept, the payments belong to the accounting and may have
+    /// redeemed vouchers. So are customers with appointments on issued invoices.
+    pub async fn delete_customer(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let paid = sqlx::query_as::<_, IdRow>(
             r#"
-        SELECT last_insert_rowid() as id;
+            SELECT p.id
+            FROM payment p
+            WHERE p.customer_id = $1
+            LIMIT 1;
         "#,
         )
-        .fetch_one(connection.as_mut())
+        .bind(id)
+        .fetch_optional(connection.as_mut())
         .await?
-        .id)
-    }
+        .is_some();
+        if paid {
+            return Err(ServiceError::Conflict(
+                "customer has payments, which have to be kept".to_string(),
+            ));
+        }
 
-    pub async fn delete_customer(&self, id: i64) -> ServiceResult<()> {
-        let mut connection = self.pool.acquire().await.unwrap();
+        let invoiced = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT a.id
+            FROM appointment a
+            JOIN invoice_item i ON i.appointment_id = a.id
+            JOIN invoice inv ON inv.id = i.invoice_id
+            WHERE a.customer_id = $1 AND inv.kind = 'invoice' AND inv.status = 'issued'
+            LIMIT 1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .is_some();
+        if invoiced {
+            return Err(ServiceError::Conflict(
+                "customer has appointments on issued invoices, which have to be kept".to_string(),
+            ));
+        }
 
         sqlx::query(
             r#"
@@ -163,11 +276,62 @@ impl CustomerState {
     }
 }
 
+/// Tags of all customers or a single one, grouped by customer.
+pub async fn get_tag_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+) -> ServiceResult<HashMap<i64, Vec<String>>> {
+    Ok(sqlx::query_as::<_, TagRow>(
+        r#"
+        SELECT t.customer_id, t.tag
+        FROM customer_tag t
+        WHERE ($1 IS NULL OR t.customer_id = $1)
+        ORDER BY t.customer_id ASC, t.tag ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .fold(HashMap::new(), |mut map, row| {
+        map.entry(row.customer_id)
+            .or_insert_with(Vec::new)
+            .push(row.tag);
+        map
+    }))
+}
+
+/// Tags are trimmed and compared without case, the first spelling of a tag is kept.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty()
+            && !normalized
+                .iter()
+                .any(|other| other.to_lowercase() == tag.to_lowercase())
+        {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// Packages that still have sessions to use.
+fn is_open(package: &CustomerPackageDto) -> bool {
+    package.remaining_sessions > 0 && !package.expired
+}
+
 #[derive(sqlx::FromRow)]
 struct IdRow {
     pub id: i64,
 }
 
+#[derive(sqlx::FromRow)]
+struct TagRow {
+    pub customer_id: i64,
+    pub tag: String,
+}
+
 #[derive(sqlx::FromRow)]
 struct CustomerRow {
     pub id: i64,
@@ -175,23 +339,29 @@ struct CustomerRow {
     pub first_name: String,
     pub last_name: String,
     pub address_street: String,
+    pub address_postcode: String,
     pub address_city: String,
     pub phone: String,
     pub mobile_phone: String,
+    pub email: String,
     pub birthdate: Option<NaiveDate>,
     pub customer_since: Option<NaiveDate>,
     pub note: String,
     pub responsible_employee_id: Option<i64>,
     pub responsible_employee_name: Option<String>,
+    pub responsible_employee_active: Option<bool>,
 }
 
 impl From<CustomerRow> for CustomerDto {
     fn from(row: CustomerRow) -> CustomerDto {
-        let responsible_employee =
-            match (row.responsible_employee_id, row.responsible_employee_name) {
-                (Some(id), Some(name)) => Some(EmployeeDto { id, name }),
-                _ => None,
-            };
+        let responsible_employee = match (
+            row.responsible_employee_id,
+            row.responsible_employee_name,
+            row.responsible_employee_active,
+        ) {
+            (Some(id), Some(name), Some(active)) => Some(EmployeeRefDto { id, name, active }),
+            _ => None,
+        };
 
         CustomerDto {
             id: row.id,
@@ -199,13 +369,96 @@ impl From<CustomerRow> for CustomerDto {
             first_name: row.first_name,
             last_name: row.last_name,
             address_street: row.address_street,
+            address_postcode: row.address_postcode,
             address_city: row.address_city,
             phone: row.phone,
             mobile_phone: row.mobile_phone,
+            email: row.email,
             birthdate: row.birthdate,
             customer_since: row.customer_since,
             note: row.note,
             responsible_employee,
+            tags: Vec::new(),
+            packages: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::{EditPaymentDto, EditVoucherDto, PaymentMethod};
+    use crate::state::testing::{customer, state};
+
+    #[tokio::test]
+    async fn customers_with_payments_are_kept() {
+        let state = state().await;
+        let date = NaiveDate::from_ymd_opt(2025, 3, 3).unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let unpaid_customer_id = state
+            .customer
+            .store_customer(customer("Tom", "Berg"))
+            .await
+            .unwrap();
+        let voucher_id = state
+            .voucher
+            .store_voucher(EditVoucherDto {
+                id: None,
+                initial_value: 5000,
+                issue_date: date,
+                expiry_date: None,
+                purchaser_customer_id: None,
+                recipient_customer_id: None,
+                note: String::new(),
+            })
+            .await
+            .unwrap();
+        state
+            .payment
+            .store_payment(EditPaymentDto {
+                id: None,
+                customer_id,
+                date,
+                amount: 2000,
+                method: PaymentMethod::Voucher,
+                voucher_id: Some(voucher_id),
+                reference: String::new(),
+                note: String::new(),
+                allocations: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            state.customer.delete_customer(customer_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        // The database refuses it as well.
+        assert!(
+            sqlx::query("DELETE FROM customer WHERE id = $1;")
+                .bind(customer_id)
+                .execute(state.customer.pool.as_ref())
+                .await
+                .is_err()
+        );
+        let voucher = state
+            .voucher
+            .get_voucher_by_id(voucher_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(voucher.remaining_value, 3000);
+
+        state
+            .customer
+            .delete_customer(unpaid_customer_id)
+            .await
+            .unwrap();
+    }
+}
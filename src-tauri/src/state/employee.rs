@@ -1,9 +1,10 @@
+use chrono::NaiveDate;
 use sqlx::{Pool, Sqlite};
 use std::sync::Arc;
 
 use crate::{
-    error::ServiceResult,
-    model::{EditEmployeeDto, EmployeeDto},
+    error::{ServiceError, ServiceResult},
+    model::{EditEmployeeDto, EmployeeDto, EmployeeRefDto},
 };
 
 #[derive(Clone)]
@@ -16,11 +17,21 @@ impl EmployeeState {
         Self { pool }
     }
 
+    /// All employees including the archived ones, selection lists only offer the active ones.
     pub async fn get_employee_list(&self) -> ServiceResult<Vec<EmployeeDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
         Ok(sqlx::query_as::<_, EmployeeRow>(
             r#"
-            SELECT e.id, e.name FROM employee e;
+            SELECT
+                e.id,
+                e.name,
+                e.phone,
+                e.email,
+                e.color,
+                e.employed_from,
+                e.employed_until,
+                e.active
+            FROM employee e;
         "#,
         )
         .fetch_all(connection.as_mut())
@@ -34,7 +45,16 @@ impl EmployeeState {
         let mut connection = self.pool.acquire().await.unwrap();
         Ok(sqlx::query_as::<_, EmployeeRow>(
             r#"
-            SELECT e.id, e.name FROM employee e
+            SELECT
+                e.id,
+                e.name,
+                e.phone,
+                e.email,
+                e.color,
+                e.employed_from,
+                e.employed_until,
+                e.active
+            FROM employee e
             WHERE e.id = $1;
         "#,
         )
@@ -45,13 +65,39 @@ impl EmployeeState {
     }
 
     pub async fn store_employee(&self, employee: EditEmployeeDto) -> ServiceResult<i64> {
+        if employee.name.trim().is_empty() {
+            return Err(ServiceError::BadRequest(
+                "employee must have a name".to_string(),
+            ));
+        }
+        if !employee.color.is_empty() && !is_color(&employee.color) {
+            return Err(ServiceError::BadRequest(format!(
+                "calendar colour {} is not of the form #rrggbb",
+                employee.color
+            )));
+        }
+        if let (Some(from), Some(until)) = (employee.employed_from, employee.employed_until)
+            && until < from
+        {
+            return Err(ServiceError::BadRequest(
+                "employment must not end before it starts".to_string(),
+            ));
+        }
+
         let mut connection = self.pool.acquire().await.unwrap();
 
         let q = if let Some(employee_id) = employee.id {
             sqlx::query(
                 r#"
                 UPDATE employee
-                SET name = $2
+                SET
+                    name = $2,
+                    phone = $3,
+                    email = $4,
+                    color = $5,
+                    employed_from = $6,
+                    employed_until = $7,
+                    active = $8
                 WHERE id = $1;
             "#,
             )
@@ -59,13 +105,29 @@ impl EmployeeState {
         } else {
             sqlx::query(
                 r#"
-                INSERT INTO employee (name)
-                VALUES ($1);
+                INSERT INTO employee (
+                    name,
+                    phone,
+                    email,
+                    color,
+                    employed_from,
+                    employed_until,
+                    active
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7);
             "#,
             )
         };
 
-        q.bind(&employee.name).execute(connection.as_mut()).await?;
+        q.bind(employee.name.trim())
+            .bind(&employee.phone)
+            .bind(&employee.email)
+            .bind(employee.color.to_lowercase())
+            .bind(employee.employed_from)
+            .bind(employee.employed_until)
+            .bind(employee.active)
+            .execute(connection.as_mut())
+            .await?;
 
         if let Some(employee_id) = employee.id {
             return Ok(employee_id);
@@ -81,32 +143,77 @@ impl EmployeeState {
         .id)
     }
 
+    /// Deletes an employee without appointments. Employees with appointments are archived
+    /// instead, so that the appointments keep showing who performed them.
     pub async fn delete_employee(&self, id: i64) -> ServiceResult<()> {
-        let mut connection = self.pool.acquire().await.unwrap();
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
 
-        sqlx::query(
+        let has_history = sqlx::query_as::<_, CountRow>(
             r#"
+            SELECT COUNT(*) AS count
+            FROM appointment a
+            WHERE a.employee_id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .count
+            > 0;
+
+        let q = if has_history {
+            sqlx::query(
+                r#"
+                UPDATE employee
+                SET active = FALSE
+                WHERE id = $1;
+            "#,
+            )
+        } else {
+            sqlx::query(
+                r#"
                 DELETE FROM employee
                 WHERE id = $1;
             "#,
-        )
-        .bind(id)
-        .execute(connection.as_mut())
-        .await?;
+            )
+        };
+        q.bind(id).execute(transaction.as_mut()).await?;
 
+        transaction.commit().await?;
         Ok(())
     }
 }
 
+/// Whether the date lies within the employment, open ends are unbounded.
+pub fn is_employed_on(employee: &EmployeeDto, date: NaiveDate) -> bool {
+    employee.employed_from.is_none_or(|from| from <= date)
+        && employee.employed_until.is_none_or(|until| date <= until)
+}
+
+fn is_color(value: &str) -> bool {
+    value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
 #[derive(sqlx::FromRow)]
 struct IdRow {
     pub id: i64,
 }
 
+#[derive(sqlx::FromRow)]
+struct CountRow {
+    pub count: i64,
+}
+
 #[derive(sqlx::FromRow)]
 struct EmployeeRow {
     pub id: i64,
     pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub color: String,
+    pub employed_from: Option<NaiveDate>,
+    pub employed_until: Option<NaiveDate>,
+    pub active: bool,
 }
 
 impl From<EmployeeRow> for EmployeeDto {
@@ -114,6 +221,165 @@ impl From<EmployeeRow> for EmployeeDto {
         EmployeeDto {
             id: row.id,
             name: row.name,
+            phone: row.phone,
+            email: row.email,
+            color: row.color,
+            employed_from: row.employed_from,
+            employed_until: row.employed_until,
+            active: row.active,
         }
     }
 }
+
+impl From<&EmployeeDto> for EmployeeRefDto {
+    fn from(employee: &EmployeeDto) -> EmployeeRefDto {
+        EmployeeRefDto {
+            id: employee.id,
+            name: employee.name.clone(),
+            active: employee.active,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::EditCustomerAppointmentDto;
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn employee(name: &str) -> EditEmployeeDto {
+        EditEmployeeDto {
+            id: None,
+            name: name.to_string(),
+            phone: String::new(),
+            email: String::new(),
+            color: String::new(),
+            employed_from: None,
+            employed_until: None,
+            active: true,
+        }
+    }
+
+    async fn employee_names(state: &State) -> Vec<(String, bool)> {
+        state
+            .employee
+            .get_employee_list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|employee| (employee.name, employee.active))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn employees_with_appointments_are_archived() {
+        let state = state().await;
+        let eva = state
+            .employee
+            .store_employee(employee("Eva"))
+            .await
+            .unwrap();
+        let max = state
+            .employee
+            .store_employee(employee("Max"))
+            .await
+            .unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let appointment_id = state
+            .appointment
+            .store_appointment(EditCustomerAppointmentDto {
+                employee_id: Some(eva),
+                ..appointment(
+                    customer_id,
+                    date("2025-03-04").and_hms_opt(10, 0, 0).unwrap(),
+                    vec![item("Massage", 1, 5000)],
+                )
+            })
+            .await
+            .unwrap();
+
+        state.employee.delete_employee(eva).await.unwrap();
+        state.employee.delete_employee(max).await.unwrap();
+
+        assert_eq!(
+            employee_names(&state).await,
+            vec![("Eva".to_string(), false)]
+        );
+        assert!(
+            state
+                .employee
+                .get_employee_by_id(max)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let performed_by = state
+            .appointment
+            .get_appointment_by_id(appointment_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .employee
+            .unwrap();
+        assert_eq!(
+            (
+                performed_by.id,
+                performed_by.name.as_str(),
+                performed_by.active
+            ),
+            (eva, "Eva", false)
+        );
+
+        // Once the appointments are gone, the archived employee can be deleted.
+        state
+            .appointment
+            .delete_appointment(appointment_id)
+            .await
+            .unwrap();
+        state.employee.delete_employee(eva).await.unwrap();
+        assert!(employee_names(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn employments_must_not_end_before_they_start() {
+        let state = state().await;
+        let result = state
+            .employee
+            .store_employee(EditEmployeeDto {
+                employed_from: Some(date("2025-03-01")),
+                employed_until: Some(date("2025-02-28")),
+                ..employee("Eva")
+            })
+            .await;
+        assert!(matches!(result, Err(ServiceError::BadRequest(_))));
+
+        let id = state
+            .employee
+            .store_employee(EditEmployeeDto {
+                employed_from: Some(date("2025-03-01")),
+                employed_until: Some(date("2025-03-31")),
+                ..employee("Eva")
+            })
+            .await
+            .unwrap();
+        let stored = state
+            .employee
+            .get_employee_by_id(id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!is_employed_on(&stored, date("2025-02-28")));
+        assert!(is_employed_on(&stored, date("2025-03-01")));
+        assert!(is_employed_on(&stored, date("2025-03-31")));
+        assert!(!is_employed_on(&stored, date("2025-04-01")));
+    }
+}
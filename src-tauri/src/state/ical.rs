@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    error::ServiceResult,
+    ical::{self, IcalCalendarEvent, IcalEvent, IcalTime},
+    model::{
+        AppointmentFilterDto, CustomerDto, CustomerMatchKind, IcalCustomerCandidateDto,
+        IcalImportEventDto, IcalImportPreviewDto, IcalSkipReason, IcalSkippedEventDto,
+    },
+    state::{appointment::AppointmentState, customer::CustomerState, preference::PreferenceState},
+    time_zone,
+};
+
+/// Phone numbers with fewer digits are too unspecific to identify a customer.
+const MIN_PHONE_DIGITS: usize = 6;
+
+/// Preference that allows to put the customer name into exported events.
+const INCLUDE_CUSTOMER_NAME_KEY: &str = "ical.include-customer-name";
+
+#[derive(Clone)]
+pub struct IcalState {
+    appointment: AppointmentState,
+    customer: CustomerState,
+    preference: PreferenceState,
+}
+
+impl IcalState {
+    pub fn new(
+        appointment: AppointmentState,
+        customer: CustomerState,
+        preference: PreferenceState,
+    ) -> Self {
+        Self {
+            appointment,
+            customer,
+            preference,
+        }
+    }
+
+    /// Writes all appointments matching the filter to an iCalendar file and returns the number
+    /// of exported events.
+    pub async fn export_appointments(
+        &self,
+        filter: AppointmentFilterDto,
+        path: &Path,
+    ) -> ServiceResult<i64> {
+        let include_customer_name = self
+            .preference
+            .get_preference::<bool>(INCLUDE_CUSTOMER_NAME_KEY)
+            .await?
+            .unwrap_or(false);
+
+        let customer_names: HashMap<i64, String> = if include_customer_name {
+            self.customer
+                .get_customer_list()
+                .await?
+                .into_iter()
+                .map(|customer| {
+                    let name = [customer.title, customer.first_name, customer.last_name]
+                        .iter()
+                        .map(|part| part.trim())
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    (customer.id, name)
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let host = self.preference.get_ical_host().await?;
+        let events: Vec<IcalEvent> = self
+            .appointment
+            .get_appointment_list_by_filter(&filter)
+            .await?
+            .into_iter()
+            .map(|appointment| IcalEvent {
+                uid: ical::appointment_uid(appointment.id, &host),
+                start: appointment.start_utc,
+                end: appointment.end_utc,
+                summary: appointment.treatment,
+                description: customer_names.get(&appointment.customer_id).cloned(),
+            })
+            .collect();
+
+        tokio::fs::write(path, ical::write_calendar(&events)).await?;
+        Ok(events.len() as i64)
+    }
+
+    /// Reads a calendar file and proposes an appointment for every importable event. Nothing is
+    /// stored, the reviewed proposals are imported with `import_appointments`.
+    pub async fn preview_import(&self, path: &Path) -> ServiceResult<IcalImportPreviewDto> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let customers = self.customer.get_customer_list().await?;
+        let zone = self.preference.get_time_zone().await?;
+
+        let mut preview = IcalImportPreviewDto {
+            events: Vec::new(),
+            skipped: Vec::new(),
+        };
+
+        for event in ical::parse_calendar(&content, zone)? {
+            let start = match (&event.start, event.recurring, event.cancelled) {
+                (_, _, true) => Err(IcalSkipReason::Cancelled),
+                (_, true, _) => Err(IcalSkipReason::Recurring),
+                (None, _, _) => Err(IcalSkipReason::MissingStart),
+                (Some(IcalTime::Date(_)), _, _) => Err(IcalSkipReason::AllDay),
+                (Some(IcalTime::DateTime(start)), _, _) => Ok(*start),
+            };
+
+            let start = match start {
+                Ok(start) => start,
+                Err(reason) => {
+                    preview.skipped.push(IcalSkippedEventDto {
+                        uid: event.uid,
+                        summary: event.summary,
+                        reason,
+                    });
+                    continue;
+                }
+            };
+
+            let duration_minutes = match (&event.end, event.duration_minutes) {
+                (Some(IcalTime::DateTime(end)), _) => (*end - start).num_minutes(),
+                (_, Some(duration_minutes)) => duration_minutes,
+                _ => 0,
+            }
+            .max(0);
+
+            let existing = match &event.uid {
+                Some(uid) => self.appointment.get_appointment_by_ical_uid(uid).await?,
+                None => None,
+            };
+
+            let (appointment_id, customer_id, candidates) = match existing {
+                Some(appointment) => (Some(appointment.id), Some(appointment.customer_id), vec![]),
+                None => {
+                    let candidates = match_customers(&event, &customers);
+                    let customer_id = match candidates.as_slice() {
+                        [candidate] => Some(candidate.customer_id),
+                        _ => None,
+                    };
+                    (None, customer_id, candidates)
+                }
+            };
+
+            preview.events.push(IcalImportEventDto {
+                uid: event.uid,
+                summary: event.summary,
+                description: event.description,
+                start_date: time_zone::to_local_time(zone, start),
+                duration_minutes,
+                appointment_id,
+                customer_id,
+                candidates,
+            });
+        }
+
+        preview.events.sort_by_key(|event| event.start_date);
+        Ok(preview)
+    }
+}
+
+/// Finds customers mentioned by an event. The e-mail addresses of attendees are preferred over
+/// matching phone numbers, which are preferred over names, as names are far more likely to be
+/// shared by several customers.
+fn match_customers(
+    event: &IcalCalendarEvent,
+    customers: &[CustomerDto],
+) -> Vec<IcalCustomerCandidateDto> {
+    let emails: Vec<String> = event
+        .attendees
+        .iter()
+        .map(|attendee| attendee.email.trim().to_lowercase())
+        .filter(|email| email.contains('@'))
+        .collect();
+    let email_matches: Vec<&CustomerDto> = customers
+        .iter()
+        .filter(|customer| emails.contains(&customer.email.trim().to_lowercase()))
+        .collect();
+
+    if !email_matches.is_empty() {
+        return email_matches
+            .into_iter()
+            .map(|customer| candidate(customer, CustomerMatchKind::Email))
+            .collect();
+    }
+
+    let mut texts = vec![event.summary.as_str(), event.description.as_str()];
+    texts.extend(
+        event
+            .attendees
+            .iter()
+            .map(|attendee| attendee.name.as_str()),
+    );
+    let text = normalize_text(&texts.join(" "));
+
+    let phones: Vec<String> = texts
+        .iter()
+        .flat_map(|text| {
+            text.split(|c: char| !(c.is_ascii_digit() || " +/()-".contains(c)))
+                .map(normalize_phone)
+                .collect::<Vec<_>>()
+        })
+        .filter(|phone| phone.len() >= MIN_PHONE_DIGITS)
+        .collect();
+
+    let phone_matches: Vec<&CustomerDto> = customers
+        .iter()
+        .filter(|customer| {
+            [&customer.phone, &customer.mobile_phone]
+                .iter()
+                .map(|phone| normalize_phone(phone))
+                .filter(|phone| phone.len() >= MIN_PHONE_DIGITS)
+                .any(|phone| {
+                    phones
+                        .iter()
+                        .any(|other| phone.ends_with(other) || other.ends_with(&phone))
+                })
+        })
+        .collect();
+
+    if !phone_matches.is_empty() {
+        return phone_matches
+            .into_iter()
+            .map(|customer| candidate(customer, CustomerMatchKind::Phone))
+            .collect();
+    }
+
+    customers
+        .iter()
+        .filter(|customer| {
+            let first_name = normalize_text(&customer.first_name);
+            let last_name = normalize_text(&customer.last_name);
+            if first_name.is_empty() || last_name.is_empty() {
+                return false;
+            }
+
+            [
+                format!("{} {}", first_name, last_name),
+                format!("{} {}", last_name, first_name),
+                format!("{}, {}", last_name, first_name),
+            ]
+            .iter()
+            .any(|name| contains_words(&text, name))
+        })
+        .map(|customer| candidate(customer, CustomerMatchKind::Name))
+        .collect()
+}
+
+fn candidate(customer: &CustomerDto, match_kind: CustomerMatchKind) -> IcalCustomerCandidateDto {
+    IcalCustomerCandidateDto {
+        customer_id: customer.id,
+        first_name: customer.first_name.clone(),
+        last_name: customer.last_name.clone(),
+        phone: customer.phone.clone(),
+        mobile_phone: customer.mobile_phone.clone(),
+        match_kind,
+    }
+}
+
+/// Digits only, without leading zeros, so national and international notation share a suffix.
+fn normalize_phone(phone: &str) -> String {
+    phone
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .trim_start_matches('0')
+        .to_string()
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn contains_words(text: &str, words: &str) -> bool {
+    text.match_indices(words).any(|(index, _)| {
+        let before = text[..index].chars().next_back();
+        let after = text[index + words.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{EditCustomerAppointmentDto, IcalImportAppointmentDto};
+    use crate::state::testing::{appointment, customer, item, state, temp_dir};
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:event-1\r
+DTSTART:20240301T090000Z\r
+DTEND:20240301T100000Z\r
+SUMMARY:Massage Anna Berg 0171 1234567\r
+ATTENDEE;CN=Tom Berg:mailto:Tom.Berg@example.com\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[tokio::test]
+    async fn attendee_emails_are_preferred() {
+        let state = state().await;
+
+        let mut anna = customer("Anna", "Berg");
+        anna.mobile_phone = "0171 1234567".to_string();
+        state.customer.store_customer(anna).await.unwrap();
+        let mut tom = customer("Tom", "Berg");
+        tom.email = "tom.berg@example.com".to_string();
+        let tom_id = state.customer.store_customer(tom).await.unwrap();
+
+        let path = temp_dir().join("calendar.ics");
+        std::fs::write(&path, CALENDAR).unwrap();
+        let preview = state.ical.preview_import(&path).await.unwrap();
+
+        let event = &preview.events[0];
+        assert_eq!(event.customer_id, Some(tom_id));
+        assert_eq!(event.candidates.len(), 1);
+        assert!(event.candidates[0].match_kind == CustomerMatchKind::Email);
+    }
+
+    #[tokio::test]
+    async fn reimported_uids_are_not_duplicated() {
+        let state = state().await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let start_date = "2024-03-01T10:00:00".parse().unwrap();
+        let id = state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                start_date,
+                vec![item("Massage", 1, 5000)],
+            ))
+            .await
+            .unwrap();
+
+        let path = temp_dir().join("calendar.ics");
+        let filter = AppointmentFilterDto {
+            customer_id: None,
+            employee_id: None,
+            resource_id: None,
+            from: None,
+            to: None,
+        };
+        state.ical.export_appointments(filter, &path).await.unwrap();
+        let calendar = std::fs::read_to_string(&path).unwrap();
+        let host = state.preference.get_ical_host().await.unwrap();
+        let uid = format!("appointment-{}@{}", id, host);
+        assert!(calendar.contains(&format!("UID:{}\r\n", uid)));
+        // Berlin is an hour ahead of UTC in March.
+        assert!(calendar.contains("DTSTART:20240301T090000Z\r\n"));
+
+        let preview = state.ical.preview_import(&path).await.unwrap();
+        assert_eq!(preview.events[0].appointment_id, Some(id));
+
+        // Uids with the same id from another installation are foreign.
+        let foreign = temp_dir().join("foreign.ics");
+        std::fs::write(&foreign, calendar.replace(&host, "other.customer-manager")).unwrap();
+        let preview = state.ical.preview_import(&foreign).await.unwrap();
+        assert_eq!(preview.events[0].appointment_id, None);
+
+        state.appointment.delete_appointment(id).await.unwrap();
+        let preview = state.ical.preview_import(&path).await.unwrap();
+        assert_eq!(preview.events[0].appointment_id, None);
+        let import = |id| IcalImportAppointmentDto {
+            ical_uid: Some(uid.clone()),
+            appointment: EditCustomerAppointmentDto {
+                id,
+                ..appointment(customer_id, start_date, vec![item("Massage", 1, 5000)])
+            },
+        };
+        let imported = state
+            .appointment
+            .import_appointments(vec![import(None)])
+            .await
+            .unwrap();
+
+        let preview = state.ical.preview_import(&path).await.unwrap();
+        assert_eq!(preview.events[0].appointment_id, Some(imported[0]));
+        state
+            .appointment
+            .import_appointments(vec![import(Some(imported[0]))])
+            .await
+            .unwrap();
+        let appointments = state.appointment.get_appointment_list(customer_id).await;
+        assert_eq!(appointments.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,1774 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    e_invoice::{self, Contact, EInvoice, Party},
+    error::{ServiceError, ServiceResult},
+    model::{
+        Currency, EInvoiceSyntax, EditInvoiceDto, EditInvoiceItemDto, InvoiceDto, InvoiceItemDto,
+        InvoiceKind, InvoiceStatus,
+    },
+    money,
+    pdf::{self, Align, Font, PdfAttachment, PdfPage},
+    state::{appointment, preference},
+    time_zone,
+};
+
+const LANGUAGE_KEY: &str = "general.language";
+
+/// Patterns for invoice numbers, see [`format_number`].
+const NUMBER_PATTERN_KEY: &str = "invoice.number-pattern";
+const CREDIT_NOTE_PATTERN_KEY: &str = "invoice.credit-note-pattern";
+const DEFAULT_NUMBER_PATTERN: &str = "{YYYY}-{NNNN}";
+const DEFAULT_CREDIT_NOTE_PATTERN: &str = "G{YYYY}-{NNNN}";
+
+/// Days between issue and due date if the draft does not specify a due date.
+const PAYMENT_DAYS_KEY: &str = "invoice.payment-days";
+const DEFAULT_PAYMENT_DAYS: i64 = 14;
+
+/// Letterhead of the practice printed on every invoice.
+const PRACTICE_NAME_KEY: &str = "invoice.practice-name";
+const PRACTICE_ADDRESS_KEY: &str = "invoice.practice-address";
+const PRACTICE_CONTACT_KEY: &str = "invoice.practice-contact";
+const TAX_NUMBER_KEY: &str = "invoice.tax-number";
+const BANK_DETAILS_KEY: &str = "invoice.bank-details";
+const FOOTER_KEY: &str = "invoice.footer";
+
+/// Structured seller data of electronic invoices.
+const STREET_KEY: &str = "invoice.street";
+const POSTCODE_KEY: &str = "invoice.postcode";
+const CITY_KEY: &str = "invoice.city";
+const COUNTRY_KEY: &str = "invoice.country";
+const DEFAULT_COUNTRY: &str = "DE";
+const VAT_ID_KEY: &str = "invoice.vat-id";
+const CONTACT_NAME_KEY: &str = "invoice.contact-name";
+const CONTACT_PHONE_KEY: &str = "invoice.contact-phone";
+const CONTACT_EMAIL_KEY: &str = "invoice.contact-email";
+const IBAN_KEY: &str = "invoice.iban";
+const BIC_KEY: &str = "invoice.bic";
+
+#[derive(Clone)]
+pub struct InvoiceState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl InvoiceState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_invoice_list(
+        &self,
+        customer_id: Option<i64>,
+    ) -> ServiceResult<Vec<InvoiceDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_invoice_list(connection.as_mut(), customer_id, None).await
+    }
+
+    pub async fn get_invoice_by_id(&self, id: i64) -> ServiceResult<Option<InvoiceDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_invoice_list(connection.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Creates a draft with the items of the appointments and the current address of their
+    /// customer. Appointments that are part of another invoice which is not cancelled are
+    /// rejected.
+    pub async fn create_invoice(&self, appointment_ids: Vec<i64>) -> ServiceResult<i64> {
+        if appointment_ids.is_empty() {
+            return Err(ServiceError::BadRequest(
+                "invoice needs at least one appointment".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let invoiced = sqlx::query_as::<_, InvoicedAppointmentRow>(
+            r#"
+            SELECT i.appointment_id, inv.number
+            FROM invoice_item i
+            JOIN invoice inv ON inv.id = i.invoice_id
+            WHERE inv.kind = 'invoice'
+                AND inv.status <> 'cancelled'
+                AND i.appointment_id IN (SELECT value FROM json_each($1))
+            LIMIT 1;
+        "#,
+        )
+        .bind(serde_json::to_string(&appointment_ids)?)
+        .fetch_optional(transaction.as_mut())
+        .await?;
+        if let Some(invoiced) = invoiced {
+            return Err(ServiceError::Conflict(format!(
+                "appointment {} is already part of invoice {}",
+                invoiced.appointment_id,
+                invoiced.number.as_deref().unwrap_or("(draft)")
+            )));
+        }
+
+        let mut appointments = Vec::with_capacity(appointment_ids.len());
+        for &appointment_id in &appointment_ids {
+            let Some(appointment) =
+                appointment::get_edit_appointment(transaction.as_mut(), appointment_id).await?
+            else {
+                return Err(ServiceError::BadRequest(format!(
+                    "appointment {} does not exist",
+                    appointment_id
+                )));
+            };
+            appointments.push(appointment);
+        }
+        appointments.sort_by_key(|appointment| appointment.start_date);
+
+        let customer_id = appointments[0].customer_id;
+        if appointments
+            .iter()
+            .any(|appointment| appointment.customer_id != customer_id)
+        {
+            return Err(ServiceError::BadRequest(
+                "all appointments of an invoice must belong to the same customer".to_string(),
+            ));
+        }
+
+        let customer = sqlx::query_as::<_, CustomerAddressRow>(
+            r#"
+            SELECT c.title, c.first_name, c.last_name, c.address_street, c.address_postcode,
+                c.address_city
+            FROM customer c
+            WHERE c.id = $1;
+        "#,
+        )
+        .bind(customer_id)
+        .fetch_one(transaction.as_mut())
+        .await?;
+
+        let invoice = EditInvoiceDto {
+            id: None,
+            customer_id: Some(customer_id),
+            customer_name: [customer.title, customer.first_name, customer.last_name]
+                .iter()
+                .map(|part| part.trim())
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            address_street: customer.address_street,
+            address_postcode: customer.address_postcode,
+            address_city: customer.address_city,
+            buyer_reference: String::new(),
+            issue_date: None,
+            due_date: None,
+            note: String::new(),
+            items: appointments
+                .iter()
+                .flat_map(|appointment| {
+                    appointment.items.iter().map(|item| EditInvoiceItemDto {
+                        appointment_id: appointment.id,
+                        date: Some(appointment.start_date.date()),
+                        description: item.description.clone(),
+                        quantity: item.quantity,
+                        unit_price: item.unit_price,
+                        discount: item.discount,
+                        vat_rate: item.vat_rate,
+                    })
+                })
+                .collect(),
+        };
+
+        let invoice_id =
+            store_draft(transaction.as_mut(), &invoice, InvoiceKind::Invoice, None).await?;
+
+        transaction.commit().await?;
+        Ok(invoice_id)
+    }
+
+    pub async fn store_invoice(&self, invoice: EditInvoiceDto) -> ServiceResult<i64> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(invoice_id) = invoice.id {
+            check_draft(transaction.as_mut(), invoice_id).await?;
+        }
+        let invoice_id =
+            store_draft(transaction.as_mut(), &invoice, InvoiceKind::Invoice, None).await?;
+
+        transaction.commit().await?;
+        Ok(invoice_id)
+    }
+
+    /// Only drafts can be deleted, issued invoices have to be cancelled.
+    pub async fn delete_invoice(&self, id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        check_draft(transaction.as_mut(), id).await?;
+        sqlx::query(
+            r#"
+                DELETE FROM invoice
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Assigns the next number and the dates. From now on the invoice cannot be changed.
+    pub async fn issue_invoice(&self, id: i64) -> ServiceResult<InvoiceDto> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        issue(transaction.as_mut(), id).await?;
+        transaction.commit().await?;
+
+        self.get_invoice_by_id(id)
+            .await?
+            .ok_or_else(|| ServiceError::BadRequest(format!("invoice {} does not exist", id)))
+    }
+
+    /// Cancels an issued invoice by issuing a credit note with the negated items. Returns the id
+    /// of the credit note.
+    pub async fn cancel_invoice(&self, id: i64) -> ServiceResult<i64> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let Some(invoice) = get_invoice_list(transaction.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Err(ServiceError::BadRequest(format!(
+                "invoice {} does not exist",
+                id
+            )));
+        };
+        if invoice.kind != InvoiceKind::Invoice || invoice.status != InvoiceStatus::Issued {
+            return Err(ServiceError::Conflict(
+                "only issued invoices can be cancelled".to_string(),
+            ));
+        }
+
+        let today =
+            time_zone::now_local(preference::get_time_zone(transaction.as_mut()).await?).date();
+        let credit_note = EditInvoiceDto {
+            id: None,
+            customer_id: invoice.customer_id,
+            customer_name: invoice.customer_name,
+            address_street: invoice.address_street,
+            address_postcode: invoice.address_postcode,
+            address_city: invoice.address_city,
+            buyer_reference: invoice.buyer_reference,
+            issue_date: Some(today),
+            due_date: Some(today),
+            note: String::new(),
+            items: invoice
+                .items
+                .into_iter()
+                .map(|item| EditInvoiceItemDto {
+                    appointment_id: item.appointment_id,
+                    date: item.date,
+                    description: item.description,
+                    quantity: -item.quantity,
+                    unit_price: item.unit_price,
+                    discount: -item.discount,
+                    vat_rate: item.vat_rate,
+                })
+                .collect(),
+        };
+        let credit_note_id = store_draft(
+            transaction.as_mut(),
+            &credit_note,
+            InvoiceKind::CreditNote,
+            Some(id),
+        )
+        .await?;
+        issue(transaction.as_mut(), credit_note_id).await?;
+
+        sqlx::query(
+            r#"
+                UPDATE invoice
+                SET status = 'cancelled'
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        Ok(credit_note_id)
+    }
+
+    /// Writes the invoice as PDF/A-3. With `zugferd` the CII document of an issued invoice is
+    /// embedded, which makes the file a ZUGFeRD invoice in the XRechnung profile.
+    pub async fn export_invoice_pdf(
+        &self,
+        id: i64,
+        zugferd: bool,
+        path: &Path,
+    ) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let invoice = get_existing_invoice(connection.as_mut(), id).await?;
+        let cancelled_number = get_cancelled_number(connection.as_mut(), &invoice).await?;
+        let letterhead = Letterhead::load(connection.as_mut()).await?;
+        let german = preference::get_preference::<String>(connection.as_mut(), LANGUAGE_KEY)
+            .await?
+            .is_some_and(|language| language == "de");
+
+        let xml = if zugferd {
+            let e_invoice = load_e_invoice(
+                connection.as_mut(),
+                &invoice,
+                cancelled_number.as_deref(),
+                german,
+            )
+            .await?;
+            Some(e_invoice::cii(&e_invoice))
+        } else {
+            None
+        };
+        let metadata = e_invoice::zugferd_metadata();
+        let attachment = xml.as_ref().map(|xml| PdfAttachment {
+            file_name: e_invoice::ZUGFERD_FILE_NAME,
+            description: "XRechnung",
+            mime_type: "text/xml",
+            data: xml.as_bytes(),
+            metadata: &metadata,
+        });
+
+        let document = render_invoice(
+            &invoice,
+            cancelled_number.as_deref(),
+            &letterhead,
+            german,
+            attachment.as_ref(),
+        );
+        tokio::fs::write(path, document).await?;
+        Ok(())
+    }
+
+    /// Writes an issued invoice or credit note as XRechnung.
+    pub async fn export_invoice_xml(
+        &self,
+        id: i64,
+        syntax: EInvoiceSyntax,
+        path: &Path,
+    ) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let invoice = get_existing_invoice(connection.as_mut(), id).await?;
+        let cancelled_number = get_cancelled_number(connection.as_mut(), &invoice).await?;
+        let german = preference::get_preference::<String>(connection.as_mut(), LANGUAGE_KEY)
+            .await?
+            .is_some_and(|language| language == "de");
+
+        let e_invoice = load_e_invoice(
+            connection.as_mut(),
+            &invoice,
+            cancelled_number.as_deref(),
+            german,
+        )
+        .await?;
+        let xml = match syntax {
+            EInvoiceSyntax::Ubl => e_invoice::ubl(&e_invoice),
+            EInvoiceSyntax::Cii => e_invoice::cii(&e_invoice),
+        };
+        tokio::fs::write(path, xml).await?;
+        Ok(())
+    }
+}
+
+async fn get_existing_invoice(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> ServiceResult<InvoiceDto> {
+    get_invoice_list(connection, None, Some(id))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ServiceError::BadRequest(format!("invoice {} does not exist", id)))
+}
+
+/// Number of the invoice a credit note cancels.
+async fn get_cancelled_number(
+    connection: &mut SqliteConnection,
+    invoice: &InvoiceDto,
+) -> ServiceResult<Option<String>> {
+    Ok(match invoice.cancelled_invoice_id {
+        Some(cancelled_id) => get_invoice_list(connection, None, Some(cancelled_id))
+            .await?
+            .into_iter()
+            .next()
+            .and_then(|cancelled| cancelled.number),
+        None => None,
+    })
+}
+
+/// Collects the data of an electronic invoice. XRechnung requires structured addresses and the
+/// contact of the seller, missing preferences are reported together.
+async fn load_e_invoice<'a>(
+    connection: &mut SqliteConnection,
+    invoice: &'a InvoiceDto,
+    cancelled_number: Option<&'a str>,
+    german: bool,
+) -> ServiceResult<EInvoice<'a>> {
+    let (InvoiceStatus::Issued | InvoiceStatus::Cancelled, Some(number), Some(issue_date)) = (
+        invoice.status,
+        invoice.number.as_deref(),
+        invoice.issue_date,
+    ) else {
+        return Err(ServiceError::BadRequest(
+            "only issued invoices can be exported as e-invoice".to_string(),
+        ));
+    };
+
+    let mut values = HashMap::new();
+    for key in [
+        PRACTICE_NAME_KEY,
+        STREET_KEY,
+        POSTCODE_KEY,
+        CITY_KEY,
+        COUNTRY_KEY,
+        TAX_NUMBER_KEY,
+        VAT_ID_KEY,
+        CONTACT_NAME_KEY,
+        CONTACT_PHONE_KEY,
+        CONTACT_EMAIL_KEY,
+        IBAN_KEY,
+        BIC_KEY,
+    ] {
+        let value = preference::get_preference::<String>(&mut *connection, key)
+            .await?
+            .unwrap_or_default();
+        values.insert(key, value.trim().to_string());
+    }
+    let mut missing: Vec<&str> = [
+        PRACTICE_NAME_KEY,
+        STREET_KEY,
+        POSTCODE_KEY,
+        CITY_KEY,
+        CONTACT_NAME_KEY,
+        CONTACT_PHONE_KEY,
+        CONTACT_EMAIL_KEY,
+    ]
+    .into_iter()
+    .filter(|key| values[key].is_empty())
+    .collect();
+    if values[TAX_NUMBER_KEY].is_empty() && values[VAT_ID_KEY].is_empty() {
+        missing.push(VAT_ID_KEY);
+    }
+    if !missing.is_empty() {
+        return Err(ServiceError::BadRequest(format!(
+            "e-invoices need the preferences {}",
+            missing.join(", ")
+        )));
+    }
+    let mut value = |key| values.remove(key).unwrap_or_default();
+    let country = Some(value(COUNTRY_KEY))
+        .filter(|country| !country.is_empty())
+        .unwrap_or_else(|| DEFAULT_COUNTRY.to_string())
+        .to_uppercase();
+    let contact_email = value(CONTACT_EMAIL_KEY);
+
+    let email = sqlx::query_as::<_, EmailRow>(
+        r#"
+        SELECT c.email
+        FROM customer c
+        WHERE c.id = $1;
+    "#,
+    )
+    .bind(invoice.customer_id)
+    .fetch_optional(&mut *connection)
+    .await?
+    .map(|row| row.email.trim().to_string())
+    .filter(|email| !email.is_empty())
+    .ok_or_else(|| {
+        ServiceError::BadRequest("e-invoices need the email address of the customer".to_string())
+    })?;
+    if invoice.address_postcode.trim().is_empty() || invoice.address_city.trim().is_empty() {
+        return Err(ServiceError::BadRequest(
+            "e-invoices need the postcode and the city of the customer".to_string(),
+        ));
+    }
+
+    let format_date = |date: NaiveDate| {
+        if german {
+            date.format("%d.%m.%Y").to_string()
+        } else {
+            date.format("%Y-%m-%d").to_string()
+        }
+    };
+    let payment_terms = match (invoice.kind, cancelled_number, invoice.due_date) {
+        (InvoiceKind::CreditNote, Some(cancelled_number), _) if german => {
+            format!("Gutschrift zur Rechnung {}", cancelled_number)
+        }
+        (InvoiceKind::CreditNote, Some(cancelled_number), _) => {
+            format!("Credit note for invoice {}", cancelled_number)
+        }
+        (InvoiceKind::Invoice, _, Some(due_date)) if german => {
+            format!("Zahlbar bis {}", format_date(due_date))
+        }
+        (InvoiceKind::Invoice, _, Some(due_date)) => {
+            format!("Payable until {}", format_date(due_date))
+        }
+        _ if german => "Zahlbar sofort".to_string(),
+        _ => "Payable immediately".to_string(),
+    };
+
+    Ok(EInvoice {
+        invoice,
+        number,
+        issue_date,
+        due_date: invoice.due_date,
+        preceding_number: cancelled_number,
+        // Required by XRechnung, the number is used if the buyer has no reference.
+        buyer_reference: if invoice.buyer_reference.trim().is_empty() {
+            number
+        } else {
+            invoice.buyer_reference.trim()
+        },
+        seller: Party {
+            name: value(PRACTICE_NAME_KEY),
+            street: value(STREET_KEY),
+            postcode: value(POSTCODE_KEY),
+            city: value(CITY_KEY),
+            country: country.clone(),
+            email: contact_email.clone(),
+            vat_id: value(VAT_ID_KEY),
+            tax_number: value(TAX_NUMBER_KEY),
+        },
+        seller_contact: Contact {
+            name: value(CONTACT_NAME_KEY),
+            phone: value(CONTACT_PHONE_KEY),
+            email: contact_email,
+        },
+        buyer: Party {
+            name: invoice.customer_name.trim().to_string(),
+            street: invoice.address_street.trim().to_string(),
+            postcode: invoice.address_postcode.trim().to_string(),
+            city: invoice.address_city.trim().to_string(),
+            country,
+            email,
+            vat_id: String::new(),
+            tax_number: String::new(),
+        },
+        iban: value(IBAN_KEY).replace(' ', ""),
+        bic: value(BIC_KEY),
+        payment_terms,
+    })
+}
+
+/// Invoices with their items and totals, optionally restricted to a customer or a single id.
+pub async fn get_invoice_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+    id: Option<i64>,
+) -> ServiceResult<Vec<InvoiceDto>> {
+    let rows = sqlx::query_as::<_, InvoiceRow>(
+        r#"
+        SELECT
+            inv.id,
+            inv.kind,
+            inv.status,
+            inv.number,
+            inv.customer_id,
+            inv.customer_name,
+            inv.address_street,
+            inv.address_postcode,
+            inv.address_city,
+            inv.buyer_reference,
+            inv.issue_date,
+            inv.due_date,
+            inv.currency,
+            inv.cancelled_invoice_id,
+            (
+                SELECT c.id
+                FROM invoice c
+                WHERE c.cancelled_invoice_id = inv.id
+            ) AS credit_note_id,
+            inv.note
+        FROM invoice inv
+        WHERE ($1 IS NULL OR inv.customer_id = $1)
+            AND ($2 IS NULL OR inv.id = $2)
+        ORDER BY inv.id ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let invoice_ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let mut items = sqlx::query_as::<_, InvoiceItemRow>(
+        r#"
+        SELECT
+            i.id,
+            i.invoice_id,
+            i.appointment_id,
+            i.date,
+            i.description,
+            i.quantity,
+            i.unit_price,
+            i.discount,
+            i.vat_rate
+        FROM invoice_item i
+        WHERE i.invoice_id IN (SELECT value FROM json_each($1))
+        ORDER BY i.invoice_id ASC, i.position ASC;
+    "#,
+    )
+    .bind(serde_json::to_string(&invoice_ids)?)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .fold(
+        HashMap::<i64, Vec<InvoiceItemRow>>::new(),
+        |mut map, row| {
+            map.entry(row.invoice_id).or_default().push(row);
+            map
+        },
+    );
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let items: Vec<InvoiceItemDto> = items
+                .remove(&row.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| item.into_item(row.currency))
+                .collect();
+            let (price, taxes) = money::sum_prices(
+                items
+                    .iter()
+                    .map(|item| (item.total.gross.amount, item.vat_rate)),
+                row.currency,
+            );
+
+            InvoiceDto {
+                id: row.id,
+                kind: row.kind,
+                status: row.status,
+                number: row.number,
+                customer_id: row.customer_id,
+                customer_name: row.customer_name,
+                address_street: row.address_street,
+                address_postcode: row.address_postcode,
+                address_city: row.address_city,
+                buyer_reference: row.buyer_reference,
+                issue_date: row.issue_date,
+                due_date: row.due_date,
+                currency: row.currency,
+                cancelled_invoice_id: row.cancelled_invoice_id,
+                credit_note_id: row.credit_note_id,
+                note: row.note,
+                items,
+                price,
+                taxes,
+            }
+        })
+        .collect())
+}
+
+async fn check_draft(connection: &mut SqliteConnection, id: i64) -> ServiceResult<()> {
+    let status = sqlx::query_as::<_, InvoiceStatusRow>(
+        r#"
+        SELECT inv.status
+        FROM invoice inv
+        WHERE inv.id = $1;
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(connection)
+    .await?;
+
+    match status.map(|row| row.status) {
+        None => Err(ServiceError::BadRequest(format!(
+            "invoice {} does not exist",
+            id
+        ))),
+        Some(InvoiceStatus::Draft) => Ok(()),
+        Some(_) => Err(ServiceError::Conflict(
+            "issued invoices cannot be changed".to_string(),
+        )),
+    }
+}
+
+/// Inserts or updates a draft including its items. The currency is taken when the draft is
+/// created.
+async fn store_draft(
+    connection: &mut SqliteConnection,
+    invoice: &EditInvoiceDto,
+    kind: InvoiceKind,
+    cancelled_invoice_id: Option<i64>,
+) -> ServiceResult<i64> {
+    for item in &invoice.items {
+        money::validate_vat_rate(item.vat_rate)?;
+    }
+    if let (Some(issue_date), Some(due_date)) = (invoice.issue_date, invoice.due_date)
+        && due_date < issue_date
+    {
+        return Err(ServiceError::BadRequest(
+            "due date must not be before the issue date".to_string(),
+        ));
+    }
+
+    let invoice_id = if let Some(invoice_id) = invoice.id {
+        sqlx::query(
+            r#"
+            UPDATE invoice
+            SET customer_id = $2,
+                customer_name = $3,
+                address_street = $4,
+                address_postcode = $5,
+                address_city = $6,
+                issue_date = $7,
+                due_date = $8,
+                note = $9,
+                buyer_reference = $10
+            WHERE id = $1;
+        "#,
+        )
+        .bind(invoice_id)
+        .bind(invoice.customer_id)
+        .bind(&invoice.customer_name)
+        .bind(&invoice.address_street)
+        .bind(&invoice.address_postcode)
+        .bind(&invoice.address_city)
+        .bind(invoice.issue_date)
+        .bind(invoice.due_date)
+        .bind(&invoice.note)
+        .bind(invoice.buyer_reference.trim())
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM invoice_item
+            WHERE invoice_id = $1;
+        "#,
+        )
+        .bind(invoice_id)
+        .execute(&mut *connection)
+        .await?;
+
+        invoice_id
+    } else {
+        let currency = preference::get_currency(&mut *connection).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO invoice (
+                kind,
+                customer_id,
+                customer_name,
+                address_street,
+                address_postcode,
+                address_city,
+                issue_date,
+                due_date,
+                currency,
+                cancelled_invoice_id,
+                note,
+                buyer_reference
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12);
+        "#,
+        )
+        .bind(kind)
+        .bind(invoice.customer_id)
+        .bind(&invoice.customer_name)
+        .bind(&invoice.address_street)
+        .bind(&invoice.address_postcode)
+        .bind(&invoice.address_city)
+        .bind(invoice.issue_date)
+        .bind(invoice.due_date)
+        .bind(currency)
+        .bind(cancelled_invoice_id)
+        .bind(&invoice.note)
+        .bind(invoice.buyer_reference.trim())
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(&mut *connection)
+        .await?
+        .id
+    };
+
+    for (position, item) in invoice.items.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO invoice_item (
+                invoice_id,
+                position,
+                appointment_id,
+                date,
+                description,
+                quantity,
+                unit_price,
+                discount,
+                vat_rate
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);
+        "#,
+        )
+        .bind(invoice_id)
+        .bind(position as i64)
+        .bind(item.appointment_id)
+        .bind(item.date)
+        .bind(&item.description)
+        .bind(item.quantity)
+        .bind(item.unit_price)
+        .bind(item.discount)
+        .bind(item.vat_rate)
+        .execute(&mut *connection)
+        .await?;
+    }
+
+    Ok(invoice_id)
+}
+
+/// Numbers are only assigned here, inside of an exclusive transaction and counted from the
+/// issued invoices, which can never be deleted. So the sequence has no gaps.
+async fn issue(connection: &mut SqliteConnection, id: i64) -> ServiceResult<()> {
+    let Some(draft) = sqlx::query_as::<_, DraftRow>(
+        r#"
+        SELECT
+            inv.kind,
+            inv.status,
+            inv.issue_date,
+            inv.due_date,
+            (SELECT COUNT(*) FROM invoice_item i WHERE i.invoice_id = inv.id) AS item_count
+        FROM invoice inv
+        WHERE inv.id = $1;
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *connection)
+    .await?
+    else {
+        return Err(ServiceError::BadRequest(format!(
+            "invoice {} does not exist",
+            id
+        )));
+    };
+    if draft.status != InvoiceStatus::Draft {
+        return Err(ServiceError::Conflict(
+            "invoice is already issued".to_string(),
+        ));
+    }
+    if draft.item_count == 0 {
+        return Err(ServiceError::BadRequest(
+            "invoice needs at least one item".to_string(),
+        ));
+    }
+
+    let issue_date = match draft.issue_date {
+        Some(issue_date) => issue_date,
+        None => time_zone::now_local(preference::get_time_zone(&mut *connection).await?).date(),
+    };
+    let last_issue_date = sqlx::query_as::<_, LastIssueDateRow>(
+        r#"
+        SELECT MAX(inv.issue_date) AS issue_date
+        FROM invoice inv
+        WHERE inv.kind = $1 AND inv.status <> 'draft';
+    "#,
+    )
+    .bind(draft.kind)
+    .fetch_one(&mut *connection)
+    .await?
+    .issue_date;
+    if last_issue_date.is_some_and(|last_issue_date| issue_date < last_issue_date) {
+        return Err(ServiceError::BadRequest(
+            "issue date must not be before the last issued invoice".to_string(),
+        ));
+    }
+
+    let due_date = match draft.due_date {
+        Some(due_date) => due_date,
+        None => {
+            let days = preference::get_preference::<i64>(&mut *connection, PAYMENT_DAYS_KEY)
+                .await?
+                .unwrap_or(DEFAULT_PAYMENT_DAYS);
+            issue_date + Duration::days(days)
+        }
+    };
+    if due_date < issue_date {
+        return Err(ServiceError::BadRequest(
+            "due date must not be before the issue date".to_string(),
+        ));
+    }
+
+    let (pattern_key, default_pattern) = match draft.kind {
+        InvoiceKind::Invoice => (NUMBER_PATTERN_KEY, DEFAULT_NUMBER_PATTERN),
+        InvoiceKind::CreditNote => (CREDIT_NOTE_PATTERN_KEY, DEFAULT_CREDIT_NOTE_PATTERN),
+    };
+    let pattern = preference::get_preference::<String>(&mut *connection, pattern_key)
+        .await?
+        .filter(|pattern| !pattern.trim().is_empty())
+        .unwrap_or_else(|| default_pattern.to_string());
+    let sequence_year = is_yearly(&pattern).then(|| issue_date.year() as i64);
+
+    let sequence = sqlx::query_as::<_, SequenceRow>(
+        r#"
+        SELECT COALESCE(MAX(inv.sequence), 0) + 1 AS sequence
+        FROM invoice inv
+        WHERE inv.kind = $1 AND inv.sequence_year IS $2 AND inv.status <> 'draft';
+    "#,
+    )
+    .bind(draft.kind)
+    .bind(sequence_year)
+    .fetch_one(&mut *connection)
+    .await?
+    .sequence;
+    let number = format_number(&pattern, issue_date, sequence)?;
+
+    let is_used = sqlx::query_as::<_, IdRow>(
+        r#"
+        SELECT inv.id
+        FROM invoice inv
+        WHERE inv.number = $1;
+    "#,
+    )
+    .bind(&number)
+    .fetch_optional(&mut *connection)
+    .await?
+    .is_some();
+    if is_used {
+        return Err(ServiceError::Conflict(format!(
+            "invoice number {} is already used, please check the number pattern",
+            number
+        )));
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE invoice
+        SET status = 'issued',
+            number = $2,
+            sequence_year = $3,
+            sequence = $4,
+            issue_date = $5,
+            due_date = $6
+        WHERE id = $1;
+    "#,
+    )
+    .bind(id)
+    .bind(&number)
+    .bind(sequence_year)
+    .bind(sequence)
+    .bind(issue_date)
+    .bind(due_date)
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+/// The counter restarts every year if the pattern contains the year.
+fn is_yearly(pattern: &str) -> bool {
+    pattern.contains("{YYYY}") || pattern.contains("{YY}")
+}
+
+/// Replaces `{YYYY}`, `{YY}` and `{MM}` by the issue date and `{N…}` by the counter, padded
+/// with zeros to the number of `N`s.
+fn format_number(pattern: &str, issue_date: NaiveDate, sequence: i64) -> ServiceResult<String> {
+    let mut number = String::new();
+    let mut has_counter = false;
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        number.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start + 1..start + end];
+        match placeholder {
+            "YYYY" => number.push_str(&format!("{:04}", issue_date.year())),
+            "YY" => number.push_str(&format!("{:02}", issue_date.year() % 100)),
+            "MM" => number.push_str(&format!("{:02}", issue_date.month())),
+            _ if !placeholder.is_empty() && placeholder.chars().all(|c| c == 'N') => {
+                has_counter = true;
+                number.push_str(&format!("{:0width$}", sequence, width = placeholder.len()));
+            }
+            _ => number.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    number.push_str(rest);
+
+    if !has_counter {
+        return Err(ServiceError::BadRequest(format!(
+            "invoice number pattern {} needs a counter like {{NNNN}}",
+            pattern
+        )));
+    }
+    Ok(number)
+}
+
+struct Letterhead {
+    name: String,
+    address: String,
+    contact: String,
+    tax_number: String,
+    bank_details: String,
+    footer: String,
+}
+
+impl Letterhead {
+    async fn load(connection: &mut SqliteConnection) -> ServiceResult<Self> {
+        let mut values = Vec::with_capacity(6);
+        for key in [
+            PRACTICE_NAME_KEY,
+            PRACTICE_ADDRESS_KEY,
+            PRACTICE_CONTACT_KEY,
+            TAX_NUMBER_KEY,
+            BANK_DETAILS_KEY,
+            FOOTER_KEY,
+        ] {
+            values.push(
+                preference::get_preference::<String>(&mut *connection, key)
+                    .await?
+                    .unwrap_or_default(),
+            );
+        }
+        let [name, address, contact, tax_number, bank_details, footer]: [String; 6] =
+            values.try_into().unwrap();
+
+        Ok(Self {
+            name,
+            address,
+            contact,
+            tax_number,
+            bank_details,
+            footer,
+        })
+    }
+}
+
+const MARGIN_LEFT: f32 = 50.0;
+const MARGIN_RIGHT: f32 = pdf::PAGE_WIDTH - 50.0;
+const TABLE_TOP: f32 = 780.0;
+const TABLE_BOTTOM: f32 = 140.0;
+const LINE_HEIGHT: f32 = 13.0;
+
+/// Right edges of the table columns, the description starts after the date.
+const COLUMN_DESCRIPTION: f32 = 110.0;
+const COLUMN_QUANTITY: f32 = 355.0;
+const COLUMN_UNIT_PRICE: f32 = 425.0;
+const COLUMN_VAT: f32 = 470.0;
+
+fn render_invoice(
+    invoice: &InvoiceDto,
+    cancelled_number: Option<&str>,
+    letterhead: &Letterhead,
+    german: bool,
+    attachment: Option<&PdfAttachment>,
+) -> Vec<u8> {
+    let format_date = |date: NaiveDate| {
+        if german {
+            date.format("%d.%m.%Y").to_string()
+        } else {
+            date.format("%Y-%m-%d").to_string()
+        }
+    };
+    let format_amount = |amount: i64| format_money(amount, invoice.currency, german);
+    let label = |en: &'static str, de: &'static str| if german { de } else { en };
+
+    let mut pages = vec![PdfPage::new()];
+    let page = pages.last_mut().unwrap();
+
+    page.text(
+        MARGIN_LEFT,
+        790.0,
+        Font::Bold,
+        16.0,
+        Align::Left,
+        &letterhead.name,
+    );
+    let mut y = 790.0;
+    for line in letterhead.address.lines().chain(letterhead.contact.lines()) {
+        page.text(MARGIN_RIGHT, y, Font::Regular, 9.0, Align::Right, line);
+        y -= 11.0;
+    }
+
+    let sender = std::iter::once(letterhead.name.as_str())
+        .chain(letterhead.address.lines())
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ");
+    page.text(MARGIN_LEFT, 700.0, Font::Regular, 7.0, Align::Left, &sender);
+    let mut y = 684.0;
+    let city = [invoice.address_postcode.trim(), invoice.address_city.trim()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    for line in [&invoice.customer_name, &invoice.address_street, &city] {
+        page.text(MARGIN_LEFT, y, Font::Regular, 10.0, Align::Left, line);
+        y -= LINE_HEIGHT;
+    }
+
+    let number = invoice
+        .number
+        .clone()
+        .unwrap_or_else(|| label("Draft", "Entwurf").to_string());
+    let mut details = vec![(label("Number", "Nummer"), number.clone())];
+    if let Some(issue_date) = invoice.issue_date {
+        details.push((label("Date", "Datum"), format_date(issue_date)));
+    }
+    if invoice.kind == InvoiceKind::Invoice
+        && let Some(due_date) = invoice.due_date
+    {
+        details.push((label("Due date", "Fällig am"), format_date(due_date)));
+    }
+    let mut y = 684.0;
+    for (name, value) in details {
+        page.text(COLUMN_VAT, y, Font::Regular, 10.0, Align::Right, name);
+        page.text(MARGIN_RIGHT, y, Font::Regular, 10.0, Align::Right, &value);
+        y -= LINE_HEIGHT;
+    }
+
+    let title = match invoice.kind {
+        InvoiceKind::Invoice => label("Invoice", "Rechnung"),
+        InvoiceKind::CreditNote => label("Credit note", "Gutschrift"),
+    };
+    let document_title = format!("{} {}", title, number);
+    page.text(
+        MARGIN_LEFT,
+        600.0,
+        Font::Bold,
+        14.0,
+        Align::Left,
+        &document_title,
+    );
+    let mut y = 582.0;
+    if let Some(cancelled_number) = cancelled_number {
+        page.text(
+            MARGIN_LEFT,
+            y,
+            Font::Regular,
+            10.0,
+            Align::Left,
+            &format!(
+                "{} {}",
+                label("Cancellation of invoice", "Stornierung der Rechnung"),
+                cancelled_number
+            ),
+        );
+        y -= LINE_HEIGHT;
+    }
+
+    let header = |page: &mut PdfPage, y: f32| {
+        page.text(
+            MARGIN_LEFT,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Left,
+            label("Date", "Datum"),
+        );
+        page.text(
+            COLUMN_DESCRIPTION,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Left,
+            label("Description", "Leistung"),
+        );
+        page.text(
+            COLUMN_QUANTITY,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Right,
+            label("Qty", "Menge"),
+        );
+        page.text(
+            COLUMN_UNIT_PRICE,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Right,
+            label("Unit price", "Einzelpreis"),
+        );
+        page.text(
+            COLUMN_VAT,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Right,
+            label("VAT", "MwSt."),
+        );
+        page.text(
+            MARGIN_RIGHT,
+            y,
+            Font::Bold,
+            9.0,
+            Align::Right,
+            label("Total", "Gesamt"),
+        );
+        page.line(MARGIN_LEFT, y - 4.0, MARGIN_RIGHT, y - 4.0);
+        y - 4.0 - LINE_HEIGHT
+    };
+
+    let mut y = header(page, y - 16.0);
+    for item in &invoice.items {
+        let lines = pdf::wrap_text(
+            &item.description,
+            Font::Regular,
+            9.0,
+            COLUMN_QUANTITY - COLUMN_DESCRIPTION - 40.0,
+        );
+        if y - LINE_HEIGHT * (lines.len() as f32 - 1.0) < TABLE_BOTTOM {
+            pages.push(PdfPage::new());
+            y = header(pages.last_mut().unwrap(), TABLE_TOP);
+        }
+        let page = pages.last_mut().unwrap();
+
+        if let Some(date) = item.date {
+            page.text(
+                MARGIN_LEFT,
+                y,
+                Font::Regular,
+                9.0,
+                Align::Left,
+                &format_date(date),
+            );
+        }
+        page.text(
+            COLUMN_QUANTITY,
+            y,
+            Font::Regular,
+            9.0,
+            Align::Right,
+            &item.quantity.to_string(),
+        );
+        page.text(
+            COLUMN_UNIT_PRICE,
+            y,
+            Font::Regular,
+            9.0,
+            Align::Right,
+            &format_amount(item.unit_price),
+        );
+        page.text(
+            COLUMN_VAT,
+            y,
+            Font::Regular,
+            9.0,
+            Align::Right,
+            &format_vat_rate(item.vat_rate, german),
+        );
+        page.text(
+            MARGIN_RIGHT,
+            y,
+            Font::Regular,
+            9.0,
+            Align::Right,
+            &format_amount(item.total.gross.amount),
+        );
+        for line in lines {
+            page.text(
+                COLUMN_DESCRIPTION,
+                y,
+                Font::Regular,
+                9.0,
+                Align::Left,
+                &line,
+            );
+            y -= LINE_HEIGHT;
+        }
+        if item.discount != 0 {
+            page.text(
+                COLUMN_DESCRIPTION,
+                y,
+                Font::Regular,
+                8.0,
+                Align::Left,
+                &format!(
+                    "{} {}",
+                    label("Discount", "Rabatt"),
+                    format_amount(item.discount)
+                ),
+            );
+            y -= LINE_HEIGHT;
+        }
+    }
+
+    let mut totals = vec![(
+        Font::Regular,
+        label("Net total", "Nettobetrag").to_string(),
+        invoice.price.net.amount,
+    )];
+    for tax in &invoice.taxes {
+        totals.push((
+            Font::Regular,
+            format!(
+                "{} {}",
+                label("VAT", "MwSt."),
+                format_vat_rate(tax.vat_rate, german)
+            ),
+            tax.tax.amount,
+        ));
+    }
+    totals.push((
+        Font::Bold,
+        label("Total", "Gesamtbetrag").to_string(),
+        invoice.price.gross.amount,
+    ));
+
+    let note_lines = pdf::wrap_text(
+        &invoice.note,
+        Font::Regular,
+        9.0,
+        MARGIN_RIGHT - MARGIN_LEFT,
+    );
+    if y - LINE_HEIGHT * ((totals.len() + note_lines.len() + 1) as f32) < TABLE_BOTTOM {
+        pages.push(PdfPage::new());
+        y = TABLE_TOP;
+    }
+    let page = pages.last_mut().unwrap();
+    page.line(COLUMN_UNIT_PRICE - 60.0, y + 4.0, MARGIN_RIGHT, y + 4.0);
+    y -= 4.0;
+    for (font, name, amount) in totals {
+        page.text(COLUMN_VAT, y, font, 9.0, Align::Right, &name);
+        page.text(
+            MARGIN_RIGHT,
+            y,
+            font,
+            9.0,
+            Align::Right,
+            &format_amount(amount),
+        );
+        y -= LINE_HEIGHT;
+    }
+    y -= LINE_HEIGHT;
+    for line in note_lines {
+        page.text(MARGIN_LEFT, y, Font::Regular, 9.0, Align::Left, &line);
+        y -= LINE_HEIGHT;
+    }
+
+    let mut footer: Vec<String> = Vec::new();
+    if !letterhead.tax_number.trim().is_empty() {
+        footer.push(format!(
+            "{} {}",
+            label("Tax number:", "Steuernummer:"),
+            letterhead.tax_number.trim()
+        ));
+    }
+    footer.extend(
+        letterhead
+            .bank_details
+            .lines()
+            .chain(letterhead.footer.lines())
+            .map(|line| line.to_string()),
+    );
+    let page_count = pages.len();
+    for (index, page) in pages.iter_mut().enumerate() {
+        page.line(MARGIN_LEFT, 110.0, MARGIN_RIGHT, 110.0);
+        let mut y = 98.0;
+        for line in &footer {
+            page.text(MARGIN_LEFT, y, Font::Regular, 8.0, Align::Left, line);
+            y -= 10.0;
+        }
+        page.text(
+            MARGIN_RIGHT,
+            98.0,
+            Font::Regular,
+            8.0,
+            Align::Right,
+            &format!("{} {} / {}", label("Page", "Seite"), index + 1, page_count),
+        );
+    }
+
+    pdf::write_document(&document_title, &pages, attachment)
+}
+
+fn format_money(amount: i64, currency: Currency, german: bool) -> String {
+    let (thousands, decimal) = if german { ('.', ',') } else { (',', '.') };
+    let digits = (amount.unsigned_abs() / 100).to_string();
+    let mut whole = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            whole.push(thousands);
+        }
+        whole.push(digit);
+    }
+    let sign = if amount < 0 { "-" } else { "" };
+    let symbol = match currency {
+        Currency::Eur => "€",
+        Currency::Chf => "CHF",
+        Currency::Usd => "$",
+        Currency::Gbp => "£",
+    };
+    format!(
+        "{}{}{}{:02} {}",
+        sign,
+        whole,
+        decimal,
+        amount.unsigned_abs() % 100,
+        symbol
+    )
+}
+
+fn format_vat_rate(vat_rate: i64, german: bool) -> String {
+    let text = format!("{}", vat_rate as f64 / 100.0);
+    if german {
+        format!("{} %", text.replace('.', ","))
+    } else {
+        format!("{} %", text)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoicedAppointmentRow {
+    pub appointment_id: i64,
+    pub number: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct CustomerAddressRow {
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub address_street: String,
+    pub address_postcode: String,
+    pub address_city: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoiceStatusRow {
+    pub status: InvoiceStatus,
+}
+
+#[derive(sqlx::FromRow)]
+struct DraftRow {
+    pub kind: InvoiceKind,
+    pub status: InvoiceStatus,
+    pub issue_date: Option<NaiveDate>,
+    pub due_date: Option<NaiveDate>,
+    pub item_count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct LastIssueDateRow {
+    pub issue_date: Option<NaiveDate>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SequenceRow {
+    pub sequence: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct EmailRow {
+    pub email: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoiceRow {
+    pub id: i64,
+    pub kind: InvoiceKind,
+    pub status: InvoiceStatus,
+    pub number: Option<String>,
+    pub customer_id: Option<i64>,
+    pub customer_name: String,
+    pub address_street: String,
+    pub address_postcode: String,
+    pub address_city: String,
+    pub buyer_reference: String,
+    pub issue_date: Option<NaiveDate>,
+    pub due_date: Option<NaiveDate>,
+    pub currency: Currency,
+    pub cancelled_invoice_id: Option<i64>,
+    pub credit_note_id: Option<i64>,
+    pub note: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoiceItemRow {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub appointment_id: Option<i64>,
+    pub date: Option<NaiveDate>,
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub discount: i64,
+    pub vat_rate: i64,
+}
+
+impl InvoiceItemRow {
+    fn into_item(self, currency: Currency) -> InvoiceItemDto {
+        InvoiceItemDto {
+            id: self.id,
+            appointment_id: self.appointment_id,
+            date: self.date,
+            description: self.description,
+            quantity: self.quantity,
+            unit_price: self.unit_price,
+            discount: self.discount,
+            vat_rate: self.vat_rate,
+            total: money::split_gross(
+                self.quantity * self.unit_price - self.discount,
+                self.vat_rate,
+                currency,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use crate::model::{EInvoiceSyntax, EditInvoiceDto, EditInvoiceItemDto};
+    use crate::state::State;
+    use crate::state::testing::{customer, set_preference, state, temp_dir};
+
+    /// Subset of the `resources` folder of the KoSIT validator configuration for XRechnung 3.0.
+    const SCHEMA_DIR: &str = "tests/xrechnung/resources";
+    const UBL_INVOICE_SCHEMA: &str = "ubl/2.1/xsd/maindoc/UBL-Invoice-2.1.xsd";
+    const UBL_CREDIT_NOTE_SCHEMA: &str = "ubl/2.1/xsd/maindoc/UBL-CreditNote-2.1.xsd";
+    const CII_SCHEMA: &str = "cii/16b/xsd/CrossIndustryInvoice_100pD16B.xsd";
+    const UBL_RULES: &str = "xrechnung/schematron/XRechnung-UBL-validation.sch";
+    const CII_RULES: &str = "xrechnung/schematron/XRechnung-CII-validation.sch";
+
+    fn item(
+        description: &str,
+        quantity: i64,
+        unit_price: i64,
+        vat_rate: i64,
+    ) -> EditInvoiceItemDto {
+        EditInvoiceItemDto {
+            appointment_id: None,
+            date: Some("2024-03-01".parse().unwrap()),
+            description: description.to_string(),
+            quantity,
+            unit_price,
+            discount: 0,
+            vat_rate,
+        }
+    }
+
+    /// Issued invoice with two VAT rates and an exempt line, and the credit note cancelling it.
+    async fn issued_invoice(state: &State) -> (i64, i64) {
+        for (key, value) in [
+            ("invoice.practice-name", "Praxis Müller"),
+            ("invoice.street", "Weg 2"),
+            ("invoice.postcode", "20095"),
+            ("invoice.city", "Hamburg"),
+            ("invoice.vat-id", "DE123456789"),
+            ("invoice.contact-name", "Eva Müller"),
+            ("invoice.contact-phone", "040 123"),
+            ("invoice.contact-email", "praxis@example.org"),
+            ("invoice.iban", "DE02 1203 0000 0000 2020 51"),
+            ("invoice.bic", "BYLADEM1001"),
+        ] {
+            set_preference(state, key, value).await;
+        }
+
+        let mut buyer = customer("Anna", "Berg");
+        buyer.email = "anna@example.org".to_string();
+        let customer_id = state.customer.store_customer(buyer).await.unwrap();
+
+        let mut oil = item("Öl & Creme <fein>", 2, 1070, 700);
+        oil.discount = 40;
+        let id = state
+            .invoice
+            .store_invoice(EditInvoiceDto {
+                id: None,
+                customer_id: Some(customer_id),
+                customer_name: "Anna Berg".to_string(),
+                address_street: "Hauptstr. 1".to_string(),
+                address_postcode: "10115".to_string(),
+                address_city: "Berlin".to_string(),
+                buyer_reference: String::new(),
+                issue_date: None,
+                due_date: None,
+                note: String::new(),
+                items: vec![
+                    item("Massage", 1, 5950, 1900),
+                    oil,
+                    item("Heilpraktische Behandlung", 1, 8000, 0),
+                    item("Fango", 3, 1190, 1900),
+                ],
+            })
+            .await
+            .unwrap();
+        state.invoice.issue_invoice(id).await.unwrap();
+        let credit_note_id = state.invoice.cancel_invoice(id).await.unwrap();
+        (id, credit_note_id)
+    }
+
+    async fn export(state: &State, id: i64, syntax: EInvoiceSyntax) -> (PathBuf, String) {
+        let path = temp_dir().join("invoice.xml");
+        state
+            .invoice
+            .export_invoice_xml(id, syntax, &path)
+            .await
+            .unwrap();
+        let xml = std::fs::read_to_string(&path).unwrap();
+        (path, xml)
+    }
+
+    /// Amounts of all elements with the tag.
+    fn amounts(xml: &str, tag: &str) -> Vec<f64> {
+        xml.match_indices(&format!("<{}", tag))
+            .map(|(start, _)| {
+                let value = &xml[start..];
+                let value = &value[value.find('>').unwrap() + 1..];
+                value[..value.find('<').unwrap()].parse().unwrap()
+            })
+            .collect()
+    }
+
+    /// Checks the document against the XML schema and the Schematron rules with `xmllint`.
+    fn validate(schema: &str, rules: &str, path: &Path) -> Result<(), String> {
+        let resources = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_DIR);
+        for (option, file) in [("--schema", schema), ("--schematron", rules)] {
+            let output = Command::new("xmllint")
+                .arg("--noout")
+                .arg(option)
+                .arg(resources.join(file))
+                .arg(path)
+                .output()
+                .expect("xmllint is not installed");
+            if !output.status.success() {
+                return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ubl_tax_breakdown() {
+        let state = state().await;
+        let (id, credit_note_id) = issued_invoice(&state).await;
+
+        let (_, xml) = export(&state, id, EInvoiceSyntax::Ubl).await;
+        assert!(xml.contains("<ubl:Invoice "));
+        assert!(xml.contains("<cbc:InvoiceTypeCode>380</cbc:InvoiceTypeCode>"));
+        assert!(xml.contains("Öl &amp; Creme &lt;fein&gt;"));
+        assert_eq!(xml.matches("<cac:TaxSubtotal>").count(), 3);
+        assert!(xml.contains("<cbc:TaxExemptionReason>"));
+        assert!(xml.contains(r#"<cbc:PayableAmount currencyID="EUR">196.20</cbc:PayableAmount>"#));
+        // The first amount is the total of the document, the others belong to the lines.
+        let lines: f64 = amounts(&xml, "cbc:LineExtensionAmount")
+            .iter()
+            .skip(1)
+            .sum();
+        let basis: f64 = amounts(&xml, "cbc:TaxableAmount").iter().sum();
+        let net = amounts(&xml, "cbc:TaxExclusiveAmount")[0];
+        assert!((lines - basis).abs() < 0.001 && (basis - net).abs() < 0.001);
+
+        let (_, xml) = export(&state, credit_note_id, EInvoiceSyntax::Ubl).await;
+        assert!(xml.contains("<ubl:CreditNote "));
+        assert!(xml.contains("<cbc:CreditNoteTypeCode>381</cbc:CreditNoteTypeCode>"));
+        assert!(xml.contains("<cac:BillingReference>"));
+        assert!(xml.contains(r#"<cbc:PayableAmount currencyID="EUR">196.20</cbc:PayableAmount>"#));
+        assert!(!xml.contains(">-"));
+    }
+
+    #[tokio::test]
+    async fn cii_tax_breakdown() {
+        let state = state().await;
+        let (id, credit_note_id) = issued_invoice(&state).await;
+
+        let (_, xml) = export(&state, id, EInvoiceSyntax::Cii).await;
+        assert!(xml.contains("<ram:TypeCode>380</ram:TypeCode>"));
+        assert_eq!(xml.matches("<ram:ApplicableTradeTax>").count(), 4 + 3);
+        assert!(xml.contains("<ram:ExemptionReason>"));
+        assert!(xml.contains("<ram:GrandTotalAmount>196.20</ram:GrandTotalAmount>"));
+        // The last amount is the total of the document, the others belong to the lines.
+        let line_totals = amounts(&xml, "ram:LineTotalAmount");
+        let lines: f64 = line_totals[..line_totals.len() - 1].iter().sum();
+        let basis: f64 = amounts(&xml, "ram:BasisAmount").iter().sum();
+        assert!((lines - basis).abs() < 0.001);
+
+        let (_, xml) = export(&state, credit_note_id, EInvoiceSyntax::Cii).await;
+        assert!(xml.contains("<ram:TypeCode>381</ram:TypeCode>"));
+        assert!(xml.contains("<ram:InvoiceReferencedDocument>"));
+        assert!(xml.contains("<ram:GrandTotalAmount>196.20</ram:GrandTotalAmount>"));
+        assert!(!xml.contains(">-"));
+    }
+
+    #[tokio::test]
+    async fn buyer_postcode_is_required() {
+        let state = state().await;
+        // Also stores the preferences of the seller.
+        issued_invoice(&state).await;
+        let mut buyer = customer("Ben", "Kurz");
+        buyer.email = "ben@example.org".to_string();
+        buyer.address_city = "Berlin".to_string();
+        let customer_id = state.customer.store_customer(buyer).await.unwrap();
+        let draft = state
+            .invoice
+            .store_invoice(EditInvoiceDto {
+                id: None,
+                customer_id: Some(customer_id),
+                customer_name: "Ben Kurz".to_string(),
+                address_street: "Hauptstr. 2".to_string(),
+                address_postcode: String::new(),
+                address_city: "Berlin".to_string(),
+                buyer_reference: String::new(),
+                issue_date: None,
+                due_date: None,
+                note: String::new(),
+                items: vec![item("Massage", 1, 5950, 1900)],
+            })
+            .await
+            .unwrap();
+        state.invoice.issue_invoice(draft).await.unwrap();
+
+        let path = temp_dir().join("invoice.xml");
+        let error = state
+            .invoice
+            .export_invoice_xml(draft, EInvoiceSyntax::Ubl, &path)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("postcode"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn xrechnung_schemas() {
+        let state = state().await;
+        let (id, credit_note_id) = issued_invoice(&state).await;
+
+        for (id, syntax, schema, rules) in [
+            (id, EInvoiceSyntax::Ubl, UBL_INVOICE_SCHEMA, UBL_RULES),
+            (id, EInvoiceSyntax::Cii, CII_SCHEMA, CII_RULES),
+            (
+                credit_note_id,
+                EInvoiceSyntax::Ubl,
+                UBL_CREDIT_NOTE_SCHEMA,
+                UBL_RULES,
+            ),
+            (credit_note_id, EInvoiceSyntax::Cii, CII_SCHEMA, CII_RULES),
+        ] {
+            let (path, xml) = export(&state, id, syntax).await;
+            validate(schema, rules, &path).unwrap();
+
+            // The validation has to notice a broken document.
+            let broken = xml
+                .replacen("<cbc:PostalZone>10115</cbc:PostalZone>", "", 1)
+                .replacen("<ram:PostcodeCode>10115</ram:PostcodeCode>", "", 1);
+            std::fs::write(&path, broken).unwrap();
+            let error = validate(schema, rules, &path).unwrap_err();
+            assert!(error.contains("BR-DE-9"), "{}", error);
+        }
+    }
+}
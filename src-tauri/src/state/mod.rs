@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -6,15 +7,55 @@ use log::info;
 use sqlx::sqlite::SqlitePoolOptions;
 use sqlx::{Pool, Sqlite};
 
+use crate::error::{ServiceError, ServiceResult};
+
+use crate::state::accounting::AccountingState;
 use crate::state::appointment::AppointmentState;
+use crate::state::cash_journal::{CashJournalHead, CashJournalState};
+use crate::state::commission::CommissionState;
 use crate::state::customer::CustomerState;
 use crate::state::employee::EmployeeState;
+use crate::state::ical::IcalState;
+use crate::state::invoice::InvoiceState;
+use crate::state::notifier::SmtpNotifier;
+use crate::state::package::PackageState;
+use crate::state::payment::PaymentState;
 use crate::state::preference::PreferenceState;
+use crate::state::price_rule::PriceRuleState;
+use crate::state::product::ProductState;
+use crate::state::reminder::ReminderState;
+use crate::state::report::ReportState;
+use crate::state::reschedule::RescheduleState;
+use crate::state::resource::ResourceState;
+use crate::state::treatment::TreatmentState;
+use crate::state::user::UserState;
+use crate::state::voucher::VoucherState;
+use crate::state::waitlist::WaitlistState;
 
+mod accounting;
 mod appointment;
+mod cash_journal;
+mod commission;
 mod customer;
 mod employee;
+mod ical;
+mod invoice;
+mod notifier;
+mod package;
+mod payment;
 mod preference;
+mod price_rule;
+mod product;
+mod reminder;
+mod report;
+mod reschedule;
+mod resource;
+#[cfg(test)]
+pub mod testing;
+mod treatment;
+mod user;
+mod voucher;
+mod waitlist;
 
 #[derive(Clone)]
 pub struct State {
@@ -24,10 +65,31 @@ pub struct State {
     pub customer: CustomerState,
     pub appointment: AppointmentState,
     pub preference: PreferenceState,
+    pub treatment: TreatmentState,
+    pub product: ProductState,
+    pub ical: IcalState,
+    pub reminder: ReminderState,
+    pub waitlist: WaitlistState,
+    pub resource: ResourceState,
+    pub package: PackageState,
+    pub reschedule: RescheduleState,
+    pub invoice: InvoiceState,
+    pub payment: PaymentState,
+    pub report: ReportState,
+    pub voucher: VoucherState,
+    pub price_rule: PriceRuleState,
+    pub cash_journal: CashJournalState,
+    pub accounting: AccountingState,
+    pub user: UserState,
+    pub commission: CommissionState,
 }
 
+/// Migration that makes the time zone and the UTC instants of appointments required. They depend
+/// on the time zone preference and are assigned to older appointments before it runs.
+const APPOINTMENT_TIME_ZONE_MIGRATION: i64 = 25;
+
 impl State {
-    pub async fn new(app_data_dir: &Path) -> Self {
+    pub async fn new(app_data_dir: &Path) -> ServiceResult<Self> {
         fs::create_dir_all(app_data_dir).expect("failed to create app data dir");
 
         let db_path = app_data_dir.join("data.db");
@@ -43,21 +105,221 @@ impl State {
             .await
             .unwrap();
 
-        sqlx::migrate!().run(&db_pool).await.unwrap();
+        migrate(&db_pool).await?;
         let pool = Arc::new(db_pool);
 
+        let cash_journal_head = CashJournalHead::new(app_data_dir.join("cash_journal_head.json"));
+        cash_journal_head.update(&pool).await?;
+
         let employee = EmployeeState::new(pool.clone());
         let customer = CustomerState::new(pool.clone());
         let appointment = AppointmentState::new(pool.clone());
         let preference = PreferenceState::new(pool.clone());
+        let treatment = TreatmentState::new(pool.clone());
+        let product = ProductState::new(pool.clone());
+        let ical = IcalState::new(appointment.clone(), customer.clone(), preference.clone());
+        let waitlist = WaitlistState::new(pool.clone());
+        let resource = ResourceState::new(pool.clone(), appointment.clone());
+        let package = PackageState::new(pool.clone());
+        let reschedule = RescheduleState::new(pool.clone(), appointment.clone(), employee.clone());
+        let invoice = InvoiceState::new(pool.clone());
+        let payment = PaymentState::new(pool.clone(), cash_journal_head.clone());
+        let report = ReportState::new(pool.clone());
+        let voucher = VoucherState::new(pool.clone());
+        let price_rule = PriceRuleState::new(pool.clone());
+        let cash_journal = CashJournalState::new(pool.clone(), cash_journal_head);
+        let accounting = AccountingState::new(pool.clone());
+        let user = UserState::new(pool.clone());
+        let commission = CommissionState::new(pool.clone());
+        let reminder = ReminderState::new(
+            pool.clone(),
+            preference.clone(),
+            vec![Box::new(SmtpNotifier::new(preference.clone()))],
+        );
 
-        Self {
+        Ok(Self {
             _pool: pool,
             db_path,
             employee,
             customer,
             appointment,
             preference,
-        }
+            treatment,
+            product,
+            ical,
+            reminder,
+            waitlist,
+            resource,
+            package,
+            reschedule,
+            invoice,
+            payment,
+            report,
+            voucher,
+            price_rule,
+            cash_journal,
+            accounting,
+            user,
+            commission,
+        })
+    }
+}
+
+/// Runs the pending migrations. The ones before the appointment time zone migration run first, so
+/// the time zones can be assigned in between. An invalid time zone preference is returned as an
+/// error then, the migration is retried on the next start.
+///
+/// The later migrations run with foreign keys off, as SQLite deletes the rows referencing a table
+/// that is dropped to rebuild it. The references are checked once they are done.
+async fn migrate(pool: &Pool<Sqlite>) -> ServiceResult<()> {
+    let mut previous = sqlx::migrate!();
+    previous.migrations = Cow::Owned(
+        previous
+            .iter()
+            .filter(|migration| migration.version < APPOINTMENT_TIME_ZONE_MIGRATION)
+            .cloned()
+            .collect(),
+    );
+    previous.set_ignore_missing(true);
+    previous.run(pool).await?;
+
+    appointment::assign_time_zones(pool).await?;
+
+    // The connection is not returned to the pool, so foreign keys stay on for all others.
+    let mut connection = pool.acquire().await?.detach();
+    sqlx::query("PRAGMA foreign_keys = OFF;")
+        .execute(&mut connection)
+        .await?;
+    sqlx::migrate!().run(&mut connection).await?;
+
+    let violations = sqlx::query("PRAGMA foreign_key_check;")
+        .fetch_all(&mut connection)
+        .await?;
+    if !violations.is_empty() {
+        return Err(ServiceError::InternalServerError(
+            "Migration error",
+            format!("{} rows violate foreign keys", violations.len()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::state::testing::temp_dir;
+
+    /// Database of a version before appointments had a time zone, with an appointment and its
+    /// item stored in winter.
+    async fn legacy_database(time_zone: &str) -> PathBuf {
+        let dir = temp_dir();
+        let db_path = dir.join("data.db");
+        File::create(&db_path).unwrap();
+        let pool = SqlitePoolOptions::new()
+            .connect(&format!("sqlite:{}", db_path.display()))
+            .await
+            .unwrap();
+
+        let mut migrator = sqlx::migrate!();
+        migrator.migrations = Cow::Owned(
+            migrator
+                .iter()
+                .filter(|migration| migration.version < 13)
+                .cloned()
+                .collect(),
+        );
+        migrator.run(&pool).await.unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO customer (
+                id, title, first_name, last_name, address_street, address_city, phone,
+                mobile_phone, note
+            )
+            VALUES (1, '', 'Anna', 'Berg', '', '', '', '', '');
+        "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO appointment (id, customer_id, number, start_date, duration_minutes)
+            VALUES (1, 1, 1, '2024-01-15 10:00:00', 90);
+        "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO appointment_item (
+                appointment_id, position, description, quantity, unit_price, discount
+            )
+            VALUES (1, 0, 'Haircut', 1, 3500, 0);
+        "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO preference (key, value) VALUES ($1, $2);")
+            .bind(crate::time_zone::TIME_ZONE_KEY)
+            .bind(serde_json::to_string(time_zone).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        pool.close().await;
+        dir
+    }
+
+    #[tokio::test]
+    async fn assigns_time_zones_of_old_appointments() {
+        let state = State::new(&legacy_database("Europe/Lisbon").await)
+            .await
+            .unwrap();
+
+        let appointment = state
+            .appointment
+            .get_appointment_by_id(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(appointment.time_zone, "Europe/Lisbon");
+        assert_eq!(
+            appointment.end_date,
+            "2024-01-15T11:30:00".parse::<NaiveDateTime>().unwrap()
+        );
+        assert_eq!(appointment.items.len(), 1);
+        assert_eq!(appointment.items[0].description, "Haircut");
+    }
+
+    #[tokio::test]
+    async fn invalid_time_zone_fails_the_migration() {
+        let dir = legacy_database("Europe/Nowhere").await;
+        assert!(State::new(&dir).await.is_err());
+
+        // The migration is retried once the preference is corrected.
+        let pool = SqlitePoolOptions::new()
+            .connect(&format!("sqlite:{}", dir.join("data.db").display()))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE preference SET value = $1;")
+            .bind(serde_json::to_string("Europe/Berlin").unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let state = State::new(&dir).await.unwrap();
+        let appointment = state
+            .appointment
+            .get_appointment_by_id(1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(appointment.time_zone, "Europe/Berlin");
     }
 }
@@ -0,0 +1,259 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::ReminderChannel,
+    state::preference::PreferenceState,
+};
+
+const SMTP_HOST_KEY: &str = "smtp.host";
+const SMTP_PORT_KEY: &str = "smtp.port";
+const SMTP_SECURITY_KEY: &str = "smtp.security";
+const SMTP_USERNAME_KEY: &str = "smtp.username";
+const SMTP_PASSWORD_KEY: &str = "smtp.password";
+const SMTP_SENDER_KEY: &str = "smtp.sender";
+const DEFAULT_SMTP_PORT: u16 = 587;
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type NotifierFuture<'a> = Pin<Box<dyn Future<Output = ServiceResult<()>> + Send + 'a>>;
+
+pub struct ReminderMessage {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers reminder messages over a single channel. Reminders are only queued for channels
+/// with a registered notifier.
+pub trait Notifier: Send + Sync {
+    fn channel(&self) -> ReminderChannel;
+
+    fn send<'a>(&'a self, message: &'a ReminderMessage) -> NotifierFuture<'a>;
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+/// Sends e-mails through the smtp server configured in the preferences. The configuration is
+/// read for every message, so changes apply without a restart.
+pub struct SmtpNotifier {
+    preference: PreferenceState,
+}
+
+impl SmtpNotifier {
+    pub fn new(preference: PreferenceState) -> Self {
+        Self { preference }
+    }
+
+    async fn deliver(&self, message: &ReminderMessage) -> ServiceResult<()> {
+        let host = self
+            .preference
+            .get_preference::<String>(SMTP_HOST_KEY)
+            .await?
+            .unwrap_or_default();
+        let sender = self
+            .preference
+            .get_preference::<String>(SMTP_SENDER_KEY)
+            .await?
+            .unwrap_or_default();
+        if host.trim().is_empty() || sender.trim().is_empty() {
+            return Err(ServiceError::BadRequest(
+                "smtp server is not configured".to_string(),
+            ));
+        }
+
+        let security = self
+            .preference
+            .get_preference::<SmtpSecurity>(SMTP_SECURITY_KEY)
+            .await?
+            .unwrap_or(SmtpSecurity::StartTls);
+        let port = self
+            .preference
+            .get_preference::<u16>(SMTP_PORT_KEY)
+            .await?
+            .unwrap_or(DEFAULT_SMTP_PORT);
+        let username = self
+            .preference
+            .get_preference::<String>(SMTP_USERNAME_KEY)
+            .await?
+            .unwrap_or_default();
+        let password = self
+            .preference
+            .get_preference::<String>(SMTP_PASSWORD_KEY)
+            .await?
+            .unwrap_or_default();
+
+        let host = host.trim();
+        let mut transport = match security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        }
+        .port(port)
+        .timeout(Some(SMTP_TIMEOUT));
+
+        if !username.is_empty() {
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+
+        let email = Message::builder()
+            .from(sender.trim().parse()?)
+            .to(message.recipient.parse()?)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())?;
+
+        transport.build().send(email).await?;
+
+        Ok(())
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    fn channel(&self) -> ReminderChannel {
+        ReminderChannel::Email
+    }
+
+    fn send<'a>(&'a self, message: &'a ReminderMessage) -> NotifierFuture<'a> {
+        Box::pin(self.deliver(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    use super::*;
+    use crate::state::testing::{set_preference, state};
+
+    /// Accepts a single connection and speaks just enough SMTP for a delivery. Recipients
+    /// starting with `rejected` are refused. Returns the commands and the message data.
+    async fn smtp_server() -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut transcript = Vec::new();
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-localhost\r\n250 AUTH PLAIN\r\n"
+                } else if command.starts_with("AUTH") {
+                    b"235 2.7.0 Authenticated\r\n"
+                } else if command.starts_with("RCPT TO:<REJECTED") {
+                    b"550 5.1.1 Mailbox unavailable\r\n"
+                } else if command.starts_with("DATA") {
+                    transcript.push(line);
+                    writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+                    let mut data = String::new();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    transcript.push(data);
+                    writer.write_all(b"250 2.0.0 Queued\r\n").await.unwrap();
+                    continue;
+                } else if command.starts_with("QUIT") {
+                    transcript.push(line);
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                transcript.push(line);
+                writer.write_all(reply).await.unwrap();
+            }
+            transcript
+        });
+        (port, server)
+    }
+
+    async fn notifier(port: u16) -> SmtpNotifier {
+        let state = state().await;
+        set_preference(&state, SMTP_HOST_KEY, "127.0.0.1").await;
+        set_preference(&state, SMTP_PORT_KEY, port).await;
+        set_preference(&state, SMTP_SECURITY_KEY, "none").await;
+        set_preference(&state, SMTP_USERNAME_KEY, "praxis").await;
+        set_preference(&state, SMTP_PASSWORD_KEY, "geheim").await;
+        set_preference(&state, SMTP_SENDER_KEY, "Praxis <praxis@example.org>").await;
+        SmtpNotifier::new(state.preference.clone())
+    }
+
+    fn message(recipient: &str) -> ReminderMessage {
+        ReminderMessage {
+            recipient: recipient.to_string(),
+            subject: "Appointment reminder".to_string(),
+            body: "Hello Anna Berg,\n\nsee you tomorrow at 10:00.".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_through_smtp() {
+        let (port, server) = smtp_server().await;
+        let notifier = notifier(port).await;
+
+        notifier.send(&message("anna@example.org")).await.unwrap();
+        let transcript = server.await.unwrap();
+
+        assert!(transcript[0].starts_with("EHLO "));
+        // PLAIN credentials are the base64 encoding of "\0praxis\0geheim".
+        assert_eq!(transcript[1], "AUTH PLAIN AHByYXhpcwBnZWhlaW0=");
+        assert_eq!(transcript[2], "MAIL FROM:<praxis@example.org>");
+        assert_eq!(transcript[3], "RCPT TO:<anna@example.org>");
+        assert_eq!(transcript[4], "DATA");
+        let data = &transcript[5];
+        assert!(data.contains("From: Praxis <praxis@example.org>\n"));
+        assert!(data.contains("To: anna@example.org\n"));
+        assert!(data.contains("Subject: Appointment reminder\n"));
+        assert!(data.contains("Content-Type: text/plain; charset=utf-8\n"));
+        assert!(data.ends_with("\nHello Anna Berg,\n\nsee you tomorrow at 10:00.\n"));
+        assert_eq!(transcript.last().unwrap(), "QUIT");
+    }
+
+    #[tokio::test]
+    async fn rejected_recipients_fail() {
+        let (port, server) = smtp_server().await;
+        let notifier = notifier(port).await;
+
+        let result = notifier.send(&message("rejected@example.org")).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Mailbox unavailable")
+        );
+        drop(notifier);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn requires_server_and_sender() {
+        let state = state().await;
+        let notifier = SmtpNotifier::new(state.preference.clone());
+        assert!(matches!(
+            notifier.send(&message("anna@example.org")).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,607 @@
+use chrono::{Days, NaiveDate, Utc};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{CustomerPackageDto, EditCustomerPackageDto, EditSessionPackageDto, SessionPackageDto},
+    money,
+    state::preference,
+    time_zone,
+};
+
+#[derive(Clone)]
+pub struct PackageState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl PackageState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_session_package_list(&self) -> ServiceResult<Vec<SessionPackageDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, SessionPackageRow>(
+            r#"
+            SELECT p.id, p.name, p.treatment_id, p.session_count, p.price, p.validity_days, p.active
+            FROM session_package p
+            ORDER BY p.name ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    pub async fn get_session_package_by_id(
+        &self,
+        id: i64,
+    ) -> ServiceResult<Option<SessionPackageDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, SessionPackageRow>(
+            r#"
+            SELECT p.id, p.name, p.treatment_id, p.session_count, p.price, p.validity_days, p.active
+            FROM session_package p
+            WHERE p.id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .map(|row| row.into()))
+    }
+
+    pub async fn store_session_package(
+        &self,
+        package: EditSessionPackageDto,
+    ) -> ServiceResult<i64> {
+        if package.session_count <= 0 {
+            return Err(ServiceError::BadRequest(
+                "package must contain at least one session".to_string(),
+            ));
+        }
+        if package.validity_days.is_some_and(|days| days <= 0) {
+            return Err(ServiceError::BadRequest(
+                "validity must be at least one day".to_string(),
+            ));
+        }
+
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let q = if let Some(package_id) = package.id {
+            sqlx::query(
+                r#"
+                UPDATE session_package
+                SET name = $2,
+                    treatment_id = $3,
+                    session_count = $4,
+                    price = $5,
+                    validity_days = $6,
+                    active = $7
+                WHERE id = $1;
+            "#,
+            )
+            .bind(package_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO session_package (
+                    name,
+                    treatment_id,
+                    session_count,
+                    price,
+                    validity_days,
+                    active
+                )
+                VALUES ($1, $2, $3, $4, $5, $6);
+            "#,
+            )
+        };
+
+        q.bind(&package.name)
+            .bind(package.treatment_id)
+            .bind(package.session_count)
+            .bind(package.price)
+            .bind(package.validity_days)
+            .bind(package.active)
+            .execute(connection.as_mut())
+            .await?;
+
+        if let Some(package_id) = package.id {
+            return Ok(package_id);
+        }
+
+        Ok(sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(connection.as_mut())
+        .await?
+        .id)
+    }
+
+    /// Packages already sold keep their own copy of the terms.
+    pub async fn delete_session_package(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        sqlx::query(
+            r#"
+                DELETE FROM session_package
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    /// All packages of the customer including used up and expired ones.
+    pub async fn get_customer_package_list(
+        &self,
+        customer_id: i64,
+    ) -> ServiceResult<Vec<CustomerPackageDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_package_list(connection.as_mut(), Some(customer_id), None).await
+    }
+
+    pub async fn get_customer_package_by_id(
+        &self,
+        id: i64,
+    ) -> ServiceResult<Option<CustomerPackageDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_package_list(connection.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Sells a package to a customer. Without an explicit expiry date the validity of the
+    /// session package is counted from the purchase date.
+    pub async fn store_customer_package(
+        &self,
+        package: EditCustomerPackageDto,
+    ) -> ServiceResult<i64> {
+        if package.session_count <= 0 {
+            return Err(ServiceError::BadRequest(
+                "package must contain at least one session".to_string(),
+            ));
+        }
+        money::validate_vat_rate(package.vat_rate)?;
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let expiry_date = match (package.expiry_date, package.session_package_id) {
+            (Some(expiry_date), _) => Some(expiry_date),
+            (None, Some(session_package_id)) if package.id.is_none() => {
+                sqlx::query_as::<_, ValidityRow>(
+                    r#"
+                    SELECT p.validity_days
+                    FROM session_package p
+                    WHERE p.id = $1;
+                "#,
+                )
+                .bind(session_package_id)
+                .fetch_optional(transaction.as_mut())
+                .await?
+                .and_then(|row| row.validity_days)
+                .and_then(|days| {
+                    package
+                        .purchase_date
+                        .checked_add_days(Days::new(days as u64))
+                })
+            }
+            (None, _) => None,
+        };
+        if expiry_date.is_some_and(|expiry_date| expiry_date < package.purchase_date) {
+            return Err(ServiceError::BadRequest(
+                "package must not expire before its purchase".to_string(),
+            ));
+        }
+
+        if let Some(package_id) = package.id {
+            let assigned = sqlx::query_as::<_, AssignedSessionsRow>(
+                r#"
+                SELECT COALESCE(SUM(i.quantity), 0) AS sessions
+                FROM appointment_item i
+                WHERE i.customer_package_id = $1;
+            "#,
+            )
+            .bind(package_id)
+            .fetch_one(transaction.as_mut())
+            .await?
+            .sessions;
+            if package.session_count < assigned {
+                return Err(ServiceError::BadRequest(format!(
+                    "{} sessions are already used or booked",
+                    assigned
+                )));
+            }
+        }
+
+        let q = if let Some(package_id) = package.id {
+            sqlx::query(
+                r#"
+                UPDATE customer_package
+                SET customer_id = $2,
+                    session_package_id = $3,
+                    name = $4,
+                    treatment_id = $5,
+                    session_count = $6,
+                    price = $7,
+                    vat_rate = $8,
+                    purchase_date = $9,
+                    expiry_date = $10,
+                    note = $11
+                WHERE id = $1;
+            "#,
+            )
+            .bind(package_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO customer_package (
+                    customer_id,
+                    session_package_id,
+                    name,
+                    treatment_id,
+                    session_count,
+                    price,
+                    vat_rate,
+                    purchase_date,
+                    expiry_date,
+                    note
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+            "#,
+            )
+        };
+
+        q.bind(package.customer_id)
+            .bind(package.session_package_id)
+            .bind(&package.name)
+            .bind(package.treatment_id)
+            .bind(package.session_count)
+            .bind(package.price)
+            .bind(package.vat_rate)
+            .bind(package.purchase_date)
+            .bind(expiry_date)
+            .bind(&package.note)
+            .execute(transaction.as_mut())
+            .await?;
+
+        let package_id = if let Some(package_id) = package.id {
+            package_id
+        } else {
+            sqlx::query_as::<_, IdRow>(
+                r#"
+            SELECT last_insert_rowid() as id;
+            "#,
+            )
+            .fetch_one(transaction.as_mut())
+            .await?
+            .id
+        };
+
+        transaction.commit().await?;
+        Ok(package_id)
+    }
+
+    /// Packages can only be deleted as long as no session is booked on them, afterwards they
+    /// are part of the history of the customer and stay until they are used up or expire.
+    pub async fn delete_customer_package(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let package = get_package_list(connection.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next();
+        if package.is_some_and(|package| package.used_sessions + package.booked_sessions > 0) {
+            return Err(ServiceError::Conflict(
+                "package has sessions booked on appointments, which have to be kept".to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM customer_package
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Packages with their session balance, sessions count as used once their appointment is over.
+pub async fn get_package_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+    id: Option<i64>,
+) -> ServiceResult<Vec<CustomerPackageDto>> {
+    let now = Utc::now();
+    let today = time_zone::now_local(preference::get_time_zone(&mut *connection).await?).date();
+
+    Ok(sqlx::query_as::<_, CustomerPackageRow>(
+        r#"
+        SELECT
+            cp.id,
+            cp.customer_id,
+            cp.session_package_id,
+            cp.name,
+            cp.treatment_id,
+            cp.session_count,
+            COALESCE(SUM(
+                CASE WHEN a.end_utc <= $3 THEN i.quantity END
+            ), 0) AS used_sessions,
+            COALESCE(SUM(
+                CASE WHEN a.end_utc > $3 THEN i.quantity END
+            ), 0) AS booked_sessions,
+            cp.price,
+            cp.vat_rate,
+            cp.purchase_date,
+            cp.expiry_date,
+            cp.note
+        FROM customer_package cp
+        LEFT JOIN appointment_item i ON i.customer_package_id = cp.id
+        LEFT JOIN appointment a ON a.id = i.appointment_id
+        WHERE ($1 IS NULL OR cp.customer_id = $1)
+            AND ($2 IS NULL OR cp.id = $2)
+        GROUP BY cp.id
+        ORDER BY cp.purchase_date ASC, cp.id ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .bind(id)
+    .bind(now)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .map(|row| CustomerPackageDto {
+        id: row.id,
+        customer_id: row.customer_id,
+        session_package_id: row.session_package_id,
+        name: row.name,
+        treatment_id: row.treatment_id,
+        session_count: row.session_count,
+        used_sessions: row.used_sessions,
+        booked_sessions: row.booked_sessions,
+        remaining_sessions: row.session_count - row.used_sessions,
+        price: row.price,
+        vat_rate: row.vat_rate,
+        purchase_date: row.purchase_date,
+        expiry_date: row.expiry_date,
+        expired: row
+            .expiry_date
+            .is_some_and(|expiry_date| expiry_date < today),
+        note: row.note,
+    })
+    .collect())
+}
+
+/// Whether a package of the treatment still has sessions left and has not expired. These
+/// sessions are booked with the treatment, so it has to stay in the catalog.
+pub async fn has_open_packages(
+    connection: &mut SqliteConnection,
+    treatment_id: i64,
+) -> ServiceResult<bool> {
+    Ok(get_package_list(connection, None, None)
+        .await?
+        .iter()
+        .any(|package| {
+            package.treatment_id == Some(treatment_id)
+                && package.remaining_sessions > 0
+                && !package.expired
+        }))
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct ValidityRow {
+    pub validity_days: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AssignedSessionsRow {
+    pub sessions: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionPackageRow {
+    pub id: i64,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
+    pub price: i64,
+    pub validity_days: Option<i64>,
+    pub active: bool,
+}
+
+impl From<SessionPackageRow> for SessionPackageDto {
+    fn from(row: SessionPackageRow) -> SessionPackageDto {
+        SessionPackageDto {
+            id: row.id,
+            name: row.name,
+            treatment_id: row.treatment_id,
+            session_count: row.session_count,
+            price: row.price,
+            validity_days: row.validity_days,
+            active: row.active,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CustomerPackageRow {
+    pub id: i64,
+    pub customer_id: i64,
+    pub session_package_id: Option<i64>,
+    pub name: String,
+    pub treatment_id: Option<i64>,
+    pub session_count: i64,
+    pub used_sessions: i64,
+    pub booked_sessions: i64,
+    pub price: i64,
+    pub vat_rate: i64,
+    pub purchase_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub note: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::model::{EditCustomerAppointmentDto, EditTreatmentDto};
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    async fn sell(
+        state: &State,
+        customer_id: i64,
+        treatment_id: i64,
+        session_count: i64,
+        expiry_date: Option<&str>,
+    ) -> i64 {
+        state
+            .package
+            .store_customer_package(EditCustomerPackageDto {
+                id: None,
+                customer_id,
+                session_package_id: None,
+                name: format!("{} × Massage", session_count),
+                treatment_id: Some(treatment_id),
+                session_count,
+                price: session_count * 4500,
+                vat_rate: 1900,
+                purchase_date: date("2024-01-02"),
+                expiry_date: expiry_date.map(date),
+                note: String::new(),
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sessions_are_consumed_until_packages_are_used_up_or_expired() {
+        let state = state().await;
+        let treatment_id = state
+            .treatment
+            .store_treatment(EditTreatmentDto {
+                id: None,
+                name: "Massage".to_string(),
+                category: String::new(),
+                default_duration_minutes: 60,
+                default_price: 5000,
+                vat_rate: 1900,
+                active: true,
+                resource_ids: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let short = sell(&state, customer_id, treatment_id, 2, Some("2024-06-30")).await;
+        let long = sell(&state, customer_id, treatment_id, 5, None).await;
+        let expiring = sell(&state, customer_id, treatment_id, 3, Some("2024-03-31")).await;
+
+        let massage = |customer_package_id| {
+            let mut massage = item("Massage", 1, 5000);
+            massage.treatment_id = Some(treatment_id);
+            massage.customer_package_id = customer_package_id;
+            massage
+        };
+        let book = async |start_date: &str, customer_package_id| {
+            let start_date = start_date.parse::<NaiveDateTime>().unwrap();
+            let appointment: EditCustomerAppointmentDto =
+                appointment(customer_id, start_date, vec![massage(customer_package_id)]);
+            let id = state.appointment.store_appointment(appointment).await?;
+            let stored = state.appointment.get_appointment_by_id(id).await?.unwrap();
+            Ok::<_, ServiceError>(stored.items[0].customer_package_id)
+        };
+
+        // The package that expires first is used first, expired ones are skipped.
+        assert_eq!(
+            book("2024-03-04T10:00:00", None).await.unwrap(),
+            Some(expiring)
+        );
+        assert_eq!(
+            book("2024-04-08T10:00:00", None).await.unwrap(),
+            Some(short)
+        );
+        assert_eq!(
+            book("2024-04-15T10:00:00", None).await.unwrap(),
+            Some(short)
+        );
+        assert_eq!(book("2024-04-22T10:00:00", None).await.unwrap(), Some(long));
+        assert!(matches!(
+            book("2024-04-29T10:00:00", Some(expiring)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(matches!(
+            book("2024-05-06T10:00:00", Some(short)).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert_eq!(
+            book("2099-01-05T10:00:00", Some(long)).await.unwrap(),
+            Some(long)
+        );
+
+        let packages = state
+            .package
+            .get_customer_package_list(customer_id)
+            .await
+            .unwrap();
+        let balance = |id| {
+            let package = packages.iter().find(|package| package.id == id).unwrap();
+            (
+                package.used_sessions,
+                package.booked_sessions,
+                package.remaining_sessions,
+                package.expired,
+            )
+        };
+        assert_eq!(balance(short), (2, 0, 0, true));
+        assert_eq!(balance(long), (1, 1, 4, false));
+        assert_eq!(balance(expiring), (1, 0, 2, true));
+        let stored = state.appointment.get_appointment_list(customer_id).await;
+        assert!(
+            stored
+                .unwrap()
+                .iter()
+                .all(|appointment| appointment.price.gross.amount == 0)
+        );
+
+        // Packages with sessions on appointments are kept, like the treatment of open ones.
+        assert!(matches!(
+            state.package.delete_customer_package(short).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        let unused = sell(&state, customer_id, treatment_id, 1, None).await;
+        state.package.delete_customer_package(unused).await.unwrap();
+        assert!(matches!(
+            state.treatment.delete_treatment(treatment_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+}
@@ -0,0 +1,655 @@
+use chrono::{NaiveDate, Utc};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{
+        CustomerBalanceDto, EditPaymentDto, InvoiceKind, InvoiceStatus, OpenItemDto, OpenItemKind,
+        PaymentAllocationDto, PaymentDto, PaymentMethod,
+    },
+    money,
+    state::{
+        cash_journal::{self, CashJournalHead},
+        preference, voucher,
+    },
+};
+
+#[derive(Clone)]
+pub struct PaymentState {
+    pool: Arc<Pool<Sqlite>>,
+    cash_journal_head: CashJournalHead,
+}
+
+impl PaymentState {
+    pub fn new(pool: Arc<Pool<Sqlite>>, cash_journal_head: CashJournalHead) -> Self {
+        Self {
+            pool,
+            cash_journal_head,
+        }
+    }
+
+    pub async fn get_payment_list(
+        &self,
+        customer_id: Option<i64>,
+    ) -> ServiceResult<Vec<PaymentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_payment_list(connection.as_mut(), customer_id, None).await
+    }
+
+    pub async fn get_payment_by_id(&self, id: i64) -> ServiceResult<Option<PaymentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_payment_list(connection.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Stores the payment with its allocations. An allocation must not exceed the amount that
+    /// is still open on its appointment or invoice, the allocations together must not exceed
+    /// the payment.
+    pub async fn store_payment(&self, payment: EditPaymentDto) -> ServiceResult<i64> {
+        if payment.amount <= 0 {
+            return Err(ServiceError::BadRequest(
+                "payment amount must be positive".to_string(),
+            ));
+        }
+        let allocated: i64 = payment
+            .allocations
+            .iter()
+            .map(|allocation| allocation.amount)
+            .sum();
+        if allocated > payment.amount {
+            return Err(ServiceError::BadRequest(
+                "allocations exceed the payment amount".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let mut allocated_per_target: HashMap<(Option<i64>, Option<i64>), i64> = HashMap::new();
+        for allocation in &payment.allocations {
+            let previous = allocated_per_target
+                .entry((allocation.appointment_id, allocation.invoice_id))
+                .or_default();
+            check_allocation(
+                transaction.as_mut(),
+                payment.id,
+                payment.customer_id,
+                allocation,
+                *previous,
+            )
+            .await?;
+            *previous += allocation.amount;
+        }
+
+        match (payment.method, payment.voucher_id) {
+            (PaymentMethod::Voucher, Some(voucher_id)) => {
+                voucher::check_redemption(
+                    transaction.as_mut(),
+                    voucher_id,
+                    payment.id,
+                    payment.date,
+                    payment.amount,
+                )
+                .await?;
+            }
+            (PaymentMethod::Voucher, None) => {
+                return Err(ServiceError::BadRequest(
+                    "voucher payments need a voucher".to_string(),
+                ));
+            }
+            (_, Some(_)) => {
+                return Err(ServiceError::BadRequest(
+                    "only voucher payments can redeem a voucher".to_string(),
+                ));
+            }
+            (_, None) => {}
+        }
+
+        let q = if let Some(payment_id) = payment.id {
+            sqlx::query(
+                r#"
+                UPDATE payment
+                SET customer_id = $2,
+                    date = $3,
+                    amount = $4,
+                    method = $5,
+                    voucher_id = $6,
+                    reference = $7,
+                    note = $8
+                WHERE id = $1;
+            "#,
+            )
+            .bind(payment_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO payment (customer_id, date, amount, method, voucher_id, reference, note)
+                VALUES ($1, $2, $3, $4, $5, $6, $7);
+            "#,
+            )
+        };
+
+        q.bind(payment.customer_id)
+            .bind(payment.date)
+            .bind(payment.amount)
+            .bind(payment.method)
+            .bind(payment.voucher_id)
+            .bind(&payment.reference)
+            .bind(&payment.note)
+            .execute(transaction.as_mut())
+            .await?;
+
+        let payment_id = if let Some(payment_id) = payment.id {
+            sqlx::query(
+                r#"
+                DELETE FROM payment_allocation
+                WHERE payment_id = $1;
+            "#,
+            )
+            .bind(payment_id)
+            .execute(transaction.as_mut())
+            .await?;
+
+            payment_id
+        } else {
+            sqlx::query_as::<_, IdRow>(
+                r#"
+            SELECT last_insert_rowid() as id;
+            "#,
+            )
+            .fetch_one(transaction.as_mut())
+            .await?
+            .id
+        };
+
+        for allocation in &payment.allocations {
+            sqlx::query(
+                r#"
+                INSERT INTO payment_allocation (payment_id, appointment_id, invoice_id, amount)
+                VALUES ($1, $2, $3, $4);
+            "#,
+            )
+            .bind(payment_id)
+            .bind(allocation.appointment_id)
+            .bind(allocation.invoice_id)
+            .bind(allocation.amount)
+            .execute(transaction.as_mut())
+            .await?;
+        }
+
+        let cash_amount = if payment.method == PaymentMethod::Cash {
+            payment.amount
+        } else {
+            0
+        };
+        cash_journal::book_payment(
+            transaction.as_mut(),
+            payment_id,
+            payment.date,
+            cash_amount,
+            &payment.reference,
+        )
+        .await?;
+
+        transaction.commit().await?;
+        self.cash_journal_head
+            .update_after_booking(&self.pool)
+            .await;
+        Ok(payment_id)
+    }
+
+    /// Cash payments are reversed in the cash journal.
+    pub async fn delete_payment(&self, id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let payment = get_payment_list(transaction.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next();
+        if let Some(payment) = payment {
+            cash_journal::book_payment(
+                transaction.as_mut(),
+                payment.id,
+                payment.date,
+                0,
+                &payment.reference,
+            )
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM payment
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        self.cash_journal_head
+            .update_after_booking(&self.pool)
+            .await;
+        Ok(())
+    }
+
+    /// Items with an outstanding amount, ordered by due date.
+    pub async fn get_open_item_list(
+        &self,
+        customer_id: Option<i64>,
+    ) -> ServiceResult<Vec<OpenItemDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_item_list(connection.as_mut(), customer_id)
+            .await?
+            .into_iter()
+            .filter(|item| item.outstanding.amount > 0)
+            .collect())
+    }
+
+    /// Past appointments and issued invoices against all payments of the customer. Unallocated
+    /// payments and payments for future appointments count as credit.
+    pub async fn get_customer_balance(
+        &self,
+        customer_id: i64,
+    ) -> ServiceResult<CustomerBalanceDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let currency = preference::get_currency(connection.as_mut()).await?;
+
+        let due: i64 = get_item_list(connection.as_mut(), Some(customer_id))
+            .await?
+            .iter()
+            .map(|item| item.amount.amount)
+            .sum();
+        let paid = sqlx::query_as::<_, SumRow>(
+            r#"
+            SELECT COALESCE(SUM(p.amount), 0) AS amount
+            FROM payment p
+            WHERE p.customer_id = $1;
+        "#,
+        )
+        .bind(customer_id)
+        .fetch_one(connection.as_mut())
+        .await?
+        .amount;
+
+        Ok(CustomerBalanceDto {
+            customer_id,
+            due: money::money(due, currency),
+            paid: money::money(paid, currency),
+            balance: money::money(due - paid, currency),
+        })
+    }
+}
+
+pub async fn get_payment_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+    id: Option<i64>,
+) -> ServiceResult<Vec<PaymentDto>> {
+    let rows = sqlx::query_as::<_, PaymentRow>(
+        r#"
+        SELECT p.id, p.customer_id, p.date, p.amount, p.method, p.voucher_id, p.reference, p.note
+        FROM payment p
+        WHERE ($1 IS NULL OR p.customer_id = $1)
+            AND ($2 IS NULL OR p.id = $2)
+        ORDER BY p.date ASC, p.id ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let payment_ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let mut allocations = sqlx::query_as::<_, AllocationRow>(
+        r#"
+        SELECT pa.payment_id, pa.appointment_id, pa.invoice_id, pa.amount
+        FROM payment_allocation pa
+        WHERE pa.payment_id IN (SELECT value FROM json_each($1))
+        ORDER BY pa.payment_id ASC, pa.id ASC;
+    "#,
+    )
+    .bind(serde_json::to_string(&payment_ids)?)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .fold(
+        HashMap::<i64, Vec<PaymentAllocationDto>>::new(),
+        |mut map, row| {
+            map.entry(row.payment_id)
+                .or_default()
+                .push(PaymentAllocationDto {
+                    appointment_id: row.appointment_id,
+                    invoice_id: row.invoice_id,
+                    amount: row.amount,
+                });
+            map
+        },
+    );
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let allocations = allocations.remove(&row.id).unwrap_or_default();
+            let allocated: i64 = allocations.iter().map(|allocation| allocation.amount).sum();
+            PaymentDto {
+                id: row.id,
+                customer_id: row.customer_id,
+                date: row.date,
+                amount: row.amount,
+                method: row.method,
+                voucher_id: row.voucher_id,
+                reference: row.reference,
+                note: row.note,
+                allocations,
+                unallocated: row.amount - allocated,
+            }
+        })
+        .collect())
+}
+
+/// All claims against customers with their paid amount: appointments that have started and
+/// are not part of an issued invoice, and issued invoices. Payments allocated to an appointment
+/// count for the invoice that contains it.
+pub async fn get_item_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+) -> ServiceResult<Vec<OpenItemDto>> {
+    let now = Utc::now();
+    let currency = preference::get_currency(&mut *connection).await?;
+
+    Ok(sqlx::query_as::<_, ItemRow>(
+        r#"
+        SELECT
+            'appointment' AS kind,
+            a.id AS appointment_id,
+            NULL AS invoice_id,
+            a.customer_id,
+            c.first_name,
+            c.last_name,
+            CAST(a.number AS TEXT) AS reference,
+            date(a.start_date) AS date,
+            date(a.start_date) AS due_date,
+            COALESCE((
+                SELECT SUM(i.quantity * i.unit_price - i.discount)
+                FROM appointment_item i
+                WHERE i.appointment_id = a.id
+            ), 0) AS amount,
+            COALESCE((
+                SELECT SUM(pa.amount)
+                FROM payment_allocation pa
+                WHERE pa.appointment_id = a.id
+            ), 0) AS paid
+        FROM appointment a
+        JOIN customer c ON c.id = a.customer_id
+        WHERE ($1 IS NULL OR a.customer_id = $1)
+            AND a.start_utc <= $2
+            AND NOT EXISTS (
+                SELECT 1
+                FROM invoice_item ii
+                JOIN invoice inv ON inv.id = ii.invoice_id
+                WHERE ii.appointment_id = a.id
+                    AND inv.kind = 'invoice'
+                    AND inv.status = 'issued'
+            )
+        UNION ALL
+        SELECT
+            'invoice' AS kind,
+            NULL AS appointment_id,
+            inv.id AS invoice_id,
+            inv.customer_id,
+            c.first_name,
+            c.last_name,
+            inv.number AS reference,
+            inv.issue_date AS date,
+            inv.due_date,
+            COALESCE((
+                SELECT SUM(i.quantity * i.unit_price - i.discount)
+                FROM invoice_item i
+                WHERE i.invoice_id = inv.id
+            ), 0) AS amount,
+            COALESCE((
+                SELECT SUM(pa.amount)
+                FROM payment_allocation pa
+                WHERE pa.invoice_id = inv.id
+                    OR pa.appointment_id IN (
+                        SELECT ii.appointment_id
+                        FROM invoice_item ii
+                        WHERE ii.invoice_id = inv.id
+                    )
+            ), 0) AS paid
+        FROM invoice inv
+        JOIN customer c ON c.id = inv.customer_id
+        WHERE ($1 IS NULL OR inv.customer_id = $1)
+            AND inv.kind = 'invoice'
+            AND inv.status = 'issued'
+        ORDER BY due_date ASC, reference ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .bind(now)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| OpenItemDto {
+        kind: row.kind,
+        appointment_id: row.appointment_id,
+        invoice_id: row.invoice_id,
+        customer_id: row.customer_id,
+        first_name: row.first_name,
+        last_name: row.last_name,
+        reference: row.reference,
+        date: row.date,
+        due_date: row.due_date,
+        amount: money::money(row.amount, currency),
+        paid: money::money(row.paid, currency),
+        outstanding: money::money(row.amount - row.paid, currency),
+    })
+    .collect())
+}
+
+/// `previous` is the amount that earlier allocations of the same payment assign to the target.
+async fn check_allocation(
+    connection: &mut SqliteConnection,
+    payment_id: Option<i64>,
+    customer_id: i64,
+    allocation: &PaymentAllocationDto,
+    previous: i64,
+) -> ServiceResult<()> {
+    if allocation.amount <= 0 {
+        return Err(ServiceError::BadRequest(
+            "allocated amount must be positive".to_string(),
+        ));
+    }
+
+    let target = match (allocation.appointment_id, allocation.invoice_id) {
+        (Some(appointment_id), None) => {
+            let Some(target) = sqlx::query_as::<_, AppointmentTargetRow>(
+                r#"
+                SELECT
+                    a.customer_id,
+                    COALESCE((
+                        SELECT SUM(i.quantity * i.unit_price - i.discount)
+                        FROM appointment_item i
+                        WHERE i.appointment_id = a.id
+                    ), 0) AS amount,
+                    COALESCE((
+                        SELECT SUM(pa.amount)
+                        FROM payment_allocation pa
+                        WHERE pa.appointment_id = a.id AND pa.payment_id IS NOT $2
+                    ), 0) AS paid,
+                    (
+                        SELECT inv.number
+                        FROM invoice_item ii
+                        JOIN invoice inv ON inv.id = ii.invoice_id
+                        WHERE ii.appointment_id = a.id
+                            AND inv.kind = 'invoice'
+                            AND inv.status = 'issued'
+                    ) AS invoice_number
+                FROM appointment a
+                WHERE a.id = $1;
+            "#,
+            )
+            .bind(appointment_id)
+            .bind(payment_id)
+            .fetch_optional(&mut *connection)
+            .await?
+            else {
+                return Err(ServiceError::BadRequest(format!(
+                    "appointment {} does not exist",
+                    appointment_id
+                )));
+            };
+            if let Some(invoice_number) = target.invoice_number {
+                return Err(ServiceError::BadRequest(format!(
+                    "appointment {} is part of invoice {}, please allocate the payment to the invoice",
+                    appointment_id, invoice_number
+                )));
+            }
+            (
+                format!("appointment {}", appointment_id),
+                target.customer_id,
+                target.amount,
+                target.paid,
+            )
+        }
+        (None, Some(invoice_id)) => {
+            let Some(target) = sqlx::query_as::<_, InvoiceTargetRow>(
+                r#"
+                SELECT
+                    inv.customer_id,
+                    inv.kind,
+                    inv.status,
+                    COALESCE((
+                        SELECT SUM(i.quantity * i.unit_price - i.discount)
+                        FROM invoice_item i
+                        WHERE i.invoice_id = inv.id
+                    ), 0) AS amount,
+                    COALESCE((
+                        SELECT SUM(pa.amount)
+                        FROM payment_allocation pa
+                        WHERE (
+                            pa.invoice_id = inv.id
+                            OR pa.appointment_id IN (
+                                SELECT ii.appointment_id
+                                FROM invoice_item ii
+                                WHERE ii.invoice_id = inv.id
+                            )
+                        ) AND pa.payment_id IS NOT $2
+                    ), 0) AS paid
+                FROM invoice inv
+                WHERE inv.id = $1;
+            "#,
+            )
+            .bind(invoice_id)
+            .bind(payment_id)
+            .fetch_optional(&mut *connection)
+            .await?
+            else {
+                return Err(ServiceError::BadRequest(format!(
+                    "invoice {} does not exist",
+                    invoice_id
+                )));
+            };
+            if target.kind != InvoiceKind::Invoice || target.status != InvoiceStatus::Issued {
+                return Err(ServiceError::BadRequest(format!(
+                    "invoice {} is not open for payments",
+                    invoice_id
+                )));
+            }
+            (
+                format!("invoice {}", invoice_id),
+                target.customer_id.unwrap_or_default(),
+                target.amount,
+                target.paid,
+            )
+        }
+        _ => {
+            return Err(ServiceError::BadRequest(
+                "allocation needs either an appointment or an invoice".to_string(),
+            ));
+        }
+    };
+
+    let (name, target_customer_id, amount, paid) = target;
+    if target_customer_id != customer_id {
+        return Err(ServiceError::BadRequest(format!(
+            "{} belongs to another customer",
+            name
+        )));
+    }
+    if paid + previous + allocation.amount > amount {
+        return Err(ServiceError::BadRequest(format!(
+            "allocation exceeds the open amount of {}",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SumRow {
+    pub amount: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct PaymentRow {
+    pub id: i64,
+    pub customer_id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+    pub method: PaymentMethod,
+    pub voucher_id: Option<i64>,
+    pub reference: String,
+    pub note: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct AllocationRow {
+    pub payment_id: i64,
+    pub appointment_id: Option<i64>,
+    pub invoice_id: Option<i64>,
+    pub amount: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct ItemRow {
+    pub kind: OpenItemKind,
+    pub appointment_id: Option<i64>,
+    pub invoice_id: Option<i64>,
+    pub customer_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub reference: String,
+    pub date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub amount: i64,
+    pub paid: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct AppointmentTargetRow {
+    pub customer_id: i64,
+    pub amount: i64,
+    pub paid: i64,
+    pub invoice_number: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct InvoiceTargetRow {
+    pub customer_id: Option<i64>,
+    pub kind: InvoiceKind,
+    pub status: InvoiceStatus,
+    pub amount: i64,
+    pub paid: i64,
+}
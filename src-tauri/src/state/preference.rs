@@ -1,11 +1,20 @@
-use sqlx::{Pool, Sqlite};
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+use sqlx::{Pool, Sqlite, SqliteConnection};
 use std::sync::Arc;
 
 use crate::{
-    error::ServiceResult,
-    model::{EditPreferenceDto, PreferenceDto},
+    error::{ServiceError, ServiceResult},
+    model::{Currency, EditPreferenceDto, PreferenceDto},
+    time_zone,
 };
 
+const CURRENCY_KEY: &str = "general.currency";
+const ICAL_HOST_KEY: &str = "ical.host";
+
+/// Credentials that are only listed for administrators.
+const SECRET_KEYS: [&str; 1] = ["smtp.password"];
+
 #[derive(Clone)]
 pub struct PreferenceState {
     pool: Arc<Pool<Sqlite>>,
@@ -16,7 +25,11 @@ impl PreferenceState {
         Self { pool }
     }
 
-    pub async fn get_preference_list(&self) -> ServiceResult<Vec<PreferenceDto>> {
+    /// All preferences, without secrets the secret keys are left out.
+    pub async fn get_preference_list(
+        &self,
+        with_secrets: bool,
+    ) -> ServiceResult<Vec<PreferenceDto>> {
         let mut connection = self.pool.acquire().await.unwrap();
         Ok(sqlx::query_as::<_, PreferenceRow>(
             r#"
@@ -26,13 +39,34 @@ impl PreferenceState {
         .fetch_all(connection.as_mut())
         .await?
         .into_iter()
+        .filter(|row| with_secrets || !SECRET_KEYS.contains(&row.key.as_str()))
         .map(|row| row.into())
         .collect())
     }
 
+    /// Reads a single preference, values are stored as json by the frontend.
+    pub async fn get_preference<T: DeserializeOwned>(&self, key: &str) -> ServiceResult<Option<T>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_preference(connection.as_mut(), key).await
+    }
+
+    pub async fn get_time_zone(&self) -> ServiceResult<Tz> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_time_zone(connection.as_mut()).await
+    }
+
+    pub async fn get_ical_host(&self) -> ServiceResult<String> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_ical_host(connection.as_mut()).await
+    }
+
     pub async fn store_preference(&self, preference: EditPreferenceDto) -> ServiceResult<()> {
         let mut connection = self.pool.acquire().await.unwrap();
 
+        if preference.key == CURRENCY_KEY {
+            check_currency_change(connection.as_mut(), preference.value.as_deref()).await?;
+        }
+
         if let Some(value) = preference.value {
             sqlx::query(
                 r#"
@@ -61,6 +95,104 @@ impl PreferenceState {
     }
 }
 
+pub async fn get_preference<T: DeserializeOwned>(
+    connection: &mut SqliteConnection,
+    key: &str,
+) -> ServiceResult<Option<T>> {
+    let row = sqlx::query_as::<_, PreferenceRow>(
+        r#"
+        SELECT p.key, p.value FROM preference p
+        WHERE p.key = $1;
+    "#,
+    )
+    .bind(key)
+    .fetch_optional(connection)
+    .await?;
+
+    Ok(match row {
+        Some(row) => Some(serde_json::from_str(&row.value)?),
+        None => None,
+    })
+}
+
+/// Time zone of the practice, all appointments are entered in this zone.
+pub async fn get_time_zone(connection: &mut SqliteConnection) -> ServiceResult<Tz> {
+    match get_preference::<String>(connection, time_zone::TIME_ZONE_KEY).await? {
+        Some(name) => time_zone::parse_time_zone(&name),
+        None => Ok(time_zone::DEFAULT_TIME_ZONE),
+    }
+}
+
+/// Currency of all prices, amounts are stored without it.
+pub async fn get_currency(connection: &mut SqliteConnection) -> ServiceResult<Currency> {
+    Ok(get_preference::<Currency>(connection, CURRENCY_KEY)
+        .await?
+        .unwrap_or_default())
+}
+
+/// Host part of the uids of exported appointments. It is generated on first use and kept, so
+/// every installation exports its own uids.
+pub async fn get_ical_host(connection: &mut SqliteConnection) -> ServiceResult<String> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO preference (key, value)
+        VALUES ($1, json_quote(lower(hex(randomblob(8))) || '.customer-manager'));
+    "#,
+    )
+    .bind(ICAL_HOST_KEY)
+    .execute(&mut *connection)
+    .await?;
+
+    get_preference::<String>(connection, ICAL_HOST_KEY)
+        .await?
+        .ok_or(ServiceError::InternalServerError(
+            "preference",
+            "iCalendar host is missing".to_string(),
+        ))
+}
+
+/// Amounts other than invoices are stored without their currency and shown in the current one,
+/// so it can only be changed as long as no amounts have been recorded.
+async fn check_currency_change(
+    connection: &mut SqliteConnection,
+    value: Option<&str>,
+) -> ServiceResult<()> {
+    let currency = match value {
+        Some(value) => serde_json::from_str::<Currency>(value)
+            .map_err(|_| ServiceError::BadRequest(format!("unknown currency {}", value)))?,
+        None => Currency::default(),
+    };
+    if currency == get_currency(&mut *connection).await? {
+        return Ok(());
+    }
+
+    let recorded = sqlx::query_as::<_, RecordedRow>(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM appointment_item)
+            OR EXISTS (SELECT 1 FROM invoice)
+            OR EXISTS (SELECT 1 FROM payment)
+            OR EXISTS (SELECT 1 FROM voucher)
+            OR EXISTS (SELECT 1 FROM customer_package)
+            OR EXISTS (SELECT 1 FROM cash_journal_entry) AS recorded;
+    "#,
+    )
+    .fetch_one(connection)
+    .await?
+    .recorded;
+    if recorded {
+        return Err(ServiceError::Conflict(
+            "currency can't be changed once amounts have been recorded".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct RecordedRow {
+    pub recorded: bool,
+}
+
 #[derive(sqlx::FromRow)]
 struct PreferenceRow {
     pub key: String,
@@ -75,3 +207,73 @@ impl From<PreferenceRow> for PreferenceDto {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::state::testing::{appointment, customer, item, set_preference, state};
+
+    #[tokio::test]
+    async fn secrets_are_only_listed_on_request() {
+        let state = state().await;
+        set_preference(&state, "smtp.username", "praxis").await;
+        set_preference(&state, "smtp.password", "geheim").await;
+
+        let keys = |preferences: Vec<PreferenceDto>| {
+            preferences
+                .into_iter()
+                .map(|preference| preference.key)
+                .collect::<Vec<_>>()
+        };
+        let public = keys(state.preference.get_preference_list(false).await.unwrap());
+        assert!(public.contains(&"smtp.username".to_string()));
+        assert!(!public.contains(&"smtp.password".to_string()));
+        let all = keys(state.preference.get_preference_list(true).await.unwrap());
+        assert!(all.contains(&"smtp.password".to_string()));
+    }
+
+    #[tokio::test]
+    async fn currency_is_fixed_once_amounts_are_recorded() {
+        let state = state().await;
+        let currency = |currency: Option<Currency>| EditPreferenceDto {
+            key: CURRENCY_KEY.to_string(),
+            value: currency.map(|currency| serde_json::to_string(&currency).unwrap()),
+        };
+        set_preference(&state, CURRENCY_KEY, Currency::Chf).await;
+
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let start_date = NaiveDate::from_ymd_opt(2025, 3, 3)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                start_date,
+                vec![item("Massage", 1, 5000)],
+            ))
+            .await
+            .unwrap();
+
+        for value in [Some(Currency::Eur), None] {
+            assert!(matches!(
+                state.preference.store_preference(currency(value)).await,
+                Err(ServiceError::Conflict(_))
+            ));
+        }
+        state
+            .preference
+            .store_preference(currency(Some(Currency::Chf)))
+            .await
+            .unwrap();
+        let stored = state.preference.get_preference::<Currency>(CURRENCY_KEY);
+        assert_eq!(stored.await.unwrap(), Some(Currency::Chf));
+    }
+}
@@ -0,0 +1,571 @@
+use chrono::{Datelike, NaiveTime};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{
+        AppliedPriceRuleDto, EditAppointmentItemDto, EditCustomerAppointmentDto, EditPriceRuleDto,
+        PriceRuleDto, PriceRuleKind,
+    },
+    money,
+    state::customer,
+};
+
+/// Percentages are given in basis points like VAT rates.
+const PERCENTAGE_BASIS: i64 = 10_000;
+
+#[derive(Clone)]
+pub struct PriceRuleState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl PriceRuleState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_price_rule_list(&self) -> ServiceResult<Vec<PriceRuleDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_price_rule_list(connection.as_mut(), None).await
+    }
+
+    pub async fn get_price_rule_by_id(&self, id: i64) -> ServiceResult<Option<PriceRuleDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_price_rule_list(connection.as_mut(), Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    pub async fn store_price_rule(&self, rule: EditPriceRuleDto) -> ServiceResult<i64> {
+        match rule.kind {
+            PriceRuleKind::Percentage if !(1..=PERCENTAGE_BASIS).contains(&rule.value) => {
+                return Err(ServiceError::BadRequest(format!(
+                    "percentage must be between 1 and {}",
+                    PERCENTAGE_BASIS
+                )));
+            }
+            PriceRuleKind::Fixed if rule.value <= 0 => {
+                return Err(ServiceError::BadRequest(
+                    "discount must be positive".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if rule
+            .weekdays
+            .iter()
+            .any(|weekday| !(1..=7).contains(weekday))
+        {
+            return Err(ServiceError::BadRequest(
+                "weekdays must be between 1 and 7".to_string(),
+            ));
+        }
+        match (rule.start_time, rule.end_time) {
+            (Some(start_time), Some(end_time)) if start_time >= end_time => {
+                return Err(ServiceError::BadRequest(
+                    "start time must be before the end time".to_string(),
+                ));
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(ServiceError::BadRequest(
+                    "time range needs a start and an end".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if rule.min_visit_count.is_some_and(|count| count < 0) {
+            return Err(ServiceError::BadRequest(
+                "visit count must not be negative".to_string(),
+            ));
+        }
+
+        let customer_tag = rule
+            .customer_tag
+            .as_deref()
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty());
+        let mut weekdays = rule.weekdays.clone();
+        weekdays.sort();
+        weekdays.dedup();
+
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let q = if let Some(rule_id) = rule.id {
+            sqlx::query(
+                r#"
+                UPDATE price_rule
+                SET name = $2,
+                    kind = $3,
+                    value = $4,
+                    customer_tag = $5,
+                    treatment_id = $6,
+                    min_visit_count = $7,
+                    weekdays = $8,
+                    start_time = $9,
+                    end_time = $10,
+                    active = $11
+                WHERE id = $1;
+            "#,
+            )
+            .bind(rule_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO price_rule (
+                    name,
+                    kind,
+                    value,
+                    customer_tag,
+                    treatment_id,
+                    min_visit_count,
+                    weekdays,
+                    start_time,
+                    end_time,
+                    active
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+            "#,
+            )
+        };
+
+        q.bind(&rule.name)
+            .bind(rule.kind)
+            .bind(rule.value)
+            .bind(customer_tag)
+            .bind(rule.treatment_id)
+            .bind(rule.min_visit_count)
+            .bind(serde_json::to_string(&weekdays)?)
+            .bind(rule.start_time)
+            .bind(rule.end_time)
+            .bind(rule.active)
+            .execute(connection.as_mut())
+            .await?;
+
+        if let Some(rule_id) = rule.id {
+            return Ok(rule_id);
+        }
+
+        Ok(sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(connection.as_mut())
+        .await?
+        .id)
+    }
+
+    /// Rules that have been applied to an appointment can only be deactivated.
+    pub async fn delete_price_rule(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let applied = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT i.id
+            FROM appointment_item i
+            WHERE i.price_rule_id = $1
+            LIMIT 1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .is_some();
+        if applied {
+            return Err(ServiceError::Conflict(
+                "price rule has been applied to appointments, please deactivate it instead"
+                    .to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM price_rule
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    /// The rules that storing the appointment would apply to items without a discount, one entry
+    /// per item.
+    pub async fn price_appointment(
+        &self,
+        appointment: EditCustomerAppointmentDto,
+    ) -> ServiceResult<Vec<Option<AppliedPriceRuleDto>>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        evaluate_price_rules(connection.as_mut(), &appointment).await
+    }
+}
+
+pub async fn get_price_rule_list(
+    connection: &mut SqliteConnection,
+    id: Option<i64>,
+) -> ServiceResult<Vec<PriceRuleDto>> {
+    sqlx::query_as::<_, PriceRuleRow>(
+        r#"
+        SELECT
+            r.id,
+            r.name,
+            r.kind,
+            r.value,
+            r.customer_tag,
+            r.treatment_id,
+            r.min_visit_count,
+            r.weekdays,
+            r.start_time,
+            r.end_time,
+            r.active
+        FROM price_rule r
+        WHERE ($1 IS NULL OR r.id = $1)
+        ORDER BY r.name ASC, r.id ASC;
+    "#,
+    )
+    .bind(id)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(PriceRuleDto {
+            id: row.id,
+            name: row.name,
+            kind: row.kind,
+            value: row.value,
+            customer_tag: row.customer_tag,
+            treatment_id: row.treatment_id,
+            min_visit_count: row.min_visit_count,
+            weekdays: serde_json::from_str(&row.weekdays)?,
+            start_time: row.start_time,
+            end_time: row.end_time,
+            active: row.active,
+        })
+    })
+    .collect()
+}
+
+/// The active rule with the highest discount for every item of the appointment.
+pub async fn evaluate_price_rules(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<Vec<Option<AppliedPriceRuleDto>>> {
+    Ok(get_applicable_price_rules(connection, appointment)
+        .await?
+        .into_iter()
+        .map(|rules| rules.into_iter().next())
+        .collect())
+}
+
+/// All active rules that grant a discount on an item, for every item of the appointment and
+/// the highest discount first. Rules only apply to treatment items, items paid by a package are
+/// priced by the package instead.
+pub async fn get_applicable_price_rules(
+    connection: &mut SqliteConnection,
+    appointment: &EditCustomerAppointmentDto,
+) -> ServiceResult<Vec<Vec<AppliedPriceRuleDto>>> {
+    let rules: Vec<PriceRuleDto> = get_price_rule_list(&mut *connection, None)
+        .await?
+        .into_iter()
+        .filter(|rule| rule.active)
+        .collect();
+    if rules.is_empty() {
+        return Ok(vec![Vec::new(); appointment.items.len()]);
+    }
+
+    let tags = customer::get_tag_list(&mut *connection, Some(appointment.customer_id))
+        .await?
+        .remove(&appointment.customer_id)
+        .unwrap_or_default();
+    // Several appointments on the same day count as a single visit.
+    let visit_count = sqlx::query_as::<_, CountRow>(
+        r#"
+        SELECT COUNT(DISTINCT date(a.start_date)) AS count
+        FROM appointment a
+        WHERE a.customer_id = $1
+            AND date(a.start_date) < date($2)
+            AND a.id IS NOT $3;
+    "#,
+    )
+    .bind(appointment.customer_id)
+    .bind(appointment.start_date)
+    .bind(appointment.id)
+    .fetch_one(&mut *connection)
+    .await?
+    .count;
+
+    let weekday = appointment.start_date.weekday().number_from_monday();
+    let time = appointment.start_date.time();
+    let matching: Vec<&PriceRuleDto> = rules
+        .iter()
+        .filter(|rule| {
+            rule.customer_tag.as_ref().is_none_or(|rule_tag| {
+                tags.iter()
+                    .any(|tag| tag.to_lowercase() == rule_tag.to_lowercase())
+            }) && rule
+                .min_visit_count
+                .is_none_or(|min_visit_count| visit_count >= min_visit_count)
+                && (rule.weekdays.is_empty() || rule.weekdays.contains(&weekday))
+                && is_in_time_range(time, rule.start_time, rule.end_time)
+        })
+        .collect();
+
+    Ok(appointment
+        .items
+        .iter()
+        .map(|item| {
+            let Some(treatment_id) = item.treatment_id else {
+                return Vec::new();
+            };
+            if item.customer_package_id.is_some() {
+                return Vec::new();
+            }
+            let mut applicable: Vec<AppliedPriceRuleDto> = matching
+                .iter()
+                .filter(|rule| {
+                    rule.treatment_id
+                        .is_none_or(|rule_treatment_id| rule_treatment_id == treatment_id)
+                })
+                .map(|rule| AppliedPriceRuleDto {
+                    price_rule_id: rule.id,
+                    name: rule.name.clone(),
+                    discount: discount_of(rule, item),
+                })
+                .filter(|applied| applied.discount > 0)
+                .collect();
+            applicable.sort_by_key(|applied| (-applied.discount, applied.price_rule_id));
+            applicable
+        })
+        .collect())
+}
+
+fn is_in_time_range(time: NaiveTime, start: Option<NaiveTime>, end: Option<NaiveTime>) -> bool {
+    start.is_none_or(|start| time >= start) && end.is_none_or(|end| time < end)
+}
+
+/// Discount of the rule on the whole item, at most the item price.
+fn discount_of(rule: &PriceRuleDto, item: &EditAppointmentItemDto) -> i64 {
+    let price = item.quantity * item.unit_price;
+    let discount = match rule.kind {
+        PriceRuleKind::Percentage => {
+            money::round_div(price as i128 * rule.value as i128, PERCENTAGE_BASIS as i128)
+        }
+        PriceRuleKind::Fixed => rule.value * item.quantity,
+    };
+    discount.min(price)
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct CountRow {
+    pub count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct PriceRuleRow {
+    pub id: i64,
+    pub name: String,
+    pub kind: PriceRuleKind,
+    pub value: i64,
+    pub customer_tag: Option<String>,
+    pub treatment_id: Option<i64>,
+    pub min_visit_count: Option<i64>,
+    pub weekdays: String,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub active: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::{AppointmentItemDto, EditTreatmentDto};
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    /// Rule of 10 % on the treatment, or on all treatments without one.
+    fn rule(treatment_id: Option<i64>) -> EditPriceRuleDto {
+        EditPriceRuleDto {
+            id: None,
+            name: "Massage week".to_string(),
+            kind: PriceRuleKind::Percentage,
+            value: 1000,
+            customer_tag: None,
+            treatment_id,
+            min_visit_count: None,
+            weekdays: Vec::new(),
+            start_time: None,
+            end_time: None,
+            active: true,
+        }
+    }
+
+    /// Stores a rule of 10 % on massages, returns the ids of the rule, the massage and the
+    /// customer.
+    async fn massage_rule(state: &State) -> (Option<i64>, i64, i64) {
+        let treatment_id = state
+            .treatment
+            .store_treatment(EditTreatmentDto {
+                id: None,
+                name: "Massage".to_string(),
+                category: String::new(),
+                default_duration_minutes: 60,
+                default_price: 5000,
+                vat_rate: 1900,
+                active: true,
+                resource_ids: Vec::new(),
+            })
+            .await
+            .unwrap();
+        let rule_id = state
+            .price_rule
+            .store_price_rule(rule(Some(treatment_id)))
+            .await
+            .unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        (Some(rule_id), treatment_id, customer_id)
+    }
+
+    fn massage(
+        treatment_id: i64,
+        quantity: i64,
+        discount: i64,
+        price_rule_id: Option<i64>,
+    ) -> EditAppointmentItemDto {
+        EditAppointmentItemDto {
+            treatment_id: Some(treatment_id),
+            discount,
+            price_rule_id,
+            ..item("Massage", quantity, 5000)
+        }
+    }
+
+    /// Stores the appointment of the customer with the items and returns its stored items.
+    async fn store(
+        state: &State,
+        id: Option<i64>,
+        customer_id: i64,
+        items: Vec<EditAppointmentItemDto>,
+    ) -> (i64, Vec<AppointmentItemDto>) {
+        let start_date = NaiveDate::from_ymd_opt(2025, 3, 3)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let appointment = EditCustomerAppointmentDto {
+            id,
+            ..appointment(customer_id, start_date, items)
+        };
+        let id = state
+            .appointment
+            .store_appointment(appointment)
+            .await
+            .unwrap();
+        let stored = state
+            .appointment
+            .get_appointment_by_id(id)
+            .await
+            .unwrap()
+            .unwrap();
+        (id, stored.items)
+    }
+
+    fn discounts(items: &[AppointmentItemDto]) -> Vec<(i64, Option<i64>)> {
+        items
+            .iter()
+            .map(|item| (item.discount, item.price_rule_id))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn edited_appointments_apply_rules_to_new_items() {
+        let state = state().await;
+        let (rule_id, treatment_id, customer_id) = massage_rule(&state).await;
+
+        let (id, items) = store(&state, None, customer_id, vec![item("Oil", 1, 1000)]).await;
+        assert_eq!(discounts(&items), vec![(0, None)]);
+
+        let items = vec![
+            item("Oil", 1, 1000),
+            massage(treatment_id, 1, 0, None),
+            massage(treatment_id, 1, 300, None),
+        ];
+        let (_, items) = store(&state, Some(id), customer_id, items).await;
+        assert_eq!(
+            discounts(&items),
+            vec![(0, None), (500, rule_id), (300, None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn discounts_of_rules_are_recomputed() {
+        let state = state().await;
+        let (rule_id, treatment_id, customer_id) = massage_rule(&state).await;
+        let other_rule = EditPriceRuleDto {
+            active: false,
+            ..rule(None)
+        };
+        let other_rule_id = state.price_rule.store_price_rule(other_rule).await.unwrap();
+
+        let items = vec![
+            massage(treatment_id, 1, 5000, rule_id),
+            massage(treatment_id, 1, 4000, Some(other_rule_id)),
+        ];
+        let (_, items) = store(&state, None, customer_id, items).await;
+        assert_eq!(discounts(&items), vec![(500, rule_id), (500, rule_id)]);
+    }
+
+    #[tokio::test]
+    async fn unchanged_items_keep_their_discount() {
+        let state = state().await;
+        let (rule_id, treatment_id, customer_id) = massage_rule(&state).await;
+        let (id, items) = store(
+            &state,
+            None,
+            customer_id,
+            vec![massage(treatment_id, 1, 0, None)],
+        )
+        .await;
+        assert_eq!(discounts(&items), vec![(500, rule_id)]);
+
+        state
+            .price_rule
+            .store_price_rule(EditPriceRuleDto {
+                id: rule_id,
+                value: 2000,
+                ..rule(Some(treatment_id))
+            })
+            .await
+            .unwrap();
+        let (_, items) = store(
+            &state,
+            Some(id),
+            customer_id,
+            vec![massage(treatment_id, 1, 500, rule_id)],
+        )
+        .await;
+        assert_eq!(discounts(&items), vec![(500, rule_id)]);
+
+        let (_, items) = store(
+            &state,
+            Some(id),
+            customer_id,
+            vec![massage(treatment_id, 2, 500, rule_id)],
+        )
+        .await;
+        assert_eq!(discounts(&items), vec![(2000, rule_id)]);
+    }
+}
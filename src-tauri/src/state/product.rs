@@ -0,0 +1,476 @@
+use chrono::NaiveDateTime;
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{
+        EditProductDto, EditStockMovementDto, ProductDto, StockMovementDto, StockMovementKind,
+    },
+    money,
+};
+
+#[derive(Clone)]
+pub struct ProductState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl ProductState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_product_list(&self) -> ServiceResult<Vec<ProductDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ProductRow>(
+            r#"
+            SELECT
+                p.id,
+                p.name,
+                p.sku,
+                p.ean,
+                p.purchase_price,
+                p.sale_price,
+                p.vat_rate,
+                COALESCE((
+                    SELECT SUM(m.quantity)
+                    FROM stock_movement m
+                    WHERE m.product_id = p.id
+                ), 0) AS stock,
+                p.reorder_threshold,
+                p.active
+            FROM product p
+            ORDER BY p.name ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    pub async fn get_product_by_id(&self, id: i64) -> ServiceResult<Option<ProductDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ProductRow>(
+            r#"
+            SELECT
+                p.id,
+                p.name,
+                p.sku,
+                p.ean,
+                p.purchase_price,
+                p.sale_price,
+                p.vat_rate,
+                COALESCE((
+                    SELECT SUM(m.quantity)
+                    FROM stock_movement m
+                    WHERE m.product_id = p.id
+                ), 0) AS stock,
+                p.reorder_threshold,
+                p.active
+            FROM product p
+            WHERE p.id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .map(|row| row.into()))
+    }
+
+    /// Active products whose stock reached their reorder threshold.
+    pub async fn get_low_stock_product_list(&self) -> ServiceResult<Vec<ProductDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ProductRow>(
+            r#"
+            WITH product_stock AS (
+                SELECT
+                    p.id,
+                    p.name,
+                    p.sku,
+                    p.ean,
+                    p.purchase_price,
+                    p.sale_price,
+                    p.vat_rate,
+                    COALESCE((
+                        SELECT SUM(m.quantity)
+                        FROM stock_movement m
+                        WHERE m.product_id = p.id
+                    ), 0) AS stock,
+                    p.reorder_threshold,
+                    p.active
+                FROM product p
+            )
+            SELECT *
+            FROM product_stock
+            WHERE active AND stock <= reorder_threshold
+            ORDER BY stock - reorder_threshold ASC, name ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    pub async fn store_product(&self, product: EditProductDto) -> ServiceResult<i64> {
+        money::validate_vat_rate(product.vat_rate)?;
+
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let q = if let Some(product_id) = product.id {
+            sqlx::query(
+                r#"
+                UPDATE product
+                SET name = $2,
+                    sku = $3,
+                    ean = $4,
+                    purchase_price = $5,
+                    sale_price = $6,
+                    vat_rate = $7,
+                    reorder_threshold = $8,
+                    active = $9
+                WHERE id = $1;
+            "#,
+            )
+            .bind(product_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO product (
+                    name,
+                    sku,
+                    ean,
+                    purchase_price,
+                    sale_price,
+                    vat_rate,
+                    reorder_threshold,
+                    active
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+            "#,
+            )
+        };
+
+        q.bind(&product.name)
+            .bind(&product.sku)
+            .bind(&product.ean)
+            .bind(product.purchase_price)
+            .bind(product.sale_price)
+            .bind(product.vat_rate)
+            .bind(product.reorder_threshold)
+            .bind(product.active)
+            .execute(connection.as_mut())
+            .await?;
+
+        if let Some(product_id) = product.id {
+            return Ok(product_id);
+        }
+
+        Ok(sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(connection.as_mut())
+        .await?
+        .id)
+    }
+
+    /// Deletes a product without stock movements. Products with stock movements are archived
+    /// instead, so that the stock history is kept.
+    pub async fn delete_product(&self, id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let has_history = sqlx::query_as::<_, CountRow>(
+            r#"
+            SELECT COUNT(*) AS count
+            FROM stock_movement m
+            WHERE m.product_id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .count
+            > 0;
+
+        let q = if has_history {
+            sqlx::query(
+                r#"
+                UPDATE product
+                SET active = FALSE
+                WHERE id = $1;
+            "#,
+            )
+        } else {
+            sqlx::query(
+                r#"
+                DELETE FROM product
+                WHERE id = $1;
+            "#,
+            )
+        };
+        q.bind(id).execute(transaction.as_mut()).await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_stock_movement_list(
+        &self,
+        product_id: i64,
+    ) -> ServiceResult<Vec<StockMovementDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, StockMovementRow>(
+            r#"
+            SELECT
+                m.id,
+                m.product_id,
+                m.date,
+                m.kind,
+                m.quantity,
+                i.appointment_id,
+                m.note
+            FROM stock_movement m
+            LEFT JOIN appointment_item i ON m.appointment_item_id = i.id
+            WHERE m.product_id = $1
+            ORDER BY m.date DESC, m.id DESC;
+        "#,
+        )
+        .bind(product_id)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    /// Records a goods receipt or a correction. Sales are only booked through appointment items.
+    /// Goods receipts have to add to the stock, corrections may go either way.
+    pub async fn store_stock_movement(&self, movement: EditStockMovementDto) -> ServiceResult<i64> {
+        match movement.kind {
+            StockMovementKind::Sale => {
+                return Err(ServiceError::BadRequest(
+                    "Sales are booked through appointment items".to_string(),
+                ));
+            }
+            StockMovementKind::Receipt if movement.quantity <= 0 => {
+                return Err(ServiceError::BadRequest(
+                    "Goods receipts need a positive quantity".to_string(),
+                ));
+            }
+            StockMovementKind::Correction if movement.quantity == 0 => {
+                return Err(ServiceError::BadRequest(
+                    "Corrections need a quantity".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO stock_movement (
+                product_id,
+                date,
+                kind,
+                quantity,
+                note
+            )
+            VALUES ($1, $2, $3, $4, $5);
+        "#,
+        )
+        .bind(movement.product_id)
+        .bind(movement.date)
+        .bind(movement.kind)
+        .bind(movement.quantity)
+        .bind(&movement.note)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(connection.as_mut())
+        .await?
+        .id)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct CountRow {
+    pub count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct ProductRow {
+    pub id: i64,
+    pub name: String,
+    pub sku: String,
+    pub ean: String,
+    pub purchase_price: i64,
+    pub sale_price: i64,
+    pub vat_rate: i64,
+    pub stock: i64,
+    pub reorder_threshold: i64,
+    pub active: bool,
+}
+
+impl From<ProductRow> for ProductDto {
+    fn from(row: ProductRow) -> ProductDto {
+        ProductDto {
+            id: row.id,
+            name: row.name,
+            sku: row.sku,
+            ean: row.ean,
+            purchase_price: row.purchase_price,
+            sale_price: row.sale_price,
+            vat_rate: row.vat_rate,
+            stock: row.stock,
+            reorder_threshold: row.reorder_threshold,
+            active: row.active,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StockMovementRow {
+    pub id: i64,
+    pub product_id: i64,
+    pub date: NaiveDateTime,
+    pub kind: StockMovementKind,
+    pub quantity: i64,
+    pub appointment_id: Option<i64>,
+    pub note: String,
+}
+
+impl From<StockMovementRow> for StockMovementDto {
+    fn from(row: StockMovementRow) -> StockMovementDto {
+        StockMovementDto {
+            id: row.id,
+            product_id: row.product_id,
+            date: row.date,
+            kind: row.kind,
+            quantity: row.quantity,
+            appointment_id: row.appointment_id,
+            note: row.note,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::testing::state;
+    use chrono::NaiveDate;
+
+    fn product(name: &str) -> EditProductDto {
+        EditProductDto {
+            id: None,
+            name: name.to_string(),
+            sku: String::new(),
+            ean: String::new(),
+            purchase_price: 500,
+            sale_price: 1200,
+            vat_rate: 1900,
+            reorder_threshold: 2,
+            active: true,
+        }
+    }
+
+    fn movement(product_id: i64, kind: StockMovementKind, quantity: i64) -> EditStockMovementDto {
+        EditStockMovementDto {
+            product_id,
+            date: NaiveDate::from_ymd_opt(2024, 3, 1)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap(),
+            kind,
+            quantity,
+            note: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn movements_need_a_meaningful_quantity() {
+        let state = state().await;
+        let id = state.product.store_product(product("Cream")).await.unwrap();
+
+        for (kind, quantity) in [
+            (StockMovementKind::Receipt, 0),
+            (StockMovementKind::Receipt, -3),
+            (StockMovementKind::Correction, 0),
+            (StockMovementKind::Sale, -1),
+        ] {
+            let result = state
+                .product
+                .store_stock_movement(movement(id, kind, quantity))
+                .await;
+            assert!(matches!(result, Err(ServiceError::BadRequest(_))));
+        }
+
+        for (kind, quantity) in [
+            (StockMovementKind::Receipt, 10),
+            (StockMovementKind::Correction, -2),
+            (StockMovementKind::Correction, 1),
+        ] {
+            state
+                .product
+                .store_stock_movement(movement(id, kind, quantity))
+                .await
+                .unwrap();
+        }
+
+        let stock = state.product.get_product_by_id(id).await.unwrap().unwrap();
+        assert_eq!(stock.stock, 9);
+    }
+
+    #[tokio::test]
+    async fn products_with_movements_are_archived() {
+        let state = state().await;
+        let unused = state.product.store_product(product("Oil")).await.unwrap();
+        let stocked = state.product.store_product(product("Cream")).await.unwrap();
+        state
+            .product
+            .store_stock_movement(movement(stocked, StockMovementKind::Receipt, 5))
+            .await
+            .unwrap();
+
+        state.product.delete_product(unused).await.unwrap();
+        state.product.delete_product(stocked).await.unwrap();
+
+        assert!(
+            state
+                .product
+                .get_product_by_id(unused)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let archived = state
+            .product
+            .get_product_by_id(stocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!archived.active);
+        assert_eq!(archived.stock, 5);
+        let movements = state
+            .product
+            .get_stock_movement_list(stocked)
+            .await
+            .unwrap();
+        assert_eq!(movements.len(), 1);
+    }
+}
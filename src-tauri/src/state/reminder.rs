@@ -0,0 +1,731 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::error;
+use sqlx::{Pool, Sqlite};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{
+    error::ServiceResult,
+    model::{ReminderChannel, ReminderDto, ReminderStatus},
+    state::{
+        notifier::{Notifier, ReminderMessage},
+        preference::PreferenceState,
+    },
+    time_zone,
+};
+
+const ENABLED_KEY: &str = "reminder.enabled";
+const HOURS_BEFORE_KEY: &str = "reminder.hours-before";
+const SUBJECT_KEY: &str = "reminder.subject";
+const MESSAGE_KEY: &str = "reminder.message";
+const LANGUAGE_KEY: &str = "general.language";
+
+const DEFAULT_HOURS_BEFORE: i64 = 24;
+
+/// A reminder is marked as failed after this many unsuccessful deliveries.
+const MAX_ATTEMPTS: i64 = 5;
+/// Delay before the first retry, doubled for every further attempt.
+const RETRY_DELAY_MINUTES: i64 = 5;
+const WORKER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct ReminderState {
+    pool: Arc<Pool<Sqlite>>,
+    preference: PreferenceState,
+    notifiers: Arc<Vec<Box<dyn Notifier>>>,
+    /// Held while the queue is processed, so the worker and a manual run never send the same
+    /// reminder twice.
+    sending: Arc<Mutex<()>>,
+}
+
+impl ReminderState {
+    pub fn new(
+        pool: Arc<Pool<Sqlite>>,
+        preference: PreferenceState,
+        notifiers: Vec<Box<dyn Notifier>>,
+    ) -> Self {
+        Self {
+            pool,
+            preference,
+            notifiers: Arc::new(notifiers),
+            sending: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub async fn get_reminder_list(
+        &self,
+        appointment_id: Option<i64>,
+        status: Option<ReminderStatus>,
+    ) -> ServiceResult<Vec<ReminderDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ReminderRow>(
+            r#"
+            SELECT
+                r.id,
+                r.appointment_id,
+                a.customer_id,
+                r.channel,
+                r.recipient,
+                r.appointment_start_date,
+                r.due_date,
+                r.status,
+                r.attempts,
+                r.last_error,
+                r.sent_date
+            FROM reminder r
+            JOIN appointment a ON a.id = r.appointment_id
+            WHERE ($1 IS NULL OR r.appointment_id = $1)
+                AND ($2 IS NULL OR r.status = $2)
+            ORDER BY r.due_date DESC, r.id DESC;
+        "#,
+        )
+        .bind(appointment_id)
+        .bind(status)
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    /// Brings the queue in line with the upcoming appointments. Pending reminders follow changes
+    /// of the start date and the customer contact data, reminders that were already sent or
+    /// failed are kept as history.
+    pub async fn schedule_reminders(&self) -> ServiceResult<()> {
+        let hours_before = self
+            .preference
+            .get_preference::<i64>(HOURS_BEFORE_KEY)
+            .await?
+            .unwrap_or(DEFAULT_HOURS_BEFORE);
+        let now = Utc::now();
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let appointments = sqlx::query_as::<_, UpcomingAppointmentRow>(
+            r#"
+            SELECT
+                a.id,
+                a.start_date,
+                a.start_utc,
+                c.email,
+                c.mobile_phone
+            FROM appointment a
+            JOIN customer c ON c.id = a.customer_id
+            WHERE a.start_utc > $1;
+        "#,
+        )
+        .bind(now)
+        .fetch_all(transaction.as_mut())
+        .await?;
+
+        let mut reminders: HashMap<(i64, ReminderChannel), Vec<QueueRow>> = HashMap::new();
+        for row in sqlx::query_as::<_, QueueRow>(
+            r#"
+            SELECT
+                r.id,
+                r.appointment_id,
+                r.channel,
+                r.recipient,
+                r.appointment_start_date,
+                r.due_date,
+                r.status,
+                r.attempts
+            FROM reminder r
+            JOIN appointment a ON a.id = r.appointment_id
+            WHERE a.start_utc > $1;
+        "#,
+        )
+        .bind(now)
+        .fetch_all(transaction.as_mut())
+        .await?
+        {
+            reminders
+                .entry((row.appointment_id, row.channel))
+                .or_default()
+                .push(row);
+        }
+
+        for appointment in appointments {
+            // Counted in real hours, so a daylight saving change in between is respected.
+            let due_date = appointment.start_utc - Duration::hours(hours_before);
+
+            for (channel, contact) in [
+                (ReminderChannel::Email, &appointment.email),
+                (ReminderChannel::Sms, &appointment.mobile_phone),
+            ] {
+                let recipient = contact.trim();
+                let wanted = !recipient.is_empty() && self.notifier(channel).is_some();
+                let existing = reminders
+                    .remove(&(appointment.id, channel))
+                    .unwrap_or_default();
+
+                let current = existing.iter().find(|reminder| {
+                    wanted
+                        && reminder.recipient == recipient
+                        && reminder.appointment_start_date == appointment.start_date
+                });
+
+                for reminder in &existing {
+                    let is_current = current.is_some_and(|current| current.id == reminder.id);
+                    if reminder.status == ReminderStatus::Pending && !is_current {
+                        sqlx::query(
+                            r#"
+                                DELETE FROM reminder
+                                WHERE id = $1;
+                            "#,
+                        )
+                        .bind(reminder.id)
+                        .execute(transaction.as_mut())
+                        .await?;
+                    }
+                }
+
+                match current {
+                    Some(reminder)
+                        if reminder.status == ReminderStatus::Pending
+                            && reminder.attempts == 0
+                            && reminder.due_date != due_date =>
+                    {
+                        sqlx::query(
+                            r#"
+                                UPDATE reminder
+                                SET due_date = $2,
+                                    next_attempt_date = $2
+                                WHERE id = $1;
+                            "#,
+                        )
+                        .bind(reminder.id)
+                        .bind(due_date)
+                        .execute(transaction.as_mut())
+                        .await?;
+                    }
+                    Some(_) => {}
+                    None if wanted => {
+                        sqlx::query(
+                            r#"
+                                INSERT INTO reminder (
+                                    appointment_id,
+                                    channel,
+                                    recipient,
+                                    appointment_start_date,
+                                    due_date,
+                                    status,
+                                    attempts,
+                                    next_attempt_date
+                                )
+                                VALUES ($1, $2, $3, $4, $5, $6, 0, $5);
+                            "#,
+                        )
+                        .bind(appointment.id)
+                        .bind(channel)
+                        .bind(recipient)
+                        .bind(appointment.start_date)
+                        .bind(due_date)
+                        .bind(ReminderStatus::Pending)
+                        .execute(transaction.as_mut())
+                        .await?;
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Schedules and delivers all reminders that are due. Returns the number of sent reminders.
+    pub async fn send_due_reminders(&self) -> ServiceResult<i64> {
+        let enabled = self
+            .preference
+            .get_preference::<bool>(ENABLED_KEY)
+            .await?
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(0);
+        }
+
+        let _sending = self.sending.lock().await;
+        self.schedule_reminders().await?;
+
+        let now = Utc::now();
+        let due = {
+            let mut connection = self.pool.acquire().await.unwrap();
+            sqlx::query_as::<_, DueReminderRow>(
+                r#"
+                SELECT
+                    r.id,
+                    r.channel,
+                    r.recipient,
+                    r.attempts,
+                    a.start_date,
+                    a.start_utc,
+                    c.title,
+                    c.first_name,
+                    c.last_name,
+                    e.name AS employee_name,
+                    (
+                        SELECT COALESCE(group_concat(i.description, ', '), '')
+                        FROM (
+                            SELECT trim(description) AS description
+                            FROM appointment_item
+                            WHERE appointment_id = a.id AND trim(description) <> ''
+                            ORDER BY position
+                        ) i
+                    ) AS treatment
+                FROM reminder r
+                JOIN appointment a ON a.id = r.appointment_id
+                JOIN customer c ON c.id = a.customer_id
+                LEFT JOIN employee e ON e.id = a.employee_id
+                WHERE r.status = 'pending'
+                    AND r.due_date <= $1
+                    AND r.next_attempt_date <= $1
+                ORDER BY r.due_date ASC;
+            "#,
+            )
+            .bind(now)
+            .fetch_all(connection.as_mut())
+            .await?
+        };
+
+        let mut sent = 0;
+        for reminder in due {
+            if reminder.start_utc <= Utc::now() {
+                self.store_failure(reminder.id, MAX_ATTEMPTS, "appointment already started")
+                    .await?;
+                continue;
+            }
+
+            let Some(notifier) = self.notifier(reminder.channel) else {
+                self.store_failure(reminder.id, MAX_ATTEMPTS, "no notifier for channel")
+                    .await?;
+                continue;
+            };
+
+            let message = self.render_message(&reminder).await?;
+            match notifier.send(&message).await {
+                Ok(()) => {
+                    let mut connection = self.pool.acquire().await.unwrap();
+                    sqlx::query(
+                        r#"
+                            UPDATE reminder
+                            SET status = $2,
+                                attempts = attempts + 1,
+                                last_error = NULL,
+                                sent_date = $3
+                            WHERE id = $1;
+                        "#,
+                    )
+                    .bind(reminder.id)
+                    .bind(ReminderStatus::Sent)
+                    .bind(self.now().await?)
+                    .execute(connection.as_mut())
+                    .await?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    self.store_failure(reminder.id, reminder.attempts + 1, &e.to_string())
+                        .await?;
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Puts a failed reminder back into the queue with a fresh set of attempts.
+    pub async fn retry_reminder(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        sqlx::query(
+            r#"
+                UPDATE reminder
+                SET status = $2,
+                    attempts = 0,
+                    next_attempt_date = $3
+                WHERE id = $1 AND status = $4;
+            "#,
+        )
+        .bind(id)
+        .bind(ReminderStatus::Pending)
+        .bind(Utc::now())
+        .bind(ReminderStatus::Failed)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Background worker that periodically sends due reminders.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(WORKER_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.send_due_reminders().await {
+                error!("Failed to send reminders: {}", e);
+            }
+        }
+    }
+
+    /// Reminders are due at UTC instants, but sent at local times of the practice.
+    async fn now(&self) -> ServiceResult<NaiveDateTime> {
+        Ok(time_zone::now_local(self.preference.get_time_zone().await?))
+    }
+
+    fn notifier(&self, channel: ReminderChannel) -> Option<&dyn Notifier> {
+        self.notifiers
+            .iter()
+            .find(|notifier| notifier.channel() == channel)
+            .map(|notifier| notifier.as_ref())
+    }
+
+    async fn store_failure(&self, id: i64, attempts: i64, message: &str) -> ServiceResult<()> {
+        let now = Utc::now();
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let (status, next_attempt_date) = if attempts >= MAX_ATTEMPTS {
+            (ReminderStatus::Failed, now)
+        } else {
+            let delay = RETRY_DELAY_MINUTES << (attempts - 1).max(0);
+            (ReminderStatus::Pending, now + Duration::minutes(delay))
+        };
+
+        sqlx::query(
+            r#"
+                UPDATE reminder
+                SET status = $2,
+                    attempts = $3,
+                    next_attempt_date = $4,
+                    last_error = $5
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(attempts)
+        .bind(next_attempt_date)
+        .bind(message)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn render_message(&self, reminder: &DueReminderRow) -> ServiceResult<ReminderMessage> {
+        let german = self
+            .preference
+            .get_preference::<String>(LANGUAGE_KEY)
+            .await?
+            .is_some_and(|language| language == "de");
+
+        let subject = self
+            .preference
+            .get_preference::<String>(SUBJECT_KEY)
+            .await?
+            .filter(|subject| !subject.trim().is_empty())
+            .unwrap_or_else(|| {
+                if german {
+                    "Terminerinnerung".to_string()
+                } else {
+                    "Appointment reminder".to_string()
+                }
+            });
+        let body = self
+            .preference
+            .get_preference::<String>(MESSAGE_KEY)
+            .await?
+            .filter(|body| !body.trim().is_empty())
+            .unwrap_or_else(|| {
+                if german {
+                    "Hallo {first_name} {last_name},\n\nwir möchten Sie an Ihren Termin am {date} um {time} Uhr erinnern.\n\nViele Grüße".to_string()
+                } else {
+                    "Hello {first_name} {last_name},\n\nthis is a reminder of your appointment on {date} at {time}.\n\nBest regards".to_string()
+                }
+            });
+
+        let date = if german {
+            reminder.start_date.format("%d.%m.%Y").to_string()
+        } else {
+            reminder.start_date.format("%Y-%m-%d").to_string()
+        };
+        let time = reminder.start_date.format("%H:%M").to_string();
+        let employee = reminder.employee_name.clone().unwrap_or_default();
+
+        let render = |template: &str| {
+            template
+                .replace("{title}", &reminder.title)
+                .replace("{first_name}", &reminder.first_name)
+                .replace("{last_name}", &reminder.last_name)
+                .replace("{date}", &date)
+                .replace("{time}", &time)
+                .replace("{treatment}", &reminder.treatment)
+                .replace("{employee}", &employee)
+        };
+
+        Ok(ReminderMessage {
+            recipient: reminder.recipient.clone(),
+            subject: render(&subject),
+            body: render(&body),
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UpcomingAppointmentRow {
+    pub id: i64,
+    pub start_date: NaiveDateTime,
+    pub start_utc: DateTime<Utc>,
+    pub email: String,
+    pub mobile_phone: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct QueueRow {
+    pub id: i64,
+    pub appointment_id: i64,
+    pub channel: ReminderChannel,
+    pub recipient: String,
+    pub appointment_start_date: NaiveDateTime,
+    pub due_date: DateTime<Utc>,
+    pub status: ReminderStatus,
+    pub attempts: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct DueReminderRow {
+    pub id: i64,
+    pub channel: ReminderChannel,
+    pub recipient: String,
+    pub attempts: i64,
+    pub start_date: NaiveDateTime,
+    pub start_utc: DateTime<Utc>,
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub employee_name: Option<String>,
+    pub treatment: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ReminderRow {
+    pub id: i64,
+    pub appointment_id: i64,
+    pub customer_id: i64,
+    pub channel: ReminderChannel,
+    pub recipient: String,
+    pub appointment_start_date: NaiveDateTime,
+    pub due_date: DateTime<Utc>,
+    pub status: ReminderStatus,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub sent_date: Option<NaiveDateTime>,
+}
+
+impl From<ReminderRow> for ReminderDto {
+    fn from(row: ReminderRow) -> ReminderDto {
+        ReminderDto {
+            id: row.id,
+            appointment_id: row.appointment_id,
+            customer_id: row.customer_id,
+            channel: row.channel,
+            recipient: row.recipient,
+            appointment_start_date: row.appointment_start_date,
+            due_date: row.due_date,
+            status: row.status,
+            attempts: row.attempts,
+            last_error: row.last_error,
+            sent_date: row.sent_date,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::{MAX_ATTEMPTS, RETRY_DELAY_MINUTES, ReminderState};
+    use crate::error::ServiceError;
+    use crate::model::{ReminderChannel, ReminderDto, ReminderStatus};
+    use crate::state::State;
+    use crate::state::notifier::{Notifier, NotifierFuture, ReminderMessage};
+    use crate::state::testing::{appointment, customer, item, set_preference, state};
+    use crate::time_zone;
+
+    /// Records the messages, the first deliveries fail as requested.
+    #[derive(Clone, Default)]
+    struct StubNotifier {
+        sent: Arc<Mutex<Vec<String>>>,
+        failures: Arc<AtomicUsize>,
+    }
+
+    impl Notifier for StubNotifier {
+        fn channel(&self) -> ReminderChannel {
+            ReminderChannel::Email
+        }
+
+        fn send<'a>(&'a self, message: &'a ReminderMessage) -> NotifierFuture<'a> {
+            Box::pin(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                let failures = self.failures.load(Ordering::SeqCst);
+                if failures > 0 {
+                    self.failures.store(failures - 1, Ordering::SeqCst);
+                    return Err(ServiceError::BadRequest("mailbox unavailable".to_string()));
+                }
+                self.sent.lock().unwrap().push(message.recipient.clone());
+                Ok(())
+            })
+        }
+    }
+
+    /// Reminders of the state that are delivered by the stub, with an appointment tomorrow
+    /// whose reminder is due.
+    async fn reminders(state: &State, notifier: &StubNotifier) -> ReminderState {
+        set_preference(state, "reminder.enabled", true).await;
+        set_preference(state, "reminder.hours-before", 48).await;
+        let mut anna = customer("Anna", "Berg");
+        anna.email = "anna@example.org".to_string();
+        let customer_id = state.customer.store_customer(anna).await.unwrap();
+        let zone = state.preference.get_time_zone().await.unwrap();
+        let tomorrow = time_zone::now_local(zone) + Duration::days(1);
+        state
+            .appointment
+            .store_appointment(appointment(
+                customer_id,
+                tomorrow,
+                vec![item("Massage", 1, 5000)],
+            ))
+            .await
+            .unwrap();
+
+        ReminderState::new(
+            state.reminder.pool.clone(),
+            state.preference.clone(),
+            vec![Box::new(notifier.clone())],
+        )
+    }
+
+    async fn reminder(reminders: &ReminderState) -> ReminderDto {
+        let mut list = reminders.get_reminder_list(None, None).await.unwrap();
+        assert_eq!(list.len(), 1);
+        list.remove(0)
+    }
+
+    /// Minutes until the next attempt of the reminder.
+    async fn retry_delay(reminders: &ReminderState, id: i64) -> i64 {
+        let next_attempt_date: DateTime<Utc> =
+            sqlx::query_scalar("SELECT next_attempt_date FROM reminder WHERE id = $1")
+                .bind(id)
+                .fetch_one(reminders.pool.as_ref())
+                .await
+                .unwrap();
+        // The delay was counted from a moment ago, so it is rounded up to whole minutes.
+        ((next_attempt_date - Utc::now()).num_seconds() + 59) / 60
+    }
+
+    /// Makes the next attempt due at once instead of waiting for the delay.
+    async fn skip_delay(reminders: &ReminderState, id: i64) {
+        sqlx::query("UPDATE reminder SET next_attempt_date = due_date WHERE id = $1")
+            .bind(id)
+            .execute(reminders.pool.as_ref())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_due_reminder() {
+        let state = state().await;
+        let notifier = StubNotifier::default();
+        let reminders = reminders(&state, &notifier).await;
+
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 1);
+        let sent = reminder(&reminders).await;
+        assert_eq!(sent.status, ReminderStatus::Sent);
+        assert_eq!(sent.attempts, 1);
+        assert!(sent.sent_date.is_some());
+        assert_eq!(*notifier.sent.lock().unwrap(), vec!["anna@example.org"]);
+
+        // Sent reminders are kept and not delivered again.
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 0);
+        assert_eq!(notifier.sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_with_backoff() {
+        let state = state().await;
+        let notifier = StubNotifier::default();
+        notifier.failures.store(2, Ordering::SeqCst);
+        let reminders = reminders(&state, &notifier).await;
+
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 0);
+        let failed = reminder(&reminders).await;
+        assert_eq!(failed.status, ReminderStatus::Pending);
+        assert_eq!(failed.attempts, 1);
+        assert!(failed.last_error.unwrap().contains("mailbox unavailable"));
+        assert_eq!(
+            retry_delay(&reminders, failed.id).await,
+            RETRY_DELAY_MINUTES
+        );
+
+        // Nothing is sent before the delay has passed.
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 0);
+        assert_eq!(reminder(&reminders).await.attempts, 1);
+
+        skip_delay(&reminders, failed.id).await;
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 0);
+        assert_eq!(reminder(&reminders).await.attempts, 2);
+        assert_eq!(
+            retry_delay(&reminders, failed.id).await,
+            2 * RETRY_DELAY_MINUTES
+        );
+
+        skip_delay(&reminders, failed.id).await;
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 1);
+        let sent = reminder(&reminders).await;
+        assert_eq!(sent.status, ReminderStatus::Sent);
+        assert_eq!(sent.attempts, 3);
+        assert_eq!(sent.last_error, None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let state = state().await;
+        let notifier = StubNotifier::default();
+        notifier.failures.store(usize::MAX, Ordering::SeqCst);
+        let reminders = reminders(&state, &notifier).await;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            assert_eq!(reminders.send_due_reminders().await.unwrap(), 0);
+            let failed = reminder(&reminders).await;
+            assert_eq!(failed.attempts, attempt);
+            skip_delay(&reminders, failed.id).await;
+        }
+        let failed = reminder(&reminders).await;
+        assert_eq!(failed.status, ReminderStatus::Failed);
+        assert!(notifier.sent.lock().unwrap().is_empty());
+
+        // Failed reminders wait for a manual retry.
+        reminders.send_due_reminders().await.unwrap();
+        assert_eq!(reminder(&reminders).await.attempts, MAX_ATTEMPTS);
+        notifier.failures.store(0, Ordering::SeqCst);
+        reminders.retry_reminder(failed.id).await.unwrap();
+        assert_eq!(reminders.send_due_reminders().await.unwrap(), 1);
+        assert_eq!(reminder(&reminders).await.status, ReminderStatus::Sent);
+    }
+
+    #[tokio::test]
+    async fn concurrent_runs_send_once() {
+        let state = state().await;
+        let notifier = StubNotifier::default();
+        let reminders = reminders(&state, &notifier).await;
+
+        let (first, second) = tokio::join!(
+            reminders.send_due_reminders(),
+            reminders.send_due_reminders()
+        );
+        assert_eq!(first.unwrap() + second.unwrap(), 1);
+        assert_eq!(*notifier.sent.lock().unwrap(), vec!["anna@example.org"]);
+    }
+}
@@ -0,0 +1,696 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    csv::CsvWriter,
+    error::{ServiceError, ServiceResult},
+    model::{
+        Currency, RevenueBasis, RevenueGrouping, RevenueReportDto, RevenueReportFilterDto,
+        RevenueRowDto,
+    },
+    money,
+    state::preference,
+};
+
+const LANGUAGE_KEY: &str = "general.language";
+
+#[derive(Clone)]
+pub struct ReportState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl ReportState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_revenue_report(
+        &self,
+        filter: RevenueReportFilterDto,
+    ) -> ServiceResult<RevenueReportDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_revenue_report(connection.as_mut(), filter).await
+    }
+
+    pub async fn export_revenue_report_csv(
+        &self,
+        filter: RevenueReportFilterDto,
+        path: &Path,
+    ) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let report = get_revenue_report(connection.as_mut(), filter).await?;
+        let german = preference::get_preference::<String>(connection.as_mut(), LANGUAGE_KEY)
+            .await?
+            .is_some_and(|language| language == "de");
+
+        let mut csv = CsvWriter::new(german);
+        csv.row(&if german {
+            [
+                "Schlüssel",
+                "Bezeichnung",
+                "Anzahl",
+                "Netto",
+                "MwSt.",
+                "Brutto",
+                "Anzahl Vorperiode",
+                "Brutto Vorperiode",
+                "Veränderung %",
+            ]
+        } else {
+            [
+                "Key",
+                "Label",
+                "Count",
+                "Net",
+                "VAT",
+                "Gross",
+                "Previous count",
+                "Previous gross",
+                "Change %",
+            ]
+        });
+        for row in report.rows.iter().chain([&report.total]) {
+            csv.row(&[
+                row.key.clone(),
+                row.label.clone(),
+                row.count.to_string(),
+                csv.amount(row.price.net.amount),
+                csv.amount(row.price.tax.amount),
+                csv.amount(row.price.gross.amount),
+                row.previous_count.to_string(),
+                csv.amount(row.previous_gross.amount),
+                row.change_percent
+                    .map(|change| csv.decimal(change, 1))
+                    .unwrap_or_default(),
+            ]);
+        }
+
+        tokio::fs::write(path, csv.finish()).await?;
+        Ok(())
+    }
+}
+
+/// Revenue between the filter dates grouped as requested. The previous period has the same
+/// number of days and ends the day before the report starts.
+pub async fn get_revenue_report(
+    connection: &mut SqliteConnection,
+    filter: RevenueReportFilterDto,
+) -> ServiceResult<RevenueReportDto> {
+    if filter.to < filter.from {
+        return Err(ServiceError::BadRequest(
+            "report must not end before it starts".to_string(),
+        ));
+    }
+    let days = (filter.to - filter.from).num_days() as u64 + 1;
+    let previous_to = filter.from - Days::new(1);
+    let previous_from = filter.from - Days::new(days);
+    let period = period_of(filter.group_by);
+
+    // Rows of a period grouping are compared with the whole preceding period, which may start
+    // before the previous report period.
+    let load_from = match period {
+        Some(period) => previous_from.min(previous_start(start_of(filter.from, period), period)),
+        None => previous_from,
+    };
+
+    let currency = preference::get_currency(&mut *connection).await?;
+    let entries = match filter.basis {
+        RevenueBasis::Appointments => {
+            load_appointment_entries(&mut *connection, load_from, filter.to).await?
+        }
+        RevenueBasis::Payments => {
+            if matches!(
+                filter.group_by,
+                RevenueGrouping::Employee | RevenueGrouping::Treatment
+            ) {
+                return Err(ServiceError::BadRequest(
+                    "payments can only be grouped by period or customer".to_string(),
+                ));
+            }
+            load_payment_entries(&mut *connection, load_from, filter.to).await?
+        }
+    };
+
+    let in_range =
+        |entry: &&Entry, from: NaiveDate, to: NaiveDate| entry.date >= from && entry.date <= to;
+
+    let rows = match period {
+        Some(period) => {
+            let mut buckets: HashMap<NaiveDate, (Sum, Sum)> = HashMap::new();
+            for entry in &entries {
+                let start = start_of(entry.date, period);
+                if entry.date >= filter.from {
+                    buckets.entry(start).or_default().0.add(entry);
+                }
+                let next = next_start(start, period);
+                if next <= filter.to {
+                    buckets.entry(next).or_default().1.add(entry);
+                }
+            }
+
+            let mut rows = Vec::new();
+            let mut start = start_of(filter.from, period);
+            while start <= filter.to {
+                let (current, previous) = buckets.remove(&start).unwrap_or_default();
+                rows.push(revenue_row(
+                    start.to_string(),
+                    period_label(start, period),
+                    &current,
+                    &previous,
+                    currency,
+                ));
+                start = next_start(start, period);
+            }
+            rows
+        }
+        None => {
+            let mut groups: HashMap<String, (String, Sum, Sum)> = HashMap::new();
+            for entry in &entries {
+                let (key, label) = group_of(entry, filter.group_by);
+                let group = groups
+                    .entry(key)
+                    .or_insert_with(|| (label, Sum::default(), Sum::default()));
+                if entry.date >= filter.from {
+                    group.1.add(entry);
+                } else {
+                    group.2.add(entry);
+                }
+            }
+
+            let mut rows: Vec<RevenueRowDto> = groups
+                .into_iter()
+                .map(|(key, (label, current, previous))| {
+                    revenue_row(key, label, &current, &previous, currency)
+                })
+                .collect();
+            rows.sort_by(|a, b| {
+                b.price
+                    .gross
+                    .amount
+                    .cmp(&a.price.gross.amount)
+                    .then_with(|| a.label.cmp(&b.label))
+            });
+            rows
+        }
+    };
+
+    let mut current = Sum::default();
+    let mut previous = Sum::default();
+    for entry in entries
+        .iter()
+        .filter(|entry| in_range(entry, filter.from, filter.to))
+    {
+        current.add(entry);
+    }
+    for entry in entries
+        .iter()
+        .filter(|entry| in_range(entry, previous_from, previous_to))
+    {
+        previous.add(entry);
+    }
+
+    Ok(RevenueReportDto {
+        filter,
+        previous_from,
+        previous_to,
+        rows,
+        total: revenue_row(
+            "total".to_string(),
+            String::new(),
+            &current,
+            &previous,
+            currency,
+        ),
+    })
+}
+
+async fn load_appointment_entries(
+    connection: &mut SqliteConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ServiceResult<Vec<Entry>> {
+    Ok(sqlx::query_as::<_, Entry>(
+        r#"
+        SELECT
+            a.id,
+            date(a.start_date) AS date,
+            a.employee_id,
+            e.name AS employee_name,
+            i.treatment_id,
+            t.name AS treatment_name,
+            i.product_id,
+            p.name AS product_name,
+            a.customer_id,
+            c.first_name,
+            c.last_name,
+            i.vat_rate,
+            i.quantity * i.unit_price - i.discount AS gross
+        FROM appointment_item i
+        JOIN appointment a ON a.id = i.appointment_id
+        JOIN customer c ON c.id = a.customer_id
+        LEFT JOIN employee e ON e.id = a.employee_id
+        LEFT JOIN treatment t ON t.id = i.treatment_id
+        LEFT JOIN product p ON p.id = i.product_id
+        WHERE date(a.start_date) BETWEEN $1 AND $2;
+    "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(connection)
+    .await?)
+}
+
+async fn load_payment_entries(
+    connection: &mut SqliteConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ServiceResult<Vec<Entry>> {
+    Ok(sqlx::query_as::<_, Entry>(
+        r#"
+        SELECT
+            p.id,
+            p.date,
+            NULL AS employee_id,
+            NULL AS employee_name,
+            NULL AS treatment_id,
+            NULL AS treatment_name,
+            NULL AS product_id,
+            NULL AS product_name,
+            p.customer_id,
+            c.first_name,
+            c.last_name,
+            0 AS vat_rate,
+            p.amount AS gross
+        FROM payment p
+        JOIN customer c ON c.id = p.customer_id
+        WHERE p.date BETWEEN $1 AND $2;
+    "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(connection)
+    .await?)
+}
+
+/// Appointment item or payment.
+#[derive(sqlx::FromRow)]
+struct Entry {
+    /// Appointment or payment id.
+    id: i64,
+    date: NaiveDate,
+    employee_id: Option<i64>,
+    employee_name: Option<String>,
+    treatment_id: Option<i64>,
+    treatment_name: Option<String>,
+    product_id: Option<i64>,
+    product_name: Option<String>,
+    customer_id: i64,
+    first_name: String,
+    last_name: String,
+    vat_rate: i64,
+    gross: i64,
+}
+
+#[derive(Default)]
+struct Sum {
+    ids: HashSet<i64>,
+    lines: Vec<(i64, i64)>,
+}
+
+impl Sum {
+    fn add(&mut self, entry: &Entry) {
+        self.ids.insert(entry.id);
+        self.lines.push((entry.gross, entry.vat_rate));
+    }
+}
+
+fn revenue_row(
+    key: String,
+    label: String,
+    current: &Sum,
+    previous: &Sum,
+    currency: Currency,
+) -> RevenueRowDto {
+    let (price, _) = money::sum_prices(current.lines.iter().copied(), currency);
+    let previous_gross: i64 = previous.lines.iter().map(|(gross, _)| gross).sum();
+    RevenueRowDto {
+        key,
+        label,
+        count: current.ids.len() as i64,
+        price,
+        previous_count: previous.ids.len() as i64,
+        previous_gross: money::money(previous_gross, currency),
+        change_percent: (previous_gross != 0).then(|| {
+            (price.gross.amount - previous_gross) as f64 * 100.0 / previous_gross.abs() as f64
+        }),
+    }
+}
+
+fn group_of(entry: &Entry, group_by: RevenueGrouping) -> (String, String) {
+    match group_by {
+        RevenueGrouping::Employee => match entry.employee_id {
+            Some(id) => (
+                format!("employee:{}", id),
+                entry.employee_name.clone().unwrap_or_default(),
+            ),
+            None => ("none".to_string(), String::new()),
+        },
+        RevenueGrouping::Treatment => match (entry.treatment_id, entry.product_id) {
+            (Some(id), _) => (
+                format!("treatment:{}", id),
+                entry.treatment_name.clone().unwrap_or_default(),
+            ),
+            (None, Some(id)) => (
+                format!("product:{}", id),
+                entry.product_name.clone().unwrap_or_default(),
+            ),
+            (None, None) => ("none".to_string(), String::new()),
+        },
+        _ => (
+            format!("customer:{}", entry.customer_id),
+            format!("{} {}", entry.first_name, entry.last_name)
+                .trim()
+                .to_string(),
+        ),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+fn period_of(group_by: RevenueGrouping) -> Option<Period> {
+    match group_by {
+        RevenueGrouping::Day => Some(Period::Day),
+        RevenueGrouping::Week => Some(Period::Week),
+        RevenueGrouping::Month => Some(Period::Month),
+        RevenueGrouping::Year => Some(Period::Year),
+        _ => None,
+    }
+}
+
+/// First day of the period containing the date, weeks start on Monday.
+fn start_of(date: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => date,
+        Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+        Period::Month => date.with_day(1).unwrap(),
+        Period::Year => date.with_ordinal(1).unwrap(),
+    }
+}
+
+fn next_start(start: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => start + Days::new(1),
+        Period::Week => start + Days::new(7),
+        Period::Month => start + Months::new(1),
+        Period::Year => start + Months::new(12),
+    }
+}
+
+fn previous_start(start: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => start - Days::new(1),
+        Period::Week => start - Days::new(7),
+        Period::Month => start - Months::new(1),
+        Period::Year => start - Months::new(12),
+    }
+}
+
+fn period_label(start: NaiveDate, period: Period) -> String {
+    match period {
+        Period::Day => start.format("%Y-%m-%d").to_string(),
+        Period::Week => {
+            let week = start.iso_week();
+            format!("{}-W{:02}", week.year(), week.week())
+        }
+        Period::Month => start.format("%Y-%m").to_string(),
+        Period::Year => start.format("%Y").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, set_preference, state, temp_dir};
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    /// Books an appointment of the given gross amount with 19 % VAT on each of the dates.
+    async fn appointments(state: &State, last_name: &str, dates: &[(&str, i64)]) {
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", last_name))
+            .await
+            .unwrap();
+        for (day, gross) in dates {
+            state
+                .appointment
+                .store_appointment(appointment(
+                    customer_id,
+                    date(day).and_hms_opt(10, 0, 0).unwrap(),
+                    vec![item("Massage", 1, *gross)],
+                ))
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn report(
+        state: &State,
+        from: &str,
+        to: &str,
+        group_by: RevenueGrouping,
+    ) -> RevenueReportDto {
+        state
+            .report
+            .get_revenue_report(RevenueReportFilterDto {
+                from: date(from),
+                to: date(to),
+                group_by,
+                basis: RevenueBasis::Appointments,
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Key, label, count, gross, previous count and previous gross of each row and the total.
+    fn rows(report: &RevenueReportDto) -> Vec<(String, String, i64, i64, i64, i64)> {
+        report
+            .rows
+            .iter()
+            .chain([&report.total])
+            .map(|row| {
+                (
+                    row.key.clone(),
+                    row.label.clone(),
+                    row.count,
+                    row.price.gross.amount,
+                    row.previous_count,
+                    row.previous_gross.amount,
+                )
+            })
+            .collect()
+    }
+
+    fn row(
+        key: &str,
+        label: &str,
+        count: i64,
+        gross: i64,
+        previous_count: i64,
+        previous_gross: i64,
+    ) -> (String, String, i64, i64, i64, i64) {
+        (
+            key.to_string(),
+            label.to_string(),
+            count,
+            gross,
+            previous_count,
+            previous_gross,
+        )
+    }
+
+    #[test]
+    fn periods_start_on_their_first_day() {
+        assert_eq!(
+            start_of(date("2025-01-01"), Period::Week),
+            date("2024-12-30")
+        );
+        assert_eq!(period_label(date("2024-12-30"), Period::Week), "2025-W01");
+        assert_eq!(
+            previous_start(date("2024-12-30"), Period::Week),
+            date("2024-12-23")
+        );
+        assert_eq!(
+            start_of(date("2024-02-29"), Period::Month),
+            date("2024-02-01")
+        );
+        assert_eq!(
+            next_start(date("2024-01-01"), Period::Month),
+            date("2024-02-01")
+        );
+        assert_eq!(
+            previous_start(date("2024-03-01"), Period::Month),
+            date("2024-02-01")
+        );
+        assert_eq!(
+            start_of(date("2024-12-31"), Period::Year),
+            date("2024-01-01")
+        );
+        assert_eq!(
+            previous_start(date("2025-01-01"), Period::Year),
+            date("2024-01-01")
+        );
+        assert_eq!(period_label(date("2024-01-01"), Period::Year), "2024");
+    }
+
+    #[tokio::test]
+    async fn weeks_are_compared_with_the_week_before() {
+        let state = state().await;
+        appointments(
+            &state,
+            "Berg",
+            &[
+                ("2024-12-22", 5000),
+                ("2024-12-29", 11900),
+                ("2025-01-05", 23800),
+                ("2025-01-06", 11900),
+            ],
+        )
+        .await;
+
+        let report = report(&state, "2024-12-30", "2025-01-12", RevenueGrouping::Week).await;
+        assert_eq!(
+            (report.previous_from, report.previous_to),
+            (date("2024-12-16"), date("2024-12-29"))
+        );
+        assert_eq!(
+            rows(&report),
+            vec![
+                row("2024-12-30", "2025-W01", 1, 23800, 1, 11900),
+                row("2025-01-06", "2025-W02", 1, 11900, 1, 23800),
+                row("total", "", 2, 35700, 2, 16900),
+            ]
+        );
+        assert_eq!(report.rows[0].change_percent, Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn months_are_compared_with_the_month_before() {
+        let state = state().await;
+        appointments(
+            &state,
+            "Berg",
+            &[
+                ("2024-12-29", 5000),
+                ("2025-02-28", 11900),
+                ("2025-03-31", 23800),
+                ("2025-04-01", 11900),
+            ],
+        )
+        .await;
+
+        let report = report(&state, "2025-03-01", "2025-04-30", RevenueGrouping::Month).await;
+        // March and April have 61 days.
+        assert_eq!(
+            (report.previous_from, report.previous_to),
+            (date("2024-12-30"), date("2025-02-28"))
+        );
+        assert_eq!(
+            rows(&report),
+            vec![
+                row("2025-03-01", "2025-03", 1, 23800, 1, 11900),
+                row("2025-04-01", "2025-04", 1, 11900, 1, 23800),
+                row("total", "", 2, 35700, 1, 11900),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn years_are_compared_with_the_whole_year_before() {
+        let state = state().await;
+        appointments(
+            &state,
+            "Berg",
+            &[
+                ("2024-01-01", 5000),
+                ("2024-06-01", 11900),
+                ("2025-12-31", 23800),
+            ],
+        )
+        .await;
+
+        let report = report(&state, "2025-01-01", "2025-12-31", RevenueGrouping::Year).await;
+        // The previous 365 days miss the first day of the leap year, the year row doesn't.
+        assert_eq!(
+            (report.previous_from, report.previous_to),
+            (date("2024-01-02"), date("2024-12-31"))
+        );
+        assert_eq!(
+            rows(&report),
+            vec![
+                row("2025-01-01", "2025", 1, 23800, 2, 16900),
+                row("total", "", 1, 23800, 1, 11900),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn csv_follows_the_language_of_the_practice() {
+        let state = state().await;
+        appointments(
+            &state,
+            "Berg; Klein",
+            &[
+                ("2025-02-10", 11900),
+                ("2025-03-10", 23800),
+                ("2025-03-20", 11900),
+            ],
+        )
+        .await;
+        let export = async |group_by| {
+            let path = temp_dir().join("revenue.csv");
+            state
+                .report
+                .export_revenue_report_csv(
+                    RevenueReportFilterDto {
+                        from: date("2025-03-01"),
+                        to: date("2025-03-31"),
+                        group_by,
+                        basis: RevenueBasis::Appointments,
+                    },
+                    &path,
+                )
+                .await
+                .unwrap();
+            std::fs::read_to_string(&path).unwrap()
+        };
+
+        assert_eq!(
+            export(RevenueGrouping::Month).await,
+            "\u{feff}Key,Label,Count,Net,VAT,Gross,Previous count,Previous gross,Change %\r\n\
+             2025-03-01,2025-03,2,300.00,57.00,357.00,1,119.00,200.0\r\n\
+             total,,2,300.00,57.00,357.00,1,119.00,200.0\r\n"
+        );
+
+        set_preference(&state, LANGUAGE_KEY, "de").await;
+        assert_eq!(
+            export(RevenueGrouping::Customer).await,
+            "\u{feff}Schlüssel;Bezeichnung;Anzahl;Netto;MwSt.;Brutto;Anzahl Vorperiode;\
+             Brutto Vorperiode;Veränderung %\r\n\
+             customer:1;\"Anna Berg; Klein\";2;300,00;57,00;357,00;1;119,00;200,0\r\n\
+             total;;2;300,00;57,00;357,00;1;119,00;200,0\r\n"
+        );
+    }
+}
@@ -0,0 +1,523 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{
+        AppointmentFilterDto, EmployeeDto, EmployeeRefDto, RescheduleAction, RescheduleChangeDto,
+        RescheduleProposalDto, RescheduleSummaryDto, RescheduledAppointmentDto,
+    },
+    state::{
+        appointment::{self, AppointmentState},
+        employee::{self, EmployeeState},
+    },
+    time_zone,
+};
+
+/// Number of days after the absence that are searched for a free slot.
+const MAX_SHIFT_DAYS: i64 = 28;
+
+#[derive(Clone)]
+pub struct RescheduleState {
+    pool: Arc<Pool<Sqlite>>,
+    appointment: AppointmentState,
+    employee: EmployeeState,
+}
+
+impl RescheduleState {
+    pub fn new(
+        pool: Arc<Pool<Sqlite>>,
+        appointment: AppointmentState,
+        employee: EmployeeState,
+    ) -> Self {
+        Self {
+            pool,
+            appointment,
+            employee,
+        }
+    }
+
+    /// Proposes a change for every appointment of the employee in the inclusive date range.
+    /// Another employee who is free at the same time is preferred, otherwise the appointment
+    /// moves to the same time on the first free day after the absence on which the employee is
+    /// still employed. Nothing is stored.
+    pub async fn plan_reschedule(
+        &self,
+        employee_id: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> ServiceResult<Vec<RescheduleProposalDto>> {
+        let appointments = self
+            .appointment
+            .get_appointment_list_by_filter(&AppointmentFilterDto {
+                customer_id: None,
+                employee_id: Some(employee_id),
+                resource_id: None,
+                from: Some(from),
+                to: Some(to),
+            })
+            .await?;
+        let (absent, employees): (Vec<EmployeeDto>, Vec<EmployeeDto>) = self
+            .employee
+            .get_employee_list()
+            .await?
+            .into_iter()
+            .filter(|employee| employee.active || employee.id == employee_id)
+            .partition(|employee| employee.id == employee_id);
+
+        let mut connection = self.pool.acquire().await.unwrap();
+        let customer_ids: Vec<i64> = appointments.iter().map(|a| a.customer_id).collect();
+        let names = get_customer_names(connection.as_mut(), &customer_ids).await?;
+
+        // The affected appointments give up their slots, the proposals take new ones.
+        let mut planner = Planner {
+            affected_ids: appointments.iter().map(|a| a.id).collect(),
+            bookings: Vec::new(),
+        };
+
+        let mut proposals = Vec::with_capacity(appointments.len());
+        for appointment in appointments {
+            let zone = time_zone::parse_time_zone(&appointment.time_zone)?;
+            let duration = Duration::minutes(appointment.duration_minutes);
+            let resource_ids: Vec<i64> = appointment.resources.iter().map(|r| r.id).collect();
+            let start_utc = appointment.start_utc;
+
+            let mut action = RescheduleAction::Unresolved;
+            let mut employee = appointment.employee.clone();
+            let mut start_date = appointment.start_date;
+            let mut booking = (start_utc, employee_id);
+
+            for candidate in &employees {
+                if !employee::is_employed_on(candidate, appointment.start_date.date()) {
+                    continue;
+                }
+                let end_utc = start_utc + duration;
+                if planner
+                    .is_free(
+                        connection.as_mut(),
+                        start_utc,
+                        end_utc,
+                        candidate.id,
+                        &resource_ids,
+                    )
+                    .await?
+                {
+                    action = RescheduleAction::Reassign;
+                    employee = Some(EmployeeRefDto::from(candidate));
+                    booking = (start_utc, candidate.id);
+                    break;
+                }
+            }
+
+            if action == RescheduleAction::Unresolved {
+                for days in 1..=MAX_SHIFT_DAYS {
+                    let day = to + Duration::days(days);
+                    // The employee keeps the appointment, so it can't move past the employment.
+                    if !absent
+                        .iter()
+                        .all(|employee| employee::is_employed_on(employee, day))
+                    {
+                        continue;
+                    }
+                    let local = day.and_time(appointment.start_date.time());
+                    let Ok(candidate_utc) = time_zone::resolve_local_time(zone, local, None) else {
+                        continue;
+                    };
+                    if planner
+                        .is_free(
+                            connection.as_mut(),
+                            candidate_utc,
+                            candidate_utc + duration,
+                            employee_id,
+                            &resource_ids,
+                        )
+                        .await?
+                    {
+                        action = RescheduleAction::Shift;
+                        start_date = local;
+                        booking = (candidate_utc, employee_id);
+                        break;
+                    }
+                }
+            }
+
+            planner.bookings.push(PlannedBooking {
+                start_utc: booking.0,
+                end_utc: booking.0 + duration,
+                employee_id: booking.1,
+                resource_ids,
+            });
+
+            let (first_name, last_name) = names
+                .get(&appointment.customer_id)
+                .cloned()
+                .unwrap_or_default();
+            proposals.push(RescheduleProposalDto {
+                appointment,
+                first_name,
+                last_name,
+                action,
+                employee,
+                start_date,
+            });
+        }
+
+        Ok(proposals)
+    }
+
+    /// Applies all changes in a single transaction. If any appointment cannot be moved, for
+    /// example because of a conflict, nothing is changed.
+    pub async fn apply_reschedule(
+        &self,
+        changes: Vec<RescheduleChangeDto>,
+    ) -> ServiceResult<RescheduleSummaryDto> {
+        let employees: HashMap<i64, EmployeeRefDto> = self
+            .employee
+            .get_employee_list()
+            .await?
+            .iter()
+            .map(|employee| (employee.id, EmployeeRefDto::from(employee)))
+            .collect();
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let mut summary = RescheduleSummaryDto {
+            reassigned: 0,
+            shifted: 0,
+            changes: Vec::new(),
+        };
+
+        for change in changes {
+            let Some(mut edit) =
+                appointment::get_edit_appointment(transaction.as_mut(), change.appointment_id)
+                    .await?
+            else {
+                return Err(ServiceError::BadRequest(format!(
+                    "appointment {} does not exist",
+                    change.appointment_id
+                )));
+            };
+
+            let is_reassigned = edit.employee_id != change.employee_id;
+            let is_shifted = edit.start_date != change.start_date;
+            if !is_reassigned && !is_shifted {
+                continue;
+            }
+
+            let old_start_date = edit.start_date;
+            let old_employee_id = edit.employee_id;
+            edit.employee_id = change.employee_id;
+            edit.start_date = change.start_date;
+            appointment::store_appointment(transaction.as_mut(), &edit).await?;
+
+            let (first_name, last_name) =
+                get_customer_names(transaction.as_mut(), &[edit.customer_id])
+                    .await?
+                    .remove(&edit.customer_id)
+                    .unwrap_or_default();
+
+            summary.reassigned += is_reassigned as i64;
+            summary.shifted += is_shifted as i64;
+            summary.changes.push(RescheduledAppointmentDto {
+                appointment_id: change.appointment_id,
+                customer_id: edit.customer_id,
+                first_name,
+                last_name,
+                old_start_date,
+                new_start_date: change.start_date,
+                old_employee: old_employee_id.and_then(|id| employees.get(&id).cloned()),
+                new_employee: change
+                    .employee_id
+                    .and_then(|id| employees.get(&id).cloned()),
+            });
+        }
+
+        transaction.commit().await?;
+        Ok(summary)
+    }
+}
+
+struct PlannedBooking {
+    start_utc: DateTime<Utc>,
+    end_utc: DateTime<Utc>,
+    employee_id: i64,
+    resource_ids: Vec<i64>,
+}
+
+struct Planner {
+    affected_ids: Vec<i64>,
+    bookings: Vec<PlannedBooking>,
+}
+
+impl Planner {
+    /// Free means no stored appointment apart from the affected ones and no booking proposed
+    /// earlier in the plan needs the employee or one of the resources at that time.
+    async fn is_free(
+        &self,
+        connection: &mut SqliteConnection,
+        start_utc: DateTime<Utc>,
+        end_utc: DateTime<Utc>,
+        employee_id: i64,
+        resource_ids: &[i64],
+    ) -> ServiceResult<bool> {
+        let is_planned = self.bookings.iter().any(|booking| {
+            booking.start_utc < end_utc
+                && booking.end_utc > start_utc
+                && (booking.employee_id == employee_id
+                    || booking
+                        .resource_ids
+                        .iter()
+                        .any(|id| resource_ids.contains(id)))
+        });
+        if is_planned {
+            return Ok(false);
+        }
+
+        Ok(appointment::find_conflicts(
+            connection,
+            None,
+            start_utc,
+            end_utc,
+            Some(employee_id),
+            resource_ids,
+        )
+        .await?
+        .iter()
+        .all(|conflict| self.affected_ids.contains(&conflict.appointment_id)))
+    }
+}
+
+async fn get_customer_names(
+    connection: &mut SqliteConnection,
+    customer_ids: &[i64],
+) -> ServiceResult<HashMap<i64, (String, String)>> {
+    Ok(sqlx::query_as::<_, CustomerNameRow>(
+        r#"
+        SELECT c.id, c.first_name, c.last_name
+        FROM customer c
+        WHERE c.id IN (SELECT value FROM json_each($1));
+    "#,
+    )
+    .bind(serde_json::to_string(customer_ids)?)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| (row.id, (row.first_name, row.last_name)))
+    .collect())
+}
+
+#[derive(sqlx::FromRow)]
+struct CustomerNameRow {
+    pub id: i64,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::model::{AmbiguousTime, EditCustomerAppointmentDto, EditEmployeeDto};
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        date(day).and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    async fn employee(state: &State, name: &str, employed_until: Option<NaiveDate>) -> i64 {
+        state
+            .employee
+            .store_employee(EditEmployeeDto {
+                id: None,
+                name: name.to_string(),
+                phone: String::new(),
+                email: String::new(),
+                color: String::new(),
+                employed_from: None,
+                employed_until,
+                active: true,
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn book(state: &State, employee_id: i64, start_date: NaiveDateTime) -> i64 {
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        state
+            .appointment
+            .store_appointment(EditCustomerAppointmentDto {
+                employee_id: Some(employee_id),
+                ..appointment(customer_id, start_date, vec![item("Massage", 1, 5000)])
+            })
+            .await
+            .unwrap()
+    }
+
+    fn actions(proposals: &[RescheduleProposalDto]) -> Vec<(i64, RescheduleAction, Option<i64>)> {
+        proposals
+            .iter()
+            .map(|proposal| {
+                (
+                    proposal.appointment.id,
+                    proposal.action,
+                    proposal.employee.as_ref().map(|employee| employee.id),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn plans_prefer_other_employees_over_shifting() {
+        let state = state().await;
+        let eva = employee(&state, "Eva", None).await;
+        let max = employee(&state, "Max", None).await;
+
+        let reassigned = book(&state, eva, at(4, 10)).await;
+        let shifted = book(&state, eva, at(5, 10)).await;
+        book(&state, max, at(5, 10)).await;
+        // Eva is busy on the first day after the absence.
+        book(&state, eva, at(7, 10)).await;
+
+        let proposals = state
+            .reschedule
+            .plan_reschedule(eva, date(4), date(6))
+            .await
+            .unwrap();
+        assert_eq!(
+            actions(&proposals),
+            vec![
+                (reassigned, RescheduleAction::Reassign, Some(max)),
+                (shifted, RescheduleAction::Shift, Some(eva)),
+            ]
+        );
+        assert_eq!(proposals[0].start_date, at(4, 10));
+        assert_eq!(proposals[1].start_date, at(8, 10));
+    }
+
+    #[tokio::test]
+    async fn shifts_stay_within_the_employment() {
+        let state = state().await;
+        let eva = employee(&state, "Eva", Some(date(7))).await;
+        let max = employee(&state, "Max", None).await;
+
+        let id = book(&state, eva, at(5, 10)).await;
+        book(&state, max, at(5, 10)).await;
+        book(&state, eva, at(7, 10)).await;
+
+        let proposals = state
+            .reschedule
+            .plan_reschedule(eva, date(5), date(6))
+            .await
+            .unwrap();
+        assert_eq!(
+            actions(&proposals),
+            vec![(id, RescheduleAction::Unresolved, Some(eva))]
+        );
+        assert_eq!(proposals[0].start_date, at(5, 10));
+    }
+
+    #[tokio::test]
+    async fn plans_use_the_stored_instants() {
+        let state = state().await;
+        let eva = employee(&state, "Eva", None).await;
+        let max = employee(&state, "Max", None).await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        // 2:30 occurs twice in Berlin on this day, an hour apart.
+        let start_date = NaiveDate::from_ymd_opt(2024, 10, 27)
+            .unwrap()
+            .and_hms_opt(2, 30, 0)
+            .unwrap();
+        let store = async |employee_id, ambiguous_time| {
+            state
+                .appointment
+                .store_appointment(EditCustomerAppointmentDto {
+                    employee_id: Some(employee_id),
+                    ambiguous_time: Some(ambiguous_time),
+                    ..appointment(customer_id, start_date, vec![item("Massage", 1, 5000)])
+                })
+                .await
+                .unwrap()
+        };
+        store(max, AmbiguousTime::Earlier).await;
+        let id = store(eva, AmbiguousTime::Later).await;
+
+        let day = start_date.date();
+        let proposals = state.reschedule.plan_reschedule(eva, day, day).await;
+        assert_eq!(
+            actions(&proposals.unwrap()),
+            vec![(id, RescheduleAction::Reassign, Some(max))]
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_are_applied_all_or_nothing() {
+        let state = state().await;
+        let eva = employee(&state, "Eva", None).await;
+        let max = employee(&state, "Max", None).await;
+
+        let first = book(&state, eva, at(4, 10)).await;
+        let second = book(&state, eva, at(5, 10)).await;
+        book(&state, max, at(5, 10)).await;
+
+        let reassign = |appointment_id, start_date| RescheduleChangeDto {
+            appointment_id,
+            employee_id: Some(max),
+            start_date,
+        };
+        let employee_of = async |id| {
+            let appointment = state.appointment.get_appointment_by_id(id).await;
+            let appointment = appointment.unwrap().unwrap();
+            (
+                appointment.employee.map(|employee| employee.id),
+                appointment.start_date,
+            )
+        };
+
+        // Max is busy at the time of the second appointment, so the first one stays too.
+        assert!(matches!(
+            state
+                .reschedule
+                .apply_reschedule(vec![
+                    reassign(first, at(4, 10)),
+                    reassign(second, at(5, 10))
+                ])
+                .await,
+            Err(ServiceError::Conflict(_))
+        ));
+        assert_eq!(employee_of(first).await, (Some(eva), at(4, 10)));
+        assert_eq!(employee_of(second).await, (Some(eva), at(5, 10)));
+
+        let summary = state
+            .reschedule
+            .apply_reschedule(vec![
+                reassign(first, at(4, 10)),
+                RescheduleChangeDto {
+                    appointment_id: second,
+                    employee_id: Some(eva),
+                    start_date: at(6, 10),
+                },
+            ])
+            .await
+            .unwrap();
+        assert_eq!((summary.reassigned, summary.shifted), (1, 1));
+        assert_eq!(summary.changes[1].old_start_date, at(5, 10));
+        assert_eq!(employee_of(first).await, (Some(max), at(4, 10)));
+        assert_eq!(employee_of(second).await, (Some(eva), at(6, 10)));
+    }
+}
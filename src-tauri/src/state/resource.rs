@@ -0,0 +1,338 @@
+use chrono::NaiveDate;
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+
+use crate::{
+    error::ServiceResult,
+    model::{
+        AppointmentFilterDto, EditResourceDto, ResourceCalendarDto, ResourceDto, ResourceKind,
+    },
+    state::appointment::AppointmentState,
+};
+
+#[derive(Clone)]
+pub struct ResourceState {
+    pool: Arc<Pool<Sqlite>>,
+    appointment: AppointmentState,
+}
+
+impl ResourceState {
+    pub fn new(pool: Arc<Pool<Sqlite>>, appointment: AppointmentState) -> Self {
+        Self { pool, appointment }
+    }
+
+    pub async fn get_resource_list(&self) -> ServiceResult<Vec<ResourceDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ResourceRow>(
+            r#"
+            SELECT r.id, r.name, r.kind, r.active
+            FROM resource r
+            ORDER BY r.kind ASC, r.name ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    pub async fn get_resource_by_id(&self, id: i64) -> ServiceResult<Option<ResourceDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, ResourceRow>(
+            r#"
+            SELECT r.id, r.name, r.kind, r.active
+            FROM resource r
+            WHERE r.id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .map(|row| row.into()))
+    }
+
+    pub async fn store_resource(&self, resource: EditResourceDto) -> ServiceResult<i64> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let q = if let Some(resource_id) = resource.id {
+            sqlx::query(
+                r#"
+                UPDATE resource
+                SET name = $2,
+                    kind = $3,
+                    active = $4
+                WHERE id = $1;
+            "#,
+            )
+            .bind(resource_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO resource (name, kind, active)
+                VALUES ($1, $2, $3);
+            "#,
+            )
+        };
+
+        q.bind(&resource.name)
+            .bind(resource.kind)
+            .bind(resource.active)
+            .execute(connection.as_mut())
+            .await?;
+
+        if let Some(resource_id) = resource.id {
+            return Ok(resource_id);
+        }
+
+        Ok(sqlx::query_as::<_, IdRow>(
+            r#"
+        SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(connection.as_mut())
+        .await?
+        .id)
+    }
+
+    /// Also removes the resource from all appointments, deactivate it to keep the bookings.
+    pub async fn delete_resource(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        sqlx::query(
+            r#"
+                DELETE FROM resource
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Appointments per active resource in the given inclusive date range.
+    pub async fn get_resource_calendar(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> ServiceResult<Vec<ResourceCalendarDto>> {
+        let appointments = self
+            .appointment
+            .get_appointment_list_by_filter(&AppointmentFilterDto {
+                customer_id: None,
+                employee_id: None,
+                resource_id: None,
+                from: Some(from),
+                to: Some(to),
+            })
+            .await?;
+
+        Ok(self
+            .get_resource_list()
+            .await?
+            .into_iter()
+            .filter(|resource| resource.active)
+            .map(|resource| ResourceCalendarDto {
+                appointments: appointments
+                    .iter()
+                    .filter(|appointment| appointment.resources.iter().any(|r| r.id == resource.id))
+                    .cloned()
+                    .collect(),
+                resource,
+            })
+            .collect())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct ResourceRow {
+    pub id: i64,
+    pub name: String,
+    pub kind: ResourceKind,
+    pub active: bool,
+}
+
+impl From<ResourceRow> for ResourceDto {
+    fn from(row: ResourceRow) -> ResourceDto {
+        ResourceDto {
+            id: row.id,
+            name: row.name,
+            kind: row.kind,
+            active: row.active,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+
+    use super::*;
+    use crate::error::ServiceError;
+    use crate::model::{EditCustomerAppointmentDto, EditTreatmentDto};
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, state};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    async fn resource(state: &State, name: &str, kind: ResourceKind, active: bool) -> i64 {
+        state
+            .resource
+            .store_resource(EditResourceDto {
+                id: None,
+                name: name.to_string(),
+                kind,
+                active,
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resources_cannot_be_booked_twice() {
+        let state = state().await;
+        let room = resource(&state, "Room 1", ResourceKind::Room, true).await;
+        let lamp = resource(&state, "Red light", ResourceKind::Device, true).await;
+        let treatment_id = state
+            .treatment
+            .store_treatment(EditTreatmentDto {
+                id: None,
+                name: "Light therapy".to_string(),
+                category: String::new(),
+                default_duration_minutes: 60,
+                default_price: 5000,
+                vat_rate: 1900,
+                active: true,
+                resource_ids: vec![lamp],
+            })
+            .await
+            .unwrap();
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let book = |start_date, resource_ids: Vec<i64>, treatment_id| {
+            let mut item = item("Treatment", 1, 5000);
+            item.treatment_id = treatment_id;
+            EditCustomerAppointmentDto {
+                resource_ids,
+                ..appointment(customer_id, start_date, vec![item])
+            }
+        };
+
+        let first = state
+            .appointment
+            .store_appointment(book(at(4, 10), vec![room], Some(treatment_id)))
+            .await
+            .unwrap();
+
+        // Overlapping bookings of the room or of the lamp required by the treatment.
+        for appointment in [
+            book(at(4, 10) + Duration::minutes(30), vec![room], None),
+            book(
+                at(4, 9) + Duration::minutes(30),
+                Vec::new(),
+                Some(treatment_id),
+            ),
+        ] {
+            assert!(matches!(
+                state.appointment.store_appointment(appointment).await,
+                Err(ServiceError::Conflict(_))
+            ));
+        }
+        let conflict = state
+            .appointment
+            .store_appointment(book(at(4, 10), vec![room, lamp], None))
+            .await;
+        let Err(ServiceError::Conflict(message)) = conflict else {
+            panic!("booking should conflict");
+        };
+        assert!(message.contains("Room 1 is already booked from 2025-03-04 10:00 to 11:00"));
+        assert!(message.contains("Red light is already booked"));
+
+        // Back to back and other resources are fine.
+        state
+            .appointment
+            .store_appointment(book(at(4, 11), vec![room], Some(treatment_id)))
+            .await
+            .unwrap();
+        let other_room = resource(&state, "Room 2", ResourceKind::Room, true).await;
+        state
+            .appointment
+            .store_appointment(book(at(4, 10), vec![other_room], None))
+            .await
+            .unwrap();
+
+        // Moving the appointment itself doesn't conflict with its old slot.
+        state
+            .appointment
+            .store_appointment(EditCustomerAppointmentDto {
+                id: Some(first),
+                ..book(
+                    at(4, 9) + Duration::minutes(30),
+                    vec![room],
+                    Some(treatment_id),
+                )
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn calendar_lists_the_bookings_of_active_resources() {
+        let state = state().await;
+        let room = resource(&state, "Room 1", ResourceKind::Room, true).await;
+        let other_room = resource(&state, "Room 2", ResourceKind::Room, true).await;
+        let inactive = resource(&state, "Old room", ResourceKind::Room, false).await;
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let book = async |start_date, resource_ids| {
+            state
+                .appointment
+                .store_appointment(EditCustomerAppointmentDto {
+                    resource_ids,
+                    ..appointment(customer_id, start_date, vec![item("Massage", 1, 5000)])
+                })
+                .await
+                .unwrap()
+        };
+
+        let both = book(at(4, 10), vec![room, other_room]).await;
+        let last_day = book(at(6, 18), vec![room]).await;
+        book(at(7, 10), vec![room]).await;
+        book(at(5, 10), vec![inactive]).await;
+        book(at(5, 12), Vec::new()).await;
+
+        let calendar = state
+            .resource
+            .get_resource_calendar(at(4, 0).date(), at(6, 0).date())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| {
+                let ids = entry.appointments.iter().map(|a| a.id).collect::<Vec<_>>();
+                (entry.resource.id, ids)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calendar,
+            vec![(room, vec![both, last_day]), (other_room, vec![both])]
+        );
+    }
+}
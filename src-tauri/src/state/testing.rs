@@ -0,0 +1,96 @@
+//! Helpers for the tests of the services, every test works on its own database.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::NaiveDateTime;
+
+use crate::model::{
+    EditAppointmentItemDto, EditCustomerAppointmentDto, EditCustomerDto, EditPreferenceDto,
+};
+use crate::state::State;
+
+/// New empty directory below the temporary directory of the system.
+pub fn temp_dir() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "customer-manager-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// State on a new database with all migrations applied.
+pub async fn state() -> State {
+    State::new(&temp_dir()).await.unwrap()
+}
+
+pub fn customer(first_name: &str, last_name: &str) -> EditCustomerDto {
+    EditCustomerDto {
+        id: None,
+        title: String::new(),
+        first_name: first_name.to_string(),
+        last_name: last_name.to_string(),
+        address_street: String::new(),
+        address_postcode: String::new(),
+        address_city: String::new(),
+        phone: String::new(),
+        mobile_phone: String::new(),
+        email: String::new(),
+        birthdate: None,
+        customer_since: None,
+        note: String::new(),
+        responsible_employee_id: None,
+        tags: Vec::new(),
+    }
+}
+
+/// Item with 19 % VAT and without discount.
+pub fn item(description: &str, quantity: i64, unit_price: i64) -> EditAppointmentItemDto {
+    EditAppointmentItemDto {
+        treatment_id: None,
+        product_id: None,
+        description: description.to_string(),
+        quantity,
+        unit_price,
+        discount: 0,
+        vat_rate: 1900,
+        customer_package_id: None,
+        price_rule_id: None,
+    }
+}
+
+/// New appointment of an hour.
+pub fn appointment(
+    customer_id: i64,
+    start_date: NaiveDateTime,
+    items: Vec<EditAppointmentItemDto>,
+) -> EditCustomerAppointmentDto {
+    EditCustomerAppointmentDto {
+        id: None,
+        customer_id,
+        start_date,
+        ambiguous_time: None,
+        duration_minutes: 60,
+        employee_id: None,
+        items,
+        resource_ids: Vec::new(),
+    }
+}
+
+/// Stores a preference, the value is serialized as JSON.
+pub async fn set_preference(state: &State, key: &str, value: impl serde::Serialize) {
+    state
+        .preference
+        .store_preference(EditPreferenceDto {
+            key: key.to_string(),
+            value: Some(serde_json::to_string(&value).unwrap()),
+        })
+        .await
+        .unwrap();
+}
@@ -0,0 +1,363 @@
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{EditTreatmentDto, TreatmentDto},
+    money,
+    state::{commission, package},
+};
+
+#[derive(Clone)]
+pub struct TreatmentState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl TreatmentState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_treatment_list(&self) -> ServiceResult<Vec<TreatmentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let mut treatments = sqlx::query_as::<_, TreatmentRow>(
+            r#"
+            SELECT
+                t.id,
+                t.name,
+                t.category,
+                t.default_duration_minutes,
+                t.default_price,
+                t.vat_rate,
+                t.active
+            FROM treatment t
+            ORDER BY t.category ASC, t.name ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect::<Vec<TreatmentDto>>();
+
+        let mut resource_ids = get_resource_id_map(connection.as_mut(), None).await?;
+        for treatment in &mut treatments {
+            treatment.resource_ids = resource_ids.remove(&treatment.id).unwrap_or_default();
+        }
+
+        Ok(treatments)
+    }
+
+    pub async fn get_treatment_by_id(&self, id: i64) -> ServiceResult<Option<TreatmentDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let mut treatment = sqlx::query_as::<_, TreatmentRow>(
+            r#"
+            SELECT
+                t.id,
+                t.name,
+                t.category,
+                t.default_duration_minutes,
+                t.default_price,
+                t.vat_rate,
+                t.active
+            FROM treatment t
+            WHERE t.id = $1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .map(TreatmentDto::from);
+
+        if let Some(treatment) = &mut treatment {
+            treatment.resource_ids = get_resource_id_map(connection.as_mut(), Some(id))
+                .await?
+                .remove(&id)
+                .unwrap_or_default();
+        }
+
+        Ok(treatment)
+    }
+
+    pub async fn store_treatment(&self, treatment: EditTreatmentDto) -> ServiceResult<i64> {
+        money::validate_vat_rate(treatment.vat_rate)?;
+
+        let mut transaction = self.pool.begin().await?;
+
+        let q = if let Some(treatment_id) = treatment.id {
+            sqlx::query(
+                r#"
+                UPDATE treatment
+                SET name = $2,
+                    category = $3,
+                    default_duration_minutes = $4,
+                    default_price = $5,
+                    vat_rate = $6,
+                    active = $7
+                WHERE id = $1;
+            "#,
+            )
+            .bind(treatment_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO treatment (
+                    name,
+                    category,
+                    default_duration_minutes,
+                    default_price,
+                    vat_rate,
+                    active
+                )
+                VALUES ($1, $2, $3, $4, $5, $6);
+            "#,
+            )
+        };
+
+        q.bind(&treatment.name)
+            .bind(&treatment.category)
+            .bind(treatment.default_duration_minutes)
+            .bind(treatment.default_price)
+            .bind(treatment.vat_rate)
+            .bind(treatment.active)
+            .execute(transaction.as_mut())
+            .await?;
+
+        let treatment_id = if let Some(treatment_id) = treatment.id {
+            treatment_id
+        } else {
+            sqlx::query_as::<_, IdRow>(
+                r#"
+            SELECT last_insert_rowid() as id;
+            "#,
+            )
+            .fetch_one(transaction.as_mut())
+            .await?
+            .id
+        };
+
+        sqlx::query(
+            r#"
+                DELETE FROM treatment_resource
+                WHERE treatment_id = $1;
+            "#,
+        )
+        .bind(treatment_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        for resource_id in &treatment.resource_ids {
+            sqlx::query(
+                r#"
+                    INSERT INTO treatment_resource (treatment_id, resource_id)
+                    VALUES ($1, $2)
+                    ON CONFLICT DO NOTHING;
+                "#,
+            )
+            .bind(treatment_id)
+            .bind(resource_id)
+            .execute(transaction.as_mut())
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(treatment_id)
+    }
+
+    /// Appointments keep their own treatment text and price, so removing a catalog entry only
+    /// clears the reference and leaves the history untouched. Price and commission rules of the
+    /// treatment are removed with it, unless they apply to past appointments. Treatments with
+    /// open packages are kept, as the remaining sessions are booked with them.
+    pub async fn delete_treatment(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let applied = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT r.id
+            FROM price_rule r
+            JOIN appointment_item i ON i.price_rule_id = r.id
+            WHERE r.treatment_id = $1
+            LIMIT 1;
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(connection.as_mut())
+        .await?
+        .is_some();
+        if applied {
+            return Err(ServiceError::Conflict(
+                "treatment has price rules that have been applied to appointments, please \
+                 deactivate it instead"
+                    .to_string(),
+            ));
+        }
+        if commission::has_used_treatment_rules(connection.as_mut(), id).await? {
+            return Err(ServiceError::Conflict(
+                "treatment has commission rules that apply to past appointments, please \
+                 deactivate it instead"
+                    .to_string(),
+            ));
+        }
+        if package::has_open_packages(connection.as_mut(), id).await? {
+            return Err(ServiceError::Conflict(
+                "treatment has packages with open sessions, please deactivate it instead"
+                    .to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM treatment
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Resources required by the treatments, by treatment id.
+async fn get_resource_id_map(
+    connection: &mut SqliteConnection,
+    treatment_id: Option<i64>,
+) -> ServiceResult<HashMap<i64, Vec<i64>>> {
+    Ok(sqlx::query_as::<_, TreatmentResourceRow>(
+        r#"
+        SELECT tr.treatment_id, tr.resource_id
+        FROM treatment_resource tr
+        WHERE ($1 IS NULL OR tr.treatment_id = $1)
+        ORDER BY tr.resource_id ASC;
+    "#,
+    )
+    .bind(treatment_id)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .fold(HashMap::new(), |mut map, row| {
+        map.entry(row.treatment_id)
+            .or_insert_with(Vec::new)
+            .push(row.resource_id);
+        map
+    }))
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct TreatmentRow {
+    pub id: i64,
+    pub name: String,
+    pub category: String,
+    pub default_duration_minutes: i64,
+    pub default_price: i64,
+    pub vat_rate: i64,
+    pub active: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct TreatmentResourceRow {
+    pub treatment_id: i64,
+    pub resource_id: i64,
+}
+
+impl From<TreatmentRow> for TreatmentDto {
+    fn from(row: TreatmentRow) -> TreatmentDto {
+        TreatmentDto {
+            id: row.id,
+            name: row.name,
+            category: row.category,
+            default_duration_minutes: row.default_duration_minutes,
+            default_price: row.default_price,
+            vat_rate: row.vat_rate,
+            active: row.active,
+            resource_ids: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::{EditPriceRuleDto, PriceRuleKind};
+    use crate::state::testing::{appointment, customer, item, state};
+
+    #[tokio::test]
+    async fn applied_price_rules_keep_their_treatment() {
+        let state = state().await;
+        let treatment = |name: &str| EditTreatmentDto {
+            id: None,
+            name: name.to_string(),
+            category: String::new(),
+            default_duration_minutes: 60,
+            default_price: 5000,
+            vat_rate: 1900,
+            active: true,
+            resource_ids: Vec::new(),
+        };
+        let rule = |treatment_id| EditPriceRuleDto {
+            id: None,
+            name: "Happy hour".to_string(),
+            kind: PriceRuleKind::Percentage,
+            value: 1000,
+            customer_tag: None,
+            treatment_id: Some(treatment_id),
+            min_visit_count: None,
+            weekdays: Vec::new(),
+            start_time: None,
+            end_time: None,
+            active: true,
+        };
+        let applied = state
+            .treatment
+            .store_treatment(treatment("Massage"))
+            .await
+            .unwrap();
+        let unapplied = state
+            .treatment
+            .store_treatment(treatment("Facial"))
+            .await
+            .unwrap();
+        for treatment_id in [applied, unapplied] {
+            state
+                .price_rule
+                .store_price_rule(rule(treatment_id))
+                .await
+                .unwrap();
+        }
+
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        let mut massage = item("Massage", 1, 5000);
+        massage.treatment_id = Some(applied);
+        let start_date = NaiveDate::from_ymd_opt(2025, 3, 3)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        state
+            .appointment
+            .store_appointment(appointment(customer_id, start_date, vec![massage]))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            state.treatment.delete_treatment(applied).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        state.treatment.delete_treatment(unapplied).await.unwrap();
+        let rules = state.price_rule.get_price_rule_list().await.unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].treatment_id, Some(applied));
+    }
+}
@@ -0,0 +1,689 @@
+use argon2::password_hash::{SaltString, rand_core::OsRng};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{EditUserDto, EmployeeRefDto, SessionDto, UserDto, UserRole},
+};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// User accounts and the session of the user that is logged in. Commands check the role of
+/// the session with [`UserState::authorize`].
+#[derive(Clone)]
+pub struct UserState {
+    pool: Arc<Pool<Sqlite>>,
+    session: Arc<RwLock<Option<SessionDto>>>,
+}
+
+impl UserState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self {
+            pool,
+            session: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Returns the session if its user has one of the roles.
+    pub fn authorize(&self, roles: &[UserRole]) -> ServiceResult<SessionDto> {
+        let session = self.session.read()?;
+        match session.as_ref() {
+            None => Err(ServiceError::Unauthorized("login required".to_string())),
+            Some(session) if !roles.contains(&session.role) => Err(ServiceError::Forbidden(
+                format!("not permitted for the role {}", role_name(session.role)),
+            )),
+            Some(session) => Ok(session.clone()),
+        }
+    }
+
+    pub fn get_session(&self) -> ServiceResult<Option<SessionDto>> {
+        Ok(self.session.read()?.clone())
+    }
+
+    /// A new installation has no accounts, the first one is created by [`Self::setup_admin`].
+    pub async fn is_setup_required(&self) -> ServiceResult<bool> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(count_users(connection.as_mut()).await? == 0)
+    }
+
+    /// Creates the first account as administrator and logs it in.
+    pub async fn setup_admin(&self, username: &str, password: &str) -> ServiceResult<SessionDto> {
+        let username = validate_username(username)?;
+        validate_password(password)?;
+        let password_hash = hash_password(password).await?;
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        if count_users(transaction.as_mut()).await? > 0 {
+            return Err(ServiceError::Conflict(
+                "user accounts are already set up".to_string(),
+            ));
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO user_account (username, password_hash, role, employee_id, active)
+            VALUES ($1, $2, $3, NULL, TRUE);
+        "#,
+        )
+        .bind(username)
+        .bind(&password_hash)
+        .bind(UserRole::Admin)
+        .execute(transaction.as_mut())
+        .await?;
+        let user_id = last_insert_id(transaction.as_mut()).await?;
+        transaction.commit().await?;
+
+        let session = SessionDto {
+            user_id,
+            username: username.to_string(),
+            role: UserRole::Admin,
+            employee_id: None,
+        };
+        *self.session.write()? = Some(session.clone());
+        Ok(session)
+    }
+
+    /// Logs in an active account. Accounts linked to an archived employee cannot log in.
+    pub async fn login(&self, username: &str, password: &str) -> ServiceResult<SessionDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let row = sqlx::query_as::<_, LoginRow>(
+            r#"
+            SELECT u.id, u.username, u.password_hash, u.role, u.employee_id
+            FROM user_account u
+            LEFT JOIN employee e ON e.id = u.employee_id
+            WHERE u.username = $1
+                AND u.active
+                AND (e.id IS NULL OR e.active);
+        "#,
+        )
+        .bind(username.trim())
+        .fetch_optional(connection.as_mut())
+        .await?;
+
+        let Some(row) = row else {
+            return Err(invalid_login());
+        };
+        if !verify_password(password, row.password_hash).await? {
+            return Err(invalid_login());
+        }
+
+        let session = SessionDto {
+            user_id: row.id,
+            username: row.username,
+            role: row.role,
+            employee_id: row.employee_id,
+        };
+        *self.session.write()? = Some(session.clone());
+        Ok(session)
+    }
+
+    pub fn logout(&self) -> ServiceResult<()> {
+        *self.session.write()? = None;
+        Ok(())
+    }
+
+    /// Changes the password of the user that is logged in.
+    pub async fn change_password(
+        &self,
+        current_password: &str,
+        new_password: &str,
+    ) -> ServiceResult<()> {
+        let Some(session) = self.get_session()? else {
+            return Err(ServiceError::Unauthorized("login required".to_string()));
+        };
+        validate_password(new_password)?;
+
+        let mut connection = self.pool.acquire().await.unwrap();
+        let password_hash = sqlx::query_as::<_, PasswordRow>(
+            r#"
+            SELECT u.password_hash
+            FROM user_account u
+            WHERE u.id = $1;
+        "#,
+        )
+        .bind(session.user_id)
+        .fetch_one(connection.as_mut())
+        .await?
+        .password_hash;
+        if !verify_password(current_password, password_hash).await? {
+            return Err(ServiceError::BadRequest(
+                "current password is wrong".to_string(),
+            ));
+        }
+
+        let password_hash = hash_password(new_password).await?;
+        sqlx::query(
+            r#"
+            UPDATE user_account
+            SET password_hash = $2
+            WHERE id = $1;
+        "#,
+        )
+        .bind(session.user_id)
+        .bind(&password_hash)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_user_list(&self) -> ServiceResult<Vec<UserDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT
+                u.id,
+                u.username,
+                u.role,
+                e.id AS employee_id,
+                e.name AS employee_name,
+                e.active AS employee_active,
+                u.active
+            FROM user_account u
+            LEFT JOIN employee e ON e.id = u.employee_id
+            ORDER BY u.username ASC;
+        "#,
+        )
+        .fetch_all(connection.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+    }
+
+    /// Stores the account, at least one active administrator must remain. Changes of the user
+    /// that is logged in apply to the session at once.
+    pub async fn store_user(&self, user: EditUserDto) -> ServiceResult<i64> {
+        let username = validate_username(&user.username)?;
+        let password_hash = if user.password.is_empty() {
+            if user.id.is_none() {
+                return Err(ServiceError::BadRequest(
+                    "new user accounts need a password".to_string(),
+                ));
+            }
+            None
+        } else {
+            validate_password(&user.password)?;
+            Some(hash_password(&user.password).await?)
+        };
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let is_taken = sqlx::query_as::<_, CountRow>(
+            r#"
+            SELECT COUNT(*) AS count
+            FROM user_account u
+            WHERE u.username = $1 AND ($2 IS NULL OR u.id <> $2);
+        "#,
+        )
+        .bind(username)
+        .bind(user.id)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .count
+            > 0;
+        if is_taken {
+            return Err(ServiceError::Conflict(format!(
+                "username {} is already taken",
+                username
+            )));
+        }
+        if let Some(user_id) = user.id
+            && (user.role != UserRole::Admin || !user.active)
+        {
+            ensure_other_admin(transaction.as_mut(), user_id).await?;
+        }
+
+        let user_id = if let Some(user_id) = user.id {
+            let result = sqlx::query(
+                r#"
+                UPDATE user_account
+                SET
+                    username = $2,
+                    password_hash = COALESCE($3, password_hash),
+                    role = $4,
+                    employee_id = $5,
+                    active = $6
+                WHERE id = $1;
+            "#,
+            )
+            .bind(user_id)
+            .bind(username)
+            .bind(&password_hash)
+            .bind(user.role)
+            .bind(user.employee_id)
+            .bind(user.active)
+            .execute(transaction.as_mut())
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(ServiceError::BadRequest(format!(
+                    "user {} does not exist",
+                    user_id
+                )));
+            }
+            user_id
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO user_account (username, password_hash, role, employee_id, active)
+                VALUES ($1, $2, $3, $4, $5);
+            "#,
+            )
+            .bind(username)
+            .bind(&password_hash)
+            .bind(user.role)
+            .bind(user.employee_id)
+            .bind(user.active)
+            .execute(transaction.as_mut())
+            .await?;
+            last_insert_id(transaction.as_mut()).await?
+        };
+
+        transaction.commit().await?;
+
+        let mut session = self.session.write()?;
+        if session.as_ref().is_some_and(|s| s.user_id == user_id) {
+            *session = user.active.then(|| SessionDto {
+                user_id,
+                username: username.to_string(),
+                role: user.role,
+                employee_id: user.employee_id,
+            });
+        }
+
+        Ok(user_id)
+    }
+
+    pub async fn delete_user(&self, id: i64) -> ServiceResult<()> {
+        if self
+            .get_session()?
+            .is_some_and(|session| session.user_id == id)
+        {
+            return Err(ServiceError::Conflict(
+                "the user that is logged in cannot be deleted".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        ensure_other_admin(transaction.as_mut(), id).await?;
+        sqlx::query(
+            r#"
+            DELETE FROM user_account
+            WHERE id = $1;
+        "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+fn role_name(role: UserRole) -> &'static str {
+    match role {
+        UserRole::Admin => "admin",
+        UserRole::Reception => "reception",
+        UserRole::Therapist => "therapist",
+    }
+}
+
+/// Unknown users and wrong passwords are not told apart.
+fn invalid_login() -> ServiceError {
+    ServiceError::Unauthorized("unknown user or wrong password".to_string())
+}
+
+fn validate_username(username: &str) -> ServiceResult<&str> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err(ServiceError::BadRequest(
+            "username must not be empty".to_string(),
+        ));
+    }
+    Ok(username)
+}
+
+fn validate_password(password: &str) -> ServiceResult<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ServiceError::BadRequest(format!(
+            "password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Fails if the account is the only active administrator.
+async fn ensure_other_admin(connection: &mut SqliteConnection, user_id: i64) -> ServiceResult<()> {
+    let other_admins = sqlx::query_as::<_, CountRow>(
+        r#"
+        SELECT COUNT(*) AS count
+        FROM user_account u
+        WHERE u.role = 'admin' AND u.active AND u.id <> $1;
+    "#,
+    )
+    .bind(user_id)
+    .fetch_one(connection)
+    .await?
+    .count;
+    if other_admins == 0 {
+        return Err(ServiceError::Conflict(
+            "at least one active administrator is required".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn count_users(connection: &mut SqliteConnection) -> ServiceResult<i64> {
+    Ok(sqlx::query_as::<_, CountRow>(
+        r#"
+        SELECT COUNT(*) AS count
+        FROM user_account;
+    "#,
+    )
+    .fetch_one(connection)
+    .await?
+    .count)
+}
+
+async fn last_insert_id(connection: &mut SqliteConnection) -> ServiceResult<i64> {
+    Ok(sqlx::query_as::<_, IdRow>(
+        r#"
+        SELECT last_insert_rowid() AS id;
+    "#,
+    )
+    .fetch_one(connection)
+    .await?
+    .id)
+}
+
+/// Hashes with Argon2id and a random salt from the operating system. Hashing is deliberately
+/// slow and runs on a blocking thread.
+async fn hash_password(password: &str) -> ServiceResult<String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default()
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string())
+    })
+    .await?
+}
+
+async fn verify_password(password: &str, password_hash: String) -> ServiceResult<bool> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let password_hash = PasswordHash::new(&password_hash)?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &password_hash)
+            .is_ok())
+    })
+    .await?
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct CountRow {
+    pub count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct PasswordRow {
+    pub password_hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct LoginRow {
+    pub id: i64,
+    pub username: String,
+    pub password_hash: String,
+    pub role: UserRole,
+    pub employee_id: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    pub id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub employee_id: Option<i64>,
+    pub employee_name: Option<String>,
+    pub employee_active: Option<bool>,
+    pub active: bool,
+}
+
+impl From<UserRow> for UserDto {
+    fn from(row: UserRow) -> UserDto {
+        let employee = match (row.employee_id, row.employee_name, row.employee_active) {
+            (Some(id), Some(name), Some(active)) => Some(EmployeeRefDto { id, name, active }),
+            _ => None,
+        };
+
+        UserDto {
+            id: row.id,
+            username: row.username,
+            role: row.role,
+            employee,
+            active: row.active,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ServiceError;
+    use crate::model::{EditEmployeeDto, EditUserDto, UserRole};
+    use crate::state::State;
+    use crate::state::testing::state;
+
+    const PASSWORD: &str = "correct horse";
+
+    fn user(username: &str, role: UserRole) -> EditUserDto {
+        EditUserDto {
+            id: None,
+            username: username.to_string(),
+            password: PASSWORD.to_string(),
+            role,
+            employee_id: None,
+            active: true,
+        }
+    }
+
+    async fn password_hash(state: &State, username: &str) -> String {
+        sqlx::query_scalar("SELECT password_hash FROM user_account WHERE username = $1")
+            .bind(username)
+            .fetch_one(state.user.pool.as_ref())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn setup() {
+        let state = state().await;
+        assert!(state.user.is_setup_required().await.unwrap());
+        assert!(matches!(
+            state.user.setup_admin("admin", "short").await,
+            Err(ServiceError::BadRequest(_))
+        ));
+
+        let session = state.user.setup_admin(" admin ", PASSWORD).await.unwrap();
+        assert_eq!(session.username, "admin");
+        assert_eq!(session.role, UserRole::Admin);
+        assert_eq!(
+            state.user.get_session().unwrap().unwrap().user_id,
+            session.user_id
+        );
+        assert!(!state.user.is_setup_required().await.unwrap());
+        assert!(matches!(
+            state.user.setup_admin("second", PASSWORD).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn login() {
+        let state = state().await;
+        state.user.setup_admin("admin", PASSWORD).await.unwrap();
+        state
+            .user
+            .store_user(user("eva", UserRole::Therapist))
+            .await
+            .unwrap();
+        state.user.logout().unwrap();
+        assert!(state.user.get_session().unwrap().is_none());
+
+        // The same password is hashed with different salts.
+        let hash = password_hash(&state, "eva").await;
+        assert!(hash.starts_with("$argon2id$"));
+        assert_ne!(hash, password_hash(&state, "admin").await);
+
+        let wrong_password = state.user.login("eva", "wrong password").await.unwrap_err();
+        let unknown_user = state.user.login("bob", PASSWORD).await.unwrap_err();
+        assert!(matches!(wrong_password, ServiceError::Unauthorized(_)));
+        assert_eq!(wrong_password.to_string(), unknown_user.to_string());
+        assert!(state.user.get_session().unwrap().is_none());
+
+        let session = state.user.login("eva", PASSWORD).await.unwrap();
+        assert_eq!(session.role, UserRole::Therapist);
+        state
+            .user
+            .change_password("wrong password", "new password")
+            .await
+            .unwrap_err();
+        state
+            .user
+            .change_password(PASSWORD, "new password")
+            .await
+            .unwrap();
+        state.user.logout().unwrap();
+        state.user.login("eva", PASSWORD).await.unwrap_err();
+        state.user.login("eva", "new password").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn inactive_accounts_cannot_log_in() {
+        let state = state().await;
+        state.user.setup_admin("admin", PASSWORD).await.unwrap();
+        let mut inactive = user("old", UserRole::Reception);
+        inactive.active = false;
+        state.user.store_user(inactive).await.unwrap();
+
+        let employee_id = state
+            .employee
+            .store_employee(EditEmployeeDto {
+                id: None,
+                name: "Eva".to_string(),
+                phone: String::new(),
+                email: String::new(),
+                color: String::new(),
+                employed_from: None,
+                employed_until: None,
+                active: false,
+            })
+            .await
+            .unwrap();
+        let mut archived = user("eva", UserRole::Therapist);
+        archived.employee_id = Some(employee_id);
+        state.user.store_user(archived).await.unwrap();
+
+        assert!(state.user.login("old", PASSWORD).await.is_err());
+        assert!(state.user.login("eva", PASSWORD).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn last_admin_remains() {
+        let state = state().await;
+        let admin = state.user.setup_admin("admin", PASSWORD).await.unwrap();
+
+        let mut demoted = user("admin", UserRole::Reception);
+        demoted.id = Some(admin.user_id);
+        demoted.password = String::new();
+        assert!(matches!(
+            state.user.store_user(demoted).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        let mut deactivated = user("admin", UserRole::Admin);
+        deactivated.id = Some(admin.user_id);
+        deactivated.active = false;
+        assert!(matches!(
+            state.user.store_user(deactivated).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        assert!(matches!(
+            state.user.delete_user(admin.user_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        let second_id = state
+            .user
+            .store_user(user("second", UserRole::Admin))
+            .await
+            .unwrap();
+        state.user.logout().unwrap();
+        state.user.login("second", PASSWORD).await.unwrap();
+        state.user.delete_user(admin.user_id).await.unwrap();
+
+        // The administrator that is left can neither be demoted nor deleted.
+        let mut demoted = user("second", UserRole::Reception);
+        demoted.id = Some(second_id);
+        demoted.password = String::new();
+        assert!(matches!(
+            state.user.store_user(demoted).await,
+            Err(ServiceError::Conflict(_))
+        ));
+        state.user.logout().unwrap();
+        assert!(matches!(
+            state.user.delete_user(second_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn roles() {
+        let state = state().await;
+        assert!(matches!(
+            state.user.authorize(&[UserRole::Admin]),
+            Err(ServiceError::Unauthorized(_))
+        ));
+
+        state.user.setup_admin("admin", PASSWORD).await.unwrap();
+        let therapist_id = state
+            .user
+            .store_user(user("eva", UserRole::Therapist))
+            .await
+            .unwrap();
+        state.user.authorize(&[UserRole::Admin]).unwrap();
+
+        state.user.logout().unwrap();
+        state.user.login("eva", PASSWORD).await.unwrap();
+        assert!(matches!(
+            state.user.authorize(&[UserRole::Admin]),
+            Err(ServiceError::Forbidden(_))
+        ));
+        let session = state
+            .user
+            .authorize(&[UserRole::Admin, UserRole::Therapist])
+            .unwrap();
+        assert_eq!(session.user_id, therapist_id);
+        assert!(matches!(
+            state.user.delete_user(therapist_id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        // Changes of the own account apply to the session at once.
+        let mut reception = user("eva", UserRole::Reception);
+        reception.id = Some(therapist_id);
+        reception.password = String::new();
+        state.user.store_user(reception).await.unwrap();
+        assert_eq!(
+            state.user.get_session().unwrap().unwrap().role,
+            UserRole::Reception
+        );
+    }
+}
@@ -0,0 +1,735 @@
+use chrono::NaiveDate;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{EditVoucherDto, VoucherDto, VoucherLiabilityReportDto, VoucherRedemptionDto},
+    money,
+    state::preference,
+};
+
+/// Characters of generated codes, without the easily confused 0, O, 1 and I. There are 32 of
+/// them, so every random byte maps to a character with equal probability.
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 12;
+const CODE_GROUP_LENGTH: usize = 4;
+
+#[derive(Clone)]
+pub struct VoucherState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl VoucherState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    /// Vouchers bought by or for the customer.
+    pub async fn get_voucher_list(
+        &self,
+        customer_id: Option<i64>,
+    ) -> ServiceResult<Vec<VoucherDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_voucher_list(connection.as_mut(), customer_id, None).await
+    }
+
+    pub async fn get_voucher_by_id(&self, id: i64) -> ServiceResult<Option<VoucherDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_voucher_list(connection.as_mut(), None, Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Looks up a voucher by its code, ignoring case, spaces and dashes.
+    pub async fn get_voucher_by_code(&self, code: String) -> ServiceResult<Option<VoucherDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        let Some(row) = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT v.id
+            FROM voucher v
+            WHERE v.code = $1;
+        "#,
+        )
+        .bind(normalize_code(&code))
+        .fetch_optional(connection.as_mut())
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        Ok(get_voucher_list(connection.as_mut(), None, Some(row.id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Stores the voucher, new vouchers get a unique random code. The values and dates of a
+    /// redeemed voucher must still cover its redemptions.
+    pub async fn store_voucher(&self, voucher: EditVoucherDto) -> ServiceResult<i64> {
+        if voucher.initial_value <= 0 {
+            return Err(ServiceError::BadRequest(
+                "voucher value must be positive".to_string(),
+            ));
+        }
+        if voucher
+            .expiry_date
+            .is_some_and(|expiry_date| expiry_date < voucher.issue_date)
+        {
+            return Err(ServiceError::BadRequest(
+                "voucher must not expire before it is issued".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(voucher_id) = voucher.id {
+            let redemptions = get_redemption_list(transaction.as_mut(), &[voucher_id]).await?;
+            let redeemed: i64 = redemptions.iter().map(|(_, r)| r.amount).sum();
+            if redeemed > voucher.initial_value {
+                return Err(ServiceError::BadRequest(
+                    "voucher value is below the redeemed amount".to_string(),
+                ));
+            }
+            if redemptions.iter().any(|(_, r)| {
+                r.date < voucher.issue_date
+                    || voucher
+                        .expiry_date
+                        .is_some_and(|expiry_date| r.date > expiry_date)
+            }) {
+                return Err(ServiceError::BadRequest(
+                    "voucher has been redeemed outside of the new validity".to_string(),
+                ));
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE voucher
+                SET initial_value = $2,
+                    issue_date = $3,
+                    expiry_date = $4,
+                    purchaser_customer_id = $5,
+                    recipient_customer_id = $6,
+                    note = $7
+                WHERE id = $1;
+            "#,
+            )
+            .bind(voucher_id)
+            .bind(voucher.initial_value)
+            .bind(voucher.issue_date)
+            .bind(voucher.expiry_date)
+            .bind(voucher.purchaser_customer_id)
+            .bind(voucher.recipient_customer_id)
+            .bind(&voucher.note)
+            .execute(transaction.as_mut())
+            .await?;
+
+            transaction.commit().await?;
+            return Ok(voucher_id);
+        }
+
+        let code = loop {
+            let code = generate_code(transaction.as_mut()).await?;
+            let exists = sqlx::query_as::<_, IdRow>(
+                r#"
+                SELECT v.id
+                FROM voucher v
+                WHERE v.code = $1;
+            "#,
+            )
+            .bind(&code)
+            .fetch_optional(transaction.as_mut())
+            .await?
+            .is_some();
+            if !exists {
+                break code;
+            }
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO voucher (
+                code,
+                initial_value,
+                issue_date,
+                expiry_date,
+                purchaser_customer_id,
+                recipient_customer_id,
+                note
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7);
+        "#,
+        )
+        .bind(&code)
+        .bind(voucher.initial_value)
+        .bind(voucher.issue_date)
+        .bind(voucher.expiry_date)
+        .bind(voucher.purchaser_customer_id)
+        .bind(voucher.recipient_customer_id)
+        .bind(&voucher.note)
+        .execute(transaction.as_mut())
+        .await?;
+
+        let voucher_id = sqlx::query_as::<_, IdRow>(
+            r#"
+            SELECT last_insert_rowid() as id;
+        "#,
+        )
+        .fetch_one(transaction.as_mut())
+        .await?
+        .id;
+
+        transaction.commit().await?;
+        Ok(voucher_id)
+    }
+
+    /// Only vouchers that were never redeemed can be deleted.
+    pub async fn delete_voucher(&self, id: i64) -> ServiceResult<()> {
+        let mut transaction = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if !get_redemption_list(transaction.as_mut(), &[id])
+            .await?
+            .is_empty()
+        {
+            return Err(ServiceError::Conflict(
+                "voucher has been redeemed and can't be deleted".to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+                DELETE FROM voucher
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_voucher_liability_report(
+        &self,
+        date: NaiveDate,
+    ) -> ServiceResult<VoucherLiabilityReportDto> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        let currency = preference::get_currency(connection.as_mut()).await?;
+
+        let mut vouchers = Vec::new();
+        let mut outstanding = 0;
+        let mut expired = 0;
+        for mut voucher in get_voucher_list(connection.as_mut(), None, None).await? {
+            if voucher.issue_date > date {
+                continue;
+            }
+            voucher
+                .redemptions
+                .retain(|redemption| redemption.date <= date);
+            voucher.remaining_value = voucher.initial_value
+                - voucher
+                    .redemptions
+                    .iter()
+                    .map(|redemption| redemption.amount)
+                    .sum::<i64>();
+            if voucher.remaining_value <= 0 {
+                continue;
+            }
+
+            if voucher
+                .expiry_date
+                .is_some_and(|expiry_date| expiry_date < date)
+            {
+                expired += voucher.remaining_value;
+            } else {
+                outstanding += voucher.remaining_value;
+                vouchers.push(voucher);
+            }
+        }
+
+        Ok(VoucherLiabilityReportDto {
+            date,
+            vouchers,
+            outstanding: money::money(outstanding, currency),
+            expired: money::money(expired, currency),
+        })
+    }
+}
+
+pub async fn get_voucher_list(
+    connection: &mut SqliteConnection,
+    customer_id: Option<i64>,
+    id: Option<i64>,
+) -> ServiceResult<Vec<VoucherDto>> {
+    let rows = sqlx::query_as::<_, VoucherRow>(
+        r#"
+        SELECT
+            v.id,
+            v.code,
+            v.initial_value,
+            v.issue_date,
+            v.expiry_date,
+            v.purchaser_customer_id,
+            v.recipient_customer_id,
+            v.note
+        FROM voucher v
+        WHERE ($1 IS NULL OR v.purchaser_customer_id = $1 OR v.recipient_customer_id = $1)
+            AND ($2 IS NULL OR v.id = $2)
+        ORDER BY v.issue_date ASC, v.id ASC;
+    "#,
+    )
+    .bind(customer_id)
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let voucher_ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let mut redemptions = get_redemption_list(&mut *connection, &voucher_ids)
+        .await?
+        .into_iter()
+        .fold(
+            HashMap::<i64, Vec<VoucherRedemptionDto>>::new(),
+            |mut map, (voucher_id, redemption)| {
+                map.entry(voucher_id).or_default().push(redemption);
+                map
+            },
+        );
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let redemptions = redemptions.remove(&row.id).unwrap_or_default();
+            let redeemed: i64 = redemptions.iter().map(|redemption| redemption.amount).sum();
+            VoucherDto {
+                id: row.id,
+                code: row.code,
+                initial_value: row.initial_value,
+                remaining_value: row.initial_value - redeemed,
+                issue_date: row.issue_date,
+                expiry_date: row.expiry_date,
+                purchaser_customer_id: row.purchaser_customer_id,
+                recipient_customer_id: row.recipient_customer_id,
+                note: row.note,
+                redemptions,
+            }
+        })
+        .collect())
+}
+
+/// Checks that the voucher is valid on the payment date and covers the amount. Earlier
+/// redemptions by the payment itself are not counted.
+pub async fn check_redemption(
+    connection: &mut SqliteConnection,
+    voucher_id: i64,
+    payment_id: Option<i64>,
+    date: NaiveDate,
+    amount: i64,
+) -> ServiceResult<()> {
+    let Some(voucher) = sqlx::query_as::<_, VoucherRow>(
+        r#"
+        SELECT
+            v.id,
+            v.code,
+            v.initial_value,
+            v.issue_date,
+            v.expiry_date,
+            v.purchaser_customer_id,
+            v.recipient_customer_id,
+            v.note
+        FROM voucher v
+        WHERE v.id = $1;
+    "#,
+    )
+    .bind(voucher_id)
+    .fetch_optional(&mut *connection)
+    .await?
+    else {
+        return Err(ServiceError::BadRequest(format!(
+            "voucher {} does not exist",
+            voucher_id
+        )));
+    };
+
+    if date < voucher.issue_date {
+        return Err(ServiceError::BadRequest(format!(
+            "voucher {} is not issued before {}",
+            voucher.code, voucher.issue_date
+        )));
+    }
+    if let Some(expiry_date) = voucher.expiry_date
+        && date > expiry_date
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "voucher {} expired on {}",
+            voucher.code, expiry_date
+        )));
+    }
+
+    let redeemed = sqlx::query_as::<_, SumRow>(
+        r#"
+        SELECT COALESCE(SUM(p.amount), 0) AS amount
+        FROM payment p
+        WHERE p.voucher_id = $1 AND p.id IS NOT $2;
+    "#,
+    )
+    .bind(voucher_id)
+    .bind(payment_id)
+    .fetch_one(&mut *connection)
+    .await?
+    .amount;
+    if redeemed + amount > voucher.initial_value {
+        return Err(ServiceError::BadRequest(format!(
+            "payment exceeds the remaining value of voucher {}",
+            voucher.code
+        )));
+    }
+
+    Ok(())
+}
+
+/// Voucher payments with the id of the redeemed voucher, ordered by date.
+async fn get_redemption_list(
+    connection: &mut SqliteConnection,
+    voucher_ids: &[i64],
+) -> ServiceResult<Vec<(i64, VoucherRedemptionDto)>> {
+    Ok(sqlx::query_as::<_, RedemptionRow>(
+        r#"
+        SELECT p.voucher_id, p.id AS payment_id, p.customer_id, p.date, p.amount
+        FROM payment p
+        WHERE p.voucher_id IN (SELECT value FROM json_each($1))
+        ORDER BY p.date ASC, p.id ASC;
+    "#,
+    )
+    .bind(serde_json::to_string(voucher_ids)?)
+    .fetch_all(connection)
+    .await?
+    .into_iter()
+    .map(|row| {
+        (
+            row.voucher_id,
+            VoucherRedemptionDto {
+                payment_id: row.payment_id,
+                customer_id: row.customer_id,
+                date: row.date,
+                amount: row.amount,
+            },
+        )
+    })
+    .collect())
+}
+
+async fn generate_code(connection: &mut SqliteConnection) -> ServiceResult<String> {
+    let bytes = sqlx::query_as::<_, RandomRow>(
+        r#"
+        SELECT randomblob($1) AS bytes;
+    "#,
+    )
+    .bind(CODE_LENGTH as i64)
+    .fetch_one(connection)
+    .await?
+    .bytes;
+
+    let code: String = bytes
+        .iter()
+        .map(|byte| CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char)
+        .collect();
+    Ok(group_code(&code))
+}
+
+fn normalize_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    group_code(&code)
+}
+
+fn group_code(code: &str) -> String {
+    code.as_bytes()
+        .chunks(CODE_GROUP_LENGTH)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SumRow {
+    pub amount: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct RandomRow {
+    pub bytes: Vec<u8>,
+}
+
+#[derive(sqlx::FromRow)]
+struct VoucherRow {
+    pub id: i64,
+    pub code: String,
+    pub initial_value: i64,
+    pub issue_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub purchaser_customer_id: Option<i64>,
+    pub recipient_customer_id: Option<i64>,
+    pub note: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct RedemptionRow {
+    pub voucher_id: i64,
+    pub payment_id: i64,
+    pub customer_id: i64,
+    pub date: NaiveDate,
+    pub amount: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::model::{EditPaymentDto, PaymentMethod};
+    use crate::state::State;
+    use crate::state::testing::{customer, state};
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn voucher(initial_value: i64, issue_date: &str, expiry_date: Option<&str>) -> EditVoucherDto {
+        EditVoucherDto {
+            id: None,
+            initial_value,
+            issue_date: date(issue_date),
+            expiry_date: expiry_date.map(date),
+            purchaser_customer_id: None,
+            recipient_customer_id: None,
+            note: String::new(),
+        }
+    }
+
+    async fn redeem(state: &State, voucher_id: i64, day: &str, amount: i64) -> ServiceResult<i64> {
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", "Berg"))
+            .await
+            .unwrap();
+        state
+            .payment
+            .store_payment(EditPaymentDto {
+                id: None,
+                customer_id,
+                date: date(day),
+                amount,
+                method: PaymentMethod::Voucher,
+                voucher_id: Some(voucher_id),
+                reference: String::new(),
+                note: String::new(),
+                allocations: Vec::new(),
+            })
+            .await
+    }
+
+    #[test]
+    fn codes_are_normalized() {
+        assert_eq!(normalize_code("abcd-efgh-jkmn"), "ABCD-EFGH-JKMN");
+        assert_eq!(normalize_code(" abcd efgh\tjkmn "), "ABCD-EFGH-JKMN");
+        assert_eq!(normalize_code("ABCDEFGHJKMN"), "ABCD-EFGH-JKMN");
+        assert_eq!(normalize_code("ab-cd-ef"), "ABCD-EF");
+        assert_eq!(normalize_code(""), "");
+    }
+
+    #[tokio::test]
+    async fn codes_are_unique() {
+        let state = state().await;
+
+        let mut codes = HashSet::new();
+        for _ in 0..50 {
+            let id = state
+                .voucher
+                .store_voucher(voucher(5000, "2025-01-01", None))
+                .await
+                .unwrap();
+            let code = state
+                .voucher
+                .get_voucher_by_id(id)
+                .await
+                .unwrap()
+                .unwrap()
+                .code;
+            assert_eq!(code.len(), 14);
+            assert!(code.split('-').all(|group| {
+                group.len() == CODE_GROUP_LENGTH
+                    && group.bytes().all(|byte| CODE_ALPHABET.contains(&byte))
+            }));
+            assert!(codes.insert(code));
+        }
+
+        // The database refuses duplicates as well.
+        let code = codes.iter().next().unwrap();
+        let mut connection = state.voucher.pool.acquire().await.unwrap();
+        let duplicate = sqlx::query(
+            r#"
+            INSERT INTO voucher (code, initial_value, issue_date, note)
+            VALUES ($1, 5000, '2025-01-01', '');
+        "#,
+        )
+        .bind(code)
+        .execute(connection.as_mut())
+        .await;
+        assert!(duplicate.is_err());
+    }
+
+    #[tokio::test]
+    async fn vouchers_are_found_by_their_code() {
+        let state = state().await;
+        let id = state
+            .voucher
+            .store_voucher(voucher(5000, "2025-01-01", None))
+            .await
+            .unwrap();
+        let code = state
+            .voucher
+            .get_voucher_by_id(id)
+            .await
+            .unwrap()
+            .unwrap()
+            .code;
+
+        for input in [
+            code.clone(),
+            code.to_lowercase(),
+            code.replace('-', ""),
+            code.replace('-', " "),
+        ] {
+            let found = state.voucher.get_voucher_by_code(input).await.unwrap();
+            assert_eq!(found.map(|voucher| voucher.id), Some(id));
+        }
+        let unknown = state.voucher.get_voucher_by_code("AAAA-AAAA".to_string());
+        assert!(unknown.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn vouchers_are_redeemed_in_parts_until_they_expire() {
+        let state = state().await;
+        let id = state
+            .voucher
+            .store_voucher(voucher(5000, "2025-01-10", Some("2025-12-31")))
+            .await
+            .unwrap();
+
+        redeem(&state, id, "2025-02-01", 2000).await.unwrap();
+        redeem(&state, id, "2025-03-01", 2500).await.unwrap();
+        let stored = state.voucher.get_voucher_by_id(id).await.unwrap().unwrap();
+        assert_eq!(stored.remaining_value, 500);
+        assert_eq!(
+            stored
+                .redemptions
+                .iter()
+                .map(|redemption| redemption.amount)
+                .collect::<Vec<_>>(),
+            vec![2000, 2500]
+        );
+
+        for (day, amount) in [
+            ("2025-04-01", 501),
+            ("2025-01-09", 100),
+            ("2026-01-01", 100),
+        ] {
+            assert!(matches!(
+                redeem(&state, id, day, amount).await,
+                Err(ServiceError::BadRequest(_))
+            ));
+        }
+        redeem(&state, id, "2025-12-31", 500).await.unwrap();
+
+        // Redeemed vouchers keep their value and validity and can't be deleted.
+        assert!(matches!(
+            state
+                .voucher
+                .store_voucher(EditVoucherDto {
+                    id: Some(id),
+                    ..voucher(4000, "2025-01-10", Some("2025-12-31"))
+                })
+                .await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(matches!(
+            state
+                .voucher
+                .store_voucher(EditVoucherDto {
+                    id: Some(id),
+                    ..voucher(5000, "2025-01-10", Some("2025-06-30"))
+                })
+                .await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(matches!(
+            state.voucher.delete_voucher(id).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn liabilities_are_the_unexpired_remaining_values() {
+        let state = state().await;
+        let store = async |initial_value, issue_date, expiry_date| {
+            state
+                .voucher
+                .store_voucher(voucher(initial_value, issue_date, expiry_date))
+                .await
+                .unwrap()
+        };
+        let partly = store(5000, "2025-01-10", None).await;
+        let expired = store(3000, "2025-01-10", Some("2025-05-31")).await;
+        let used_up = store(2000, "2025-01-10", None).await;
+        store(1000, "2025-07-01", None).await;
+
+        redeem(&state, partly, "2025-02-01", 1500).await.unwrap();
+        // Redeemed after the report date, still a liability on that date.
+        redeem(&state, partly, "2025-06-30", 1000).await.unwrap();
+        redeem(&state, expired, "2025-03-01", 1000).await.unwrap();
+        redeem(&state, used_up, "2025-04-01", 2000).await.unwrap();
+
+        let report = state
+            .voucher
+            .get_voucher_liability_report(date("2025-06-15"))
+            .await
+            .unwrap();
+        assert_eq!(
+            report
+                .vouchers
+                .iter()
+                .map(|voucher| (
+                    voucher.id,
+                    voucher.remaining_value,
+                    voucher.redemptions.len()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(partly, 3500, 1)]
+        );
+        assert_eq!(report.outstanding.amount, 3500);
+        assert_eq!(report.expired.amount, 2000);
+
+        // Vouchers are still a liability on their expiry date.
+        let report = state
+            .voucher
+            .get_voucher_liability_report(date("2025-05-31"))
+            .await
+            .unwrap();
+        assert_eq!(
+            (report.outstanding.amount, report.expired.amount),
+            (5500, 0)
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use chrono::{Duration, NaiveDateTime};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::{EditWaitlistEntryDto, EmployeeRefDto, WaitlistEntryDto, WaitlistWindowDto},
+    state::preference,
+    time_zone,
+};
+
+#[derive(Clone)]
+pub struct WaitlistState {
+    pool: Arc<Pool<Sqlite>>,
+}
+
+impl WaitlistState {
+    pub fn new(pool: Arc<Pool<Sqlite>>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_waitlist(&self) -> ServiceResult<Vec<WaitlistEntryDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        get_entry_list(connection.as_mut(), None).await
+    }
+
+    pub async fn get_waitlist_entry_by_id(
+        &self,
+        id: i64,
+    ) -> ServiceResult<Option<WaitlistEntryDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        Ok(get_entry_list(connection.as_mut(), Some(id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Entries that could take over the given slot, oldest entries first.
+    pub async fn get_waitlist_for_slot(
+        &self,
+        start_date: NaiveDateTime,
+        end_date: NaiveDateTime,
+        employee_id: Option<i64>,
+    ) -> ServiceResult<Vec<WaitlistEntryDto>> {
+        let mut connection = self.pool.acquire().await.unwrap();
+        find_matching_entries(connection.as_mut(), start_date, end_date, employee_id).await
+    }
+
+    pub async fn store_waitlist_entry(&self, entry: EditWaitlistEntryDto) -> ServiceResult<i64> {
+        if entry.duration_minutes < 0 {
+            return Err(ServiceError::BadRequest(
+                "duration must not be negative".to_string(),
+            ));
+        }
+        if entry
+            .windows
+            .iter()
+            .any(|window| window.end_date <= window.start_date)
+        {
+            return Err(ServiceError::BadRequest(
+                "window must end after its start".to_string(),
+            ));
+        }
+
+        let mut transaction = self.pool.begin().await?;
+
+        let q = if let Some(entry_id) = entry.id {
+            sqlx::query(
+                r#"
+                UPDATE waitlist_entry
+                SET customer_id = $2,
+                    treatment_id = $3,
+                    treatment = $4,
+                    duration_minutes = $5,
+                    employee_id = $6,
+                    note = $7
+                WHERE id = $1;
+            "#,
+            )
+            .bind(entry_id)
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO waitlist_entry (
+                    customer_id,
+                    treatment_id,
+                    treatment,
+                    duration_minutes,
+                    employee_id,
+                    note,
+                    created_date
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7);
+            "#,
+            )
+        };
+
+        let q = q
+            .bind(entry.customer_id)
+            .bind(entry.treatment_id)
+            .bind(&entry.treatment)
+            .bind(entry.duration_minutes)
+            .bind(entry.employee_id)
+            .bind(&entry.note);
+
+        // The created date is only set once, an update keeps the position on the waitlist.
+        let q = if entry.id.is_none() {
+            q.bind(time_zone::now_local(
+                preference::get_time_zone(transaction.as_mut()).await?,
+            ))
+        } else {
+            q
+        };
+        q.execute(transaction.as_mut()).await?;
+
+        let entry_id = if let Some(entry_id) = entry.id {
+            entry_id
+        } else {
+            sqlx::query_as::<_, IdRow>(
+                r#"
+            SELECT last_insert_rowid() as id;
+            "#,
+            )
+            .fetch_one(transaction.as_mut())
+            .await?
+            .id
+        };
+
+        sqlx::query(
+            r#"
+                DELETE FROM waitlist_window
+                WHERE waitlist_entry_id = $1;
+            "#,
+        )
+        .bind(entry_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+        for window in &entry.windows {
+            sqlx::query(
+                r#"
+                    INSERT INTO waitlist_window (waitlist_entry_id, start_date, end_date)
+                    VALUES ($1, $2, $3);
+                "#,
+            )
+            .bind(entry_id)
+            .bind(window.start_date)
+            .bind(window.end_date)
+            .execute(transaction.as_mut())
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(entry_id)
+    }
+
+    pub async fn delete_waitlist_entry(&self, id: i64) -> ServiceResult<()> {
+        let mut connection = self.pool.acquire().await.unwrap();
+
+        sqlx::query(
+            r#"
+                DELETE FROM waitlist_entry
+                WHERE id = $1;
+            "#,
+        )
+        .bind(id)
+        .execute(connection.as_mut())
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// An entry fits if one of its windows overlaps the slot long enough for the desired duration
+/// and its preferred employee, if any, is the one of the slot.
+pub async fn find_matching_entries(
+    connection: &mut SqliteConnection,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    employee_id: Option<i64>,
+) -> ServiceResult<Vec<WaitlistEntryDto>> {
+    Ok(get_entry_list(connection, None)
+        .await?
+        .into_iter()
+        .filter(|entry| match (&entry.employee, employee_id) {
+            (Some(preferred), Some(employee_id)) => preferred.id == employee_id,
+            _ => true,
+        })
+        .filter(|entry| {
+            let duration = Duration::minutes(entry.duration_minutes);
+            entry.windows.iter().any(|window| {
+                let start = window.start_date.max(start_date);
+                let end = window.end_date.min(end_date);
+                start < end && start + duration <= end
+            })
+        })
+        .collect())
+}
+
+async fn get_entry_list(
+    connection: &mut SqliteConnection,
+    id: Option<i64>,
+) -> ServiceResult<Vec<WaitlistEntryDto>> {
+    let mut windows: HashMap<i64, Vec<WaitlistWindowDto>> = HashMap::new();
+    for row in sqlx::query_as::<_, WaitlistWindowRow>(
+        r#"
+        SELECT w.waitlist_entry_id, w.start_date, w.end_date
+        FROM waitlist_window w
+        WHERE ($1 IS NULL OR w.waitlist_entry_id = $1)
+        ORDER BY w.start_date ASC;
+    "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?
+    {
+        windows
+            .entry(row.waitlist_entry_id)
+            .or_default()
+            .push(WaitlistWindowDto {
+                start_date: row.start_date,
+                end_date: row.end_date,
+            });
+    }
+
+    Ok(sqlx::query_as::<_, WaitlistEntryRow>(
+        r#"
+        SELECT
+            w.id,
+            w.customer_id,
+            c.title,
+            c.first_name,
+            c.last_name,
+            c.phone,
+            c.mobile_phone,
+            w.treatment_id,
+            w.treatment,
+            w.duration_minutes,
+            e.id AS employee_id,
+            e.name AS employee_name,
+            e.active AS employee_active,
+            w.note,
+            w.created_date
+        FROM waitlist_entry w
+        JOIN customer c ON c.id = w.customer_id
+        LEFT JOIN employee e ON e.id = w.employee_id
+        WHERE ($1 IS NULL OR w.id = $1)
+        ORDER BY w.created_date ASC, w.id ASC;
+    "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *connection)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let employee = match (row.employee_id, row.employee_name, row.employee_active) {
+            (Some(id), Some(name), Some(active)) => Some(EmployeeRefDto { id, name, active }),
+            _ => None,
+        };
+
+        WaitlistEntryDto {
+            id: row.id,
+            customer_id: row.customer_id,
+            title: row.title,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            phone: row.phone,
+            mobile_phone: row.mobile_phone,
+            treatment_id: row.treatment_id,
+            treatment: row.treatment,
+            duration_minutes: row.duration_minutes,
+            employee,
+            note: row.note,
+            created_date: row.created_date,
+            windows: windows.remove(&row.id).unwrap_or_default(),
+        }
+    })
+    .collect())
+}
+
+#[derive(sqlx::FromRow)]
+struct IdRow {
+    pub id: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct WaitlistWindowRow {
+    pub waitlist_entry_id: i64,
+    pub start_date: NaiveDateTime,
+    pub end_date: NaiveDateTime,
+}
+
+#[derive(sqlx::FromRow)]
+struct WaitlistEntryRow {
+    pub id: i64,
+    pub customer_id: i64,
+    pub title: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub mobile_phone: String,
+    pub treatment_id: Option<i64>,
+    pub treatment: String,
+    pub duration_minutes: i64,
+    pub employee_id: Option<i64>,
+    pub employee_name: Option<String>,
+    pub employee_active: Option<bool>,
+    pub note: String,
+    pub created_date: NaiveDateTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::EditEmployeeDto;
+    use crate::state::State;
+    use crate::state::testing::{appointment, customer, item, set_preference, state};
+
+    fn date(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    async fn employee(state: &State, name: &str) -> i64 {
+        state
+            .employee
+            .store_employee(EditEmployeeDto {
+                id: None,
+                name: name.to_string(),
+                phone: String::new(),
+                email: String::new(),
+                color: String::new(),
+                employed_from: None,
+                employed_until: None,
+                active: true,
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn entry(
+        state: &State,
+        last_name: &str,
+        duration_minutes: i64,
+        employee_id: Option<i64>,
+        windows: Vec<(NaiveDateTime, NaiveDateTime)>,
+    ) -> i64 {
+        let customer_id = state
+            .customer
+            .store_customer(customer("Anna", last_name))
+            .await
+            .unwrap();
+        state
+            .waitlist
+            .store_waitlist_entry(EditWaitlistEntryDto {
+                id: None,
+                customer_id,
+                treatment_id: None,
+                treatment: "Massage".to_string(),
+                duration_minutes,
+                employee_id,
+                note: String::new(),
+                windows: windows
+                    .into_iter()
+                    .map(|(start_date, end_date)| WaitlistWindowDto {
+                        start_date,
+                        end_date,
+                    })
+                    .collect(),
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn deleted_appointments_offer_their_slot() {
+        let state = state().await;
+        let eva = employee(&state, "Eva").await;
+        let max = employee(&state, "Max").await;
+
+        let fits = entry(
+            &state,
+            "Berg",
+            60,
+            None,
+            vec![(date(3, 8, 0), date(3, 12, 0))],
+        )
+        .await;
+        let same_employee = entry(
+            &state,
+            "Klein",
+            30,
+            Some(eva),
+            vec![(date(3, 10, 30), date(3, 18, 0))],
+        )
+        .await;
+        // Overlaps the slot, but not for the full duration.
+        entry(
+            &state,
+            "Lang",
+            60,
+            None,
+            vec![(date(3, 10, 30), date(3, 12, 0))],
+        )
+        .await;
+        entry(
+            &state,
+            "Other",
+            30,
+            Some(max),
+            vec![(date(3, 8, 0), date(3, 12, 0))],
+        )
+        .await;
+        entry(
+            &state,
+            "Later",
+            30,
+            None,
+            vec![(date(4, 10, 0), date(4, 11, 0))],
+        )
+        .await;
+
+        let customer_id = state
+            .customer
+            .store_customer(customer("Paul", "Roth"))
+            .await
+            .unwrap();
+        let mut booked = appointment(customer_id, date(3, 10, 0), vec![item("Massage", 1, 5000)]);
+        booked.employee_id = Some(eva);
+        let appointment_id = state.appointment.store_appointment(booked).await.unwrap();
+
+        let offered = state
+            .appointment
+            .delete_appointment(appointment_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        assert_eq!(offered, vec![fits, same_employee]);
+    }
+
+    #[tokio::test]
+    async fn entries_are_created_at_the_practice_time() {
+        let state = state().await;
+        let zone = chrono_tz::Pacific::Auckland;
+        set_preference(&state, time_zone::TIME_ZONE_KEY, zone.name()).await;
+
+        let id = entry(&state, "Berg", 30, None, Vec::new()).await;
+        let created_date = state
+            .waitlist
+            .get_waitlist_entry_by_id(id)
+            .await
+            .unwrap()
+            .unwrap()
+            .created_date;
+        let difference = time_zone::now_local(zone) - created_date;
+        assert!(difference >= Duration::zero() && difference < Duration::minutes(1));
+    }
+}
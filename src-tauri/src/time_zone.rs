@@ -0,0 +1,76 @@
+//! Conversion between the wall clock time of the practice and absolute points in time.
+//!
+//! Appointments are entered as local time, but overlaps, durations and reminders are computed on
+//! UTC instants so they stay correct across daylight saving changes.
+
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    model::AmbiguousTime,
+};
+
+pub const TIME_ZONE_KEY: &str = "general.time-zone";
+pub const DEFAULT_TIME_ZONE: Tz = chrono_tz::Europe::Berlin;
+
+/// Parses an IANA time zone name like `Europe/Berlin`.
+pub fn parse_time_zone(name: &str) -> ServiceResult<Tz> {
+    name.parse()
+        .map_err(|_| ServiceError::BadRequest(format!("Unknown time zone: {}", name)))
+}
+
+/// Resolves a time entered by a user. Times skipped by a daylight saving change are rejected,
+/// times that occur twice need the choice of the occurrence.
+pub fn resolve_local_time(
+    zone: Tz,
+    local: NaiveDateTime,
+    choice: Option<AmbiguousTime>,
+) -> ServiceResult<DateTime<Utc>> {
+    match zone.from_local_datetime(&local) {
+        LocalResult::Single(date_time) => Ok(date_time.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earliest, latest) => match choice {
+            Some(AmbiguousTime::Earlier) => Ok(earliest.with_timezone(&Utc)),
+            Some(AmbiguousTime::Later) => Ok(latest.with_timezone(&Utc)),
+            None => Err(ServiceError::Conflict(format!(
+                "{} occurs twice in {} because of a daylight saving change, the earlier or \
+                 later one has to be chosen",
+                local.format("%Y-%m-%d %H:%M"),
+                zone.name()
+            ))),
+        },
+        LocalResult::None => Err(ServiceError::BadRequest(format!(
+            "{} does not exist in {} because of a daylight saving change",
+            local.format("%Y-%m-%d %H:%M"),
+            zone.name()
+        ))),
+    }
+}
+
+/// Like [`resolve_local_time`], but a skipped time is moved forward by the length of the gap,
+/// just like a wall clock that is set forward. Used for stored and imported data.
+pub fn resolve_local_time_lenient(zone: Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match zone.from_local_datetime(&local) {
+        LocalResult::Single(date_time) => date_time.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        LocalResult::None => {
+            // The offset before the gap maps the skipped time behind it.
+            let offset = zone
+                .offset_from_utc_datetime(&(local - Duration::days(1)))
+                .fix();
+            DateTime::<Utc>::from_naive_utc_and_offset(
+                local - Duration::seconds(offset.local_minus_utc() as i64),
+                Utc,
+            )
+        }
+    }
+}
+
+/// Wall clock time of the instant in the given zone.
+pub fn to_local_time(zone: Tz, date_time: DateTime<Utc>) -> NaiveDateTime {
+    date_time.with_timezone(&zone).naive_local()
+}
+
+pub fn now_local(zone: Tz) -> NaiveDateTime {
+    to_local_time(zone, Utc::now())
+}
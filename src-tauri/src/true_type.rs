@@ -0,0 +1,305 @@
+//! Reading and subsetting of TrueType fonts for embedding into PDF documents.
+//!
+//! Only the fonts shipped with the application are read, so malformed fonts are a programming
+//! error and panic.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tables a PDF reader needs to render the glyphs, the layout tables are dropped.
+const KEPT_TABLES: [&[u8; 4]; 13] = [
+    b"OS/2", b"cmap", b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp",
+    b"name", b"post", b"prep",
+];
+
+/// Composite glyph flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+pub struct TrueTypeFont {
+    data: &'static [u8],
+    tables: BTreeMap<[u8; 4], (usize, usize)>,
+    pub units_per_em: u16,
+    /// xMin, yMin, xMax, yMax in font units.
+    pub bbox: [i16; 4],
+    pub ascent: i16,
+    pub descent: i16,
+    glyph_count: u16,
+    long_offsets: bool,
+    h_metric_count: u16,
+    /// Offset of the Windows Unicode BMP subtable of the cmap.
+    cmap_offset: usize,
+}
+
+impl TrueTypeFont {
+    pub fn parse(data: &'static [u8]) -> Self {
+        let table_count = u16_at(data, 4) as usize;
+        let tables: BTreeMap<[u8; 4], (usize, usize)> = (0..table_count)
+            .map(|index| {
+                let entry = 12 + 16 * index;
+                let tag: [u8; 4] = data[entry..entry + 4].try_into().unwrap();
+                (
+                    tag,
+                    (
+                        u32_at(data, entry + 8) as usize,
+                        u32_at(data, entry + 12) as usize,
+                    ),
+                )
+            })
+            .collect();
+        let table = |tag: &[u8; 4]| tables.get(tag).expect("font table is missing").0;
+
+        let head = table(b"head");
+        let hhea = table(b"hhea");
+        let cmap = table(b"cmap");
+        let cmap_offset = (0..u16_at(data, cmap + 2) as usize)
+            .map(|index| cmap + 4 + 8 * index)
+            .find(|&record| u16_at(data, record) == 3 && u16_at(data, record + 2) == 1)
+            .map(|record| cmap + u32_at(data, record + 4) as usize)
+            .filter(|&subtable| u16_at(data, subtable) == 4)
+            .expect("font has no Unicode BMP cmap");
+
+        Self {
+            data,
+            units_per_em: u16_at(data, head + 18),
+            bbox: [
+                i16_at(data, head + 36),
+                i16_at(data, head + 38),
+                i16_at(data, head + 40),
+                i16_at(data, head + 42),
+            ],
+            long_offsets: i16_at(data, head + 50) == 1,
+            ascent: i16_at(data, hhea + 4),
+            descent: i16_at(data, hhea + 6),
+            h_metric_count: u16_at(data, hhea + 34),
+            glyph_count: u16_at(data, table(b"maxp") + 4),
+            cmap_offset,
+            tables,
+        }
+    }
+
+    /// Glyph of the character, 0 is the missing glyph.
+    pub fn glyph_id(&self, c: char) -> u16 {
+        let Ok(code) = u16::try_from(c as u32) else {
+            return 0;
+        };
+        let data = self.data;
+        let subtable = self.cmap_offset;
+        let segment_count = u16_at(data, subtable + 6) as usize / 2;
+        let end_codes = subtable + 14;
+        let start_codes = end_codes + 2 * segment_count + 2;
+        let deltas = start_codes + 2 * segment_count;
+        let range_offsets = deltas + 2 * segment_count;
+
+        let Some(segment) =
+            (0..segment_count).find(|&segment| u16_at(data, end_codes + 2 * segment) >= code)
+        else {
+            return 0;
+        };
+        let start = u16_at(data, start_codes + 2 * segment);
+        if code < start {
+            return 0;
+        }
+        let delta = u16_at(data, deltas + 2 * segment);
+        let range_offset_position = range_offsets + 2 * segment;
+        let range_offset = u16_at(data, range_offset_position) as usize;
+        if range_offset == 0 {
+            return code.wrapping_add(delta);
+        }
+        let glyph = u16_at(
+            data,
+            range_offset_position + range_offset + 2 * (code - start) as usize,
+        );
+        if glyph == 0 {
+            0
+        } else {
+            glyph.wrapping_add(delta)
+        }
+    }
+
+    /// Advance width of the glyph in font units.
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let index = glyph.min(self.h_metric_count - 1) as usize;
+        u16_at(self.data, self.table(b"hmtx").0 + 4 * index)
+    }
+
+    /// Top of the glyph outline in font units.
+    pub fn y_max(&self, glyph: u16) -> i16 {
+        let (start, end) = self.glyph_range(glyph);
+        if start == end {
+            return 0;
+        }
+        i16_at(self.data, self.table(b"glyf").0 + start + 8)
+    }
+
+    /// Font program that only contains the outlines of the given glyphs and the components they
+    /// are composed of. Glyph ids stay the same, so the cmap and the metrics remain valid.
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> Vec<u8> {
+        let glyf = self.table(b"glyf").0;
+
+        // The missing glyph is always required.
+        let mut kept = BTreeSet::new();
+        let mut pending: Vec<u16> = glyphs.iter().copied().chain([0]).collect();
+        while let Some(glyph) = pending.pop() {
+            if glyph >= self.glyph_count || !kept.insert(glyph) {
+                continue;
+            }
+            pending.extend(self.components(glyph));
+        }
+
+        let mut new_glyf = Vec::new();
+        let mut new_loca = Vec::with_capacity(4 * (self.glyph_count as usize + 1));
+        for glyph in 0..self.glyph_count {
+            new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+            if kept.contains(&glyph) {
+                let (start, end) = self.glyph_range(glyph);
+                new_glyf.extend_from_slice(&self.data[glyf + start..glyf + end]);
+                while new_glyf.len() % 4 != 0 {
+                    new_glyf.push(0);
+                }
+            }
+        }
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+        let mut tables: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+        for tag in KEPT_TABLES {
+            let Some(&(offset, length)) = self.tables.get(tag) else {
+                continue;
+            };
+            let data = match tag {
+                b"glyf" => new_glyf.clone(),
+                b"loca" => new_loca.clone(),
+                b"head" => {
+                    let mut head = self.data[offset..offset + length].to_vec();
+                    head[8..12].fill(0);
+                    // The new loca table always has long offsets.
+                    head[50..52].copy_from_slice(&1i16.to_be_bytes());
+                    head
+                }
+                // Version 3 has no glyph names, the reader maps characters through the cmap.
+                b"post" => {
+                    let mut post = self.data[offset..offset + 32].to_vec();
+                    post[0..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+                    post
+                }
+                _ => self.data[offset..offset + length].to_vec(),
+            };
+            tables.push((*tag, data));
+        }
+
+        write_font(tables)
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> (usize, usize) {
+        *self.tables.get(tag).expect("font table is missing")
+    }
+
+    /// Start and end of the glyph relative to the glyf table.
+    fn glyph_range(&self, glyph: u16) -> (usize, usize) {
+        let loca = self.table(b"loca").0;
+        let index = glyph as usize;
+        if self.long_offsets {
+            (
+                u32_at(self.data, loca + 4 * index) as usize,
+                u32_at(self.data, loca + 4 * index + 4) as usize,
+            )
+        } else {
+            (
+                2 * u16_at(self.data, loca + 2 * index) as usize,
+                2 * u16_at(self.data, loca + 2 * index + 2) as usize,
+            )
+        }
+    }
+
+    /// Glyphs a composite glyph is built from, empty for simple glyphs.
+    fn components(&self, glyph: u16) -> Vec<u16> {
+        let (start, end) = self.glyph_range(glyph);
+        let glyph_offset = self.table(b"glyf").0 + start;
+        if start == end || i16_at(self.data, glyph_offset) >= 0 {
+            return Vec::new();
+        }
+
+        let mut components = Vec::new();
+        let mut position = glyph_offset + 10;
+        loop {
+            let flags = u16_at(self.data, position);
+            components.push(u16_at(self.data, position + 2));
+            position += 4;
+            position += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                4
+            } else {
+                2
+            };
+            if flags & WE_HAVE_A_SCALE != 0 {
+                position += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                position += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                position += 8;
+            }
+            if flags & MORE_COMPONENTS == 0 {
+                return components;
+            }
+        }
+    }
+}
+
+/// Assembles the tables into a font file with a valid directory and checksums.
+fn write_font(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let table_count = tables.len() as u16;
+    let entry_selector = 15 - table_count.leading_zeros() as u16;
+    let search_range: u16 = 16 << entry_selector;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    font.extend_from_slice(&table_count.to_be_bytes());
+    font.extend_from_slice(&search_range.to_be_bytes());
+    font.extend_from_slice(&entry_selector.to_be_bytes());
+    font.extend_from_slice(&(table_count * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in &tables {
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn i16_at(data: &[u8], offset: usize) -> i16 {
+    i16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
@@ -1,7 +1,15 @@
-import { createEffect, createSignal, Match, Switch } from "solid-js";
+import {
+  createEffect,
+  createResource,
+  createSignal,
+  Match,
+  Show,
+  Switch,
+} from "solid-js";
 import styles from "./App.module.css";
 import EmployeeEditDialog from "./employee/EmployeeEditDialog";
 import {
+  RiSystemLogoutBoxRLine,
   RiSystemSettings4Fill,
   RiUserFacesAccountCircleFill,
 } from "solid-icons/ri";
@@ -9,14 +17,51 @@ import { useTranslation } from "./translation";
 import SettingsDialog from "./settings/SettingsDialog";
 import CustomerList from "./customer/CustomerList";
 import { autofocus } from "@solid-primitives/autofocus";
-import { createCustomerListResource } from "./model";
+import { createCustomerListResource, logout, SessionDto } from "./model";
 import SplitView from "./components/SplitView";
 import CustomerDetails from "./customer/CustomerDetails";
-import { appConfig } from "./appConfig";
+import { appConfig, loadAppConfig } from "./appConfig";
 import { createGuardedSelectedId } from "./hooks/masterDetails";
 import MessageBox from "./components/MessageBox";
+import LoginScreen from "./user/LoginScreen";
+import { hasRole, restoreSession, session, setSession } from "./session";
 
 export default function App() {
+  const [restored] = createResource(async () => {
+    await restoreSession();
+    if (session() !== null) {
+      await loadAppConfig();
+    }
+    return true;
+  });
+
+  const handleLogin = async (newSession: SessionDto) => {
+    await loadAppConfig();
+    setSession(newSession);
+  };
+
+  const fontSize = () => appConfig("general.font-size");
+
+  createEffect(() => {
+    document.documentElement.style.setProperty(
+      "--font-size",
+      `${fontSize()}px`,
+    );
+  });
+
+  return (
+    <Show when={restored()}>
+      <Show
+        when={session() !== null}
+        fallback={<LoginScreen onLogin={handleLogin} />}
+      >
+        <MainWindow />
+      </Show>
+    </Show>
+  );
+}
+
+function MainWindow() {
   const { t } = useTranslation();
 
   const [search, setSearch] = createSignal("");
@@ -28,26 +73,24 @@ export default function App() {
 
   const [customers, { refetch }] = createCustomerListResource();
 
-  const fontSize = () => appConfig("general.font-size");
-
-  createEffect(() => {
-    document.documentElement.style.setProperty(
-      "--font-size",
-      `${fontSize()}px`,
-    );
-  });
+  const handleLogout = async () => {
+    await logout();
+    setSession(null);
+  };
 
   return (
     <>
       <div class={styles.app}>
         <header class={styles.titleBar}>
           <div class={styles.titleBarLeft}>
-            <button
-              class={styles.titleBarButton}
-              onClick={() => sel.requestSelect(null)}
-            >
-              {t("customer.create")}
-            </button>
+            <Show when={hasRole("admin", "reception")}>
+              <button
+                class={styles.titleBarButton}
+                onClick={() => sel.requestSelect(null)}
+              >
+                {t("customer.create")}
+              </button>
+            </Show>
           </div>
           <div class={styles.titleBarCenter}>
             <input
@@ -64,19 +107,28 @@ export default function App() {
             />
           </div>
           <div class={styles.titleBarRight}>
+            <Show when={hasRole("admin")}>
+              <button
+                class={styles.titleBarButton}
+                onClick={() => setShowEmployeeEditDialog((x) => !x)}
+                title={t("employee.dialogTitle")}
+              >
+                <RiUserFacesAccountCircleFill />
+              </button>
+            </Show>
             <button
               class={styles.titleBarButton}
-              onClick={() => setShowEmployeeEditDialog((x) => !x)}
-              title={t("employee.dialogTitle")}
+              onClick={() => setShowSettingsDialog((x) => !x)}
+              title={t("settings.title")}
             >
-              <RiUserFacesAccountCircleFill />
+              <RiSystemSettings4Fill />
             </button>
             <button
               class={styles.titleBarButton}
-              onClick={() => setShowSettingsDialog((x) => !x)}
-              title={t("settings.title")}
+              onClick={handleLogout}
+              title={t("user.logout", { username: session()?.username ?? "" })}
             >
-              <RiSystemSettings4Fill />
+              <RiSystemLogoutBoxRLine />
             </button>
           </div>
         </header>
@@ -1,21 +1,116 @@
 import { createStore } from "solid-js/store";
-import { getPreferenceList, storePreference } from "./model";
+import {
+  Currency,
+  DatevAccountsDto,
+  getPreferenceList,
+  storePreference,
+} from "./model";
 
 export type Locale = "en" | "de";
 
+export type SmtpSecurity = "none" | "start_tls" | "tls";
+
 export type AppConfig = {
   "general.font-size": number;
   "general.language": Locale;
+  "general.time-zone": string;
+  "general.currency": Currency;
+  "ical.include-customer-name": boolean;
+  "invoice.practice-name": string;
+  "invoice.practice-address": string;
+  "invoice.practice-contact": string;
+  "invoice.tax-number": string;
+  "invoice.bank-details": string;
+  "invoice.footer": string;
+  "invoice.street": string;
+  "invoice.postcode": string;
+  "invoice.city": string;
+  "invoice.country": string;
+  "invoice.vat-id": string;
+  "invoice.contact-name": string;
+  "invoice.contact-phone": string;
+  "invoice.contact-email": string;
+  "invoice.iban": string;
+  "invoice.bic": string;
+  "invoice.number-pattern": string;
+  "invoice.credit-note-pattern": string;
+  "invoice.payment-days": number;
+  "datev.consultant-number": number;
+  "datev.client-number": number;
+  "datev.account-length": number;
+  "datev.accounts": DatevAccountsDto;
+  "reminder.enabled": boolean;
+  "reminder.hours-before": number;
+  "reminder.subject": string;
+  "reminder.message": string;
+  "smtp.host": string;
+  "smtp.port": number;
+  "smtp.security": SmtpSecurity;
+  "smtp.username": string;
+  "smtp.password": string;
+  "smtp.sender": string;
 };
 
 const defaultAppConfig: AppConfig = {
   "general.font-size": 14,
   "general.language": "en",
+  "general.time-zone": "Europe/Berlin",
+  "general.currency": "EUR",
+  "ical.include-customer-name": false,
+  "invoice.practice-name": "",
+  "invoice.practice-address": "",
+  "invoice.practice-contact": "",
+  "invoice.tax-number": "",
+  "invoice.bank-details": "",
+  "invoice.footer": "",
+  "invoice.street": "",
+  "invoice.postcode": "",
+  "invoice.city": "",
+  "invoice.country": "DE",
+  "invoice.vat-id": "",
+  "invoice.contact-name": "",
+  "invoice.contact-phone": "",
+  "invoice.contact-email": "",
+  "invoice.iban": "",
+  "invoice.bic": "",
+  "invoice.number-pattern": "{YYYY}-{NNNN}",
+  "invoice.credit-note-pattern": "G{YYYY}-{NNNN}",
+  "invoice.payment-days": 14,
+  "datev.consultant-number": 0,
+  "datev.client-number": 0,
+  "datev.account-length": 4,
+  "datev.accounts": {
+    receivable_account: 10000,
+    revenue_accounts: [
+      { vat_rate: 1900, account: 8400 },
+      { vat_rate: 700, account: 8300 },
+      { vat_rate: 0, account: 8200 },
+    ],
+    payment_accounts: [
+      { method: "cash", account: 1000 },
+      { method: "card", account: 1360 },
+      { method: "transfer", account: 1200 },
+      { method: "voucher", account: 1700 },
+    ],
+  },
+  "reminder.enabled": false,
+  "reminder.hours-before": 24,
+  "reminder.subject": "",
+  "reminder.message": "",
+  "smtp.host": "",
+  "smtp.port": 587,
+  "smtp.security": "start_tls",
+  "smtp.username": "",
+  "smtp.password": "",
+  "smtp.sender": "",
 };
 
 const [appConfigRaw, setAppConfigRaw] = createStore<AppConfig>(null!);
 
-(async () => {
+/**
+ * Reading the preferences requires a session, they are loaded after login.
+ */
+export async function loadAppConfig() {
   const preferences = await getPreferenceList();
 
   for (const key in defaultAppConfig) {
@@ -24,21 +119,25 @@ const [appConfigRaw, setAppConfigRaw] = createStore<AppConfig>(null!);
       setAppConfigRaw(key as keyof AppConfig, JSON.parse(value.value));
     }
   }
-})();
+}
 
-export function setAppConfig<K extends keyof AppConfig>(
+/**
+ * The value is only taken over once it has been stored, as the backend may
+ * refuse it, e.g. a change of the currency.
+ */
+export async function setAppConfig<K extends keyof AppConfig>(
   key: K,
   value: AppConfig[K],
-): void {
+): Promise<void> {
   if (defaultAppConfig[key] === value) {
-    storePreference({
+    await storePreference({
       key,
       value: null,
     });
 
     setAppConfigRaw(key, undefined!);
   } else {
-    storePreference({
+    await storePreference({
       key,
       value: JSON.stringify(value),
     });
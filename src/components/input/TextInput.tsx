@@ -16,6 +16,7 @@ export default function TextInput(props: {
   value: string;
   onChange: (value: string) => void;
   rows?: number;
+  password?: boolean;
   autoComplete?: string[];
   prefix?: JSX.Element;
   suffix?: JSX.Element;
@@ -145,7 +146,7 @@ export default function TextInput(props: {
             classList={{
               [styles.canAutoComplete]: canAutoComplete() && hasFocus(),
             }}
-            type="text"
+            type={props.password ? "password" : "text"}
             value={props.value}
             onInput={(e) => props.onChange(e.currentTarget.value)}
             onKeyDown={keyDownHandler}
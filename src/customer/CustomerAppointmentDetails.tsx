@@ -1,18 +1,36 @@
-import { createEffect, createResource, createSignal, Show } from "solid-js";
+import {
+  createEffect,
+  createResource,
+  createSignal,
+  For,
+  Index,
+  Show,
+} from "solid-js";
 import TextInput from "../components/input/TextInput";
 import styles from "./CustomerAppointmentDetails.module.css";
 import Button from "../components/Button";
 import {
+  AppointmentConflictDto,
+  checkAppointmentConflicts,
   createCustomerByIdResource,
   deleteCustomerAppointment,
+  EditAppointmentItemDto,
   EditCustomerAppointmentDto,
+  EmployeeDto,
   getCustomerAppointmentById,
   getEmployeeList,
+  getProductList,
+  getTreatmentList,
+  ProductDto,
   storeCustomerAppointment,
+  TreatmentDto,
+  WaitlistEntryDto,
 } from "../model";
 import { useTranslation } from "../translation";
 import SelectBox, { SelectBoxPossibleValue } from "../components/SelectBox";
 import InputGroup from "../components/InputGroup";
+import { appConfig } from "../appConfig";
+import { formatMoney } from "../utils";
 import {
   getCurrentDateTime,
   getDateFromDateTime,
@@ -30,20 +48,42 @@ const emptyEditData: EditCustomerAppointmentDto = {
   id: null,
   customer_id: 0,
   start_date: "",
+  ambiguous_time: null,
   duration_minutes: 0,
-  treatment: "",
-  price: 0,
   employee_id: null,
+  items: [],
+  resource_ids: [],
 };
 
-async function getEmployeeEntries(): Promise<SelectBoxPossibleValue[]> {
-  const employees = await getEmployeeList();
-  const entries: SelectBoxPossibleValue[] = employees.map((employee) => {
-    return {
-      id: employee.id,
-      name: employee.name,
-    };
-  });
+const emptyItem: EditAppointmentItemDto = {
+  treatment_id: null,
+  product_id: null,
+  description: "",
+  quantity: 1,
+  unit_price: 0,
+  discount: 0,
+  vat_rate: 1900,
+  customer_package_id: null,
+  price_rule_id: null,
+};
+
+function getItemTotal(item: EditAppointmentItemDto): number {
+  return item.quantity * item.unit_price - item.discount;
+}
+
+// Archived employees are only offered while they are still selected.
+function getEmployeeEntries(
+  employees: EmployeeDto[],
+  selectedId: number | null,
+): SelectBoxPossibleValue[] {
+  const entries: SelectBoxPossibleValue[] = employees
+    .filter((employee) => employee.active || employee.id === selectedId)
+    .map((employee) => {
+      return {
+        id: employee.id,
+        name: employee.name,
+      };
+    });
 
   entries.push({
     id: null,
@@ -53,6 +93,16 @@ async function getEmployeeEntries(): Promise<SelectBoxPossibleValue[]> {
   return entries;
 }
 
+async function getTreatmentEntries(): Promise<TreatmentDto[]> {
+  const treatments = await getTreatmentList();
+  return treatments.filter((treatment) => treatment.active);
+}
+
+async function getProductEntries(): Promise<ProductDto[]> {
+  const products = await getProductList();
+  return products.filter((product) => product.active);
+}
+
 function areEqual(
   a: EditCustomerAppointmentDto,
   b: EditCustomerAppointmentDto,
@@ -60,9 +110,10 @@ function areEqual(
   if (a.customer_id !== b.customer_id) return false;
   if (a.start_date !== b.start_date) return false;
   if (a.duration_minutes !== b.duration_minutes) return false;
-  if (a.treatment !== b.treatment) return false;
-  if (a.price !== b.price) return false;
   if (a.employee_id !== b.employee_id) return false;
+  if (JSON.stringify(a.items) !== JSON.stringify(b.items)) return false;
+  if (JSON.stringify(a.resource_ids) !== JSON.stringify(b.resource_ids))
+    return false;
 
   return true;
 }
@@ -72,12 +123,35 @@ export default function CustomerAppointmentDetails(props: {
   selectedId: number | null;
   setSelectedId: (id: number | undefined) => void;
   onUpdate: () => void;
+  onDelete: (waitlist: WaitlistEntryDto[]) => void;
 }) {
   const { t } = useTranslation();
 
   const [deleteMessageBox, setDeleteMessageBox] = createSignal(false);
+  const [conflicts, setConflicts] = createSignal<AppointmentConflictDto[]>([]);
   const [customer] = createCustomerByIdResource(() => props.customerId);
-  const [employeeEntries] = createResource(getEmployeeEntries);
+  const [employees] = createResource(getEmployeeList);
+  const [treatments] = createResource(getTreatmentEntries);
+  const [products] = createResource(getProductEntries);
+
+  const treatmentEntries = (): SelectBoxPossibleValue[] => {
+    const entries: SelectBoxPossibleValue[] = (treatments() ?? []).map(
+      (treatment) => {
+        return {
+          id: treatment.id,
+          name: treatment.name,
+          additionalSearchTerms: [treatment.category],
+        };
+      },
+    );
+
+    entries.push({
+      id: null,
+      name: "---",
+    });
+
+    return entries;
+  };
 
   const draft = createEditDraft<EditCustomerAppointmentDto>({
     selectedId: () => props.selectedId,
@@ -85,6 +159,7 @@ export default function CustomerAppointmentDetails(props: {
       ...emptyEditData,
       customer_id: props.customerId,
       start_date: getCurrentDateTime(),
+      items: [{ ...emptyItem }],
     },
     equals: areEqual,
     load: async (id) => {
@@ -97,10 +172,23 @@ export default function CustomerAppointmentDetails(props: {
         id: data.id,
         customer_id: props.customerId,
         start_date: data.start_date,
+        ambiguous_time: null,
         duration_minutes: data.duration_minutes,
-        treatment: data.treatment,
-        price: data.price,
         employee_id: data.employee?.id ?? null,
+        items: data.items.map((item) => {
+          return {
+            treatment_id: item.treatment_id,
+            product_id: item.product_id,
+            description: item.description,
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            discount: item.discount,
+            vat_rate: item.vat_rate,
+            customer_package_id: item.customer_package_id,
+            price_rule_id: item.price_rule_id,
+          };
+        }),
+        resource_ids: data.resources.map((resource) => resource.id),
       };
     },
   });
@@ -114,6 +202,100 @@ export default function CustomerAppointmentDetails(props: {
     }
   });
 
+  const productEntries = (): SelectBoxPossibleValue[] => {
+    const entries: SelectBoxPossibleValue[] = (products() ?? []).map(
+      (product) => {
+        return {
+          id: product.id,
+          name: product.name,
+          additionalSearchTerms: [product.sku, product.ean],
+        };
+      },
+    );
+
+    entries.push({
+      id: null,
+      name: "---",
+    });
+
+    return entries;
+  };
+
+  const patchItem = (
+    index: number,
+    partial: (prev: EditAppointmentItemDto) => Partial<EditAppointmentItemDto>,
+  ) => {
+    draft.patch((prev) => {
+      return {
+        items: prev.items.map((item, i) =>
+          i === index ? { ...item, ...partial(item) } : item,
+        ),
+      };
+    });
+  };
+
+  const addItem = () => {
+    draft.patch((prev) => {
+      return {
+        items: [...prev.items, { ...emptyItem }],
+      };
+    });
+  };
+
+  const removeItem = (index: number) => {
+    draft.patch((prev) => {
+      return {
+        items: prev.items.filter((_, i) => i !== index),
+      };
+    });
+  };
+
+  const setItemTreatment = (index: number, id: number | null) => {
+    const treatment = treatments()?.find((entry) => entry.id === id);
+    if (!treatment) {
+      patchItem(index, () => ({ treatment_id: null }));
+      return;
+    }
+
+    patchItem(index, () => {
+      return {
+        treatment_id: treatment.id,
+        description: treatment.name,
+        unit_price: treatment.default_price,
+        vat_rate: treatment.vat_rate,
+        customer_package_id: null,
+        price_rule_id: null,
+      };
+    });
+
+    if (draft.editData().items.length === 1) {
+      draft.handleChange(
+        "duration_minutes",
+        treatment.default_duration_minutes,
+      );
+    }
+  };
+
+  const setItemProduct = (index: number, id: number | null) => {
+    const product = products()?.find((entry) => entry.id === id);
+    if (!product) {
+      patchItem(index, () => ({ product_id: null }));
+      return;
+    }
+
+    patchItem(index, () => {
+      return {
+        product_id: product.id,
+        description: product.name,
+        unit_price: product.sale_price,
+        vat_rate: product.vat_rate,
+      };
+    });
+  };
+
+  const total = () =>
+    draft.editData().items.reduce((sum, item) => sum + getItemTotal(item), 0);
+
   const setStartDate = (value: string) => {
     draft.patch((prev) => {
       return {
@@ -132,6 +314,13 @@ export default function CustomerAppointmentDetails(props: {
 
   const storeData = async () => {
     const data = { ...draft.editData() };
+
+    const found = await checkAppointmentConflicts(data);
+    if (found.length > 0) {
+      setConflicts(found);
+      return;
+    }
+
     data.id = await storeCustomerAppointment(data);
     draft.commitSaved(data);
 
@@ -144,10 +333,11 @@ export default function CustomerAppointmentDetails(props: {
       return;
     }
 
-    await deleteCustomerAppointment(props.selectedId);
+    const waitlist = await deleteCustomerAppointment(props.selectedId);
     draft.reset();
 
     props.onUpdate();
+    props.onDelete(waitlist);
     props.setSelectedId(undefined);
   };
 
@@ -176,27 +366,95 @@ export default function CustomerAppointmentDetails(props: {
         <SelectBox
           label={t("customer.responsibleEmployee")}
           selected={draft.editData().employee_id}
-          possibleValues={employeeEntries() ?? []}
+          possibleValues={getEmployeeEntries(
+            employees() ?? [],
+            draft.editData().employee_id,
+          )}
           onSelect={(value) =>
             draft.handleChange("employee_id", value as number | null)
           }
         />
-        <NumberInput
-          label={t("customer.appointment.price")}
-          value={draft.editData().price}
-          onChange={(v) => draft.handleChange("price", v)}
-          decimalPlaces={2}
-          prefix={<span>€</span>}
-        />
-      </InputGroup>
-      <InputGroup>
-        <TextInput
-          label={t("customer.appointment.treatment")}
-          value={draft.editData().treatment}
-          onChange={(v) => draft.handleChange("treatment", v)}
-          rows={5}
-        />
       </InputGroup>
+      <Index each={draft.editData().items}>
+        {(item, index) => (
+          <div class={styles.item}>
+            <InputGroup>
+              <SelectBox
+                label={t("customer.appointment.catalogTreatment")}
+                selected={item().treatment_id}
+                possibleValues={treatmentEntries()}
+                onSelect={(value) =>
+                  setItemTreatment(index, value as number | null)
+                }
+              />
+              <SelectBox
+                label={t("customer.appointment.product")}
+                selected={item().product_id}
+                possibleValues={productEntries()}
+                onSelect={(value) =>
+                  setItemProduct(index, value as number | null)
+                }
+              />
+              <NumberInput
+                label={t("customer.appointment.quantity")}
+                value={item().quantity}
+                onChange={(v) => patchItem(index, () => ({ quantity: v }))}
+                min={0}
+              />
+              <NumberInput
+                label={t("customer.appointment.unitPrice")}
+                value={item().unit_price}
+                onChange={(v) => patchItem(index, () => ({ unit_price: v }))}
+                decimalPlaces={2}
+                prefix={<span>€</span>}
+              />
+              <NumberInput
+                label={t("customer.appointment.discount")}
+                value={item().discount}
+                onChange={(v) =>
+                  patchItem(index, () => ({ discount: v, price_rule_id: null }))
+                }
+                decimalPlaces={2}
+                min={0}
+                prefix={<span>€</span>}
+              />
+              <NumberInput
+                label={t("customer.appointment.vatRate")}
+                value={item().vat_rate}
+                onChange={(v) => patchItem(index, () => ({ vat_rate: v }))}
+                decimalPlaces={2}
+                min={0}
+                prefix={<span>%</span>}
+              />
+            </InputGroup>
+            <InputGroup>
+              <TextInput
+                label={t("customer.appointment.treatment")}
+                value={item().description}
+                onChange={(v) => patchItem(index, () => ({ description: v }))}
+                rows={2}
+              />
+              <Button color="flat" onClick={() => removeItem(index)}>
+                {t("customer.appointment.removeItem")}
+              </Button>
+            </InputGroup>
+          </div>
+        )}
+      </Index>
+      <div class={styles.itemRow}>
+        <Button color="flat" onClick={addItem}>
+          {t("customer.appointment.addItem")}
+        </Button>
+        <div class={styles.actionRowSpacer}></div>
+        <span>
+          {t("customer.appointment.price")}:{" "}
+          {formatMoney(
+            total(),
+            appConfig("general.currency"),
+            appConfig("general.language"),
+          )}
+        </span>
+      </div>
 
       <div class={styles.actionRow}>
         <Show when={props.selectedId !== null}>
@@ -238,6 +496,31 @@ export default function CustomerAppointmentDetails(props: {
           })}
         </span>
       </MessageBox>
+
+      <MessageBox
+        show={conflicts().length > 0}
+        setShow={() => setConflicts([])}
+        title={t("customer.appointment.conflictTitle")}
+        actions={[
+          {
+            label: t("general.close"),
+            onAction: () => {},
+          },
+        ]}
+      >
+        <span>{t("customer.appointment.conflictMessage")}</span>
+        <ul>
+          <For each={conflicts()}>
+            {(conflict) => (
+              <li>
+                {conflict.name}: {conflict.first_name} {conflict.last_name},{" "}
+                {getTimeFromDateTime(conflict.start_date)} -{" "}
+                {getTimeFromDateTime(conflict.end_date)}
+              </li>
+            )}
+          </For>
+        </ul>
+      </MessageBox>
     </div>
   );
 }
@@ -1,12 +1,14 @@
 import Dialog from "../components/Dialog";
 import CustomerAppointmentDetails from "./CustomerAppointmentDetails";
 import { useTranslation } from "../translation";
+import { WaitlistEntryDto } from "../model";
 
 export default function CustomerAppointmentEditDialog(props: {
   customerId: number;
   selectedId: number | null | undefined;
   setSelectedId: (id: number | null | undefined) => void;
   onUpdate: () => void;
+  onDelete: (waitlist: WaitlistEntryDto[]) => void;
 }) {
   const { t } = useTranslation();
 
@@ -21,6 +23,7 @@ export default function CustomerAppointmentEditDialog(props: {
         selectedId={props.selectedId ?? null}
         setSelectedId={props.setSelectedId}
         onUpdate={props.onUpdate}
+        onDelete={props.onDelete}
       />
     </Dialog>
   );
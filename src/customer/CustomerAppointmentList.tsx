@@ -3,14 +3,18 @@ import styles from "./CustomerAppointmentList.module.css";
 import {
   createCustomerAppointmentListResource,
   CustomerAppointmentDto,
+  renumberCustomerAppointments,
+  WaitlistEntryDto,
 } from "../model";
 import LoadingSpinner from "../components/LoadingSpinner";
 import { RiSystemErrorWarningLine } from "solid-icons/ri";
 import CustomerAppointmentEditDialog from "./CustomerAppointmentEditDialog";
 import { useTranslation } from "../translation";
 import { formatDays, formatMinutes } from "../datetime";
+import { formatMoney } from "../utils";
 import Button from "../components/Button";
 import { appConfig } from "../appConfig";
+import MessageBox from "../components/MessageBox";
 
 const options: Intl.DateTimeFormatOptions = {
   weekday: "short",
@@ -32,6 +36,13 @@ export default function CustomerAppointmentList(props: { customerId: number }) {
     undefined,
   );
 
+  const [waitlist, setWaitlist] = createSignal<WaitlistEntryDto[]>([]);
+
+  const renumber = async () => {
+    await renumberCustomerAppointments(props.customerId);
+    refetch();
+  };
+
   const formatter = () =>
     new Intl.DateTimeFormat(appConfig("general.language"), options);
 
@@ -42,6 +53,9 @@ export default function CustomerAppointmentList(props: { customerId: number }) {
           {t("customer.appointment.title")}
         </span>
         <div class={styles.headerSpacer}></div>
+        <Button color="flat" onClick={renumber}>
+          {t("customer.appointment.renumber")}
+        </Button>
         <Button color="flat" onClick={() => setSelectedId(null)}>
           {t("customer.appointment.create")}
         </Button>
@@ -93,7 +107,32 @@ export default function CustomerAppointmentList(props: { customerId: number }) {
         selectedId={selectedId()}
         setSelectedId={setSelectedId}
         onUpdate={refetch}
+        onDelete={setWaitlist}
       />
+
+      <MessageBox
+        show={waitlist().length > 0}
+        setShow={() => setWaitlist([])}
+        title={t("customer.appointment.waitlistTitle")}
+        actions={[
+          {
+            label: t("general.close"),
+            onAction: () => {},
+          },
+        ]}
+      >
+        <span>{t("customer.appointment.waitlistMessage")}</span>
+        <ul>
+          <For each={waitlist()}>
+            {(entry) => (
+              <li>
+                {entry.first_name} {entry.last_name} (
+                {entry.mobile_phone || entry.phone}): {entry.treatment}
+              </li>
+            )}
+          </For>
+        </ul>
+      </MessageBox>
     </div>
   );
 }
@@ -110,7 +149,13 @@ function CustomerAppointmentListEntry(props: {
       <td>{formatMinutes(props.appointment.duration_minutes)}</td>
       <td>{props.formatter.format(new Date(props.appointment.end_date))}</td>
       <td>{formatDays(props.appointment.period_days)}</td>
-      <td>{(props.appointment.price / 100).toFixed(2) + " €"}</td>
+      <td>
+        {formatMoney(
+          props.appointment.price.gross.amount,
+          props.appointment.price.gross.currency,
+          appConfig("general.language"),
+        )}
+      </td>
       <td>{props.appointment.treatment}</td>
       <td>{props.appointment.employee?.name ?? "---"}</td>
     </tr>
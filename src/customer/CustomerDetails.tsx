@@ -5,6 +5,7 @@ import Button from "../components/Button";
 import {
   deleteCustomer,
   EditCustomerDto,
+  EmployeeDto,
   getCustomerById,
   getEmployeeList,
   storeCustomer,
@@ -24,23 +25,31 @@ const emptyEditData: EditCustomerDto = {
   first_name: "",
   last_name: "",
   address_street: "",
+  address_postcode: "",
   address_city: "",
   phone: "",
   mobile_phone: "",
+  email: "",
   birthdate: null,
   customer_since: null,
   note: "",
   responsible_employee_id: null,
+  tags: [],
 };
 
-async function getEmployeeEntries(): Promise<SelectBoxPossibleValue[]> {
-  const employees = await getEmployeeList();
-  const entries: SelectBoxPossibleValue[] = employees.map((employee) => {
-    return {
-      id: employee.id,
-      name: employee.name,
-    };
-  });
+// Archived employees are only offered while they are still selected.
+function getEmployeeEntries(
+  employees: EmployeeDto[],
+  selectedId: number | null,
+): SelectBoxPossibleValue[] {
+  const entries: SelectBoxPossibleValue[] = employees
+    .filter((employee) => employee.active || employee.id === selectedId)
+    .map((employee) => {
+      return {
+        id: employee.id,
+        name: employee.name,
+      };
+    });
 
   entries.push({
     id: null,
@@ -55,13 +64,16 @@ function areEqual(a: EditCustomerDto, b: EditCustomerDto): boolean {
   if (a.first_name !== b.first_name) return false;
   if (a.last_name !== b.last_name) return false;
   if (a.address_street !== b.address_street) return false;
+  if (a.address_postcode !== b.address_postcode) return false;
   if (a.address_city !== b.address_city) return false;
   if (a.phone !== b.phone) return false;
   if (a.mobile_phone !== b.mobile_phone) return false;
+  if (a.email !== b.email) return false;
   if (a.birthdate !== b.birthdate) return false;
   if (a.customer_since !== b.customer_since) return false;
   if (a.note !== b.note) return false;
   if (a.responsible_employee_id !== b.responsible_employee_id) return false;
+  if (a.tags.join(",") !== b.tags.join(",")) return false;
 
   return true;
 }
@@ -75,7 +87,7 @@ export default function CustomerDetails(props: {
   const { t } = useTranslation();
 
   const [deleteMessageBox, setDeleteMessageBox] = createSignal(false);
-  const [employeeEntries] = createResource(getEmployeeEntries);
+  const [employees] = createResource(getEmployeeList);
 
   const draft = createEditDraft<EditCustomerDto>({
     selectedId: () => props.selectedId,
@@ -94,13 +106,16 @@ export default function CustomerDetails(props: {
         first_name: data.first_name,
         last_name: data.last_name,
         address_street: data.address_street,
+        address_postcode: data.address_postcode,
         address_city: data.address_city,
         phone: data.phone,
         mobile_phone: data.mobile_phone,
+        email: data.email,
         birthdate: data.birthdate,
         customer_since: data.customer_since,
         note: data.note,
         responsible_employee_id: data.responsible_employee?.id ?? null,
+        tags: data.tags,
       };
     },
   });
@@ -152,6 +167,11 @@ export default function CustomerDetails(props: {
               />
             </InputGroup>
             <InputGroup>
+              <TextInput
+                label={t("customer.addressPostcode")}
+                value={draft.editData().address_postcode}
+                onChange={(v) => draft.handleChange("address_postcode", v)}
+              />
               <TextInput
                 label={t("customer.addressCity")}
                 value={draft.editData().address_city}
@@ -175,6 +195,13 @@ export default function CustomerDetails(props: {
                 onChange={(v) => draft.handleChange("mobile_phone", v)}
               />
             </InputGroup>
+            <InputGroup>
+              <TextInput
+                label={t("customer.email")}
+                value={draft.editData().email}
+                onChange={(v) => draft.handleChange("email", v)}
+              />
+            </InputGroup>
             <InputGroup>
               <DateInput
                 label={t("customer.birthdate")}
@@ -191,7 +218,10 @@ export default function CustomerDetails(props: {
               <SelectBox
                 label={t("customer.responsibleEmployee")}
                 selected={draft.editData().responsible_employee_id}
-                possibleValues={employeeEntries() ?? []}
+                possibleValues={getEmployeeEntries(
+                  employees() ?? [],
+                  draft.editData().responsible_employee_id,
+                )}
                 onSelect={(value) =>
                   draft.handleChange(
                     "responsible_employee_id",
@@ -200,6 +230,13 @@ export default function CustomerDetails(props: {
                 }
               />
             </InputGroup>
+            <InputGroup>
+              <TextInput
+                label={t("customer.tags")}
+                value={draft.editData().tags.join(",")}
+                onChange={(v) => draft.handleChange("tags", v.split(","))}
+              />
+            </InputGroup>
           </div>
         }
         right={
@@ -9,6 +9,7 @@ function searchCustomer(customer: CustomerDto, search: string): boolean {
   const values = [
     customer.first_name,
     customer.last_name,
+    customer.address_postcode,
     customer.address_city,
     customer.address_street,
   ].map((v) => replaceSpecialCharacters(v).toLowerCase());
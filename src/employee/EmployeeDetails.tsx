@@ -1,5 +1,8 @@
 import { createSignal, Show } from "solid-js";
 import TextInput from "../components/input/TextInput";
+import DateInput from "../components/input/DateInput";
+import InputGroup from "../components/InputGroup";
+import SelectBox, { SelectBoxPossibleValue } from "../components/SelectBox";
 import styles from "./EmployeeDetails.module.css";
 import Button from "../components/Button";
 import {
@@ -15,10 +18,22 @@ import { createEditDraft } from "../hooks/form";
 const emptyEditData: EditEmployeeDto = {
   id: null,
   name: "",
+  phone: "",
+  email: "",
+  color: "",
+  employed_from: null,
+  employed_until: null,
+  active: true,
 };
 
 function areEqual(a: EditEmployeeDto, b: EditEmployeeDto): boolean {
   if (a.name !== b.name) return false;
+  if (a.phone !== b.phone) return false;
+  if (a.email !== b.email) return false;
+  if (a.color !== b.color) return false;
+  if (a.employed_from !== b.employed_from) return false;
+  if (a.employed_until !== b.employed_until) return false;
+  if (a.active !== b.active) return false;
 
   return true;
 }
@@ -33,6 +48,17 @@ export default function EmployeeDetails(props: {
 
   const [deleteMessageBox, setDeleteMessageBox] = createSignal(false);
 
+  const activeEntries = (): SelectBoxPossibleValue[] => [
+    {
+      id: "true",
+      name: t("employee.statusActive"),
+    },
+    {
+      id: "false",
+      name: t("employee.statusArchived"),
+    },
+  ];
+
   const draft = createEditDraft<EditEmployeeDto>({
     selectedId: () => props.selectedId,
     setParentDirty: props.onHasUnsavedChanges,
@@ -47,6 +73,12 @@ export default function EmployeeDetails(props: {
       return {
         id: data.id,
         name: data.name,
+        phone: data.phone,
+        email: data.email,
+        color: data.color,
+        employed_from: data.employed_from,
+        employed_until: data.employed_until,
+        active: data.active,
       };
     },
   });
@@ -79,6 +111,43 @@ export default function EmployeeDetails(props: {
         value={draft.editData().name}
         onChange={(v) => draft.handleChange("name", v)}
       />
+      <InputGroup>
+        <TextInput
+          label={t("employee.phone")}
+          value={draft.editData().phone}
+          onChange={(v) => draft.handleChange("phone", v)}
+        />
+        <TextInput
+          label={t("employee.email")}
+          value={draft.editData().email}
+          onChange={(v) => draft.handleChange("email", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <DateInput
+          label={t("employee.employedFrom")}
+          value={draft.editData().employed_from ?? ""}
+          onChange={(v) => draft.handleChange("employed_from", v)}
+        />
+        <DateInput
+          label={t("employee.employedUntil")}
+          value={draft.editData().employed_until ?? ""}
+          onChange={(v) => draft.handleChange("employed_until", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("employee.color")}
+          value={draft.editData().color}
+          onChange={(v) => draft.handleChange("color", v)}
+        />
+        <SelectBox
+          label={t("employee.status")}
+          selected={draft.editData().active ? "true" : "false"}
+          possibleValues={activeEntries()}
+          onSelect={(value) => draft.handleChange("active", value === "true")}
+        />
+      </InputGroup>
 
       <div class={styles.actionRow}>
         <Show when={props.selectedId !== null}>
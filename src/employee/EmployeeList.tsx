@@ -84,9 +84,16 @@ export default function EmployeeList(props: {
                     class={styles.employeeListEntry}
                     classList={{
                       [styles.selected]: employee.id === props.selectedId,
+                      [styles.archived]: !employee.active,
                     }}
                     onClick={() => props.setSelectedId(employee.id)}
                   >
+                    <span
+                      class={styles.employeeColor}
+                      style={{
+                        "background-color": employee.color || "transparent",
+                      }}
+                    />
                     {employee.name}
                   </div>
                 );
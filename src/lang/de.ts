@@ -7,6 +7,7 @@ export const dict: RawDictionary = {
     cancel: "Abbrechen",
     save: "Speichern",
     delete: "Löschen",
+    close: "Schließen",
   },
   masterDetails: {
     dirtyConfirmTitle: "Ungespeicherte Änderungen",
@@ -19,10 +20,45 @@ export const dict: RawDictionary = {
     create: "Mitarbeiter erstellen",
     delete: "Mitarbeiter löschen",
     deleteMessage: i18n.template<{ id: number }>(
-      "Möchten Sie den Mitarbeiter {{ id }} löschen? Dies kann nicht rückgängig gemacht werden.",
+      "Möchten Sie den Mitarbeiter {{ id }} löschen? Mitarbeiter mit Terminen werden stattdessen archiviert.",
     ),
     idHint: i18n.template<{ id: number }>("Mitarbeiter-ID: {{ id }}"),
     name: "Name",
+    phone: "Telefon",
+    email: "E-Mail",
+    color: "Kalenderfarbe (#rrggbb)",
+    employedFrom: "Beschäftigt ab",
+    employedUntil: "Beschäftigt bis",
+    status: "Status",
+    statusActive: "Aktiv",
+    statusArchived: "Archiviert",
+  },
+  user: {
+    loginTitle: "Anmeldung",
+    setupTitle: "Administrator anlegen",
+    setupHint:
+      "Es gibt noch keine Benutzerkonten. Das erste Konto verwaltet die Praxis.",
+    username: "Benutzername",
+    password: "Passwort",
+    passwordRepeat: "Passwort wiederholen",
+    passwordMismatch: "Die Passwörter stimmen nicht überein.",
+    passwordKeep: "Neues Passwort (leer behält das bisherige)",
+    login: "Anmelden",
+    setup: "Anlegen und anmelden",
+    logout: i18n.template<{ username: string }>("{{ username }} abmelden"),
+    currentPassword: "Aktuelles Passwort",
+    newPassword: "Neues Passwort",
+    changePassword: "Passwort ändern",
+    passwordChanged: "Das Passwort wurde geändert.",
+    create: "Neuer Benutzer",
+    role: "Rolle",
+    roleAdmin: "Administrator",
+    roleReception: "Empfang",
+    roleTherapist: "Therapeut",
+    employee: "Mitarbeiter",
+    status: "Status",
+    statusActive: "Aktiv",
+    statusInactive: "Inaktiv",
   },
   customer: {
     create: "Kunde erstellen",
@@ -35,17 +71,21 @@ export const dict: RawDictionary = {
     firstName: "Vorname",
     lastName: "Nachname",
     addressStreet: "Straße",
+    addressPostcode: "Postleitzahl",
     addressCity: "Ort",
     phone: "Telefon",
     mobilePhone: "Mobil",
+    email: "E-Mail",
     birthdate: "Geburtstag",
     customerSince: "Kunde seit",
     note: "Notiz",
     responsibleEmployee: "Zuständiger Mitarbeiter",
+    tags: "Merkmale (kommagetrennt)",
     appointment: {
       title: "Termine",
       dialogTitle: "Terminverwaltung",
       create: "Termin erstellen",
+      renumber: "Nach Datum neu nummerieren",
       delete: "Termin löschen",
       deleteMessage: i18n.template<{ id: number }>(
         "Möchten Sie den Termin {{ id }} löschen? Dies kann nicht rückgängig gemacht werden.",
@@ -59,14 +99,76 @@ export const dict: RawDictionary = {
       endTime: "Ende",
       period: "Zeitraum",
       treatment: "Behandlung",
+      catalogTreatment: "Katalogbehandlung",
+      product: "Produkt",
+      quantity: "Menge",
+      unitPrice: "Einzelpreis",
+      discount: "Rabatt",
+      vatRate: "MwSt.",
+      addItem: "Position hinzufügen",
+      removeItem: "Position entfernen",
       price: "Preis",
       employee: "Mitarbeiter",
+      waitlistTitle: "Warteliste",
+      waitlistMessage: "Der freie Termin passt zu diesen Einträgen der Warteliste:",
+      conflictTitle: "Terminkonflikt",
+      conflictMessage: "Der Termin überschneidet sich mit diesen Buchungen:",
     },
   },
   settings: {
     title: "Einstellungen",
     language: "Sprache",
     fontSize: "Schriftgröße",
+    timeZone: "Zeitzone der Praxis",
+    currency: "Währung",
+    icalIncludeCustomerName: "Kundenname im Kalenderexport",
+    reminderEnabled: "Terminerinnerungen versenden",
+    reminderHoursBefore: "Erinnerung vor dem Termin",
+    reminderSubject: "Betreff der Erinnerung",
+    reminderMessage:
+      "Text der Erinnerung ({first_name}, {last_name}, {title}, {date}, {time}, {treatment}, {employee})",
+    smtpHost: "SMTP-Server",
+    smtpPort: "Port",
+    smtpSecurity: "Verschlüsselung",
+    smtpSecurityNone: "Keine",
+    smtpUsername: "Benutzername",
+    smtpPassword: "Passwort",
+    smtpSender: "Absenderadresse",
+    sendDueReminders: "Fällige Erinnerungen jetzt senden",
+    invoicePracticeName: "Praxisname auf Rechnungen",
+    invoicePracticeAddress: "Praxisanschrift",
+    invoicePracticeContact: "Kontaktdaten",
+    invoiceTaxNumber: "Steuernummer",
+    invoicePaymentDays: "Zahlungsziel in Tagen",
+    invoiceBankDetails: "Bankverbindung",
+    invoiceFooter: "Fußzeile der Rechnung",
+    invoiceNumberPattern: "Schema der Rechnungsnummer, z. B. {YYYY}-{NNNN}",
+    invoiceCreditNotePattern: "Schema der Gutschriftnummer",
+    invoiceStreet: "Straße der Praxis",
+    invoicePostcode: "Postleitzahl der Praxis",
+    invoiceCity: "Ort der Praxis",
+    invoiceCountry: "Ländercode der Praxis",
+    invoiceVatId: "USt-IdNr.",
+    invoiceContactName: "Ansprechpartner für E-Rechnungen",
+    invoiceContactPhone: "Telefon des Ansprechpartners",
+    invoiceContactEmail: "E-Mail des Ansprechpartners",
+    invoiceIban: "IBAN",
+    invoiceBic: "BIC",
+    datevConsultantNumber: "DATEV-Beraternummer",
+    datevClientNumber: "DATEV-Mandantennummer",
+    datevAccountLength: "Sachkontenlänge",
+    datevReceivableAccount: "Debitorenkonto",
+    datevRevenueAccount: i18n.template<{ rate: string }>(
+      "Erlöskonto {{ rate }} % USt.",
+    ),
+    datevPaymentAccount: {
+      cash: "Konto für Barzahlungen",
+      card: "Konto für Kartenzahlungen",
+      transfer: "Konto für Überweisungen",
+      voucher: "Konto für Gutscheinzahlungen",
+    },
+    yes: "Ja",
+    no: "Nein",
     openAppDataDirectory: "App-Ordner öffnen",
     update: {
       loading: "Updates werden gesucht...",
@@ -6,6 +6,7 @@ export const dict = {
     cancel: "Cancel",
     save: "Save",
     delete: "Delete",
+    close: "Close",
   },
   masterDetails: {
     dirtyConfirmTitle: "Unsaved changes",
@@ -18,10 +19,45 @@ export const dict = {
     create: "Create employee",
     delete: "Delete employee",
     deleteMessage: i18n.template<{ id: number }>(
-      "Do you want to delete employee {{ id }}? This cannot be undone.",
+      "Do you want to delete employee {{ id }}? Employees with appointments are archived instead.",
     ),
     idHint: i18n.template<{ id: number }>("Employee ID: {{ id }}"),
     name: "Name",
+    phone: "Phone",
+    email: "Email",
+    color: "Calendar colour (#rrggbb)",
+    employedFrom: "Employed from",
+    employedUntil: "Employed until",
+    status: "Status",
+    statusActive: "Active",
+    statusArchived: "Archived",
+  },
+  user: {
+    loginTitle: "Login",
+    setupTitle: "Create administrator",
+    setupHint:
+      "No user accounts exist yet. The first account administers the practice.",
+    username: "Username",
+    password: "Password",
+    passwordRepeat: "Repeat password",
+    passwordMismatch: "The passwords do not match.",
+    passwordKeep: "New password (empty keeps the current one)",
+    login: "Login",
+    setup: "Create and login",
+    logout: i18n.template<{ username: string }>("Logout {{ username }}"),
+    currentPassword: "Current password",
+    newPassword: "New password",
+    changePassword: "Change password",
+    passwordChanged: "The password has been changed.",
+    create: "New user",
+    role: "Role",
+    roleAdmin: "Administrator",
+    roleReception: "Reception",
+    roleTherapist: "Therapist",
+    employee: "Employee",
+    status: "Status",
+    statusActive: "Active",
+    statusInactive: "Inactive",
   },
   customer: {
     create: "Create customer",
@@ -34,17 +70,21 @@ export const dict = {
     firstName: "First name",
     lastName: "Last name",
     addressStreet: "Street",
+    addressPostcode: "Postcode",
     addressCity: "City",
     phone: "Phone",
     mobilePhone: "Mobile phone",
+    email: "E-mail",
     birthdate: "Birthdate",
     customerSince: "Customer since",
     note: "Note",
     responsibleEmployee: "Responsible employee",
+    tags: "Tags (comma separated)",
     appointment: {
       title: "Appointments",
       dialogTitle: "Appointment management",
       create: "Create appointment",
+      renumber: "Renumber by date",
       delete: "Delete appointment",
       deleteMessage: i18n.template<{ id: number }>(
         "Do you want to delete appointment {{ id }}? This cannot be undone.",
@@ -58,14 +98,76 @@ export const dict = {
       endTime: "End",
       period: "Period",
       treatment: "Treatment",
+      catalogTreatment: "Catalog treatment",
+      product: "Product",
+      quantity: "Quantity",
+      unitPrice: "Unit price",
+      discount: "Discount",
+      vatRate: "VAT",
+      addItem: "Add item",
+      removeItem: "Remove item",
       price: "Price",
       employee: "Employee",
+      waitlistTitle: "Waitlist",
+      waitlistMessage: "The freed slot fits these waitlist entries:",
+      conflictTitle: "Booking conflict",
+      conflictMessage: "The appointment overlaps with these bookings:",
     },
   },
   settings: {
     title: "Settings",
     language: "Language",
     fontSize: "Font size",
+    timeZone: "Time zone of the practice",
+    currency: "Currency",
+    icalIncludeCustomerName: "Customer name in calendar export",
+    reminderEnabled: "Send appointment reminders",
+    reminderHoursBefore: "Reminder before appointment",
+    reminderSubject: "Reminder subject",
+    reminderMessage:
+      "Reminder message ({first_name}, {last_name}, {title}, {date}, {time}, {treatment}, {employee})",
+    smtpHost: "SMTP server",
+    smtpPort: "Port",
+    smtpSecurity: "Encryption",
+    smtpSecurityNone: "None",
+    smtpUsername: "Username",
+    smtpPassword: "Password",
+    smtpSender: "Sender address",
+    sendDueReminders: "Send due reminders now",
+    invoicePracticeName: "Practice name on invoices",
+    invoicePracticeAddress: "Practice address",
+    invoicePracticeContact: "Contact details",
+    invoiceTaxNumber: "Tax number",
+    invoicePaymentDays: "Payment term in days",
+    invoiceBankDetails: "Bank details",
+    invoiceFooter: "Invoice footer",
+    invoiceNumberPattern: "Invoice number pattern, e.g. {YYYY}-{NNNN}",
+    invoiceCreditNotePattern: "Credit note number pattern",
+    invoiceStreet: "Street of the practice",
+    invoicePostcode: "Postcode of the practice",
+    invoiceCity: "City of the practice",
+    invoiceCountry: "Country code of the practice",
+    invoiceVatId: "VAT ID",
+    invoiceContactName: "Contact person for e-invoices",
+    invoiceContactPhone: "Phone of the contact person",
+    invoiceContactEmail: "Email of the contact person",
+    invoiceIban: "IBAN",
+    invoiceBic: "BIC",
+    datevConsultantNumber: "DATEV consultant number",
+    datevClientNumber: "DATEV client number",
+    datevAccountLength: "Account length",
+    datevReceivableAccount: "Debtor account",
+    datevRevenueAccount: i18n.template<{ rate: string }>(
+      "Revenue account {{ rate }} % VAT",
+    ),
+    datevPaymentAccount: {
+      cash: "Account for cash payments",
+      card: "Account for card payments",
+      transfer: "Account for transfers",
+      voucher: "Account for voucher payments",
+    },
+    yes: "Yes",
+    no: "No",
     openAppDataDirectory: "Open app data directory",
     update: {
       loading: "Seaching for updates...",
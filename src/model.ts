@@ -1,14 +1,52 @@
 import { invoke } from "@tauri-apps/api/core";
 import { Accessor, createResource, ResourceReturn } from "solid-js";
 
+export type Currency = "EUR" | "CHF" | "USD" | "GBP";
+
+export type Money = {
+  amount: number;
+  currency: Currency;
+};
+
+export type PriceDto = {
+  net: Money;
+  tax: Money;
+  gross: Money;
+};
+
+export type TaxBreakdownDto = {
+  vat_rate: number;
+  net: Money;
+  tax: Money;
+  gross: Money;
+};
+
 export type EmployeeDto = {
   id: number;
   name: string;
+  phone: string;
+  email: string;
+  color: string;
+  employed_from: string | null;
+  employed_until: string | null;
+  active: boolean;
 };
 
 export type EditEmployeeDto = {
   id: number | null;
   name: string;
+  phone: string;
+  email: string;
+  color: string;
+  employed_from: string | null;
+  employed_until: string | null;
+  active: boolean;
+};
+
+export type EmployeeRefDto = {
+  id: number;
+  name: string;
+  active: boolean;
 };
 
 export type CustomerDto = {
@@ -17,13 +55,17 @@ export type CustomerDto = {
   first_name: string;
   last_name: string;
   address_street: string;
+  address_postcode: string;
   address_city: string;
   phone: string;
   mobile_phone: string;
+  email: string;
   birthdate: string | null;
   customer_since: string | null;
   note: string;
-  responsible_employee: EmployeeDto | null;
+  responsible_employee: EmployeeRefDto | null;
+  tags: string[];
+  packages: CustomerPackageDto[];
 };
 
 export type EditCustomerDto = {
@@ -32,13 +74,16 @@ export type EditCustomerDto = {
   first_name: string;
   last_name: string;
   address_street: string;
+  address_postcode: string;
   address_city: string;
   phone: string;
   mobile_phone: string;
+  email: string;
   birthdate: string | null;
   customer_since: string | null;
   note: string;
   responsible_employee_id: number | null;
+  tags: string[];
 };
 
 export type CustomerAppointmentDto = {
@@ -48,20 +93,755 @@ export type CustomerAppointmentDto = {
   start_date: string;
   duration_minutes: number;
   end_date: string;
+  time_zone: string;
+  start_utc: string;
+  end_utc: string;
   period_days: number | null;
   treatment: string;
-  price: number;
-  employee: EmployeeDto | null;
+  price: PriceDto;
+  taxes: TaxBreakdownDto[];
+  employee: EmployeeRefDto | null;
+  items: AppointmentItemDto[];
+  resources: ResourceDto[];
 };
 
+export type AmbiguousTime = "earlier" | "later";
+
 export type EditCustomerAppointmentDto = {
   id: number | null;
   customer_id: number;
   start_date: string;
+  ambiguous_time: AmbiguousTime | null;
   duration_minutes: number;
-  treatment: string;
+  employee_id: number | null;
+  items: EditAppointmentItemDto[];
+  resource_ids: number[];
+};
+
+export type AppointmentFilterDto = {
+  customer_id: number | null;
+  employee_id: number | null;
+  resource_id: number | null;
+  from: string | null;
+  to: string | null;
+};
+
+export type IcalImportPreviewDto = {
+  events: IcalImportEventDto[];
+  skipped: IcalSkippedEventDto[];
+};
+
+export type IcalImportEventDto = {
+  uid: string | null;
+  summary: string;
+  description: string;
+  start_date: string;
+  duration_minutes: number;
+  appointment_id: number | null;
+  customer_id: number | null;
+  candidates: IcalCustomerCandidateDto[];
+};
+
+export type CustomerMatchKind = "email" | "phone" | "name";
+
+export type IcalCustomerCandidateDto = {
+  customer_id: number;
+  first_name: string;
+  last_name: string;
+  phone: string;
+  mobile_phone: string;
+  match_kind: CustomerMatchKind;
+};
+
+export type IcalSkipReason =
+  | "missing_start"
+  | "all_day"
+  | "recurring"
+  | "cancelled";
+
+export type IcalSkippedEventDto = {
+  uid: string | null;
+  summary: string;
+  reason: IcalSkipReason;
+};
+
+export type IcalImportAppointmentDto = {
+  ical_uid: string | null;
+  appointment: EditCustomerAppointmentDto;
+};
+
+export type VisitPredictionDto = {
+  customer_id: number;
+  last_visit: string;
+  typical_interval_days: number;
+  predicted_next_visit: string;
+};
+
+export type OverdueCustomerDto = {
+  customer_id: number;
+  title: string;
+  first_name: string;
+  last_name: string;
+  phone: string;
+  mobile_phone: string;
+  last_visit: string;
+  typical_interval_days: number;
+  predicted_next_visit: string;
+  overdue_days: number;
+};
+
+export type AppointmentItemDto = {
+  id: number;
+  treatment_id: number | null;
+  product_id: number | null;
+  description: string;
+  quantity: number;
+  unit_price: number;
+  discount: number;
+  vat_rate: number;
+  total: PriceDto;
+  customer_package_id: number | null;
+  price_rule_id: number | null;
+  price_rule_name: string | null;
+};
+
+export type EditAppointmentItemDto = {
+  treatment_id: number | null;
+  product_id: number | null;
+  description: string;
+  quantity: number;
+  unit_price: number;
+  discount: number;
+  vat_rate: number;
+  customer_package_id: number | null;
+  price_rule_id: number | null;
+};
+
+export type TreatmentDto = {
+  id: number;
+  name: string;
+  category: string;
+  default_duration_minutes: number;
+  default_price: number;
+  vat_rate: number;
+  active: boolean;
+  resource_ids: number[];
+};
+
+export type EditTreatmentDto = {
+  id: number | null;
+  name: string;
+  category: string;
+  default_duration_minutes: number;
+  default_price: number;
+  vat_rate: number;
+  active: boolean;
+  resource_ids: number[];
+};
+
+export type PriceRuleKind = "percentage" | "fixed";
+
+export type PriceRuleDto = {
+  id: number;
+  name: string;
+  kind: PriceRuleKind;
+  value: number;
+  customer_tag: string | null;
+  treatment_id: number | null;
+  min_visit_count: number | null;
+  weekdays: number[];
+  start_time: string | null;
+  end_time: string | null;
+  active: boolean;
+};
+
+export type EditPriceRuleDto = {
+  id: number | null;
+  name: string;
+  kind: PriceRuleKind;
+  value: number;
+  customer_tag: string | null;
+  treatment_id: number | null;
+  min_visit_count: number | null;
+  weekdays: number[];
+  start_time: string | null;
+  end_time: string | null;
+  active: boolean;
+};
+
+export type AppliedPriceRuleDto = {
+  price_rule_id: number;
+  name: string;
+  discount: number;
+};
+
+export type SessionPackageDto = {
+  id: number;
+  name: string;
+  treatment_id: number | null;
+  session_count: number;
+  price: number;
+  validity_days: number | null;
+  active: boolean;
+};
+
+export type EditSessionPackageDto = {
+  id: number | null;
+  name: string;
+  treatment_id: number | null;
+  session_count: number;
+  price: number;
+  validity_days: number | null;
+  active: boolean;
+};
+
+export type CustomerPackageDto = {
+  id: number;
+  customer_id: number;
+  session_package_id: number | null;
+  name: string;
+  treatment_id: number | null;
+  session_count: number;
+  used_sessions: number;
+  booked_sessions: number;
+  remaining_sessions: number;
   price: number;
+  vat_rate: number;
+  purchase_date: string;
+  expiry_date: string | null;
+  expired: boolean;
+  note: string;
+};
+
+export type EditCustomerPackageDto = {
+  id: number | null;
+  customer_id: number;
+  session_package_id: number | null;
+  name: string;
+  treatment_id: number | null;
+  session_count: number;
+  price: number;
+  vat_rate: number;
+  purchase_date: string;
+  expiry_date: string | null;
+  note: string;
+};
+
+export type InvoiceKind = "invoice" | "credit_note";
+
+export type InvoiceStatus = "draft" | "issued" | "cancelled";
+
+export type EInvoiceSyntax = "ubl" | "cii";
+
+export type InvoiceDto = {
+  id: number;
+  kind: InvoiceKind;
+  status: InvoiceStatus;
+  number: string | null;
+  customer_id: number | null;
+  customer_name: string;
+  address_street: string;
+  address_postcode: string;
+  address_city: string;
+  buyer_reference: string;
+  issue_date: string | null;
+  due_date: string | null;
+  currency: Currency;
+  cancelled_invoice_id: number | null;
+  credit_note_id: number | null;
+  note: string;
+  items: InvoiceItemDto[];
+  price: PriceDto;
+  taxes: TaxBreakdownDto[];
+};
+
+export type InvoiceItemDto = {
+  id: number;
+  appointment_id: number | null;
+  date: string | null;
+  description: string;
+  quantity: number;
+  unit_price: number;
+  discount: number;
+  vat_rate: number;
+  total: PriceDto;
+};
+
+export type EditInvoiceDto = {
+  id: number | null;
+  customer_id: number | null;
+  customer_name: string;
+  address_street: string;
+  address_postcode: string;
+  address_city: string;
+  buyer_reference: string;
+  issue_date: string | null;
+  due_date: string | null;
+  note: string;
+  items: EditInvoiceItemDto[];
+};
+
+export type EditInvoiceItemDto = {
+  appointment_id: number | null;
+  date: string | null;
+  description: string;
+  quantity: number;
+  unit_price: number;
+  discount: number;
+  vat_rate: number;
+};
+
+export type PaymentMethod = "cash" | "card" | "transfer" | "voucher";
+
+export type PaymentDto = {
+  id: number;
+  customer_id: number;
+  date: string;
+  amount: number;
+  method: PaymentMethod;
+  voucher_id: number | null;
+  reference: string;
+  note: string;
+  allocations: PaymentAllocationDto[];
+  unallocated: number;
+};
+
+export type EditPaymentDto = {
+  id: number | null;
+  customer_id: number;
+  date: string;
+  amount: number;
+  method: PaymentMethod;
+  voucher_id: number | null;
+  reference: string;
+  note: string;
+  allocations: PaymentAllocationDto[];
+};
+
+export type PaymentAllocationDto = {
+  appointment_id: number | null;
+  invoice_id: number | null;
+  amount: number;
+};
+
+export type OpenItemKind = "appointment" | "invoice";
+
+export type OpenItemDto = {
+  kind: OpenItemKind;
+  appointment_id: number | null;
+  invoice_id: number | null;
+  customer_id: number;
+  first_name: string;
+  last_name: string;
+  reference: string;
+  date: string;
+  due_date: string;
+  amount: Money;
+  paid: Money;
+  outstanding: Money;
+};
+
+export type CustomerBalanceDto = {
+  customer_id: number;
+  due: Money;
+  paid: Money;
+  balance: Money;
+};
+
+export type CashEntryKind =
+  | "payment"
+  | "correction"
+  | "refund"
+  | "withdrawal"
+  | "deposit"
+  | "difference";
+
+export type CashEntryDto = {
+  id: number;
+  created_at: string;
+  date: string;
+  kind: CashEntryKind;
+  amount: number;
+  payment_id: number | null;
+  description: string;
+  hash: string;
+};
+
+export type EditCashEntryDto = {
+  date: string;
+  kind: CashEntryKind;
+  amount: number;
+  description: string;
+};
+
+export type CashClosingDto = {
+  id: number;
+  date: string;
+  created_at: string;
+  opening_balance: number;
+  receipts: number;
+  payouts: number;
+  expected: number;
+  counted: number;
+  difference: number;
+  note: string;
+  last_entry_id: number;
+  hash: string;
+};
+
+export type CashJournalVerificationDto = {
+  valid: boolean;
+  entry_count: number;
+  closing_count: number;
+  issues: CashJournalIssueDto[];
+};
+
+export type CashJournalIssueKind =
+  | "missing"
+  | "modified"
+  | "broken_chain"
+  | "closing_mismatch";
+
+export type CashJournalIssueDto = {
+  kind: CashJournalIssueKind;
+  entry_id: number | null;
+  closing_id: number | null;
+  message: string;
+};
+
+export type DatevAccountsDto = {
+  receivable_account: number;
+  revenue_accounts: DatevRevenueAccountDto[];
+  payment_accounts: DatevPaymentAccountDto[];
+};
+
+export type DatevRevenueAccountDto = {
+  vat_rate: number;
+  account: number;
+};
+
+export type DatevPaymentAccountDto = {
+  method: PaymentMethod;
+  account: number;
+};
+
+export type VoucherDto = {
+  id: number;
+  code: string;
+  initial_value: number;
+  remaining_value: number;
+  issue_date: string;
+  expiry_date: string | null;
+  purchaser_customer_id: number | null;
+  recipient_customer_id: number | null;
+  note: string;
+  redemptions: VoucherRedemptionDto[];
+};
+
+export type EditVoucherDto = {
+  id: number | null;
+  initial_value: number;
+  issue_date: string;
+  expiry_date: string | null;
+  purchaser_customer_id: number | null;
+  recipient_customer_id: number | null;
+  note: string;
+};
+
+export type VoucherRedemptionDto = {
+  payment_id: number;
+  customer_id: number;
+  date: string;
+  amount: number;
+};
+
+export type VoucherLiabilityReportDto = {
+  date: string;
+  vouchers: VoucherDto[];
+  outstanding: Money;
+  expired: Money;
+};
+
+export type RevenueGrouping =
+  | "day"
+  | "week"
+  | "month"
+  | "year"
+  | "employee"
+  | "treatment"
+  | "customer";
+
+export type RevenueBasis = "appointments" | "payments";
+
+export type RevenueReportFilterDto = {
+  from: string;
+  to: string;
+  group_by: RevenueGrouping;
+  basis: RevenueBasis;
+};
+
+export type RevenueReportDto = {
+  filter: RevenueReportFilterDto;
+  previous_from: string;
+  previous_to: string;
+  rows: RevenueRowDto[];
+  total: RevenueRowDto;
+};
+
+export type RevenueRowDto = {
+  key: string;
+  label: string;
+  count: number;
+  price: PriceDto;
+  previous_count: number;
+  previous_gross: Money;
+  change_percent: number | null;
+};
+
+export type ResourceKind = "room" | "device";
+
+export type ResourceDto = {
+  id: number;
+  name: string;
+  kind: ResourceKind;
+  active: boolean;
+};
+
+export type EditResourceDto = {
+  id: number | null;
+  name: string;
+  kind: ResourceKind;
+  active: boolean;
+};
+
+export type ResourceCalendarDto = {
+  resource: ResourceDto;
+  appointments: CustomerAppointmentDto[];
+};
+
+export type AppointmentConflictKind = "employee" | "resource";
+
+export type AppointmentConflictDto = {
+  kind: AppointmentConflictKind;
+  appointment_id: number;
+  customer_id: number;
+  first_name: string;
+  last_name: string;
+  start_date: string;
+  end_date: string;
+  employee_id: number | null;
+  resource_id: number | null;
+  name: string;
+};
+
+export type RescheduleAction = "reassign" | "shift" | "unresolved";
+
+export type RescheduleProposalDto = {
+  appointment: CustomerAppointmentDto;
+  first_name: string;
+  last_name: string;
+  action: RescheduleAction;
+  employee: EmployeeRefDto | null;
+  start_date: string;
+};
+
+export type RescheduleChangeDto = {
+  appointment_id: number;
   employee_id: number | null;
+  start_date: string;
+};
+
+export type RescheduledAppointmentDto = {
+  appointment_id: number;
+  customer_id: number;
+  first_name: string;
+  last_name: string;
+  old_start_date: string;
+  new_start_date: string;
+  old_employee: EmployeeRefDto | null;
+  new_employee: EmployeeRefDto | null;
+};
+
+export type RescheduleSummaryDto = {
+  reassigned: number;
+  shifted: number;
+  changes: RescheduledAppointmentDto[];
+};
+
+export type ProductDto = {
+  id: number;
+  name: string;
+  sku: string;
+  ean: string;
+  purchase_price: number;
+  sale_price: number;
+  vat_rate: number;
+  stock: number;
+  reorder_threshold: number;
+  active: boolean;
+};
+
+export type EditProductDto = {
+  id: number | null;
+  name: string;
+  sku: string;
+  ean: string;
+  purchase_price: number;
+  sale_price: number;
+  vat_rate: number;
+  reorder_threshold: number;
+  active: boolean;
+};
+
+export type StockMovementKind = "sale" | "receipt" | "correction";
+
+export type StockMovementDto = {
+  id: number;
+  product_id: number;
+  date: string;
+  kind: StockMovementKind;
+  quantity: number;
+  appointment_id: number | null;
+  note: string;
+};
+
+export type EditStockMovementDto = {
+  product_id: number;
+  date: string;
+  kind: StockMovementKind;
+  quantity: number;
+  note: string;
+};
+
+export type WaitlistEntryDto = {
+  id: number;
+  customer_id: number;
+  title: string;
+  first_name: string;
+  last_name: string;
+  phone: string;
+  mobile_phone: string;
+  treatment_id: number | null;
+  treatment: string;
+  duration_minutes: number;
+  employee: EmployeeRefDto | null;
+  note: string;
+  created_date: string;
+  windows: WaitlistWindowDto[];
+};
+
+export type EditWaitlistEntryDto = {
+  id: number | null;
+  customer_id: number;
+  treatment_id: number | null;
+  treatment: string;
+  duration_minutes: number;
+  employee_id: number | null;
+  note: string;
+  windows: WaitlistWindowDto[];
+};
+
+export type WaitlistWindowDto = {
+  start_date: string;
+  end_date: string;
+};
+
+export type ReminderChannel = "email" | "sms";
+
+export type ReminderStatus = "pending" | "sent" | "failed";
+
+export type ReminderDto = {
+  id: number;
+  appointment_id: number;
+  customer_id: number;
+  channel: ReminderChannel;
+  recipient: string;
+  appointment_start_date: string;
+  due_date: string;
+  status: ReminderStatus;
+  attempts: number;
+  last_error: string | null;
+  sent_date: string | null;
+};
+
+export type UserRole = "admin" | "reception" | "therapist";
+
+export type UserDto = {
+  id: number;
+  username: string;
+  role: UserRole;
+  employee: EmployeeRefDto | null;
+  active: boolean;
+};
+
+export type EditUserDto = {
+  id: number | null;
+  username: string;
+  password: string;
+  role: UserRole;
+  employee_id: number | null;
+  active: boolean;
+};
+
+export type SessionDto = {
+  user_id: number;
+  username: string;
+  role: UserRole;
+  employee_id: number | null;
+};
+
+export type CommissionKind = "percentage" | "fixed";
+
+export type CommissionRuleDto = {
+  id: number;
+  employee_id: number;
+  kind: CommissionKind;
+  value: number;
+  treatment_category: string | null;
+  treatment_id: number | null;
+  valid_from: string | null;
+  valid_until: string | null;
+};
+
+export type EditCommissionRuleDto = {
+  id: number | null;
+  employee_id: number;
+  kind: CommissionKind;
+  value: number;
+  treatment_category: string | null;
+  treatment_id: number | null;
+  valid_from: string | null;
+  valid_until: string | null;
+};
+
+export type CommissionReportFilterDto = {
+  from: string;
+  to: string;
+  employee_id: number | null;
+};
+
+export type CommissionReportDto = {
+  filter: CommissionReportFilterDto;
+  employees: EmployeeCommissionDto[];
+  revenue: Money;
+  commission: Money;
+};
+
+export type EmployeeCommissionDto = {
+  employee: EmployeeRefDto;
+  appointments: CommissionAppointmentDto[];
+  revenue: Money;
+  commission: Money;
+};
+
+export type CommissionAppointmentDto = {
+  appointment_id: number;
+  start_date: string;
+  customer_id: number;
+  customer_name: string;
+  treatment: string;
+  revenue: Money;
+  commission: Money;
 };
 
 export type PreferenceDto = {
@@ -173,8 +953,118 @@ export async function storeCustomerAppointment(
   return await invoke<number>("store_customer_appointment", { appointment });
 }
 
-export async function deleteCustomerAppointment(id: number) {
-  await invoke("delete_customer_appointment", { id });
+export async function checkAppointmentConflicts(
+  appointment: EditCustomerAppointmentDto,
+): Promise<AppointmentConflictDto[]> {
+  return await invoke("check_appointment_conflicts", { appointment });
+}
+
+export async function planReschedule(
+  employeeId: number,
+  from: string,
+  to: string,
+): Promise<RescheduleProposalDto[]> {
+  return await invoke("plan_reschedule", { employeeId, from, to });
+}
+
+export async function applyReschedule(
+  changes: RescheduleChangeDto[],
+): Promise<RescheduleSummaryDto> {
+  return await invoke("apply_reschedule", { changes });
+}
+
+export async function deleteCustomerAppointment(
+  id: number,
+): Promise<WaitlistEntryDto[]> {
+  return await invoke("delete_customer_appointment", { id });
+}
+
+export async function renumberCustomerAppointments(customerId: number) {
+  await invoke("renumber_customer_appointments", { customerId });
+}
+
+export async function getCustomerVisitPrediction(
+  customerId: number,
+): Promise<VisitPredictionDto | null> {
+  return (
+    (await invoke<VisitPredictionDto>("get_customer_visit_prediction", {
+      customerId,
+    })) ?? null
+  );
+}
+
+export async function getOverdueCustomers(
+  toleranceDays: number,
+): Promise<OverdueCustomerDto[]> {
+  return await invoke<OverdueCustomerDto[]>("get_overdue_customers", {
+    toleranceDays,
+  });
+}
+
+export async function exportAppointmentsIcal(
+  filter: AppointmentFilterDto,
+  path: string,
+): Promise<number> {
+  return await invoke<number>("export_appointments_ical", { filter, path });
+}
+
+export async function previewIcalImport(
+  path: string,
+): Promise<IcalImportPreviewDto> {
+  return await invoke<IcalImportPreviewDto>("preview_ical_import", { path });
+}
+
+export async function importAppointments(
+  appointments: IcalImportAppointmentDto[],
+): Promise<number[]> {
+  return await invoke<number[]>("import_appointments", { appointments });
+}
+
+export async function getReminderList(
+  appointmentId: number | null,
+  status: ReminderStatus | null,
+): Promise<ReminderDto[]> {
+  return await invoke("get_reminder_list", { appointmentId, status });
+}
+
+export async function sendDueReminders(): Promise<number> {
+  return await invoke("send_due_reminders");
+}
+
+export async function retryReminder(id: number) {
+  await invoke("retry_reminder", { id });
+}
+
+export async function getWaitlist(): Promise<WaitlistEntryDto[]> {
+  return await invoke("get_waitlist");
+}
+
+export async function getWaitlistEntryById(
+  id: number,
+): Promise<WaitlistEntryDto | null> {
+  return await invoke("get_waitlist_entry_by_id", { id });
+}
+
+export async function getWaitlistForSlot(
+  startDate: string,
+  endDate: string,
+  employeeId: number | null,
+): Promise<WaitlistEntryDto[]> {
+  return await invoke("get_waitlist_for_slot", {
+    startDate,
+    endDate,
+    employeeId,
+  });
+}
+
+export async function storeWaitlistEntry(
+  entry: EditWaitlistEntryDto,
+): Promise<number> {
+  return await invoke("store_waitlist_entry", { entry });
+}
+
+export async function deleteWaitlistEntry(id: number) {
+  await invoke("delete_waitlist_entry", { id });
 }
 
 export function createCustomerAppointmentListResource(
@@ -197,6 +1087,397 @@ export async function storePreference(preference: EditPreferenceDto) {
   return await invoke("store_preference", { preference });
 }
 
+export async function isSetupRequired(): Promise<boolean> {
+  return await invoke<boolean>("is_setup_required");
+}
+
+export async function setupAdmin(
+  username: string,
+  password: string,
+): Promise<SessionDto> {
+  return await invoke<SessionDto>("setup_admin", { username, password });
+}
+
+export async function login(
+  username: string,
+  password: string,
+): Promise<SessionDto> {
+  return await invoke<SessionDto>("login", { username, password });
+}
+
+export async function logout() {
+  return await invoke("logout");
+}
+
+export async function getSession(): Promise<SessionDto | null> {
+  return await invoke<SessionDto | null>("get_session");
+}
+
+export async function changePassword(
+  currentPassword: string,
+  newPassword: string,
+) {
+  return await invoke("change_password", { currentPassword, newPassword });
+}
+
+export async function getUserList(): Promise<UserDto[]> {
+  return await invoke<UserDto[]>("get_user_list");
+}
+
+export async function storeUser(user: EditUserDto): Promise<number> {
+  return await invoke<number>("store_user", { user });
+}
+
+export async function deleteUser(id: number) {
+  return await invoke("delete_user", { id });
+}
+
+export async function getCommissionRuleList(
+  employeeId: number | null,
+): Promise<CommissionRuleDto[]> {
+  return await invoke("get_commission_rule_list", { employeeId });
+}
+
+export async function storeCommissionRule(
+  rule: EditCommissionRuleDto,
+): Promise<number> {
+  return await invoke("store_commission_rule", { rule });
+}
+
+export async function deleteCommissionRule(id: number) {
+  await invoke("delete_commission_rule", { id });
+}
+
+export async function getCommissionReport(
+  filter: CommissionReportFilterDto,
+): Promise<CommissionReportDto> {
+  return await invoke("get_commission_report", { filter });
+}
+
+export async function exportCommissionReportCsv(
+  filter: CommissionReportFilterDto,
+  path: string,
+) {
+  await invoke("export_commission_report_csv", { filter, path });
+}
+
+export async function getTreatmentList(): Promise<TreatmentDto[]> {
+  return await invoke<TreatmentDto[]>("get_treatment_list");
+}
+
+export async function getTreatmentById(
+  id: number | null,
+): Promise<TreatmentDto | null> {
+  if (id == null) {
+    return null;
+  }
+
+  return (await invoke<TreatmentDto>("get_treatment_by_id", { id })) ?? null;
+}
+
+export async function storeTreatment(
+  treatment: EditTreatmentDto,
+): Promise<number> {
+  return await invoke<number>("store_treatment", { treatment });
+}
+
+export async function deleteTreatment(id: number) {
+  await invoke("delete_treatment", { id });
+}
+
+export function createTreatmentListResource(): ResourceReturn<TreatmentDto[]> {
+  return createResource(getTreatmentList);
+}
+
+export async function getProductList(): Promise<ProductDto[]> {
+  return await invoke<ProductDto[]>("get_product_list");
+}
+
+export async function getProductById(
+  id: number | null,
+): Promise<ProductDto | null> {
+  if (id == null) {
+    return null;
+  }
+
+  return (await invoke<ProductDto>("get_product_by_id", { id })) ?? null;
+}
+
+export async function storeProduct(product: EditProductDto): Promise<number> {
+  return await invoke<number>("store_product", { product });
+}
+
+export async function deleteProduct(id: number) {
+  await invoke("delete_product", { id });
+}
+
+export async function getLowStockProductList(): Promise<ProductDto[]> {
+  return await invoke<ProductDto[]>("get_low_stock_product_list");
+}
+
+export async function getStockMovementList(
+  productId: number,
+): Promise<StockMovementDto[]> {
+  return await invoke<StockMovementDto[]>("get_stock_movement_list", {
+    productId,
+  });
+}
+
+export async function storeStockMovement(
+  movement: EditStockMovementDto,
+): Promise<number> {
+  return await invoke<number>("store_stock_movement", { movement });
+}
+
+export function createProductListResource(): ResourceReturn<ProductDto[]> {
+  return createResource(getProductList);
+}
+
+export async function getResourceList(): Promise<ResourceDto[]> {
+  return await invoke("get_resource_list");
+}
+
+export async function getResourceById(id: number): Promise<ResourceDto | null> {
+  return await invoke("get_resource_by_id", { id });
+}
+
+export async function storeResource(
+  resource: EditResourceDto,
+): Promise<number> {
+  return await invoke("store_resource", { resource });
+}
+
+export async function deleteResource(id: number) {
+  await invoke("delete_resource", { id });
+}
+
+export async function getResourceCalendar(
+  from: string,
+  to: string,
+): Promise<ResourceCalendarDto[]> {
+  return await invoke("get_resource_calendar", { from, to });
+}
+
+export async function getSessionPackageList(): Promise<SessionPackageDto[]> {
+  return await invoke("get_session_package_list");
+}
+
+export async function getSessionPackageById(
+  id: number,
+): Promise<SessionPackageDto | null> {
+  return await invoke("get_session_package_by_id", { id });
+}
+
+export async function storeSessionPackage(
+  data: EditSessionPackageDto,
+): Promise<number> {
+  return await invoke("store_session_package", { package: data });
+}
+
+export async function deleteSessionPackage(id: number) {
+  await invoke("delete_session_package", { id });
+}
+
+export async function getCustomerPackageList(
+  customerId: number,
+): Promise<CustomerPackageDto[]> {
+  return await invoke("get_customer_package_list", { customerId });
+}
+
+export async function getCustomerPackageById(
+  id: number,
+): Promise<CustomerPackageDto | null> {
+  return await invoke("get_customer_package_by_id", { id });
+}
+
+export async function storeCustomerPackage(
+  data: EditCustomerPackageDto,
+): Promise<number> {
+  return await invoke("store_customer_package", { package: data });
+}
+
+export async function deleteCustomerPackage(id: number) {
+  await invoke("delete_customer_package", { id });
+}
+
+export async function getInvoiceList(
+  customerId: number | null,
+): Promise<InvoiceDto[]> {
+  return await invoke("get_invoice_list", { customerId });
+}
+
+export async function getInvoiceById(id: number): Promise<InvoiceDto | null> {
+  return await invoke("get_invoice_by_id", { id });
+}
+
+export async function createInvoice(appointmentIds: number[]): Promise<number> {
+  return await invoke("create_invoice", { appointmentIds });
+}
+
+export async function storeInvoice(data: EditInvoiceDto): Promise<number> {
+  return await invoke("store_invoice", { invoice: data });
+}
+
+export async function deleteInvoice(id: number) {
+  await invoke("delete_invoice", { id });
+}
+
+export async function issueInvoice(id: number): Promise<InvoiceDto> {
+  return await invoke("issue_invoice", { id });
+}
+
+export async function cancelInvoice(id: number): Promise<number> {
+  return await invoke("cancel_invoice", { id });
+}
+
+export async function exportInvoicePdf(
+  id: number,
+  zugferd: boolean,
+  path: string,
+) {
+  await invoke("export_invoice_pdf", { id, zugferd, path });
+}
+
+export async function exportInvoiceXml(
+  id: number,
+  syntax: EInvoiceSyntax,
+  path: string,
+) {
+  await invoke("export_invoice_xml", { id, syntax, path });
+}
+
+export async function getPaymentList(
+  customerId: number | null,
+): Promise<PaymentDto[]> {
+  return await invoke("get_payment_list", { customerId });
+}
+
+export async function getPaymentById(id: number): Promise<PaymentDto | null> {
+  return await invoke("get_payment_by_id", { id });
+}
+
+export async function storePayment(data: EditPaymentDto): Promise<number> {
+  return await invoke("store_payment", { payment: data });
+}
+
+export async function deletePayment(id: number) {
+  await invoke("delete_payment", { id });
+}
+
+export async function getOpenItemList(
+  customerId: number | null,
+): Promise<OpenItemDto[]> {
+  return await invoke("get_open_item_list", { customerId });
+}
+
+export async function getCustomerBalance(
+  customerId: number,
+): Promise<CustomerBalanceDto> {
+  return await invoke("get_customer_balance", { customerId });
+}
+
+export async function getVoucherList(
+  customerId: number | null,
+): Promise<VoucherDto[]> {
+  return await invoke("get_voucher_list", { customerId });
+}
+
+export async function getVoucherById(id: number): Promise<VoucherDto | null> {
+  return await invoke("get_voucher_by_id", { id });
+}
+
+export async function getVoucherByCode(
+  code: string,
+): Promise<VoucherDto | null> {
+  return await invoke("get_voucher_by_code", { code });
+}
+
+export async function storeVoucher(voucher: EditVoucherDto): Promise<number> {
+  return await invoke("store_voucher", { voucher });
+}
+
+export async function deleteVoucher(id: number) {
+  await invoke("delete_voucher", { id });
+}
+
+export async function getVoucherLiabilityReport(
+  date: string,
+): Promise<VoucherLiabilityReportDto> {
+  return await invoke("get_voucher_liability_report", { date });
+}
+
+export async function getRevenueReport(
+  filter: RevenueReportFilterDto,
+): Promise<RevenueReportDto> {
+  return await invoke("get_revenue_report", { filter });
+}
+
+export async function exportRevenueReportCsv(
+  filter: RevenueReportFilterDto,
+  path: string,
+) {
+  await invoke("export_revenue_report_csv", { filter, path });
+}
+
+export async function getPriceRuleList(): Promise<PriceRuleDto[]> {
+  return await invoke("get_price_rule_list");
+}
+
+export async function getPriceRuleById(
+  id: number,
+): Promise<PriceRuleDto | null> {
+  return await invoke("get_price_rule_by_id", { id });
+}
+
+export async function storePriceRule(rule: EditPriceRuleDto): Promise<number> {
+  return await invoke("store_price_rule", { rule });
+}
+
+export async function deletePriceRule(id: number) {
+  await invoke("delete_price_rule", { id });
+}
+
+export async function priceAppointment(
+  appointment: EditCustomerAppointmentDto,
+): Promise<(AppliedPriceRuleDto | null)[]> {
+  return await invoke("price_appointment", { appointment });
+}
+
+export async function exportDatevBookings(
+  from: string,
+  to: string,
+  path: string,
+) {
+  await invoke("export_datev_bookings", { from, to, path });
+}
+
+export async function getCashEntryList(
+  from: string | null,
+  to: string | null,
+): Promise<CashEntryDto[]> {
+  return await invoke<CashEntryDto[]>("get_cash_entry_list", { from, to });
+}
+
+export async function storeCashEntry(entry: EditCashEntryDto): Promise<number> {
+  return await invoke<number>("store_cash_entry", { entry });
+}
+
+export async function getCashClosingList(): Promise<CashClosingDto[]> {
+  return await invoke<CashClosingDto[]>("get_cash_closing_list");
+}
+
+export async function closeCashDay(
+  date: string,
+  counted: number,
+  note: string,
+): Promise<CashClosingDto> {
+  return await invoke("close_cash_day", { date, counted, note });
+}
+
+export async function verifyCashJournal(): Promise<CashJournalVerificationDto> {
+  return await invoke("verify_cash_journal");
+}
+
 export async function openAppDataDirectory() {
   return await invoke("open_app_data_directory");
 }
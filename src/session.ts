@@ -0,0 +1,18 @@
+import { createSignal } from "solid-js";
+import { getSession, SessionDto, UserRole } from "./model";
+
+const [session, setSession] = createSignal<SessionDto | null>(null);
+
+export { session, setSession };
+
+/**
+ * A reload of the window keeps the session of the backend.
+ */
+export async function restoreSession() {
+  setSession(await getSession());
+}
+
+export function hasRole(...roles: UserRole[]): boolean {
+  const current = session();
+  return current !== null && roles.includes(current.role);
+}
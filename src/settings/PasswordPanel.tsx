@@ -0,0 +1,51 @@
+import { createSignal, Show } from "solid-js";
+import TextInput from "../components/input/TextInput";
+import InputGroup from "../components/InputGroup";
+import Button from "../components/Button";
+import { changePassword } from "../model";
+import { useTranslation } from "../translation";
+import styles from "./SettingsDialog.module.css";
+
+export default function PasswordPanel() {
+  const { t } = useTranslation();
+
+  const [currentPassword, setCurrentPassword] = createSignal("");
+  const [newPassword, setNewPassword] = createSignal("");
+  const [message, setMessage] = createSignal<string | null>(null);
+
+  const handleChangePassword = async () => {
+    try {
+      await changePassword(currentPassword(), newPassword());
+      setCurrentPassword("");
+      setNewPassword("");
+      setMessage(t("user.passwordChanged"));
+    } catch (e) {
+      setMessage(String(e));
+    }
+  };
+
+  return (
+    <div>
+      <InputGroup>
+        <TextInput
+          label={t("user.currentPassword")}
+          value={currentPassword()}
+          onChange={setCurrentPassword}
+          password
+        />
+        <TextInput
+          label={t("user.newPassword")}
+          value={newPassword()}
+          onChange={setNewPassword}
+          password
+        />
+      </InputGroup>
+      <Show when={message()}>
+        <p class={styles.message}>{message()}</p>
+      </Show>
+      <Button onClick={handleChangePassword}>
+        {t("user.changePassword")}
+      </Button>
+    </div>
+  );
+}
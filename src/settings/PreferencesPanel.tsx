@@ -1,15 +1,95 @@
+import { createSignal, For, Show } from "solid-js";
 import SelectBox, { SelectBoxPossibleValue } from "../components/SelectBox";
 import { useTranslation } from "../translation";
 import Button from "../components/Button";
-import { openAppDataDirectory } from "../model";
+import {
+  Currency,
+  openAppDataDirectory,
+  PaymentMethod,
+  sendDueReminders,
+} from "../model";
 import NumberInput from "../components/input/NumberInput";
-import { appConfig, Locale, setAppConfig } from "../appConfig";
+import TextInput from "../components/input/TextInput";
+import InputGroup from "../components/InputGroup";
+import {
+  appConfig,
+  Locale,
+  setAppConfig,
+  SmtpSecurity,
+} from "../appConfig";
+import styles from "./SettingsDialog.module.css";
 
 export default function PreferencesPanel() {
   const { t } = useTranslation();
 
   const language = () => appConfig("general.language");
   const fontSize = () => appConfig("general.font-size");
+  const timeZone = () => appConfig("general.time-zone");
+  const currency = () => appConfig("general.currency");
+  const [currencyMessage, setCurrencyMessage] = createSignal<string | null>(
+    null,
+  );
+  const selectCurrency = async (value: Currency) => {
+    try {
+      setCurrencyMessage(null);
+      await setAppConfig("general.currency", value);
+    } catch (e) {
+      setCurrencyMessage(String(e));
+    }
+  };
+  const icalIncludeCustomerName = () =>
+    appConfig("ical.include-customer-name");
+
+  const reminderEnabled = () => appConfig("reminder.enabled");
+
+  const datevAccounts = () => appConfig("datev.accounts");
+  const setRevenueAccount = (vatRate: number, account: number) =>
+    setAppConfig("datev.accounts", {
+      ...datevAccounts(),
+      revenue_accounts: datevAccounts().revenue_accounts.map((entry) =>
+        entry.vat_rate === vatRate ? { ...entry, account } : entry,
+      ),
+    });
+  const paymentAccountLabel = (method: PaymentMethod) =>
+    ({
+      cash: t("settings.datevPaymentAccount.cash"),
+      card: t("settings.datevPaymentAccount.card"),
+      transfer: t("settings.datevPaymentAccount.transfer"),
+      voucher: t("settings.datevPaymentAccount.voucher"),
+    })[method];
+  const setPaymentAccount = (method: PaymentMethod, account: number) =>
+    setAppConfig("datev.accounts", {
+      ...datevAccounts(),
+      payment_accounts: datevAccounts().payment_accounts.map((entry) =>
+        entry.method === method ? { ...entry, account } : entry,
+      ),
+    });
+
+  const smtpSecurityEntries = (): SelectBoxPossibleValue[] => [
+    {
+      id: "start_tls",
+      name: "STARTTLS",
+    },
+    {
+      id: "tls",
+      name: "TLS",
+    },
+    {
+      id: "none",
+      name: t("settings.smtpSecurityNone"),
+    },
+  ];
+
+  const timeZoneEntries: SelectBoxPossibleValue[] = Intl.supportedValuesOf(
+    "timeZone",
+  ).map((zone) => ({ id: zone, name: zone }));
+
+  const currencyEntries: SelectBoxPossibleValue[] = [
+    "EUR",
+    "CHF",
+    "USD",
+    "GBP",
+  ].map((code) => ({ id: code, name: code }));
 
   const languageEntries: SelectBoxPossibleValue[] = [
     {
@@ -22,6 +102,17 @@ export default function PreferencesPanel() {
     },
   ];
 
+  const booleanEntries = (): SelectBoxPossibleValue[] => [
+    {
+      id: "true",
+      name: t("settings.yes"),
+    },
+    {
+      id: "false",
+      name: t("settings.no"),
+    },
+  ];
+
   return (
     <div>
       <SelectBox
@@ -39,6 +130,280 @@ export default function PreferencesPanel() {
         max={64}
       />
 
+      <SelectBox
+        label={t("settings.timeZone")}
+        selected={timeZone()}
+        possibleValues={timeZoneEntries}
+        onSelect={(value) => setAppConfig("general.time-zone", value as string)}
+      />
+
+      <SelectBox
+        label={t("settings.currency")}
+        selected={currency()}
+        possibleValues={currencyEntries}
+        onSelect={(value) => selectCurrency(value as Currency)}
+      />
+      <Show when={currencyMessage()}>
+        <p class={styles.message}>{currencyMessage()}</p>
+      </Show>
+
+      <SelectBox
+        label={t("settings.icalIncludeCustomerName")}
+        selected={icalIncludeCustomerName() ? "true" : "false"}
+        possibleValues={booleanEntries()}
+        onSelect={(value) =>
+          setAppConfig("ical.include-customer-name", value === "true")
+        }
+      />
+
+      <TextInput
+        label={t("settings.invoicePracticeName")}
+        value={appConfig("invoice.practice-name")}
+        onChange={(v) => setAppConfig("invoice.practice-name", v)}
+      />
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoicePracticeAddress")}
+          value={appConfig("invoice.practice-address")}
+          onChange={(v) => setAppConfig("invoice.practice-address", v)}
+          rows={3}
+        />
+        <TextInput
+          label={t("settings.invoicePracticeContact")}
+          value={appConfig("invoice.practice-contact")}
+          onChange={(v) => setAppConfig("invoice.practice-contact", v)}
+          rows={3}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceTaxNumber")}
+          value={appConfig("invoice.tax-number")}
+          onChange={(v) => setAppConfig("invoice.tax-number", v)}
+        />
+        <NumberInput
+          label={t("settings.invoicePaymentDays")}
+          value={appConfig("invoice.payment-days")}
+          onChange={(v) => setAppConfig("invoice.payment-days", v)}
+          min={0}
+        />
+      </InputGroup>
+      <TextInput
+        label={t("settings.invoiceBankDetails")}
+        value={appConfig("invoice.bank-details")}
+        onChange={(v) => setAppConfig("invoice.bank-details", v)}
+        rows={2}
+      />
+      <TextInput
+        label={t("settings.invoiceFooter")}
+        value={appConfig("invoice.footer")}
+        onChange={(v) => setAppConfig("invoice.footer", v)}
+        rows={2}
+      />
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceNumberPattern")}
+          value={appConfig("invoice.number-pattern")}
+          onChange={(v) => setAppConfig("invoice.number-pattern", v)}
+        />
+        <TextInput
+          label={t("settings.invoiceCreditNotePattern")}
+          value={appConfig("invoice.credit-note-pattern")}
+          onChange={(v) => setAppConfig("invoice.credit-note-pattern", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceStreet")}
+          value={appConfig("invoice.street")}
+          onChange={(v) => setAppConfig("invoice.street", v)}
+        />
+        <TextInput
+          label={t("settings.invoicePostcode")}
+          value={appConfig("invoice.postcode")}
+          onChange={(v) => setAppConfig("invoice.postcode", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceCity")}
+          value={appConfig("invoice.city")}
+          onChange={(v) => setAppConfig("invoice.city", v)}
+        />
+        <TextInput
+          label={t("settings.invoiceCountry")}
+          value={appConfig("invoice.country")}
+          onChange={(v) => setAppConfig("invoice.country", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceVatId")}
+          value={appConfig("invoice.vat-id")}
+          onChange={(v) => setAppConfig("invoice.vat-id", v)}
+        />
+        <TextInput
+          label={t("settings.invoiceContactName")}
+          value={appConfig("invoice.contact-name")}
+          onChange={(v) => setAppConfig("invoice.contact-name", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceContactPhone")}
+          value={appConfig("invoice.contact-phone")}
+          onChange={(v) => setAppConfig("invoice.contact-phone", v)}
+        />
+        <TextInput
+          label={t("settings.invoiceContactEmail")}
+          value={appConfig("invoice.contact-email")}
+          onChange={(v) => setAppConfig("invoice.contact-email", v)}
+        />
+      </InputGroup>
+      <InputGroup>
+        <TextInput
+          label={t("settings.invoiceIban")}
+          value={appConfig("invoice.iban")}
+          onChange={(v) => setAppConfig("invoice.iban", v)}
+        />
+        <TextInput
+          label={t("settings.invoiceBic")}
+          value={appConfig("invoice.bic")}
+          onChange={(v) => setAppConfig("invoice.bic", v)}
+        />
+      </InputGroup>
+
+      <InputGroup>
+        <NumberInput
+          label={t("settings.datevConsultantNumber")}
+          value={appConfig("datev.consultant-number")}
+          onChange={(v) => setAppConfig("datev.consultant-number", v)}
+          min={0}
+          max={9_999_999}
+        />
+        <NumberInput
+          label={t("settings.datevClientNumber")}
+          value={appConfig("datev.client-number")}
+          onChange={(v) => setAppConfig("datev.client-number", v)}
+          min={0}
+          max={99_999}
+        />
+        <NumberInput
+          label={t("settings.datevAccountLength")}
+          value={appConfig("datev.account-length")}
+          onChange={(v) => setAppConfig("datev.account-length", v)}
+          min={4}
+          max={8}
+        />
+      </InputGroup>
+      <NumberInput
+        label={t("settings.datevReceivableAccount")}
+        value={datevAccounts().receivable_account}
+        onChange={(v) =>
+          setAppConfig("datev.accounts", {
+            ...datevAccounts(),
+            receivable_account: v,
+          })
+        }
+        min={0}
+      />
+      <InputGroup>
+        <For each={datevAccounts().revenue_accounts}>
+          {(entry) => (
+            <NumberInput
+              label={t("settings.datevRevenueAccount", {
+                rate: (entry.vat_rate / 100).toString(),
+              })}
+              value={entry.account}
+              onChange={(v) => setRevenueAccount(entry.vat_rate, v)}
+              min={0}
+            />
+          )}
+        </For>
+      </InputGroup>
+      <InputGroup>
+        <For each={datevAccounts().payment_accounts}>
+          {(entry) => (
+            <NumberInput
+              label={paymentAccountLabel(entry.method)}
+              value={entry.account}
+              onChange={(v) => setPaymentAccount(entry.method, v)}
+              min={0}
+            />
+          )}
+        </For>
+      </InputGroup>
+
+      <SelectBox
+        label={t("settings.reminderEnabled")}
+        selected={reminderEnabled() ? "true" : "false"}
+        possibleValues={booleanEntries()}
+        onSelect={(value) => setAppConfig("reminder.enabled", value === "true")}
+      />
+
+      <Show when={reminderEnabled()}>
+        <NumberInput
+          label={t("settings.reminderHoursBefore")}
+          value={appConfig("reminder.hours-before")}
+          onChange={(v) => setAppConfig("reminder.hours-before", v)}
+          min={1}
+          prefix={<span>h</span>}
+        />
+        <TextInput
+          label={t("settings.reminderSubject")}
+          value={appConfig("reminder.subject")}
+          onChange={(v) => setAppConfig("reminder.subject", v)}
+        />
+        <TextInput
+          label={t("settings.reminderMessage")}
+          value={appConfig("reminder.message")}
+          onChange={(v) => setAppConfig("reminder.message", v)}
+          rows={6}
+        />
+        <InputGroup>
+          <TextInput
+            label={t("settings.smtpHost")}
+            value={appConfig("smtp.host")}
+            onChange={(v) => setAppConfig("smtp.host", v)}
+          />
+          <NumberInput
+            label={t("settings.smtpPort")}
+            value={appConfig("smtp.port")}
+            onChange={(v) => setAppConfig("smtp.port", v)}
+            min={1}
+            max={65535}
+          />
+          <SelectBox
+            label={t("settings.smtpSecurity")}
+            selected={appConfig("smtp.security")}
+            possibleValues={smtpSecurityEntries()}
+            onSelect={(value) =>
+              setAppConfig("smtp.security", value as SmtpSecurity)
+            }
+          />
+        </InputGroup>
+        <InputGroup>
+          <TextInput
+            label={t("settings.smtpUsername")}
+            value={appConfig("smtp.username")}
+            onChange={(v) => setAppConfig("smtp.username", v)}
+          />
+          <TextInput
+            label={t("settings.smtpPassword")}
+            value={appConfig("smtp.password")}
+            onChange={(v) => setAppConfig("smtp.password", v)}
+          />
+        </InputGroup>
+        <TextInput
+          label={t("settings.smtpSender")}
+          value={appConfig("smtp.sender")}
+          onChange={(v) => setAppConfig("smtp.sender", v)}
+        />
+        <Button onClick={sendDueReminders}>
+          {t("settings.sendDueReminders")}
+        </Button>
+      </Show>
+
       <Button onClick={openAppDataDirectory}>
         {t("settings.openAppDataDirectory")}
       </Button>
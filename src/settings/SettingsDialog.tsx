@@ -1,8 +1,12 @@
+import { Show } from "solid-js";
 import Dialog from "../components/Dialog";
 import { useTranslation } from "../translation";
 import styles from "./SettingsDialog.module.css";
 import PreferencesPanel from "./PreferencesPanel";
 import VersionPanel from "./VersionPanel";
+import UsersPanel from "./UsersPanel";
+import PasswordPanel from "./PasswordPanel";
+import { hasRole } from "../session";
 
 export default function SettingsDialog(props: {
   show: boolean;
@@ -17,7 +21,11 @@ export default function SettingsDialog(props: {
       title={t("settings.title")}
     >
       <div class={styles.content}>
-        <PreferencesPanel />
+        <Show when={hasRole("admin")}>
+          <PreferencesPanel />
+          <UsersPanel />
+        </Show>
+        <PasswordPanel />
         <VersionPanel />
       </div>
     </Dialog>
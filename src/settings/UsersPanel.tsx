@@ -0,0 +1,201 @@
+import { createResource, createSignal, For, Show } from "solid-js";
+import TextInput from "../components/input/TextInput";
+import InputGroup from "../components/InputGroup";
+import SelectBox, { SelectBoxPossibleValue } from "../components/SelectBox";
+import Button from "../components/Button";
+import {
+  deleteUser,
+  EditUserDto,
+  EmployeeDto,
+  getEmployeeList,
+  getUserList,
+  storeUser,
+  UserDto,
+  UserRole,
+} from "../model";
+import { useTranslation } from "../translation";
+import styles from "./SettingsDialog.module.css";
+
+const emptyEditData: EditUserDto = {
+  id: null,
+  username: "",
+  password: "",
+  role: "reception",
+  employee_id: null,
+  active: true,
+};
+
+function toEditData(user: UserDto): EditUserDto {
+  return {
+    id: user.id,
+    username: user.username,
+    password: "",
+    role: user.role,
+    employee_id: user.employee?.id ?? null,
+    active: user.active,
+  };
+}
+
+function getEmployeeEntries(
+  employees: EmployeeDto[],
+  selectedId: number | null,
+): SelectBoxPossibleValue[] {
+  const entries: SelectBoxPossibleValue[] = employees
+    .filter((employee) => employee.active || employee.id === selectedId)
+    .map((employee) => ({ id: employee.id, name: employee.name }));
+
+  entries.push({
+    id: null,
+    name: "---",
+  });
+
+  return entries;
+}
+
+export default function UsersPanel() {
+  const { t } = useTranslation();
+
+  const [users, { refetch }] = createResource(getUserList);
+  const [employees] = createResource(getEmployeeList);
+
+  const [editData, setEditData] = createSignal<EditUserDto>(emptyEditData);
+  const [message, setMessage] = createSignal<string | null>(null);
+
+  const handleChange = <K extends keyof EditUserDto>(
+    key: K,
+    value: EditUserDto[K],
+  ) => setEditData((data) => ({ ...data, [key]: value }));
+
+  const select = (data: EditUserDto) => {
+    setMessage(null);
+    setEditData(data);
+  };
+
+  const roleName = (role: UserRole) =>
+    ({
+      admin: t("user.roleAdmin"),
+      reception: t("user.roleReception"),
+      therapist: t("user.roleTherapist"),
+    })[role];
+
+  const roleEntries = (): SelectBoxPossibleValue[] =>
+    (["admin", "reception", "therapist"] as UserRole[]).map((role) => ({
+      id: role,
+      name: roleName(role),
+    }));
+
+  const activeEntries = (): SelectBoxPossibleValue[] => [
+    {
+      id: "true",
+      name: t("user.statusActive"),
+    },
+    {
+      id: "false",
+      name: t("user.statusInactive"),
+    },
+  ];
+
+  const storeData = async () => {
+    try {
+      const id = await storeUser(editData());
+      await refetch();
+      select({ ...editData(), id, password: "" });
+    } catch (e) {
+      setMessage(String(e));
+    }
+  };
+
+  const deleteData = async () => {
+    const id = editData().id;
+    if (id === null) {
+      return;
+    }
+
+    try {
+      await deleteUser(id);
+      await refetch();
+      select(emptyEditData);
+    } catch (e) {
+      setMessage(String(e));
+    }
+  };
+
+  return (
+    <div>
+      <ul class={styles.userList}>
+        <For each={users()}>
+          {(user) => (
+            <li
+              classList={{
+                [styles.selected]: user.id === editData().id,
+                [styles.inactive]: !user.active,
+              }}
+              onClick={() => select(toEditData(user))}
+            >
+              {user.username} ({roleName(user.role)}
+              {user.employee ? `, ${user.employee.name}` : ""})
+            </li>
+          )}
+        </For>
+      </ul>
+      <InputGroup>
+        <TextInput
+          label={t("user.username")}
+          value={editData().username}
+          onChange={(v) => handleChange("username", v)}
+        />
+        <TextInput
+          label={
+            editData().id === null
+              ? t("user.password")
+              : t("user.passwordKeep")
+          }
+          value={editData().password}
+          onChange={(v) => handleChange("password", v)}
+          password
+        />
+      </InputGroup>
+      <InputGroup>
+        <SelectBox
+          label={t("user.role")}
+          selected={editData().role}
+          possibleValues={roleEntries()}
+          onSelect={(value) => handleChange("role", value as UserRole)}
+        />
+        <SelectBox
+          label={t("user.employee")}
+          selected={editData().employee_id}
+          possibleValues={getEmployeeEntries(
+            employees() ?? [],
+            editData().employee_id,
+          )}
+          onSelect={(value) =>
+            handleChange("employee_id", value as number | null)
+          }
+        />
+        <SelectBox
+          label={t("user.status")}
+          selected={editData().active ? "true" : "false"}
+          possibleValues={activeEntries()}
+          onSelect={(value) => handleChange("active", value === "true")}
+        />
+      </InputGroup>
+      <Show when={message()}>
+        <p class={styles.message}>{message()}</p>
+      </Show>
+      <div class={styles.actionRow}>
+        <Button color="primary" onClick={storeData}>
+          {t("general.save")}
+        </Button>
+        <Button onClick={() => select(emptyEditData)}>
+          {t("user.create")}
+        </Button>
+        <Show when={editData().id !== null}>
+          <Button color="danger" onClick={deleteData}>
+            {t("general.delete")}
+          </Button>
+        </Show>
+      </div>
+    </div>
+  );
+}
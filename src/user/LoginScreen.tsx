@@ -0,0 +1,80 @@
+import { createResource, createSignal, Show } from "solid-js";
+import styles from "./LoginScreen.module.css";
+import TextInput from "../components/input/TextInput";
+import Button from "../components/Button";
+import { isSetupRequired, login, SessionDto, setupAdmin } from "../model";
+import { useTranslation } from "../translation";
+
+export default function LoginScreen(props: {
+  onLogin: (session: SessionDto) => void;
+}) {
+  const { t } = useTranslation();
+
+  const [setupRequired] = createResource(isSetupRequired);
+
+  const [username, setUsername] = createSignal("");
+  const [password, setPassword] = createSignal("");
+  const [passwordRepeat, setPasswordRepeat] = createSignal("");
+  const [error, setError] = createSignal<string | null>(null);
+
+  const submit = async () => {
+    setError(null);
+    try {
+      if (setupRequired()) {
+        if (password() !== passwordRepeat()) {
+          setError(t("user.passwordMismatch"));
+          return;
+        }
+        props.onLogin(await setupAdmin(username(), password()));
+      } else {
+        props.onLogin(await login(username(), password()));
+      }
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div class={styles.loginScreen}>
+      <form
+        class={styles.loginForm}
+        onSubmit={(e) => {
+          e.preventDefault();
+          submit();
+        }}
+      >
+        <h1 class={styles.loginTitle}>
+          {setupRequired() ? t("user.setupTitle") : t("user.loginTitle")}
+        </h1>
+        <Show when={setupRequired()}>
+          <p>{t("user.setupHint")}</p>
+        </Show>
+        <TextInput
+          label={t("user.username")}
+          value={username()}
+          onChange={setUsername}
+        />
+        <TextInput
+          label={t("user.password")}
+          value={password()}
+          onChange={setPassword}
+          password
+        />
+        <Show when={setupRequired()}>
+          <TextInput
+            label={t("user.passwordRepeat")}
+            value={passwordRepeat()}
+            onChange={setPasswordRepeat}
+            password
+          />
+        </Show>
+        <Show when={error()}>
+          <p class={styles.loginError}>{error()}</p>
+        </Show>
+        <Button color="primary" disabled={setupRequired.loading}>
+          {setupRequired() ? t("user.setup") : t("user.login")}
+        </Button>
+      </form>
+    </div>
+  );
+}
@@ -5,6 +5,16 @@ import {
   onCleanup,
 } from "solid-js";
 
+export function formatMoney(
+  amount: number,
+  currency: string,
+  locale: string,
+): string {
+  return new Intl.NumberFormat(locale, { style: "currency", currency }).format(
+    amount / 100,
+  );
+}
+
 export function filterUndefined<T>(list: (T | undefined | null)[]): T[] {
   const result: T[] = [];
 